@@ -22,8 +22,8 @@ export const api = {
         return invoke('remove_root_path', { path });
     },
 
-    getFolderChildren(path) {
-        return invoke('get_folder_children', { path });
+    getFolderChildren(path, includeBlocked = false, ignoreDefaults = false, ignoreSession = false) {
+        return invoke('get_folder_children', { path, includeBlocked, ignoreDefaults, ignoreSession });
     },
 
     getGallery(id) {
@@ -34,6 +34,46 @@ export const api = {
         return invoke('get_gallery_pages', { id });
     },
 
+    getPageOrder(id) {
+        return invoke('get_page_order', { id });
+    },
+
+    setPageOrder(id, names) {
+        return invoke('set_page_order', { id, names });
+    },
+
+    fixPagePadding(id, dryRun = false) {
+        return invoke('fix_page_padding', { id, dryRun });
+    },
+
+    getPageExtras(id) {
+        return invoke('get_page_extras', { id });
+    },
+
+    setPageExtras(id, names) {
+        return invoke('set_page_extras', { id, names });
+    },
+
+    setReadingProgress(id, page) {
+        return invoke('set_reading_progress', { id, page });
+    },
+
+    getReadingProgress(id) {
+        return invoke('get_reading_progress', { id });
+    },
+
+    markRead(id, read) {
+        return invoke('mark_read', { id, read });
+    },
+
+    getHistory(limit = null) {
+        return invoke('get_history', { limit });
+    },
+
+    getContinueReading(limit = null) {
+        return invoke('get_continue_reading', { limit });
+    },
+
     openFile(path) {
         return invoke('open_file', { path });
     },
@@ -42,14 +82,74 @@ export const api = {
         return invoke('search_galleries', { query });
     },
 
+    getRandomGallery(query, count = 1) {
+        return invoke('get_random_gallery', { query, count });
+    },
+
+    parseSearchQuery(input) {
+        return invoke('parse_search_query', { input });
+    },
+
+    saveSearch(name, query) {
+        return invoke('save_search', { name, query });
+    },
+
+    listSavedSearches() {
+        return invoke('list_saved_searches');
+    },
+
+    deleteSavedSearch(id) {
+        return invoke('delete_saved_search', { id });
+    },
+
+    pushSessionFilter(filter) {
+        return invoke('push_session_filter', { filter });
+    },
+
+    popSessionFilter() {
+        return invoke('pop_session_filter');
+    },
+
+    clearSessionFilters() {
+        return invoke('clear_session_filters');
+    },
+
+    getSessionFilters() {
+        return invoke('get_session_filters');
+    },
+
     startScan(rootPath) {
         return invoke('start_scan', { rootPath });
     },
 
+    cancelScan(rootPath = null) {
+        return invoke('cancel_scan', { rootPath });
+    },
+
+    getAppStatus() {
+        return invoke('get_app_status');
+    },
+
     getScanStatus() {
         return invoke('get_scan_status');
     },
 
+    getLastScanReport() {
+        return invoke('get_last_scan_report');
+    },
+
+    listJobs() {
+        return invoke('list_jobs');
+    },
+
+    getJobs() {
+        return invoke('get_jobs');
+    },
+
+    cancelJob(id) {
+        return invoke('cancel_job', { id });
+    },
+
     getAssetUrl(path) {
         return invoke('get_asset_url', { path });
     },
@@ -58,28 +158,68 @@ export const api = {
         return invoke('get_duplicate_galleries');
     },
 
+    migrateGallery(id, targetRoot) {
+        return invoke('migrate_gallery', { id, targetRoot });
+    },
+
     deleteGallery(id) {
         return invoke('delete_gallery', { id });
     },
 
+    archiveGallery(id, label, removeLocal = false) {
+        return invoke('archive_gallery', { id, label, removeLocal });
+    },
+
+    toggleFavorite(id) {
+        return invoke('toggle_favorite', { id });
+    },
+
+    setMyRating(id, rating) {
+        return invoke('set_my_rating', { id, rating });
+    },
+
+    setLabel(id, label) {
+        return invoke('set_label', { id, label });
+    },
+
     moveFolders(sources, destination) {
         return invoke('move_folders', { sources, destination });
     },
 
+    splitGallery(id, pageRanges) {
+        return invoke('split_gallery', { id, pageRanges });
+    },
+
     deleteGalleryFolder(path) {
         return invoke('delete_gallery_folder', { path });
     },
 
+    purgeMissingGalleries() {
+        return invoke('purge_missing_galleries');
+    },
+
     clearCache() {
         return invoke('clear_cache');
     },
 
+    runDbMaintenance() {
+        return invoke('run_db_maintenance');
+    },
+
+    runReadonlyQuery(sql) {
+        return invoke('run_readonly_query', { sql });
+    },
+
     readThumb(path) {
         return invoke('read_thumb', { path });
     },
 
-    refreshGallery(id) {
-        return invoke('refresh_gallery', { id });
+    refreshGallery(id, force = false) {
+        return invoke('refresh_gallery', { id, force });
+    },
+
+    setFieldLock(id, field, locked) {
+        return invoke('set_field_lock', { id, field, locked });
     },
 
     setCookieFile() {
@@ -94,6 +234,281 @@ export const api = {
         return invoke('batch_refresh_galleries', { ids });
     },
 
+    pauseBatchRefresh(jobId) {
+        return invoke('pause_batch_refresh', { jobId });
+    },
+
+    resumeBatchRefresh(jobId) {
+        return invoke('resume_batch_refresh', { jobId });
+    },
+
+    getFetchQueueStatus() {
+        return invoke('get_fetch_queue_status');
+    },
+
+    clearFetchFailures() {
+        return invoke('clear_fetch_failures');
+    },
+
+    identifyAllGalleries() {
+        return invoke('identify_all_galleries');
+    },
+
+    getIdentifyCandidates() {
+        return invoke('get_identify_candidates');
+    },
+
+    confirmIdentifyCandidate(id) {
+        return invoke('confirm_identify_candidate', { id });
+    },
+
+    dismissIdentifyCandidate(id) {
+        return invoke('dismiss_identify_candidate', { id });
+    },
+
+    getGrowthStats() {
+        return invoke('get_growth_stats');
+    },
+
+    getLanguageStats() {
+        return invoke('get_language_stats');
+    },
+
+    getAllTags(namespace = null, sort = null) {
+        return invoke('get_all_tags', { namespace, sort });
+    },
+
+    getQualityReport(minWidth = null, limit = null) {
+        return invoke('get_quality_report', { minWidth, limit });
+    },
+
+    syncFavoriteSlots() {
+        return invoke('sync_favorite_slots');
+    },
+
+    getFavoriteSlots() {
+        return invoke('get_favorite_slots');
+    },
+
+    setGalleryFavoriteSlot(id, slot) {
+        return invoke('set_gallery_favorite_slot', { id, slot });
+    },
+
+    importFavorites() {
+        return invoke('import_favorites');
+    },
+
+    importEhViewerDb(path, favoriteSlot = null) {
+        return invoke('import_ehviewer_db', { path, favoriteSlot });
+    },
+
+    matchUrlList(path, dryRun = false) {
+        return invoke('match_url_list', { path, dryRun });
+    },
+
+    followArtist(artist) {
+        return invoke('follow_artist', { artist });
+    },
+
+    unfollowArtist(artist) {
+        return invoke('unfollow_artist', { artist });
+    },
+
+    listFollowedArtists() {
+        return invoke('list_followed_artists');
+    },
+
+    checkFollowedArtists() {
+        return invoke('check_followed_artists');
+    },
+
+    setFollowCheckHours(hours) {
+        return invoke('set_follow_check_hours', { hours });
+    },
+
+    getFollowCheckHours() {
+        return invoke('get_follow_check_hours');
+    },
+
+    setScanSchedules(schedules, skipOnBattery) {
+        return invoke('set_scan_schedules', { schedules, skipOnBattery });
+    },
+
+    getScanSchedules() {
+        return invoke('get_scan_schedules');
+    },
+
+    checkOwned(urlsOrGids) {
+        return invoke('check_owned', { urlsOrGids });
+    },
+
+    listIncomingDuplicates() {
+        return invoke('list_incoming_duplicates');
+    },
+
+    resolveIncomingDuplicate(path, importIt) {
+        return invoke('resolve_incoming_duplicate', { path, import: importIt });
+    },
+
+    upgradeGallery(id, trashOld = false) {
+        return invoke('upgrade_gallery', { id, trashOld });
+    },
+
+    downloadGallery(url, root = null) {
+        return invoke('download_gallery', { url, root });
+    },
+    repairGallery(id) {
+        return invoke('repair_gallery', { id });
+    },
+
+    exportSearchSidecars(ids = null) {
+        return invoke('export_search_sidecars', { ids });
+    },
+
+    listProfiles() {
+        return invoke('list_profiles');
+    },
+
+    switchProfile(name) {
+        return invoke('switch_profile', { name });
+    },
+
+    importDataDir(source, profile) {
+        return invoke('import_data_dir', { source, profile });
+    },
+
+    exportProfile(target) {
+        return invoke('export_profile', { target });
+    },
+
+    getChangesSince(cursor) {
+        return invoke('get_changes_since', { cursor });
+    },
+
+    createManifest(id) {
+        return invoke('create_manifest', { id });
+    },
+
+    verifyManifest(id) {
+        return invoke('verify_manifest', { id });
+    },
+
+    indexPageHashes() {
+        return invoke('index_page_hashes');
+    },
+
+    getPageColorStats() {
+        return invoke('get_page_color_stats');
+    },
+
+    findGalleryContainingImage(pathOrHash) {
+        return invoke('find_gallery_containing_image', { pathOrHash });
+    },
+
+    findVisuallySimilar(idOrImage, threshold = null) {
+        return invoke('find_visually_similar', { idOrImage: String(idOrImage), threshold });
+    },
+
+    setCompanionEnabled(enabled, port = null, origin = null) {
+        return invoke('set_companion_enabled', { enabled, port, origin });
+    },
+
+    getCompanionStatus() {
+        return invoke('get_companion_status');
+    },
+
+    regenerateCompanionToken() {
+        return invoke('regenerate_companion_token');
+    },
+
+    shareGallery(id, qr = false) {
+        return invoke('share_gallery', { id, qr });
+    },
+
+    resolveGalleryShare(descriptor) {
+        return invoke('resolve_gallery_share', { descriptor });
+    },
+
+    setInboxPath(path) {
+        return invoke('set_inbox_path', { path });
+    },
+
+    getInboxPath() {
+        return invoke('get_inbox_path');
+    },
+
+    scanInbox() {
+        return invoke('scan_inbox');
+    },
+
+    listInbox() {
+        return invoke('list_inbox');
+    },
+
+    groupInboxImages(ids, title, destination = null) {
+        return invoke('group_inbox_images', { ids, title, destination });
+    },
+
+    addWanted(url, title = null) {
+        return invoke('add_wanted', { url, title });
+    },
+
+    listWanted(status = null) {
+        return invoke('list_wanted', { status });
+    },
+
+    setWantedStatus(id, status) {
+        return invoke('set_wanted_status', { id, status });
+    },
+
+    removeWanted(id) {
+        return invoke('remove_wanted', { id });
+    },
+
+    createCollection(name, description = '') {
+        return invoke('create_collection', { name, description });
+    },
+
+    listCollections() {
+        return invoke('list_collections');
+    },
+
+    updateCollection(id, name, description = '') {
+        return invoke('update_collection', { id, name, description });
+    },
+
+    deleteCollection(id) {
+        return invoke('delete_collection', { id });
+    },
+
+    getCollectionItems(id) {
+        return invoke('get_collection_items', { id });
+    },
+
+    getCollectionGalleries(id) {
+        return invoke('get_collection_galleries', { id });
+    },
+
+    reorderCollection(id, gids) {
+        return invoke('reorder_collection', { id, gids });
+    },
+
+    addToCollection(id, galleryIds) {
+        return invoke('add_to_collection', { id, galleryIds });
+    },
+
+    removeFromCollection(id, gid) {
+        return invoke('remove_from_collection', { id, gid });
+    },
+
+    exportCollection(id, path) {
+        return invoke('export_collection', { id, path });
+    },
+
+    importCollection(path) {
+        return invoke('import_collection', { path });
+    },
+
     setTitlePref(pref) {
         return invoke('set_title_pref', { pref });
     },
@@ -102,6 +517,86 @@ export const api = {
         return invoke('get_title_pref');
     },
 
+    setSortLocale(locale) {
+        return invoke('set_sort_locale', { locale });
+    },
+
+    getSortLocale() {
+        return invoke('get_sort_locale');
+    },
+
+    setPreferredDomain(domain) {
+        return invoke('set_preferred_domain', { domain });
+    },
+
+    getPreferredDomain() {
+        return invoke('get_preferred_domain');
+    },
+
+    openGalleryUrl(id) {
+        return invoke('open_gallery_url', { id });
+    },
+
+    updateGalleryMetadata(id, patch, writeInfo = null) {
+        return invoke('update_gallery_metadata', { id, patch, writeInfo });
+    },
+
+    addTagsToGalleries(ids, tags, writeInfo = null) {
+        return invoke('add_tags_to_galleries', { ids, tags, writeInfo });
+    },
+
+    removeTagsFromGalleries(ids, tags, writeInfo = null) {
+        return invoke('remove_tags_from_galleries', { ids, tags, writeInfo });
+    },
+
+    applyToSelection(ids, operation, params = null) {
+        return invoke('apply_to_selection', { ids, operation, params });
+    },
+
+    setTagAlias(alias, target) {
+        return invoke('set_tag_alias', { alias, target });
+    },
+
+    removeTagAlias(alias) {
+        return invoke('remove_tag_alias', { alias });
+    },
+
+    getTagAliases() {
+        return invoke('get_tag_aliases');
+    },
+
+    migrateTag(from, to, writeInfo = null) {
+        return invoke('migrate_tag', { from, to, writeInfo });
+    },
+
+    syncInfoTxt(id) {
+        return invoke('sync_info_txt', { id });
+    },
+
+    setWriteBackInfoTxt(enabled) {
+        return invoke('set_write_back_info_txt', { enabled });
+    },
+
+    getWriteBackInfoTxt() {
+        return invoke('get_write_back_info_txt');
+    },
+
+    setCustomTitle(id, title) {
+        return invoke('set_custom_title', { id, title });
+    },
+
+    setGalleryNote(id, note) {
+        return invoke('set_gallery_note', { id, note });
+    },
+
+    setGalleryField(id, key, value) {
+        return invoke('set_gallery_field', { id, key, value });
+    },
+
+    getCustomFieldKeys() {
+        return invoke('get_custom_field_keys');
+    },
+
     setGridCardWidth(width) {
         return invoke('set_grid_card_width', { width });
     },
@@ -110,6 +605,26 @@ export const api = {
         return invoke('get_grid_card_width');
     },
 
+    getViewPrefs(context) {
+        return invoke('get_view_prefs', { context });
+    },
+
+    setViewPrefs(context, prefs) {
+        return invoke('set_view_prefs', { context, prefs });
+    },
+
+    setArchivePassword(path, password) {
+        return invoke('set_archive_password', { path, password });
+    },
+
+    setDefaultArchivePassword(password) {
+        return invoke('set_default_archive_password', { password });
+    },
+
+    getArchivePasswords() {
+        return invoke('get_archive_passwords');
+    },
+
     setGalleryCardWidth(width) {
         return invoke('set_gallery_card_width', { width });
     },
@@ -117,6 +632,98 @@ export const api = {
     getGalleryCardWidth() {
         return invoke('get_gallery_card_width');
     },
+
+    setCoverCrop(enabled) {
+        return invoke('set_cover_crop', { enabled });
+    },
+
+    getCoverCrop() {
+        return invoke('get_cover_crop');
+    },
+
+    setReaderAutoContrast(enabled) {
+        return invoke('set_reader_auto_contrast', { enabled });
+    },
+
+    getReaderAutoContrast() {
+        return invoke('get_reader_auto_contrast');
+    },
+
+    setBlocklist(blocklist) {
+        return invoke('set_blocklist', { blocklist });
+    },
+
+    getBlocklist() {
+        return invoke('get_blocklist');
+    },
+
+    setDefaultFilters(filters) {
+        return invoke('set_default_filters', { filters });
+    },
+
+    getDefaultFilters() {
+        return invoke('get_default_filters');
+    },
+
+    setTagDisplay(display) {
+        return invoke('set_tag_display', { display });
+    },
+
+    getTagDisplay() {
+        return invoke('get_tag_display');
+    },
+
+    setTagTranslationPath(path) {
+        return invoke('set_tag_translation_path', { path });
+    },
+
+    getTagTranslationPath() {
+        return invoke('get_tag_translation_path');
+    },
+
+    backfillTagTranslations() {
+        return invoke('backfill_tag_translations');
+    },
+
+    downloadTagTranslations() {
+        return invoke('download_tag_translations');
+    },
+
+    translateTag(namespace, tag) {
+        return invoke('translate_tag', { namespace, tag });
+    },
+
+    translateTags(tags) {
+        return invoke('translate_tags', { tags });
+    },
+
+    setJobLimits(limits) {
+        return invoke('set_job_limits', { limits });
+    },
+
+    getJobLimits() {
+        return invoke('get_job_limits');
+    },
+
+    setRefreshPacing(pacing) {
+        return invoke('set_refresh_pacing', { pacing });
+    },
+
+    getRefreshPacing() {
+        return invoke('get_refresh_pacing');
+    },
+
+    setPowerPolicy(policy) {
+        return invoke('set_power_policy', { policy });
+    },
+
+    getPowerPolicy() {
+        return invoke('get_power_policy');
+    },
+
+    getPowerState() {
+        return invoke('get_power_state');
+    },
 };
 
 /**
@@ -127,6 +734,22 @@ export function assetUrl(filePath) {
     return convertFileSrc(filePath);
 }
 
+/**
+ * URL for a page of a folder gallery, served by the `page` protocol so
+ * wide-gamut scans are shown in sRGB
+ */
+export function folderPageUrl(filePath) {
+    if (!filePath) return '';
+    return convertFileSrc(filePath, 'page');
+}
+
+/**
+ * URL for a page inside an archive gallery, served by the `archive` protocol
+ */
+export function archivePageUrl(archivePath, entry) {
+    return `${convertFileSrc(archivePath, 'archive')}?entry=${encodeURIComponent(entry)}`;
+}
+
 /**
  * Load a thumbnail via IPC (returns a data URL).
  * Uses an in-memory cache to avoid redundant IPC calls.
@@ -109,6 +109,11 @@ class App {
             }
         } catch (_) {}
 
+        // Restore the folder listing's saved sort
+        try {
+            this._applyViewPrefs(await api.getViewPrefs('folder'));
+        } catch (_) {}
+
         // Load gallery card (page preview) size
         try {
             const w = await api.getGalleryCardWidth();
@@ -117,13 +122,14 @@ class App {
             }
         } catch (_) {}
 
-        const paths = await api.getRootPaths();
-        if (paths.length > 0) {
+        const roots = await api.getRootPaths();
+        if (roots.length > 0) {
             this.welcomeScreen.classList.add('hidden');
             this.galleryGridEl.classList.remove('hidden');
             await this.folderTree.loadRoots();
-            // Navigate to first root
-            this.navigateToFolder(paths[0]);
+            // Navigate to first reachable root
+            const first = roots.find(r => r.online) || roots[0];
+            this.navigateToFolder(first.path);
         }
     }
 
@@ -275,7 +281,7 @@ class App {
         // Scan button — queue all root paths and process them one at a time
         document.getElementById('btn-scan').addEventListener('click', async () => {
             this.settingsModal.classList.add('hidden');
-            const paths = await api.getRootPaths();
+            const paths = (await api.getRootPaths()).filter(r => r.online).map(r => r.path);
             if (paths.length > 0) {
                 this._scanQueue = paths.slice(1);
                 await this.startScan(paths[0]);
@@ -406,6 +412,9 @@ class App {
 
         // Sort select
         this.sortSelect.addEventListener('change', () => {
+            const [sort_by, sort_order] = this.sortSelect.value.split(':');
+            const context = this.isSearchMode ? 'search' : 'folder';
+            api.setViewPrefs(context, { sort_by, sort_order }).catch(() => {});
             if (this.isSearchMode) {
                 const [sortBy, sortOrder] = this.sortSelect.value.split(':');
                 this.search.updateSort(sortBy, sortOrder);
@@ -494,7 +503,6 @@ class App {
             }
 
             this.scanOverlay.classList.add('hidden');
-            this._refreshCurrentView();
         });
 
         onEvent('batch-refresh-progress', (data) => {
@@ -507,12 +515,33 @@ class App {
 
         onEvent('batch-refresh-complete', () => {
             this.scanOverlay.classList.add('hidden');
-            this._refreshCurrentView();
         });
 
-        onEvent('watcher-update', (data) => {
+        // Scans, the watcher, refreshes and edits all report through here;
+        // reloads are debounced, so a scan's burst of changes reloads once
+        onEvent('library-changed', () => {
             this._refreshCurrentView();
         });
+
+        // Another window saved a sort for the listing shown here
+        onEvent('view-prefs-changed', (data) => {
+            const context = this.isSearchMode ? 'search' : 'folder';
+            if (data.context === context && this._applyViewPrefs(data.prefs) && !this.isSearchMode && this.currentPath) {
+                this.navigateToFolder(this.currentPath);
+            }
+        });
+
+        // A different library: nothing on screen belongs to it
+        onEvent('profile-switched', () => {
+            window.location.reload();
+        });
+
+        onEvent('root-status', async () => {
+            await this.folderTree.loadRoots();
+            if (!this.settingsModal.classList.contains('hidden')) {
+                await this.refreshSettings();
+            }
+        });
     }
 
     _setupSplitter() {
@@ -597,6 +626,18 @@ class App {
         }, 300);
     }
 
+    /**
+     * Select a saved sort if the sort menu offers it. Returns whether the
+     * selection changed.
+     */
+    _applyViewPrefs(prefs) {
+        const value = `${prefs.sort_by}:${prefs.sort_order}`;
+        const offered = Array.from(this.sortSelect.options).some(o => o.value === value);
+        if (!offered || this.sortSelect.value === value) return false;
+        this.sortSelect.value = value;
+        return true;
+    }
+
     /**
      * Sort galleries array client-side based on the current sort select value.
      */
@@ -613,6 +654,9 @@ class App {
                 case 'date_created':
                     va = a.date_created || 0; vb = b.date_created || 0;
                     break;
+                case 'added':
+                    va = a.added_at || ''; vb = b.added_at || '';
+                    break;
                 case 'title':
                     va = getDisplayTitle(a, this.titlePref).toLowerCase();
                     vb = getDisplayTitle(b, this.titlePref).toLowerCase();
@@ -1097,14 +1141,15 @@ class App {
             }
         } catch (_) {}
 
-        const paths = await api.getRootPaths();
+        const roots = await api.getRootPaths();
         this.rootPathsList.innerHTML = '';
 
-        for (const path of paths) {
+        for (const { path, online } of roots) {
             const li = document.createElement('li');
+            li.classList.toggle('offline', !online);
 
             const pathText = document.createElement('span');
-            pathText.textContent = path;
+            pathText.textContent = online ? path : `${path} (offline)`;
 
             const removeBtn = document.createElement('button');
             removeBtn.textContent = '\u00D7';
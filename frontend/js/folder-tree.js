@@ -15,20 +15,24 @@ export class FolderTree {
      * Load root folders and render the tree
      */
     async loadRoots() {
-        const paths = await api.getRootPaths();
+        const roots = await api.getRootPaths();
         this.container.innerHTML = '';
 
-        if (paths.length === 0) {
+        if (roots.length === 0) {
             this.container.innerHTML = '<div style="padding:12px;color:var(--text-muted);font-size:13px;">No folders added</div>';
             return;
         }
 
-        for (const path of paths) {
+        for (const { path, online } of roots) {
             const node = this.createNode({
                 name: this.getBasename(path),
                 path: path,
-                has_children: true,
+                has_children: online,
             }, true);
+            if (!online) {
+                node.classList.add('offline');
+                node.title = 'Offline — drive not connected';
+            }
             this.container.appendChild(node);
         }
     }
@@ -1,4 +1,4 @@
-import { api, assetUrl } from './api.js';
+import { api, archivePageUrl, folderPageUrl } from './api.js';
 import { getCategoryClass, formatRating, getDisplayTitle } from './utils.js';
 
 /**
@@ -189,12 +189,22 @@ export class GalleryView {
             a.textContent = gallery.url;
             a.addEventListener('click', (e) => {
                 e.preventDefault();
-                window.__TAURI__.shell.open(gallery.url);
+                api.openGalleryUrl(gallery.id).catch((err) => {
+                    console.error('Failed to open gallery URL:', err);
+                });
             });
             urlDiv.appendChild(a);
             header.appendChild(urlDiv);
         }
 
+        // Uploader comment
+        if (gallery.uploader_comment) {
+            const commentDiv = document.createElement('div');
+            commentDiv.className = 'gv-comment';
+            commentDiv.textContent = gallery.uploader_comment;
+            header.appendChild(commentDiv);
+        }
+
         // Tags
         if (tags.length > 0) {
             const tagsDiv = document.createElement('div');
@@ -247,7 +257,9 @@ export class GalleryView {
             const img = document.createElement('img');
             img.decoding = 'async';
             img.loading = 'lazy';
-            img.src = assetUrl(page.path);
+            img.src = page.archive_entry
+                ? archivePageUrl(page.path, page.archive_entry)
+                : folderPageUrl(page.path);
             img.alt = page.filename;
             img.onerror = () => { img.style.opacity = '0.3'; };
 
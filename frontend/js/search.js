@@ -83,6 +83,7 @@ export class SearchController {
         this.currentQuery = {
             text: parsed.text,
             tags: parsed.tags,
+            excluded_tags: parsed.excludedTags,
             category: null,
             language: null,
             sort_by: null,
@@ -99,6 +100,8 @@ export class SearchController {
             const result = await api.searchGalleries(this.currentQuery);
             // Only apply results if this is still the latest search
             if (id === this._searchId) {
+                // Later pages come from the same snapshot of the library
+                this.currentQuery.snapshot = result.snapshot;
                 this.onResults(result);
             }
         } catch (err) {
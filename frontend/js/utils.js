@@ -38,11 +38,13 @@ export function formatRating(rating) {
 
 /**
  * Parse search input into text query and tag filters.
- * Supports: free text, namespace:tag, namespace:"tag with spaces"
+ * Supports: free text, namespace:tag, namespace:"tag with spaces", and
+ * -namespace:tag to exclude a tag
  * Underscores in unquoted tags are converted to spaces for backward compat.
  */
 export function parseSearchInput(input) {
     const tags = [];
+    const excludedTags = [];
     const textParts = [];
 
     // Match quoted tag values (ns:"value") and unquoted tokens
@@ -54,7 +56,9 @@ export function parseSearchInput(input) {
         const colonIdx = part.indexOf(':');
 
         if (colonIdx > 0 && colonIdx < part.length - 1) {
-            const namespace = part.substring(0, colonIdx);
+            // A leading '-' excludes the tag (-female:netorare)
+            const negated = part.startsWith('-') && colonIdx > 1;
+            const namespace = part.substring(negated ? 1 : 0, colonIdx);
             let tag = part.substring(colonIdx + 1);
 
             // Remove surrounding quotes if present
@@ -66,7 +70,7 @@ export function parseSearchInput(input) {
             }
 
             if (tag) {
-                tags.push({ namespace, tag });
+                (negated ? excludedTags : tags).push({ namespace, tag });
             }
         } else {
             textParts.push(part);
@@ -76,16 +80,20 @@ export function parseSearchInput(input) {
     return {
         text: textParts.join(' ').trim() || null,
         tags,
+        excludedTags,
     };
 }
 
 /**
  * Get the display title based on title preference.
- * @param {object} gallery - Gallery object with title_en, title_jp, folder_name
+ * @param {object} gallery - Gallery object with custom_title, title_en, title_jp, folder_name
  * @param {string} pref - "en" or "jp"
  * @returns {string}
  */
 export function getDisplayTitle(gallery, pref) {
+    if (gallery.custom_title) {
+        return gallery.custom_title;
+    }
     if (pref === 'jp') {
         return gallery.title_jp || gallery.title_en || gallery.folder_name || '';
     }
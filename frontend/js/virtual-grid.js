@@ -426,6 +426,13 @@ export class VirtualGrid {
         const meta = document.createElement('div');
         meta.className = 'card-meta';
 
+        if (gallery.color_label) {
+            const label = document.createElement('span');
+            label.className = `card-label label-${gallery.color_label}`;
+            label.title = gallery.color_label;
+            meta.appendChild(label);
+        }
+
         if (gallery.category) {
             const badge = document.createElement('span');
             badge.className = `cat-badge ${getCategoryClass(gallery.category)}`;
@@ -447,6 +454,14 @@ export class VirtualGrid {
             meta.appendChild(pages);
         }
 
+        if (gallery.archive_label) {
+            const archived = document.createElement('span');
+            archived.className = 'card-archive';
+            archived.textContent = gallery.archive_label;
+            archived.title = `Archived to ${gallery.archive_label}`;
+            meta.appendChild(archived);
+        }
+
         info.appendChild(title);
         info.appendChild(meta);
         card.appendChild(thumb);
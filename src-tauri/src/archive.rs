@@ -0,0 +1,818 @@
+use flate2::read::DeflateDecoder;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::models::ParsedGallery;
+use crate::scanner;
+
+/// Archive extensions treated as galleries
+pub const ARCHIVE_EXTENSIONS: &[&str] = &["cbz", "zip", "cbr", "rar", "cb7", "7z"];
+
+/// Entries larger than this are refused, so a zip bomb can't exhaust memory
+const MAX_ENTRY_BYTES: u64 = 256 * 1024 * 1024;
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x0606_4b50;
+const CENTRAL_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+
+/// End-of-central-directory record plus the longest possible comment
+const EOCD_SEARCH_BYTES: u64 = 22 + 0xFFFF;
+
+/// Passwords for encrypted archives, keyed by normalized archive path
+#[derive(Default)]
+struct Passwords {
+    default: Option<String>,
+    by_path: HashMap<String, String>,
+}
+
+static PASSWORDS: Mutex<Option<Passwords>> = Mutex::new(None);
+
+/// Install the passwords used to open encrypted archives: one per archive
+/// path, falling back to `default`. Replaces any set before.
+pub fn set_passwords(default: Option<String>, by_path: HashMap<String, String>) {
+    let by_path = by_path
+        .into_iter()
+        .map(|(path, password)| (password_key(Path::new(&path)), password))
+        .collect();
+    *PASSWORDS.lock().unwrap_or_else(|e| e.into_inner()) = Some(Passwords { default, by_path });
+}
+
+/// Separators normalized, as for gallery locks
+fn password_key(path: &Path) -> String {
+    let key: PathBuf = path.components().collect();
+    scanner::to_nfc(&key.to_string_lossy())
+}
+
+fn password_for(path: &Path) -> Option<String> {
+    let passwords = PASSWORDS.lock().unwrap_or_else(|e| e.into_inner());
+    let passwords = passwords.as_ref()?;
+    passwords
+        .by_path
+        .get(&password_key(path))
+        .or(passwords.default.as_ref())
+        .cloned()
+}
+
+/// Whether `path` is a file with an archive extension
+pub fn is_archive(path: &Path) -> bool {
+    has_archive_extension(path) && path.is_file()
+}
+
+/// Whether `path` is named like an archive, whether or not it still exists
+pub fn has_archive_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| ARCHIVE_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// A file inside an archive
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    /// Path within the archive, `/`-separated
+    pub name: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+}
+
+/// Read-only access to an archive gallery, whatever its format
+pub trait Archive {
+    /// Files in the archive; directories are not listed
+    fn entries(&self) -> &[ArchiveEntry];
+
+    /// Decompress one file into memory
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, String>;
+
+    /// Image entries in reading order. macOS resource forks are skipped.
+    fn image_entries(&self) -> Vec<&ArchiveEntry> {
+        let mut images: Vec<&ArchiveEntry> = self
+            .entries()
+            .iter()
+            .filter(|e| !e.name.starts_with("__MACOSX/"))
+            .filter(|e| scanner::is_image_file(Path::new(&e.name)))
+            .collect();
+        images.sort_by(|a, b| {
+            scanner::natural_cmp(Path::new(&a.name), Path::new(&b.name)).then(a.name.cmp(&b.name))
+        });
+        images
+    }
+}
+
+/// Open an archive gallery, picking the reader by extension
+pub fn open(path: &Path) -> Result<Box<dyn Archive>, String> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "cbz" | "zip" => Ok(Box::new(ZipArchive::open(path)?)),
+        "cbr" | "rar" => Ok(Box::new(RarArchive::open(path)?)),
+        "cb7" | "7z" => Ok(Box::new(SevenZipArchive::open(path)?)),
+        _ => Err(format!("Not an archive: {}", path.display())),
+    }
+}
+
+/// Read one entry of the archive at `path` by name
+pub fn read_entry(path: &Path, name: &str) -> Result<Vec<u8>, String> {
+    open(path)?.read(name)
+}
+
+/// The first page of the archive at `path`
+pub fn read_first_image(path: &Path) -> Result<Vec<u8>, String> {
+    let mut archive = open(path)?;
+    let first = archive
+        .image_entries()
+        .first()
+        .map(|e| e.name.clone())
+        .ok_or_else(|| "No images found".to_string())?;
+    archive.read(&first)
+}
+
+/// Gallery metadata for an archive: its embedded info.txt (the shallowest
+/// one, if several) or, without one, the file name as the title and the
+/// image count as the page count.
+pub fn parse_archive_info(path: &Path) -> Result<ParsedGallery, String> {
+    let mut archive = open(path)?;
+    let info = archive
+        .entries()
+        .iter()
+        .filter(|e| {
+            let name = e.name.rsplit('/').next().unwrap_or_default();
+            name.eq_ignore_ascii_case("info.txt") && !e.name.starts_with("__MACOSX/")
+        })
+        .min_by_key(|e| e.name.matches('/').count())
+        .map(|e| e.name.clone());
+
+    match info {
+        Some(name) => scanner::parse_info_content(&scanner::decode_text(&archive.read(&name)?)),
+        None => {
+            let stem = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let mut parsed = scanner::parse_info_content(&stem)?;
+            parsed.page_count = archive.image_entries().len() as i64;
+            Ok(parsed)
+        }
+    }
+}
+
+fn too_large(name: &str, size: u64) -> Option<String> {
+    (size > MAX_ENTRY_BYTES)
+        .then(|| format!("{} is too large ({} MB)", name, size / (1024 * 1024)))
+}
+
+/// A zip central directory record
+#[derive(Debug, Clone)]
+struct ZipRecord {
+    name: String,
+    size: u64,
+    compressed_size: u64,
+    method: u16,
+    encrypted: bool,
+    /// Last byte of the ZipCrypto header, for checking the password
+    check_byte: u8,
+    header_offset: u64,
+}
+
+/// Zip/cbz reader: the central directory is parsed on open and entries are
+/// decompressed on demand (stored and deflate, with ZIP64). Traditional
+/// ZipCrypto encryption is supported; AES is not.
+pub struct ZipArchive {
+    file: File,
+    records: Vec<ZipRecord>,
+    entries: Vec<ArchiveEntry>,
+    password: Option<String>,
+}
+
+impl ZipArchive {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut file = File::open(path).map_err(|e| format!("Failed to open archive: {}", e))?;
+        let records: Vec<ZipRecord> = read_central_directory(&mut file)
+            .map_err(|e| format!("Invalid zip: {}", e))?
+            .into_iter()
+            .filter(|r| !r.name.ends_with('/'))
+            .collect();
+        let entries = records
+            .iter()
+            .map(|r| ArchiveEntry {
+                name: r.name.clone(),
+                size: r.size,
+            })
+            .collect();
+        Ok(Self {
+            file,
+            records,
+            entries,
+            password: password_for(path),
+        })
+    }
+}
+
+impl Archive for ZipArchive {
+    fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let record = self
+            .records
+            .iter()
+            .find(|r| r.name == name)
+            .ok_or_else(|| format!("{} not found in archive", name))?;
+        // The compressed size is trusted no more than the size: both are
+        // read before any allocation
+        if let Some(e) = too_large(name, record.size.max(record.compressed_size)) {
+            return Err(e);
+        }
+        let len = self.file.metadata().map_err(|e| e.to_string())?.len();
+        if record.header_offset.saturating_add(record.compressed_size) > len {
+            return Err(format!("{} runs past the end of the archive", name));
+        }
+        // AES-encrypted entries are marked with method 99
+        if record.encrypted && record.method == 99 {
+            return Err(format!("{} uses AES encryption, which isn't supported", name));
+        }
+        let password = match (&self.password, record.encrypted) {
+            (Some(password), true) => Some(password.as_bytes()),
+            (None, true) => return Err(format!("{} is encrypted; set a password for the archive", name)),
+            (_, false) => None,
+        };
+
+        let file = &mut self.file;
+        file.seek(SeekFrom::Start(record.header_offset))
+            .map_err(|e| e.to_string())?;
+        let mut header = [0u8; 30];
+        file.read_exact(&mut header).map_err(|e| e.to_string())?;
+        if u32_at(&header, 0) != LOCAL_SIGNATURE {
+            return Err(format!("Bad local header for {}", name));
+        }
+        let skip = u16_at(&header, 26) as i64 + u16_at(&header, 28) as i64;
+        file.seek(SeekFrom::Current(skip)).map_err(|e| e.to_string())?;
+
+        let mut raw: Box<dyn Read> = Box::new(file.take(record.compressed_size));
+        if let Some(password) = password {
+            let mut encrypted = Vec::with_capacity(record.compressed_size as usize);
+            raw.read_to_end(&mut encrypted)
+                .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+            let decrypted = zip_crypto_decrypt(password, &encrypted, record.check_byte)
+                .ok_or_else(|| format!("Wrong password for {}", name))?;
+            raw = Box::new(std::io::Cursor::new(decrypted));
+        }
+        let mut data = Vec::with_capacity(record.size as usize);
+        match record.method {
+            0 => raw.take(record.size).read_to_end(&mut data),
+            8 => DeflateDecoder::new(raw).take(record.size).read_to_end(&mut data),
+            m => return Err(format!("{} uses unsupported compression method {}", name, m)),
+        }
+        .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        Ok(data)
+    }
+}
+
+/// Rar/cbr reader. RAR is read sequentially (and solid archives can only be
+/// decompressed in order), so each read walks the headers up to the entry.
+pub struct RarArchive {
+    path: std::path::PathBuf,
+    entries: Vec<ArchiveEntry>,
+    password: Option<String>,
+}
+
+impl RarArchive {
+    /// `unrar`'s handle on the file, with the archive's password if any
+    fn handle(&self) -> unrar::Archive<'_> {
+        match &self.password {
+            Some(password) => unrar::Archive::with_password(&self.path, password.as_bytes()),
+            None => unrar::Archive::new(&self.path),
+        }
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut archive = Self {
+            path: path.to_path_buf(),
+            entries: Vec::new(),
+            password: password_for(path),
+        };
+        let listing = archive
+            .handle()
+            .open_for_listing()
+            .map_err(|e| format!("Invalid rar: {}", e))?;
+        let mut entries = Vec::new();
+        for header in listing {
+            let header = header.map_err(|e| format!("Invalid rar: {}", e))?;
+            if header.is_file() {
+                entries.push(ArchiveEntry {
+                    name: header.filename.to_string_lossy().replace('\\', "/"),
+                    size: header.unpacked_size,
+                });
+            }
+        }
+        archive.entries = entries;
+        Ok(archive)
+    }
+}
+
+impl Archive for RarArchive {
+    fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let mut archive = self
+            .handle()
+            .open_for_processing()
+            .map_err(|e| format!("Invalid rar: {}", e))?;
+        while let Some(header) = archive.read_header().map_err(|e| e.to_string())? {
+            let (matches, size) = {
+                let entry = header.entry();
+                let entry_name = entry.filename.to_string_lossy().replace('\\', "/");
+                (entry_name == name, entry.unpacked_size)
+            };
+            if !matches {
+                archive = header.skip().map_err(|e| e.to_string())?;
+                continue;
+            }
+            if let Some(e) = too_large(name, size) {
+                return Err(e);
+            }
+            let (data, _) = header
+                .read()
+                .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+            return Ok(data);
+        }
+        Err(format!("{} not found in archive", name))
+    }
+}
+
+/// 7z/cb7 reader. 7z streams are decoded in order, so each read decodes up
+/// to the requested entry.
+pub struct SevenZipArchive {
+    path: std::path::PathBuf,
+    entries: Vec<ArchiveEntry>,
+    password: Option<String>,
+}
+
+/// The archive's password in `sevenz_rust`'s form, empty without one
+fn sevenz_password(password: &Option<String>) -> sevenz_rust::Password {
+    match password {
+        Some(password) => sevenz_rust::Password::from(password.as_str()),
+        None => sevenz_rust::Password::empty(),
+    }
+}
+
+impl SevenZipArchive {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let password = password_for(path);
+        let reader = sevenz_rust::SevenZReader::open(path, sevenz_password(&password))
+            .map_err(|e| format!("Invalid 7z: {}", e))?;
+        let entries = reader
+            .archive()
+            .files
+            .iter()
+            .filter(|f| !f.is_directory())
+            .map(|f| ArchiveEntry {
+                name: f.name().replace('\\', "/"),
+                size: f.size(),
+            })
+            .collect();
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+            password,
+        })
+    }
+}
+
+impl Archive for SevenZipArchive {
+    fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    fn read(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let size = self
+            .entries
+            .iter()
+            .find(|e| e.name == name)
+            .ok_or_else(|| format!("{} not found in archive", name))?
+            .size;
+        if let Some(e) = too_large(name, size) {
+            return Err(e);
+        }
+
+        let mut reader = sevenz_rust::SevenZReader::open(&self.path, sevenz_password(&self.password))
+            .map_err(|e| format!("Invalid 7z: {}", e))?;
+        let mut found: Option<Vec<u8>> = None;
+        reader
+            .for_each_entries(|entry, data| {
+                if entry.name().replace('\\', "/") != name {
+                    // Entries in a solid block must still be consumed in order
+                    std::io::copy(data, &mut std::io::sink())?;
+                    return Ok(true);
+                }
+                let mut buf = Vec::with_capacity(size as usize);
+                data.take(size).read_to_end(&mut buf)?;
+                found = Some(buf);
+                Ok(false)
+            })
+            .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        found.ok_or_else(|| format!("{} not found in archive", name))
+    }
+}
+
+/// Decrypt a traditional PKWARE (ZipCrypto) entry: a 12-byte header, whose
+/// last byte must match `check_byte`, followed by the data. `None` means
+/// the password is wrong.
+fn zip_crypto_decrypt(password: &[u8], data: &[u8], check_byte: u8) -> Option<Vec<u8>> {
+    if data.len() < 12 {
+        return None;
+    }
+    let mut keys = ZipCryptoKeys::new(password);
+    let mut plain: Vec<u8> = data
+        .iter()
+        .map(|&c| {
+            let p = c ^ keys.stream_byte();
+            keys.update(p);
+            p
+        })
+        .collect();
+    (plain[11] == check_byte).then(|| plain.split_off(12))
+}
+
+/// The rolling key state of ZipCrypto, advanced by each plaintext byte
+struct ZipCryptoKeys {
+    keys: [u32; 3],
+    table: [u32; 256],
+}
+
+impl ZipCryptoKeys {
+    fn new(password: &[u8]) -> Self {
+        let mut keys = Self {
+            keys: [0x1234_5678, 0x2345_6789, 0x3456_7890],
+            table: crc32_table(),
+        };
+        for &b in password {
+            keys.update(b);
+        }
+        keys
+    }
+
+    fn crc(&self, crc: u32, b: u8) -> u32 {
+        (crc >> 8) ^ self.table[((crc ^ b as u32) & 0xFF) as usize]
+    }
+
+    fn update(&mut self, plain: u8) {
+        self.keys[0] = self.crc(self.keys[0], plain);
+        self.keys[1] = self.keys[1]
+            .wrapping_add(self.keys[0] & 0xFF)
+            .wrapping_mul(134_775_813)
+            .wrapping_add(1);
+        self.keys[2] = self.crc(self.keys[2], (self.keys[1] >> 24) as u8);
+    }
+
+    fn stream_byte(&self) -> u8 {
+        let temp = (self.keys[2] | 2) & 0xFFFF;
+        ((temp.wrapping_mul(temp ^ 1) >> 8) & 0xFF) as u8
+    }
+}
+
+fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut c = i as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    table
+}
+
+fn u16_at(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+}
+
+fn u32_at(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(buf: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+fn read_central_directory(file: &mut File) -> std::io::Result<Vec<ZipRecord>> {
+    let invalid = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string());
+
+    // The end record sits at the very end, after an optional comment
+    let len = file.seek(SeekFrom::End(0))?;
+    let tail_start = len.saturating_sub(EOCD_SEARCH_BYTES);
+    file.seek(SeekFrom::Start(tail_start))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    let eocd = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| u32_at(&tail, i) == EOCD_SIGNATURE)
+        .ok_or_else(|| invalid("no end of central directory"))?;
+
+    let mut count = u16_at(&tail, eocd + 10) as u64;
+    let mut cd_size = u32_at(&tail, eocd + 12) as u64;
+    let mut cd_offset = u32_at(&tail, eocd + 16) as u64;
+
+    // ZIP64 archives keep the real values in a separate record
+    if eocd >= 20 && u32_at(&tail, eocd - 20) == ZIP64_LOCATOR_SIGNATURE {
+        let zip64_offset = u64_at(&tail, eocd - 20 + 8);
+        file.seek(SeekFrom::Start(zip64_offset))?;
+        let mut record = [0u8; 56];
+        file.read_exact(&mut record)?;
+        if u32_at(&record, 0) != ZIP64_EOCD_SIGNATURE {
+            return Err(invalid("bad ZIP64 end record"));
+        }
+        count = u64_at(&record, 32);
+        cd_size = u64_at(&record, 40);
+        cd_offset = u64_at(&record, 48);
+    }
+
+    if cd_offset.saturating_add(cd_size) > len {
+        return Err(invalid("central directory out of bounds"));
+    }
+    file.seek(SeekFrom::Start(cd_offset))?;
+    let mut cd = vec![0u8; cd_size as usize];
+    file.read_exact(&mut cd)?;
+
+    let mut entries = Vec::with_capacity(count.min(65_536) as usize);
+    let mut pos = 0;
+    while pos + 46 <= cd.len() && u32_at(&cd, pos) == CENTRAL_SIGNATURE {
+        let flags = u16_at(&cd, pos + 8);
+        let method = u16_at(&cd, pos + 10);
+        // With a data descriptor (bit 3) the header checks the mod time
+        // instead of the CRC, which isn't known up front
+        let check_byte = if flags & 0x0008 != 0 {
+            (u16_at(&cd, pos + 12) >> 8) as u8
+        } else {
+            (u32_at(&cd, pos + 16) >> 24) as u8
+        };
+        let mut compressed_size = u32_at(&cd, pos + 20) as u64;
+        let mut size = u32_at(&cd, pos + 24) as u64;
+        let name_len = u16_at(&cd, pos + 28) as usize;
+        let extra_len = u16_at(&cd, pos + 30) as usize;
+        let comment_len = u16_at(&cd, pos + 32) as usize;
+        let mut header_offset = u32_at(&cd, pos + 42) as u64;
+
+        let name_start = pos + 46;
+        let extra_start = name_start + name_len;
+        let next = extra_start + extra_len + comment_len;
+        if next > cd.len() {
+            return Err(invalid("truncated central directory"));
+        }
+
+        // Bit 11 marks UTF-8 names; older tools wrote the local code page
+        let raw_name = &cd[name_start..extra_start];
+        let name = match std::str::from_utf8(raw_name) {
+            Ok(name) => name.to_string(),
+            Err(_) if flags & 0x0800 == 0 => scanner::decode_text(raw_name),
+            Err(_) => String::from_utf8_lossy(raw_name).to_string(),
+        };
+
+        // ZIP64 extra field: 64-bit values for the fields saturated above
+        let mut extra = &cd[extra_start..extra_start + extra_len];
+        while extra.len() >= 4 {
+            let id = u16_at(extra, 0);
+            let data_len = (u16_at(extra, 2) as usize).min(extra.len() - 4);
+            if id == 0x0001 {
+                let mut data = &extra[4..4 + data_len];
+                for field in [&mut size, &mut compressed_size, &mut header_offset] {
+                    if *field == 0xFFFF_FFFF && data.len() >= 8 {
+                        *field = u64_at(data, 0);
+                        data = &data[8..];
+                    }
+                }
+            }
+            extra = &extra[4 + data_len..];
+        }
+
+        entries.push(ZipRecord {
+            name: name.replace('\\', "/"),
+            size,
+            compressed_size,
+            method,
+            encrypted: flags & 0x0001 != 0,
+            check_byte,
+            header_offset,
+        });
+        pos = next;
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::DeflateEncoder;
+    use std::io::Write;
+
+    struct TestEntry<'a> {
+        name: &'a str,
+        data: &'a [u8],
+        method: u16,
+        password: Option<&'a [u8]>,
+        /// Compressed size written to the central directory instead of the
+        /// real one
+        claimed_compressed_size: Option<u64>,
+    }
+
+    fn entry<'a>(name: &'a str, data: &'a [u8], method: u16) -> TestEntry<'a> {
+        TestEntry {
+            name,
+            data,
+            method,
+            password: None,
+            claimed_compressed_size: None,
+        }
+    }
+
+    fn crc32(data: &[u8]) -> u32 {
+        let table = crc32_table();
+        !data
+            .iter()
+            .fold(!0u32, |crc, &b| (crc >> 8) ^ table[((crc ^ b as u32) & 0xFF) as usize])
+    }
+
+    fn zip_crypto_encrypt(password: &[u8], data: &[u8], check_byte: u8) -> Vec<u8> {
+        let mut keys = ZipCryptoKeys::new(password);
+        let mut header = [0x5Au8; 12];
+        header[11] = check_byte;
+        header
+            .iter()
+            .chain(data)
+            .map(|&p| {
+                let c = p ^ keys.stream_byte();
+                keys.update(p);
+                c
+            })
+            .collect()
+    }
+
+    /// A zip of `entries`; with `zip64`, sizes, offsets and counts are
+    /// saturated in the classic records and given in ZIP64 ones
+    fn build_zip(entries: &[TestEntry], zip64: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for e in entries {
+            let crc = crc32(e.data);
+            let mut body = match e.method {
+                8 => {
+                    let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                    encoder.write_all(e.data).unwrap();
+                    encoder.finish().unwrap()
+                }
+                _ => e.data.to_vec(),
+            };
+            let mut flags = 0x0800u16;
+            if let Some(password) = e.password {
+                flags |= 0x0001;
+                body = zip_crypto_encrypt(password, &body, (crc >> 24) as u8);
+            }
+            let offset = out.len() as u64;
+            let compressed = e.claimed_compressed_size.unwrap_or(body.len() as u64);
+            let size = e.data.len() as u64;
+
+            out.extend(LOCAL_SIGNATURE.to_le_bytes());
+            out.extend(20u16.to_le_bytes());
+            out.extend(flags.to_le_bytes());
+            out.extend(e.method.to_le_bytes());
+            out.extend([0u8; 4]);
+            out.extend(crc.to_le_bytes());
+            out.extend((body.len() as u32).to_le_bytes());
+            out.extend((size as u32).to_le_bytes());
+            out.extend((e.name.len() as u16).to_le_bytes());
+            out.extend(0u16.to_le_bytes());
+            out.extend(e.name.as_bytes());
+            out.extend(&body);
+
+            let mut extra = Vec::new();
+            let small = |v: u64| if zip64 { 0xFFFF_FFFF } else { v as u32 };
+            if zip64 {
+                extra.extend(1u16.to_le_bytes());
+                extra.extend(24u16.to_le_bytes());
+                extra.extend(size.to_le_bytes());
+                extra.extend(compressed.to_le_bytes());
+                extra.extend(offset.to_le_bytes());
+            }
+            central.extend(CENTRAL_SIGNATURE.to_le_bytes());
+            central.extend(20u16.to_le_bytes());
+            central.extend(20u16.to_le_bytes());
+            central.extend(flags.to_le_bytes());
+            central.extend(e.method.to_le_bytes());
+            central.extend([0u8; 4]);
+            central.extend(crc.to_le_bytes());
+            central.extend(small(compressed).to_le_bytes());
+            central.extend(small(size).to_le_bytes());
+            central.extend((e.name.len() as u16).to_le_bytes());
+            central.extend((extra.len() as u16).to_le_bytes());
+            central.extend([0u8; 10]);
+            central.extend(small(offset).to_le_bytes());
+            central.extend(e.name.as_bytes());
+            central.extend(&extra);
+        }
+
+        let cd_offset = out.len() as u64;
+        let cd_size = central.len() as u64;
+        let count = entries.len() as u64;
+        out.extend(central);
+        if zip64 {
+            let record_offset = out.len() as u64;
+            out.extend(ZIP64_EOCD_SIGNATURE.to_le_bytes());
+            out.extend(44u64.to_le_bytes());
+            out.extend(45u16.to_le_bytes());
+            out.extend(45u16.to_le_bytes());
+            out.extend([0u8; 8]);
+            out.extend(count.to_le_bytes());
+            out.extend(count.to_le_bytes());
+            out.extend(cd_size.to_le_bytes());
+            out.extend(cd_offset.to_le_bytes());
+            out.extend(ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+            out.extend(0u32.to_le_bytes());
+            out.extend(record_offset.to_le_bytes());
+            out.extend(1u32.to_le_bytes());
+        }
+        let (count16, size32, offset32) = if zip64 {
+            (0xFFFF, 0xFFFF_FFFF, 0xFFFF_FFFF)
+        } else {
+            (count as u16, cd_size as u32, cd_offset as u32)
+        };
+        out.extend(EOCD_SIGNATURE.to_le_bytes());
+        out.extend([0u8; 4]);
+        out.extend(count16.to_le_bytes());
+        out.extend(count16.to_le_bytes());
+        out.extend(size32.to_le_bytes());
+        out.extend(offset32.to_le_bytes());
+        out.extend(0u16.to_le_bytes());
+        out
+    }
+
+    fn open_zip(name: &str, bytes: &[u8]) -> (ZipArchive, PathBuf) {
+        let path = std::env::temp_dir().join(format!("ehmaster-{}-{}.cbz", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        (ZipArchive::open(&path).unwrap(), path)
+    }
+
+    const PAGE: &[u8] = b"not really an image, but compressible compressible compressible";
+
+    #[test]
+    fn reads_stored_and_deflated_entries() {
+        let zip = build_zip(&[entry("001.jpg", PAGE, 0), entry("dir/002.jpg", PAGE, 8)], false);
+        let (mut archive, path) = open_zip("plain", &zip);
+        let names: Vec<&str> = archive.entries().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["001.jpg", "dir/002.jpg"]);
+        assert_eq!(archive.read("001.jpg").unwrap(), PAGE);
+        assert_eq!(archive.read("dir/002.jpg").unwrap(), PAGE);
+        assert!(archive.read("003.jpg").is_err());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn reads_zip64_directory() {
+        let zip = build_zip(&[entry("001.jpg", PAGE, 8), entry("002.jpg", PAGE, 0)], true);
+        let (mut archive, path) = open_zip("zip64", &zip);
+        assert_eq!(archive.entries().len(), 2);
+        assert_eq!(archive.entries()[0].size, PAGE.len() as u64);
+        assert_eq!(archive.read("002.jpg").unwrap(), PAGE);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn decrypts_zip_crypto_with_the_right_password() {
+        let mut encrypted = entry("001.jpg", PAGE, 8);
+        encrypted.password = Some(b"secret");
+        let zip = build_zip(&[encrypted], false);
+        let (mut archive, path) = open_zip("encrypted", &zip);
+
+        archive.password = None;
+        assert!(archive.read("001.jpg").unwrap_err().contains("set a password"));
+        archive.password = Some("wrong".to_string());
+        assert!(archive.read("001.jpg").unwrap_err().contains("Wrong password"));
+        archive.password = Some("secret".to_string());
+        assert_eq!(archive.read("001.jpg").unwrap(), PAGE);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn refuses_oversized_compressed_entries() {
+        let mut huge = entry("001.jpg", PAGE, 0);
+        huge.claimed_compressed_size = Some(1 << 62);
+        // Encrypted entries are read whole before decrypting
+        huge.password = Some(b"secret");
+        let mut past_end = entry("002.jpg", PAGE, 0);
+        past_end.claimed_compressed_size = Some(1 << 20);
+        let zip = build_zip(&[huge, past_end], true);
+        let (mut archive, path) = open_zip("oversized", &zip);
+        archive.password = Some("secret".to_string());
+        assert!(archive.read("001.jpg").unwrap_err().contains("too large"));
+        assert!(archive.read("002.jpg").unwrap_err().contains("past the end"));
+        let _ = std::fs::remove_file(path);
+    }
+}
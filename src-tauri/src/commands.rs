@@ -1,13 +1,30 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, Emitter, Manager, State};
 
+use crate::archive;
+use crate::collation;
+use crate::companion;
+use crate::db::Database;
 use crate::fetcher;
+use crate::importer;
+use crate::matcher;
+use crate::jobs::{self, JobContext};
+use crate::manifest;
+use crate::metadata;
 use crate::models::*;
+use crate::power;
+use crate::profile;
 use crate::scanner;
+use crate::search;
+use crate::secret;
+use crate::sidecar;
 use crate::state::AppState;
 use crate::thumbnail;
+use crate::translation;
 use crate::watcher;
 
 #[tauri::command]
@@ -35,7 +52,7 @@ pub async fn set_root_path(
     }
 
     // Save settings
-    save_settings(&state, &app);
+    save_settings(&state);
 
     // Start file watcher
     start_watcher_for_path(&path, &state, &app);
@@ -44,28 +61,36 @@ pub async fn set_root_path(
 }
 
 #[tauri::command]
-pub async fn get_root_paths(state: State<'_, AppState>) -> Result<Vec<String>, String> {
-    let settings = state.settings.lock().unwrap();
-    Ok(settings.root_paths.clone())
+pub async fn get_root_paths(state: State<'_, AppState>) -> Result<Vec<RootPathStatus>, String> {
+    let roots = state.settings.lock().unwrap().root_paths.clone();
+    Ok(roots
+        .into_iter()
+        .map(|path| RootPathStatus {
+            online: scanner::is_root_online(Path::new(&path)),
+            path,
+        })
+        .collect())
 }
 
 #[tauri::command]
 pub async fn remove_root_path(
     path: String,
     state: State<'_, AppState>,
-    app: AppHandle,
 ) -> Result<(), String> {
     {
         let mut settings = state.settings.lock().unwrap();
         settings.root_paths.retain(|p| p != &path);
     }
-    save_settings(&state, &app);
+    save_settings(&state);
     Ok(())
 }
 
 #[tauri::command]
 pub async fn get_folder_children(
     path: String,
+    include_blocked: Option<bool>,
+    ignore_defaults: Option<bool>,
+    ignore_session: Option<bool>,
     state: State<'_, AppState>,
 ) -> Result<FolderChildren, String> {
     let path = PathBuf::from(&path);
@@ -80,11 +105,30 @@ pub async fn get_folder_children(
     let mut subfolders: Vec<FolderNode> = Vec::new();
     let mut galleries: Vec<GallerySummary> = Vec::new();
 
+    let (blocklist, defaults) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.blocklist.clone(), settings.default_filters.clone())
+    };
+    let session = if ignore_session.unwrap_or(false) {
+        Vec::new()
+    } else {
+        state.session_filters.lock().unwrap().clone()
+    };
+    let hidden = state
+        .db
+        .get_hidden_gallery_ids(
+            (!include_blocked.unwrap_or(false)).then_some(&blocklist),
+            (!ignore_defaults.unwrap_or(false)).then_some(&defaults),
+            &session,
+        )
+        .map_err(|e| e.to_string())?;
+
     let entries = fs::read_dir(&path).map_err(|e| e.to_string())?;
 
     for entry in entries.filter_map(|e| e.ok()) {
         let entry_path = entry.path();
-        if !entry_path.is_dir() {
+        let is_archive = archive::is_archive(&entry_path);
+        if !entry_path.is_dir() && !is_archive {
             continue;
         }
 
@@ -112,12 +156,14 @@ pub async fn get_folder_children(
             .map(|d| d.as_secs_f64())
             .unwrap_or(0.0);
 
-        // Check if this folder is a gallery (contains info.txt)
-        let info_path = entry_path.join("info.txt");
-        if info_path.exists() {
+        // Check if this is a gallery (an archive or a folder with info.txt)
+        if is_archive || entry_path.join("info.txt").exists() {
             // It's a gallery - get from DB or create a summary from folder name
             let path_str = normalize_path(&entry_path);
             if let Ok(Some(mut summary)) = state.db.get_gallery_by_path(&path_str) {
+                if hidden.contains(&summary.id) {
+                    continue;
+                }
                 summary.date_modified = date_modified;
                 summary.date_created = date_created;
                 galleries.push(summary);
@@ -133,6 +179,11 @@ pub async fn get_folder_children(
                     thumb_path: String::new(),
                     folder_name: name,
                     path: path_str,
+                    custom_title: String::new(),
+                    added_at: String::new(),
+                    archive_label: String::new(),
+                    read_percent: None,
+                    color_label: String::new(),
                     date_modified,
                     date_created,
                 });
@@ -149,12 +200,9 @@ pub async fn get_folder_children(
     }
 
     // Sort folders and galleries by name
-    subfolders.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-    galleries.sort_by(|a, b| {
-        a.folder_name
-            .to_lowercase()
-            .cmp(&b.folder_name.to_lowercase())
-    });
+    let locale = collation::locale();
+    subfolders.sort_by(|a, b| collation::compare_in(&a.name, &b.name, &locale));
+    galleries.sort_by(|a, b| collation::compare_in(&a.folder_name, &b.folder_name, &locale));
 
     Ok(FolderChildren { subfolders, galleries })
 }
@@ -175,7 +223,23 @@ pub async fn get_gallery(
                 .db
                 .get_tags_for_gallery(id)
                 .map_err(|e| e.to_string())?;
-            Ok(Some(GalleryDetail { gallery: g, tags }))
+            let tags = state.settings.lock().unwrap().tag_display.apply(tags);
+            let mut locked_fields: Vec<String> = state
+                .db
+                .get_field_locks(id)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .collect();
+            locked_fields.sort();
+            let (notes, custom_fields) =
+                state.db.get_gallery_notes(id).map_err(|e| e.to_string())?;
+            Ok(Some(GalleryDetail {
+                gallery: g,
+                tags,
+                locked_fields,
+                notes,
+                custom_fields,
+            }))
         }
         None => Ok(None),
     }
@@ -191,25 +255,410 @@ pub async fn get_gallery_pages(
         .get_gallery_by_id(id)
         .map_err(|e| e.to_string())?;
 
-    match gallery {
-        Some(g) => {
-            let images = scanner::get_all_images(Path::new(&g.path));
-            let pages: Vec<PageInfo> = images
-                .into_iter()
-                .enumerate()
-                .map(|(i, p)| PageInfo {
-                    filename: p
-                        .file_name()
-                        .map(|n| n.to_string_lossy().to_string())
-                        .unwrap_or_default(),
-                    path: p.to_string_lossy().to_string(),
-                    index: i,
-                })
-                .collect();
-            Ok(pages)
+    let Some(g) = gallery else {
+        return Ok(Vec::new());
+    };
+    let order = state.db.get_page_order(g.id).map_err(|e| e.to_string())?;
+
+    let pages: Vec<PageInfo> = if archive::is_archive(Path::new(&g.path)) {
+        let archive = archive::open(Path::new(&g.path))?;
+        archive
+            .image_entries()
+            .into_iter()
+            .map(|entry| PageInfo {
+                filename: entry.name.rsplit('/').next().unwrap_or_default().to_string(),
+                path: g.path.clone(),
+                index: 0,
+                archive_entry: Some(entry.name.clone()),
+                width: None,
+                height: None,
+                grayscale: None,
+            })
+            .collect()
+    } else {
+        scanner::get_all_images(Path::new(&g.path))
+            .into_iter()
+            .map(|p| PageInfo {
+                filename: p
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                path: p.to_string_lossy().to_string(),
+                index: 0,
+                archive_entry: None,
+                width: None,
+                height: None,
+                grayscale: None,
+            })
+            .collect()
+    };
+
+    // Size and colour from the last page hashing, where it still lines up
+    let hashes = state.db.get_page_hashes(g.id).unwrap_or_default();
+    let mut pages = scanner::apply_page_order(pages, &order, page_order_name);
+    for (i, page) in pages.iter_mut().enumerate() {
+        page.index = i;
+        if let Some(hash) = hashes.get(i).filter(|h| h.filename == page.filename) {
+            page.width = hash.width;
+            page.height = hash.height;
+            page.grayscale = hash.grayscale;
+        }
+    }
+    Ok(pages)
+}
+
+/// The name a page is listed under in a page order override
+fn page_order_name(page: &PageInfo) -> &str {
+    page.archive_entry.as_deref().unwrap_or(&page.filename)
+}
+
+/// A gallery's page order override; empty when pages are in natural order.
+#[tauri::command]
+pub async fn get_page_order(id: i64, state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    state.db.get_page_order(id).map_err(|e| e.to_string())
+}
+
+/// Set the reading order of a gallery's pages. `names` are page file names
+/// (entry names for archives), first page first; pages left out keep their
+/// natural order after the listed ones. An empty list restores natural order.
+#[tauri::command]
+pub async fn set_page_order(
+    id: i64,
+    names: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if !names.is_empty() {
+        let pages = get_gallery_pages(id, state.clone()).await?;
+        let known: std::collections::HashSet<&str> = pages.iter().map(page_order_name).collect();
+        if let Some(unknown) = names.iter().find(|n| !known.contains(n.as_str())) {
+            return Err(format!("No page named {}", unknown));
+        }
+    }
+    state.db.set_page_order(id, &names).map_err(|e| e.to_string())
+}
+
+/// Pages of a gallery marked as credits or extras.
+#[tauri::command]
+pub async fn get_page_extras(id: i64, state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    state.db.get_page_extras(id).map_err(|e| e.to_string())
+}
+
+/// Mark which pages of a gallery are credits or extras (page file names,
+/// entry names for archives), replacing any earlier marks. Extras are left
+/// out of reading progress and never used as the cover; the cover is
+/// regenerated if it was one.
+#[tauri::command]
+pub async fn set_page_extras(
+    id: i64,
+    names: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Gallery {} not found", id))?;
+    let pages = get_gallery_pages(id, state.clone()).await?;
+    let known: std::collections::HashSet<&str> = pages.iter().map(page_order_name).collect();
+    if let Some(unknown) = names.iter().find(|n| !known.contains(n.as_str())) {
+        return Err(format!("No page named {}", unknown));
+    }
+    state.db.set_page_extras(id, &names).map_err(|e| e.to_string())?;
+
+    let (thumb_width, cover_crop) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.thumbnail_width, settings.cover_crop)
+    };
+    let thumb = thumbnail::generate_gallery_thumbnail(
+        Path::new(&gallery.path),
+        &state.cache_dir(),
+        thumb_width,
+        cover_crop,
+        &names,
+    )?;
+    let thumb = thumb.to_string_lossy().to_string();
+    if thumb != gallery.thumb_path {
+        state.db.update_thumb_path(id, &thumb).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Record the page the reader is on and return the resulting progress. It's
+/// kept in the database, so the reader can resume there after a restart.
+#[tauri::command]
+pub async fn set_reading_progress(
+    id: i64,
+    page: usize,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<ReadProgress, String> {
+    let mut progress = read_progress(id, Some(page), state.clone()).await?;
+    state
+        .db
+        .set_reading_progress(id, page, progress.content_pages, progress.percent)
+        .map_err(|e| e.to_string())?;
+    state.db.log_reading(id, page).map_err(|e| e.to_string())?;
+    progress.updated_at = state
+        .db
+        .get_reading_progress(id)
+        .map_err(|e| e.to_string())?
+        .map(|(_, at)| at)
+        .unwrap_or_default();
+    publish_change(&app, ChangeKind::ProgressUpdated, Some(id), "");
+    Ok(progress)
+}
+
+/// Mark a gallery finished, or unread again, without going through the
+/// reader. Neither is logged as reading history.
+#[tauri::command]
+pub async fn mark_read(
+    id: i64,
+    read: bool,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    if read {
+        let progress = read_progress(id, None, state.clone()).await?;
+        let last = progress.page_count.saturating_sub(1);
+        state
+            .db
+            .set_reading_progress(id, last, progress.content_pages, 100)
+            .map_err(|e| e.to_string())?;
+    } else {
+        state.db.clear_reading_progress(id).map_err(|e| e.to_string())?;
+    }
+    publish_change(&app, ChangeKind::ProgressUpdated, Some(id), "");
+    Ok(())
+}
+
+/// Reading sessions, most recent first
+#[tauri::command]
+pub async fn get_history(
+    limit: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<Vec<HistoryEntry>, String> {
+    state.db.get_history(limit.unwrap_or(100)).map_err(|e| e.to_string())
+}
+
+/// Galleries opened but not finished, most recently read first
+#[tauri::command]
+pub async fn get_continue_reading(
+    limit: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<Vec<GallerySummary>, String> {
+    state.db.get_continue_reading(limit.unwrap_or(20)).map_err(|e| e.to_string())
+}
+
+/// Reading progress of a gallery, counting only pages that aren't extras.
+#[tauri::command]
+pub async fn get_reading_progress(
+    id: i64,
+    state: State<'_, AppState>,
+) -> Result<ReadProgress, String> {
+    let stored = state.db.get_reading_progress(id).map_err(|e| e.to_string())?;
+    let (page, updated_at) = match stored {
+        Some((page, at)) => (Some(page), at),
+        None => (None, String::new()),
+    };
+    let mut progress = read_progress(id, page, state).await?;
+    progress.updated_at = updated_at;
+    Ok(progress)
+}
+
+/// Progress with the reader on `page`, measured against the gallery's pages
+/// as they are now
+async fn read_progress(
+    id: i64,
+    page: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<ReadProgress, String> {
+    let extras: std::collections::HashSet<String> = state
+        .db
+        .get_page_extras(id)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect();
+    let pages = get_gallery_pages(id, state).await?;
+
+    let is_content = |p: &&PageInfo| !extras.contains(page_order_name(p));
+    let content_pages = pages.iter().filter(is_content).count();
+    let read = match page {
+        Some(page) => pages.iter().take(page + 1).filter(is_content).count(),
+        None => 0,
+    };
+    // A gallery of nothing but extras is finished once opened
+    let percent = (read * 100)
+        .checked_div(content_pages)
+        .unwrap_or(if page.is_some() { 100 } else { 0 }) as u32;
+    Ok(ReadProgress {
+        gallery_id: id,
+        page,
+        page_count: pages.len(),
+        content_pages,
+        percent,
+        updated_at: String::new(),
+    })
+}
+
+/// Zero-pad the page numbers in a folder gallery's file names on disk, so
+/// they sort correctly in any viewer. With `dry_run`, only reports the
+/// renames. Archives are never rewritten.
+#[tauri::command]
+pub async fn fix_page_padding(
+    id: i64,
+    dry_run: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Vec<PageRename>, String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Gallery {} not found", id))?;
+    let dir = Path::new(&gallery.path);
+    if archive::is_archive(dir) {
+        return Err("Archive galleries can't be renamed".to_string());
+    }
+
+    let names: Vec<String> = scanner::get_all_images(dir)
+        .iter()
+        .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+        .collect();
+    let renames = scanner::padding_renames(&names);
+    if dry_run.unwrap_or(false) {
+        return Ok(renames.into_iter().map(|(from, to)| PageRename { from, to }).collect());
+    }
+
+    let mut done = Vec::new();
+    let mut failure = None;
+    for (from, to) in renames {
+        match fs::rename(dir.join(&from), dir.join(&to)) {
+            Ok(()) => done.push((from, to)),
+            Err(e) => {
+                failure = Some(format!("Failed to rename {}: {}", from, e));
+                break;
+            }
+        }
+    }
+    // Keep any order override pointing at the files, even after a failure
+    state
+        .db
+        .rename_page_order_entries(id, &done)
+        .map_err(|e| e.to_string())?;
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    Ok(done.into_iter().map(|(from, to)| PageRename { from, to }).collect())
+}
+
+/// Serve one page of an archive gallery for the `archive://` protocol. The
+/// URL path is the archive and the `entry` query parameter the page in it;
+/// only archives in the library are served.
+pub fn archive_page_response(app: &AppHandle, uri: &tauri::http::Uri) -> tauri::http::Response<Vec<u8>> {
+    let respond = |status: u16, mime: &str, body: Vec<u8>| {
+        tauri::http::Response::builder()
+            .status(status)
+            .header("Content-Type", mime)
+            .body(body)
+            .unwrap_or_default()
+    };
+
+    let archive_path = percent_decode(uri.path().trim_start_matches('/'));
+    let entry = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("entry="))
+        .map(percent_decode);
+    let Some(entry) = entry else {
+        return respond(400, "text/plain", b"Missing entry".to_vec());
+    };
+
+    let state = app.state::<AppState>();
+    let known = state
+        .db
+        .get_gallery_by_path(&normalize_path(Path::new(&archive_path)))
+        .ok()
+        .flatten()
+        .is_some();
+    if !known {
+        return respond(404, "text/plain", b"Unknown archive".to_vec());
+    }
+
+    match archive::read_entry(Path::new(&archive_path), &entry) {
+        Ok(data) => page_response(Path::new(&entry), data),
+        Err(e) => respond(404, "text/plain", e.into_bytes()),
+    }
+}
+
+/// Serve one page of a folder gallery for the `page://` protocol. The URL
+/// path is the image; only images directly inside a library gallery folder
+/// are served.
+pub fn folder_page_response(app: &AppHandle, uri: &tauri::http::Uri) -> tauri::http::Response<Vec<u8>> {
+    let path = PathBuf::from(percent_decode(uri.path().trim_start_matches('/')));
+    let state = app.state::<AppState>();
+    let known = scanner::is_image_file(&path)
+        && path.parent().is_some_and(|folder| {
+            state
+                .db
+                .get_gallery_by_path(&normalize_path(folder))
+                .ok()
+                .flatten()
+                .is_some()
+        });
+    let data = if known { fs::read(&path).ok() } else { None };
+    match data {
+        Some(data) => page_response(&path, data),
+        None => tauri::http::Response::builder()
+            .status(404)
+            .header("Content-Type", "text/plain")
+            .body(b"Unknown page".to_vec())
+            .unwrap_or_default(),
+    }
+}
+
+/// Response for the page image `data` named `name`, converted to sRGB if it
+/// carries a wide-gamut profile
+fn page_response(name: &Path, data: Vec<u8>) -> tauri::http::Response<Vec<u8>> {
+    let (data, converted) = thumbnail::page_for_display(data);
+    let mime = converted.unwrap_or_else(|| {
+        match name.extension().map(|e| e.to_string_lossy().to_lowercase()).as_deref() {
+            Some("png") => "image/png",
+            Some("gif") => "image/gif",
+            Some("webp") => "image/webp",
+            Some("bmp") => "image/bmp",
+            Some("avif") => "image/avif",
+            _ => "image/jpeg",
+        }
+    });
+    tauri::http::Response::builder()
+        .status(200)
+        .header("Content-Type", mime)
+        .body(data)
+        .unwrap_or_default()
+}
+
+/// Decode `%XX` escapes (and `+` as a space) in a URL component
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (escaped, bytes[i]) {
+            (Some(b), _) => {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+            (None, b'+') => out.push(b' '),
+            (None, b) => out.push(b),
         }
-        None => Ok(Vec::new()),
+        i += 1;
     }
+    String::from_utf8_lossy(&out).to_string()
 }
 
 #[tauri::command]
@@ -291,23 +740,317 @@ pub async fn open_file(path: String) -> Result<(), String> {
     Ok(())
 }
 
+/// Open a gallery's EH page in the browser on the preferred domain.
+/// Returns the URL that was opened.
+#[tauri::command]
+pub async fn open_gallery_url(id: i64, state: State<'_, AppState>) -> Result<String, String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Gallery not found")?;
+    if gallery.url.is_empty() {
+        return Err("Gallery has no URL".to_string());
+    }
+
+    let host = match state.settings.lock().unwrap().preferred_domain.as_str() {
+        "e-hentai" => "e-hentai.org",
+        _ => "exhentai.org",
+    };
+    let url = fetcher::with_domain(&gallery.url, host);
+
+    #[cfg(target_os = "windows")]
+    let opener = "explorer";
+    #[cfg(target_os = "macos")]
+    let opener = "open";
+    #[cfg(target_os = "linux")]
+    let opener = "xdg-open";
+    std::process::Command::new(opener)
+        .arg(&url)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    Ok(url)
+}
+
 #[tauri::command]
 pub async fn search_galleries(
     query: SearchQuery,
     state: State<'_, AppState>,
 ) -> Result<SearchResult, String> {
+    let (blocklist, defaults) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.blocklist.clone(), settings.default_filters.clone())
+    };
+    let session = state.session_filters.lock().unwrap().clone();
+    state
+        .db
+        .search_galleries(&query, &blocklist, &defaults, &session)
+        .map_err(|e| e.to_string())
+}
+
+/// Pick `count` (default 1, at most 100) random galleries matching the same
+/// filters as `search_galleries`.
+#[tauri::command]
+pub async fn get_random_gallery(
+    query: SearchQuery,
+    count: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<Vec<GallerySummary>, String> {
+    let (blocklist, defaults) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.blocklist.clone(), settings.default_filters.clone())
+    };
+    let session = state.session_filters.lock().unwrap().clone();
     state
         .db
-        .search_galleries(&query)
+        .get_random_galleries(&query, &blocklist, &defaults, &session, count.unwrap_or(1).clamp(1, 100))
         .map_err(|e| e.to_string())
 }
 
+/// Compile an ExHentai-style search string into a query for
+/// `search_galleries`.
+#[tauri::command]
+pub fn parse_search_query(input: String) -> SearchQuery {
+    search::parse(&input)
+}
+
+/// Push a layer onto the session filter stack. Returns the new stack.
+#[tauri::command]
+pub async fn push_session_filter(
+    filter: SessionFilter,
+    state: State<'_, AppState>,
+) -> Result<Vec<SessionFilter>, String> {
+    if filter.is_empty() {
+        return Err("Session filter has no conditions".to_string());
+    }
+    let mut session = state.session_filters.lock().unwrap();
+    session.push(SessionFilter {
+        min_rating: filter.min_rating.clamp(0.0, 5.0),
+        ..filter
+    });
+    Ok(session.clone())
+}
+
+/// Drop the most recent session filter layer. Returns the remaining stack.
+#[tauri::command]
+pub async fn pop_session_filter(state: State<'_, AppState>) -> Result<Vec<SessionFilter>, String> {
+    let mut session = state.session_filters.lock().unwrap();
+    session.pop();
+    Ok(session.clone())
+}
+
+#[tauri::command]
+pub async fn clear_session_filters(state: State<'_, AppState>) -> Result<(), String> {
+    state.session_filters.lock().unwrap().clear();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_session_filters(state: State<'_, AppState>) -> Result<Vec<SessionFilter>, String> {
+    Ok(state.session_filters.lock().unwrap().clone())
+}
+
+/// Queue a scan of `root_path` on the job queue. Returns the job id.
 #[tauri::command]
 pub async fn start_scan(
     root_path: String,
     state: State<'_, AppState>,
-    app: AppHandle,
-) -> Result<(), String> {
+) -> Result<u64, String> {
+    Ok(submit_job(&state, JobSpec::Scan { root_path }, JobPriority::Normal))
+}
+
+/// Persist a job descriptor and queue it. Returns the job id.
+pub fn submit_job(state: &AppState, spec: JobSpec, priority: JobPriority) -> u64 {
+    let spec_json = serde_json::to_string(&spec).unwrap_or_default();
+    let priority_json = serde_json::to_value(priority)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default();
+    let persist_id = match state.db.insert_job(&spec_json, &priority_json) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            log::warn!("[jobs] Failed to persist job, it won't survive a restart: {}", e);
+            None
+        }
+    };
+    enqueue_spec(state, spec, priority, persist_id, 0)
+}
+
+/// Push the resource limits from settings into the job scheduler.
+pub fn apply_job_limits(state: &AppState) {
+    let (thumbs, network, disk) = {
+        let settings = state.settings.lock().unwrap();
+        (
+            settings.max_thumbnail_workers,
+            settings.max_network_jobs,
+            settings.max_disk_jobs,
+        )
+    };
+    state.jobs.set_resource_limit(JobResource::Thumbnail, thumbs as usize);
+    state.jobs.set_resource_limit(JobResource::Network, network as usize);
+    state.jobs.set_resource_limit(JobResource::Disk, disk as usize);
+}
+
+/// Hold or release heavy jobs for the last known power state under the
+/// power policy setting.
+pub fn apply_power_policy(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let power = *state.power.lock().unwrap();
+    let held = state.settings.lock().unwrap().power_policy.held_kinds(power);
+    for info in state.jobs.set_held(&held) {
+        let _ = app.emit("job-progress", info);
+    }
+}
+
+/// Check the power source and connection cost, then apply the power
+/// policy. Emits `power-state` when either changed.
+pub fn refresh_power_state(app: &AppHandle) {
+    let power = PowerState {
+        on_battery: power::on_battery(),
+        metered: power::on_metered_connection(),
+    };
+    let state = app.state::<AppState>();
+    let changed = std::mem::replace(&mut *state.power.lock().unwrap(), power) != power;
+    apply_power_policy(app);
+    if changed {
+        log::info!("Power state: {:?}", power);
+        let _ = app.emit("power-state", power);
+    }
+}
+
+/// Re-queue jobs that were still pending when the app last closed.
+pub fn resume_persisted_jobs(state: &AppState) {
+    let pending = match state.db.get_pending_jobs() {
+        Ok(rows) => rows,
+        Err(e) => {
+            log::warn!("[jobs] Failed to load persisted jobs: {}", e);
+            return;
+        }
+    };
+
+    let mut jobs = Vec::new();
+    for (persist_id, spec_json, priority, done) in pending {
+        let spec: JobSpec = match serde_json::from_str(&spec_json) {
+            Ok(spec) => spec,
+            Err(e) => {
+                log::warn!("[jobs] Dropping unreadable job {}: {}", persist_id, e);
+                let _ = state.db.delete_job(persist_id);
+                continue;
+            }
+        };
+        let priority = serde_json::from_value(serde_json::Value::String(priority))
+            .unwrap_or(JobPriority::Normal);
+        jobs.push((persist_id, spec, priority, done));
+    }
+    let specs: Vec<JobSpec> = jobs.iter().map(|(_, spec, _, _)| spec.clone()).collect();
+    prune_staging(state, &specs);
+
+    for (persist_id, spec, priority, done) in jobs {
+        log::info!("[jobs] Resuming {:?} from item {}", spec, done);
+        enqueue_spec(state, spec, priority, Some(persist_id), done.max(0) as usize);
+    }
+}
+
+/// Runs a stored job may fail before it's dropped instead of resumed
+const MAX_JOB_ATTEMPTS: i64 = 3;
+
+/// Build the closure for `spec` and queue it, starting at item `start`.
+/// The persisted row is removed once the job completes or is cancelled; a
+/// failed run keeps it, with its checkpoint, for the next launch to retry
+/// until `MAX_JOB_ATTEMPTS` runs have failed.
+fn enqueue_spec(
+    state: &AppState,
+    spec: JobSpec,
+    priority: JobPriority,
+    persist_id: Option<i64>,
+    start: usize,
+) -> u64 {
+    let label = match &spec {
+        JobSpec::Scan { root_path } => format!("Scan {}", root_path),
+        JobSpec::BatchRefresh { ids } => format!("Refresh {} galleries", ids.len()),
+        JobSpec::Identify { ids } => format!("Identify {} galleries", ids.len()),
+        JobSpec::ImportFavorites => "Import EH favorites".to_string(),
+        JobSpec::CheckFollowedArtists => FOLLOW_CHECK_LABEL.to_string(),
+        JobSpec::HashPages => "Index page hashes".to_string(),
+        JobSpec::ScanInbox => "Scan inbox".to_string(),
+        JobSpec::TranslateTags => "Translate tags".to_string(),
+        JobSpec::UpgradeGallery { id, .. } => format!("Upgrade gallery {}", id),
+        JobSpec::DownloadGallery { url, .. } => format!("Download {}", url),
+        JobSpec::RepairGallery { id } => format!("Repair gallery {}", id),
+        JobSpec::ExportSidecars { ids } => format!("Write search sidecars for {} galleries", ids.len()),
+    };
+    let items = match &spec {
+        JobSpec::BatchRefresh { ids }
+        | JobSpec::Identify { ids }
+        | JobSpec::ExportSidecars { ids } => ids.len(),
+        _ => 0,
+    };
+    let db = Arc::clone(&state.db);
+
+    let job_id = state
+        .jobs
+        .enqueue_persisted(spec.kind(), priority, label, persist_id, move |ctx| {
+            // Caught here too, so a panic counts as a failed run of the stored job
+            let result = jobs::run_caught(|| match spec {
+                JobSpec::Scan { root_path } => scan_root(&root_path, ctx),
+                JobSpec::BatchRefresh { ids } => {
+                    let paused = persist_id.is_some_and(|id| db.is_job_paused(id));
+                    tauri::async_runtime::block_on(run_batch_refresh(ids, start, paused, ctx))
+                }
+                JobSpec::Identify { ids } => {
+                    tauri::async_runtime::block_on(run_identify(ids, start, ctx))
+                }
+                JobSpec::ImportFavorites => {
+                    tauri::async_runtime::block_on(run_import_favorites(ctx))
+                }
+                JobSpec::CheckFollowedArtists => {
+                    tauri::async_runtime::block_on(run_check_followed_artists(ctx))
+                }
+                JobSpec::HashPages => run_hash_pages(ctx),
+                JobSpec::ScanInbox => run_scan_inbox(ctx),
+                JobSpec::TranslateTags => run_translate_tags(ctx),
+                JobSpec::UpgradeGallery { id, trash_old } => {
+                    tauri::async_runtime::block_on(run_upgrade_gallery(id, trash_old, ctx))
+                }
+                JobSpec::DownloadGallery { url, root } => {
+                    tauri::async_runtime::block_on(run_download_gallery(&url, &root, ctx))
+                }
+                JobSpec::RepairGallery { id } => {
+                    tauri::async_runtime::block_on(run_repair_gallery(id, ctx))
+                }
+                JobSpec::ExportSidecars { ids } => run_export_sidecars(&ids, start, ctx),
+            });
+            if let Some(id) = persist_id {
+                match &result {
+                    Err(e) if !ctx.is_cancelled() => {
+                        let attempts = db.record_job_failure(id, e).unwrap_or(MAX_JOB_ATTEMPTS);
+                        if attempts >= MAX_JOB_ATTEMPTS {
+                            log::warn!("[jobs] Giving up on job {} after {} failed runs", id, attempts);
+                            let _ = db.delete_job(id);
+                        } else {
+                            log::info!("[jobs] Job {} failed, will retry on next launch: {}", id, e);
+                        }
+                    }
+                    _ => {
+                        let _ = db.delete_job(id);
+                    }
+                }
+            }
+            result
+        });
+    if start > 0 {
+        state.jobs.set_checkpoint(job_id, start as u64, items as u64);
+    }
+    job_id
+}
+
+/// Scan every gallery under `root_path`, reconciling the DB with disk.
+/// Runs as a job; a cancelled scan stops early and skips the removal pass.
+pub fn scan_root(root_path: &str, ctx: &JobContext) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+
     // Check if already scanning
     {
         let status = state.scan_status.lock().unwrap();
@@ -316,12 +1059,20 @@ pub async fn start_scan(
         }
     }
 
+    // An unmounted root would look empty and flag every gallery missing
+    if !scanner::is_root_online(Path::new(root_path)) {
+        return Err(format!("Root is offline: {}", root_path));
+    }
+
     let db = Arc::clone(&state.db);
-    let cache_dir = state.cache_dir.clone();
-    let thumb_width = state.settings.lock().unwrap().thumbnail_width;
+    let cache_dir = state.cache_dir();
+    let (thumb_width, cover_crop) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.thumbnail_width, settings.cover_crop)
+    };
 
     // Find all gallery folders first
-    let root = PathBuf::from(&root_path);
+    let root = PathBuf::from(root_path);
     let gallery_folders = scanner::find_gallery_folders(&root);
     let total = gallery_folders.len() as i64;
 
@@ -339,6 +1090,13 @@ pub async fn start_scan(
         serde_json::json!({ "scanned": 0, "total": total, "current_folder": "" }),
     );
 
+    let mut report = ScanReport {
+        root_path: root_path.to_string(),
+        started_at: unix_now(),
+        total_scanned: total,
+        ..Default::default()
+    };
+
     // Get existing gallery paths for cleanup later
     let existing_paths: std::collections::HashSet<String> = db
         .get_all_gallery_paths()
@@ -348,11 +1106,26 @@ pub async fn start_scan(
 
     let mut scanned_paths: std::collections::HashSet<String> =
         std::collections::HashSet::new();
+    // Galleries indexed before sizes were tracked get theirs on this scan
+    let unsized_paths = db.get_unsized_gallery_paths().unwrap_or_default();
 
     // Scan each gallery
     for (i, folder) in gallery_folders.iter().enumerate() {
+        if ctx.is_cancelled() {
+            report.cancelled = true;
+            break;
+        }
+
         let folder_str = normalize_path(folder);
-        let info_path = folder.join("info.txt");
+        let info_path = scanner::gallery_info_path(folder);
+        let lock = state.gallery_locks.get(&folder_str);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        // A folder that went missing (e.g. unmounted drive) is back
+        if db.restore_gallery(&folder_str).unwrap_or(false) {
+            report.restored.push(folder_str.clone());
+            emit_scan_item(app, &db, "restored", &folder_str);
+        }
 
         // Check if info.txt has changed since last scan
         let info_mtime = scanner::get_file_mtime(&info_path);
@@ -362,19 +1135,82 @@ pub async fn start_scan(
         };
 
         if needs_update {
-            if let Some(parsed) = scanner::parse_info_txt(&info_path) {
-                // Generate thumbnail
-                let thumb = scanner::get_first_image(folder)
-                    .and_then(|img| {
-                        thumbnail::generate_thumbnail(&img, &cache_dir, thumb_width)
-                    })
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_default();
+            match scanner::parse_gallery(folder) {
+                Ok(parsed)
+                    if !existing_paths.contains(&folder_str)
+                        && hold_incoming_duplicate(app, &folder_str, &parsed) =>
+                {
+                    report.held_duplicates.push(folder_str.clone());
+                }
+                Ok(parsed) => {
+                    // Generate thumbnail, skipping pages marked as extras
+                    let extras = db.get_page_extras_by_path(&folder_str).unwrap_or_default();
+                    let thumb = match scanner::get_cover_image(folder, &extras) {
+                        Some(img) => {
+                            match thumbnail::generate_thumbnail(&img, &cache_dir, thumb_width, cover_crop) {
+                                Ok(p) => p.to_string_lossy().to_string(),
+                                Err(reason) => {
+                                    report.thumbnail_failures.push(ScanFailure {
+                                        path: img.to_string_lossy().to_string(),
+                                        reason,
+                                    });
+                                    String::new()
+                                }
+                            }
+                        }
+                        None if archive::is_archive(folder) => {
+                            match thumbnail::generate_archive_thumbnail(folder, &cache_dir, thumb_width, cover_crop, &extras) {
+                                Ok(p) => p.to_string_lossy().to_string(),
+                                Err(reason) => {
+                                    report.thumbnail_failures.push(ScanFailure {
+                                        path: folder_str.clone(),
+                                        reason,
+                                    });
+                                    String::new()
+                                }
+                            }
+                        }
+                        None => {
+                            report.thumbnail_failures.push(ScanFailure {
+                                path: folder_str.clone(),
+                                reason: "No images found".to_string(),
+                            });
+                            String::new()
+                        }
+                    };
 
-                let _ = db.upsert_gallery(&folder_str, &parsed, &thumb, &info_mtime);
+                    match db.upsert_gallery(&folder_str, &parsed, &thumb, &info_mtime) {
+                        Ok(gallery_id) => {
+                            let resampled = scanner::detect_resampled(folder, &parsed.file_size);
+                            let _ = db.set_resampled(gallery_id, resampled);
+                            let _ = db.set_page_stats(gallery_id, &scanner::page_stats(folder));
+                            let kind = if existing_paths.contains(&folder_str) {
+                                report.updated.push(folder_str.clone());
+                                "updated"
+                            } else {
+                                report.added.push(folder_str.clone());
+                                "added"
+                            };
+                            emit_scan_item(app, &db, kind, &folder_str);
+                            publish_change(app, ChangeKind::GalleryUpserted, Some(gallery_id), &folder_str);
+                        }
+                        Err(e) => report.parse_failures.push(ScanFailure {
+                            path: folder_str.clone(),
+                            reason: format!("DB error: {}", e),
+                        }),
+                    }
+                }
+                Err(reason) => report.parse_failures.push(ScanFailure {
+                    path: folder_str.clone(),
+                    reason,
+                }),
             }
         }
 
+        if needs_update || unsized_paths.contains(&folder_str) {
+            let _ = db.set_disk_size(&folder_str, scanner::disk_size(folder));
+        }
+
         scanned_paths.insert(folder_str.clone());
 
         // Emit progress
@@ -386,15 +1222,32 @@ pub async fn start_scan(
                 "current_folder": folder_str,
             }),
         );
+        ctx.progress((i + 1) as u64, total as u64, &folder_str);
     }
 
-    // Remove galleries that no longer exist on disk
+    // Flag galleries that no longer exist on disk as missing (skipped when
+    // cancelled or when the root went offline mid-scan, since unvisited
+    // folders would look deleted). Rows are kept so an unmounted drive coming
+    // back doesn't lose progress or edits.
     let mut removed = 0i64;
+    let root_online = scanner::is_root_online(Path::new(root_path));
     for path in &existing_paths {
-        if !scanned_paths.contains(path) {
+        if !report.cancelled && root_online && !scanned_paths.contains(path) {
             // Check if it's under this root
-            if path.starts_with(&root_path) {
-                let _ = db.delete_gallery_by_path(path);
+            if path.starts_with(root_path) {
+                let lock = state.gallery_locks.get(path);
+                let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+                if !db.mark_gallery_missing(path).unwrap_or(false) {
+                    continue;
+                }
+                if let Ok(Some(gallery)) = db.get_gallery_by_path(path) {
+                    publish_change(app, ChangeKind::GalleryRemoved, Some(gallery.id), path);
+                    let _ = app.emit(
+                        "scan-item",
+                        serde_json::json!({ "kind": "removed", "gallery": gallery }),
+                    );
+                }
+                report.removed.push(path.clone());
                 removed += 1;
             }
         }
@@ -406,17 +1259,162 @@ pub async fn start_scan(
         status.is_scanning = false;
     }
 
+    if !report.cancelled && root_online {
+        let _ = db.set_root_scanned(root_path);
+    }
+
+    report.finished_at = unix_now();
+    save_scan_report(&report, &state);
+
     let _ = app.emit(
         "scan-complete",
         serde_json::json!({
             "total_scanned": total,
             "removed": removed,
+            "added": report.added.len(),
+            "updated": report.updated.len(),
+            "failed": report.parse_failures.len(),
         }),
     );
 
     Ok(())
 }
 
+/// Return the report written by the most recent scan, if any.
+#[tauri::command]
+pub async fn get_last_scan_report(
+    state: State<'_, AppState>,
+) -> Result<Option<ScanReport>, String> {
+    let report_path = state.data_dir().join("last_scan_report.json");
+    if !report_path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&report_path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Corrupt scan report: {}", e))
+}
+
+#[tauri::command]
+pub async fn list_jobs(state: State<'_, AppState>) -> Result<Vec<JobInfo>, String> {
+    Ok(state.jobs.list())
+}
+
+/// Jobs for the jobs panel: `list_jobs`, plus when each job that survives
+/// restarts was first queued and whether it's paused.
+#[tauri::command]
+pub async fn get_jobs(state: State<'_, AppState>) -> Result<Vec<JobRecord>, String> {
+    let stored = state.db.get_job_states().map_err(|e| e.to_string())?;
+    let paused = state.fetch_queue.status().paused_jobs;
+    Ok(state
+        .jobs
+        .list()
+        .into_iter()
+        .map(|info| {
+            let (queued_at, stored_paused) = info
+                .persist_id
+                .and_then(|id| stored.get(&id).cloned())
+                .unzip();
+            JobRecord {
+                queued_at,
+                paused: paused.contains(&info.id) || stored_paused.unwrap_or(false),
+                info,
+            }
+        })
+        .collect())
+}
+
+/// Cancel a queued or running job. Returns false if the id is unknown or finished.
+#[tauri::command]
+pub async fn cancel_job(id: u64, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(cancel_job_by_id(&state, id))
+}
+
+/// Cancel queued and running scans, of one root or of all of them. A running
+/// scan stops after the gallery it's on and skips its missing-gallery pass.
+/// Returns how many scans were cancelled.
+#[tauri::command]
+pub async fn cancel_scan(
+    root_path: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    let label = root_path.map(|root| format!("Scan {}", root));
+    let ids: Vec<u64> = state
+        .jobs
+        .list()
+        .into_iter()
+        .filter(|j| j.kind == JobKind::Scan)
+        .filter(|j| matches!(j.status, JobStatus::Queued | JobStatus::Running))
+        .filter(|j| label.as_ref().is_none_or(|l| j.label == *l))
+        .map(|j| j.id)
+        .collect();
+    Ok(ids
+        .into_iter()
+        .filter(|id| cancel_job_by_id(&state, *id))
+        .count() as u64)
+}
+
+fn cancel_job_by_id(state: &AppState, id: u64) -> bool {
+    // Forget the persisted descriptor so a cancelled job isn't resumed on restart
+    let persist_id = state
+        .jobs
+        .list()
+        .into_iter()
+        .find(|j| j.id == id)
+        .and_then(|j| j.persist_id);
+    if let Some(persist_id) = persist_id {
+        let _ = state.db.delete_job(persist_id);
+    }
+    state.jobs.cancel(id)
+}
+
+/// Everything the status bar shows, gathered in one call.
+#[tauri::command]
+pub async fn get_app_status(state: State<'_, AppState>) -> Result<AppStatus, String> {
+    let scan = state.scan_status.lock().unwrap().clone();
+    let jobs = state
+        .jobs
+        .list()
+        .into_iter()
+        .filter(|j| matches!(j.status, JobStatus::Queued | JobStatus::Running))
+        .collect();
+
+    let (roots, watcher_enabled) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.root_paths.clone(), settings.watcher_enabled)
+    };
+    let watchers = {
+        let handles = state.watchers.lock().unwrap();
+        roots
+            .into_iter()
+            .map(|path| WatcherStatus {
+                watching: handles.get(&path).is_some_and(|h| h.is_running()),
+                online: scanner::is_root_online(Path::new(&path)),
+                path,
+            })
+            .collect()
+    };
+
+    let (thumbnail_files, thumbnail_bytes) = walkdir::WalkDir::new(state.cache_dir())
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .fold((0u64, 0u64), |(n, bytes), m| (n + 1, bytes + m.len()));
+
+    Ok(AppStatus {
+        scan,
+        jobs,
+        watcher_enabled,
+        watchers,
+        fetch_cooldown_secs: fetcher::cooldown_remaining().as_secs_f64().ceil() as u64,
+        cache: CacheUsage {
+            thumbnail_files,
+            thumbnail_bytes,
+        },
+    })
+}
+
 #[tauri::command]
 pub async fn get_scan_status(state: State<'_, AppState>) -> Result<ScanStatus, String> {
     let status = state.scan_status.lock().unwrap();
@@ -436,20 +1434,103 @@ pub async fn get_duplicate_galleries(
 ) -> Result<DuplicateResult, String> {
     let by_url = state.db.find_duplicates_by_url().map_err(|e| e.to_string())?;
     let by_name = state.db.find_duplicates_by_name().map_err(|e| e.to_string())?;
-    Ok(DuplicateResult { by_url, by_name })
+    let roots = state.settings.lock().unwrap().root_paths.clone();
+
+    let annotate = |groups: Vec<Vec<GallerySummary>>| -> Vec<Vec<DuplicateCopy>> {
+        groups
+            .into_iter()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .map(|gallery| DuplicateCopy {
+                        root: root_for_path(&gallery.path, &roots).unwrap_or_default(),
+                        gallery,
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+
+    Ok(DuplicateResult {
+        by_url: annotate(by_url),
+        by_name: annotate(by_name),
+    })
+}
+
+/// Move a gallery folder into another root, keeping its path relative to the
+/// root it currently lives in. Falls back to copy + delete across drives.
+/// Returns the new gallery path.
+#[tauri::command]
+pub async fn migrate_gallery(
+    id: i64,
+    target_root: String,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Gallery not found".to_string())?;
+
+    let roots = state.settings.lock().unwrap().root_paths.clone();
+    if !roots.contains(&target_root) {
+        return Err(format!("Not a configured root: {}", target_root));
+    }
+    if !scanner::is_root_online(Path::new(&target_root)) {
+        return Err(format!("Root is offline: {}", target_root));
+    }
+
+    let src = PathBuf::from(&gallery.path);
+    let relative = match root_for_path(&gallery.path, &roots) {
+        Some(root) => src
+            .strip_prefix(&root)
+            .map(|p| p.to_path_buf())
+            .map_err(|e| e.to_string())?,
+        None => PathBuf::from(src.file_name().ok_or("Invalid gallery path")?),
+    };
+    let target = PathBuf::from(&target_root).join(&relative);
+    let new_path = normalize_path(&target);
+
+    // A move across volumes is a full copy; run it off the async runtime,
+    // holding both paths so nothing claims the target once it's checked
+    let locks = Arc::clone(&state.gallery_locks);
+    let db = Arc::clone(&state.db);
+    tauri::async_runtime::spawn_blocking(move || {
+        let locks = locks.get_many(&[&gallery.path, &new_path]);
+        let _guards: Vec<_> = locks
+            .iter()
+            .map(|l| l.lock().unwrap_or_else(|e| e.into_inner()))
+            .collect();
+        if target.exists() {
+            return Err(format!("Target already exists: {}", target.to_string_lossy()));
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        move_dir(&src, &target)?;
+        db.move_gallery_paths(&normalize_path(&src), &new_path)
+            .map_err(|e| e.to_string())?;
+        Ok(new_path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
 pub async fn delete_gallery(
     id: i64,
     state: State<'_, AppState>,
+    app: AppHandle,
 ) -> Result<(), String> {
     let gallery = match state.db.get_gallery_by_id(id).map_err(|e| e.to_string())? {
         Some(g) => g,
         None => return Ok(()),
     };
 
+    let lock = state.gallery_locks.get(&gallery.path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
     state.db.delete_gallery_by_path(&gallery.path).map_err(|e| e.to_string())?;
+    publish_change(&app, ChangeKind::GalleryRemoved, Some(id), &gallery.path);
 
     // Delete cached thumbnail
     if !gallery.thumb_path.is_empty() {
@@ -470,6 +1551,95 @@ pub async fn delete_gallery(
     Ok(())
 }
 
+/// Add a gallery to the personal favorites shelf, or take it off. Returns
+/// whether it's a favorite now.
+#[tauri::command]
+pub async fn toggle_favorite(
+    id: i64,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<bool, String> {
+    let favorite = state.db.toggle_favorite(id).map_err(|e| e.to_string())?;
+    publish_change(&app, ChangeKind::GalleryUpserted, Some(id), "");
+    Ok(favorite)
+}
+
+/// Rate a gallery yourself, 0-5 in half stars; `None` clears the rating.
+#[tauri::command]
+pub async fn set_my_rating(
+    id: i64,
+    rating: Option<f64>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    if let Some(r) = rating {
+        if !(0.0..=5.0).contains(&r) || (r * 2.0).fract() != 0.0 {
+            return Err(format!("Rating must be 0-5 in steps of 0.5, got {}", r));
+        }
+    }
+    state.db.set_my_rating(id, rating).map_err(|e| e.to_string())?;
+    publish_change(&app, ChangeKind::GalleryUpserted, Some(id), "");
+    Ok(())
+}
+
+/// Put one of `COLOR_LABELS` on a gallery, or clear it with an empty label.
+#[tauri::command]
+pub async fn set_label(
+    id: i64,
+    label: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    if !label.is_empty() && !COLOR_LABELS.contains(&label.as_str()) {
+        return Err(format!("Unknown color label: {}", label));
+    }
+    state.db.set_color_label(id, &label).map_err(|e| e.to_string())?;
+    publish_change(&app, ChangeKind::GalleryUpserted, Some(id), "");
+    Ok(())
+}
+
+/// Mark a gallery as archived to the offline drive `label`, or back to local
+/// with an empty label. Archived galleries stay in search with their
+/// metadata, thumbnail and tags after their files are gone. With
+/// `remove_local`, the local folder or archive is deleted right away.
+#[tauri::command]
+pub async fn archive_gallery(
+    id: i64,
+    label: String,
+    remove_local: Option<bool>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Gallery {} not found", id))?;
+    let label = label.trim();
+    if label.is_empty() && remove_local.unwrap_or(false) {
+        return Err("A gallery needs an archive label before its files are removed".to_string());
+    }
+    state.db.set_archive_label(id, label).map_err(|e| e.to_string())?;
+
+    if remove_local.unwrap_or(false) {
+        let lock = state.gallery_locks.get(&gallery.path);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        let path = Path::new(&gallery.path);
+        let removed = if path.is_dir() {
+            fs::remove_dir_all(path)
+        } else if path.is_file() {
+            fs::remove_file(path)
+        } else {
+            Ok(())
+        };
+        removed.map_err(|e| format!("Archived, but failed to delete local files: {}", e))?;
+        state.db.mark_gallery_missing(&gallery.path).map_err(|e| e.to_string())?;
+        log::info!("[archive] {} archived to \"{}\", local files removed", gallery.path, label);
+    }
+    publish_change(&app, ChangeKind::GalleryUpserted, Some(id), &gallery.path);
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn move_folders(
     sources: Vec<String>,
@@ -518,10 +1688,118 @@ pub async fn move_folders(
     Ok(moved)
 }
 
+/// Move the pages in `page_ranges` (zero-based, inclusive, in reader order)
+/// out of a folder gallery into a new sibling folder, for folders that hold
+/// two works. The new gallery gets a copy of the descriptive metadata
+/// (titles, category, language, uploader, tags) but no URL, rating or page
+/// count of the original, so it can be identified on its own. The original
+/// info.txt is left as is. Returns the new gallery's id.
+#[tauri::command]
+pub async fn split_gallery(
+    id: i64,
+    page_ranges: Vec<PageRange>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<i64, String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Gallery not found".to_string())?;
+    let src = PathBuf::from(&gallery.path);
+    if archive::is_archive(&src) {
+        return Err("Archive galleries can't be split".to_string());
+    }
+
+    let pages = get_gallery_pages(id, state.clone()).await?;
+    if let Some(r) = page_ranges.iter().find(|r| r.start > r.end || r.end >= pages.len()) {
+        return Err(format!(
+            "Invalid page range {}-{} for {} pages",
+            r.start,
+            r.end,
+            pages.len()
+        ));
+    }
+    let (moving, staying): (Vec<PageInfo>, Vec<PageInfo>) = pages
+        .into_iter()
+        .partition(|p| page_ranges.iter().any(|r| r.contains(p.index)));
+    if moving.is_empty() || staying.is_empty() {
+        return Err("A split must leave pages in both galleries".to_string());
+    }
+
+    let parent = src.parent().ok_or("Invalid gallery path")?;
+    let target = (2..)
+        .map(|n| parent.join(format!("{} (part {})", gallery.folder_name, n)))
+        .find(|p| !p.exists())
+        .ok_or("No free folder name")?;
+
+    let mut info = scanner::parse_gallery(&src)?;
+    info.url.clear();
+    info.posted.clear();
+    info.file_size.clear();
+    info.rating = 0.0;
+    info.favorited = 0;
+    info.page_count = moving.len() as i64;
+    info.uploader_comment.clear();
+
+    let lock = state.gallery_locks.get(&gallery.path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    fs::create_dir(&target).map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+    for (i, page) in moving.iter().enumerate() {
+        if let Err(e) = fs::rename(&page.path, target.join(&page.filename)) {
+            // Put back what was moved so the original is left whole
+            for done in &moving[..i] {
+                let _ = fs::rename(target.join(&done.filename), &done.path);
+            }
+            let _ = fs::remove_dir(&target);
+            return Err(format!("Failed to move {}: {}", page.filename, e));
+        }
+    }
+    let info_path = target.join("info.txt");
+    fetcher::write_info_txt(&info_path, &info)?;
+
+    // Carry the order override and extras along with the pages they name
+    let order = state.db.get_page_order(id).unwrap_or_default();
+    let extras = state.db.get_page_extras(id).unwrap_or_default();
+    let moved: std::collections::HashSet<&str> = moving.iter().map(|p| p.filename.as_str()).collect();
+    let (moved_order, kept_order): (Vec<String>, Vec<String>) =
+        order.into_iter().partition(|n| moved.contains(n.as_str()));
+    let (moved_extras, kept_extras): (Vec<String>, Vec<String>) =
+        extras.into_iter().partition(|n| moved.contains(n.as_str()));
+    state.db.set_page_order(id, &kept_order).map_err(|e| e.to_string())?;
+    state.db.set_page_extras(id, &kept_extras).map_err(|e| e.to_string())?;
+
+    let (thumb_width, cover_crop) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.thumbnail_width, settings.cover_crop)
+    };
+    // The cover may have been among the moved pages
+    if let Ok(thumb) =
+        thumbnail::generate_gallery_thumbnail(&src, &state.cache_dir(), thumb_width, cover_crop, &kept_extras)
+    {
+        let _ = state.db.update_thumb_path(id, &thumb.to_string_lossy());
+    }
+
+    let thumb = thumbnail::generate_gallery_thumbnail(&target, &state.cache_dir(), thumb_width, cover_crop, &moved_extras)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let new_id = state
+        .db
+        .upsert_gallery(&normalize_path(&target), &info, &thumb, &scanner::get_file_mtime(&info_path))
+        .map_err(|e| e.to_string())?;
+    state.db.set_page_order(new_id, &moved_order).map_err(|e| e.to_string())?;
+    state.db.set_page_extras(new_id, &moved_extras).map_err(|e| e.to_string())?;
+
+    publish_change(&app, ChangeKind::GalleryUpserted, Some(id), &gallery.path);
+    publish_change(&app, ChangeKind::GalleryUpserted, Some(new_id), &normalize_path(&target));
+    Ok(new_id)
+}
+
 #[tauri::command]
 pub async fn delete_gallery_folder(
     path: String,
     state: State<'_, AppState>,
+    app: AppHandle,
 ) -> Result<u64, String> {
     let folder = PathBuf::from(&path);
 
@@ -534,7 +1812,8 @@ pub async fn delete_gallery_folder(
     let count = deleted.len() as u64;
 
     // Clean up thumbnails
-    for (_, thumb) in &deleted {
+    for (id, thumb) in &deleted {
+        publish_change(&app, ChangeKind::GalleryRemoved, Some(*id), "");
         if !thumb.is_empty() {
             let p = Path::new(thumb);
             if p.exists() {
@@ -553,11 +1832,82 @@ pub async fn delete_gallery_folder(
     Ok(count)
 }
 
+/// Permanently drop galleries flagged missing, along with their thumbnails.
+#[tauri::command]
+pub async fn purge_missing_galleries(state: State<'_, AppState>) -> Result<u64, String> {
+    let pending = state.incoming_duplicates.lock().unwrap().len();
+    if pending > 0 {
+        return Err(format!(
+            "{} incoming duplicate(s) are waiting to be resolved; nothing was purged",
+            pending
+        ));
+    }
+    let purged = state
+        .db
+        .purge_missing_galleries()
+        .map_err(|e| e.to_string())?;
+
+    for (_, thumb) in &purged {
+        if !thumb.is_empty() {
+            let _ = fs::remove_file(thumb);
+        }
+    }
+
+    Ok(purged.len() as u64)
+}
+
 #[tauri::command]
 pub async fn clear_cache(_state: State<'_, AppState>) -> Result<CacheCleanResult, String> {
     Ok(CacheCleanResult { removed: 0, freed_bytes: 0 })
 }
 
+/// Checkpoint the WAL, optimize the full-text index, refresh query planner
+/// statistics and VACUUM, so space freed by deletes is returned to the disk.
+#[tauri::command]
+pub async fn run_db_maintenance(state: State<'_, AppState>) -> Result<DbMaintenanceResult, String> {
+    let started = std::time::Instant::now();
+    let size_before = state.db.file_size();
+    state.db.run_maintenance().map_err(|e| e.to_string())?;
+    let size_after = state.db.file_size();
+    log::info!("[db] Maintenance: {} -> {} bytes", size_before, size_after);
+    Ok(DbMaintenanceResult {
+        size_before,
+        size_after,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Most rows `run_readonly_query` returns
+const MAX_QUERY_ROWS: usize = 5000;
+
+/// How long `run_readonly_query` lets a statement run
+const QUERY_TIME_LIMIT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Run an ad-hoc SELECT against the library database. The statement runs on
+/// its own read-only connection, so nothing it does can modify the library.
+#[tauri::command]
+pub async fn run_readonly_query(sql: String, state: State<'_, AppState>) -> Result<QueryResult, String> {
+    let keyword = sql
+        .trim_start()
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    if keyword != "select" && keyword != "with" {
+        return Err("Only SELECT statements are allowed".to_string());
+    }
+    state
+        .db
+        .run_readonly_query(&sql, MAX_QUERY_ROWS, QUERY_TIME_LIMIT)
+        .map_err(|e| match e.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::OperationInterrupted) => format!(
+                "Query timed out after {} seconds",
+                QUERY_TIME_LIMIT.as_secs()
+            ),
+            _ => e.to_string(),
+        })
+}
+
 /// Read a thumbnail file and return it as a base64 data URL.
 #[tauri::command]
 pub fn read_thumb(path: String) -> Result<String, String> {
@@ -576,7 +1926,7 @@ pub fn read_thumb(path: String) -> Result<String, String> {
     Ok(format!("data:{};base64,{}", mime, b64))
 }
 
-/// Pick a cookie file and copy it to the app data directory.
+/// Pick a cookie file and copy it to the profile directory.
 #[tauri::command]
 pub async fn set_cookie_file(app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
     use tauri_plugin_dialog::DialogExt;
@@ -592,11 +1942,8 @@ pub async fn set_cookie_file(app: AppHandle, state: State<'_, AppState>) -> Resu
         None => return Err("No file selected".to_string()),
     };
 
-    // Copy to app data dir as cookie.txt
-    let data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Cannot get app data dir: {}", e))?;
+    // Copy to the profile directory as cookie.txt
+    let data_dir = state.data_dir();
     let _ = fs::create_dir_all(&data_dir);
     let dest = data_dir.join("cookie.txt");
 
@@ -607,7 +1954,7 @@ pub async fn set_cookie_file(app: AppHandle, state: State<'_, AppState>) -> Resu
         let mut settings = state.settings.lock().unwrap();
         settings.cookie_path = String::new();
     }
-    save_settings(&state, &app);
+    save_settings(&state);
 
     Ok(dest.to_string_lossy().to_string())
 }
@@ -615,29 +1962,23 @@ pub async fn set_cookie_file(app: AppHandle, state: State<'_, AppState>) -> Resu
 /// Get the resolved cookie file path and whether it exists.
 #[tauri::command]
 pub async fn get_cookie_status(
-    app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<(String, bool), String> {
-    let settings = state.settings.lock().unwrap();
-    let path = if !settings.cookie_path.is_empty() {
-        PathBuf::from(&settings.cookie_path)
-    } else {
-        app.path()
-            .app_data_dir()
-            .map(|d| d.join("cookie.txt"))
-            .unwrap_or_else(|_| PathBuf::from("cookie.txt"))
-    };
+    let path = cookie_file_path(&state);
     let exists = path.exists();
     Ok((path.to_string_lossy().to_string(), exists))
 }
 
 /// Refresh a gallery's metadata by fetching from ExHentai and rewriting info.txt.
+/// Locally edited fields are kept and returned as conflicts unless `force`
+/// is set, in which case remote wins everywhere and edit flags are cleared.
 #[tauri::command]
 pub async fn refresh_gallery(
     id: i64,
+    force: Option<bool>,
     state: State<'_, AppState>,
     app: AppHandle,
-) -> Result<(), String> {
+) -> Result<Vec<FieldConflict>, String> {
     log::info!("[refresh] Starting refresh for gallery id={}", id);
 
     let gallery = state
@@ -652,187 +1993,3507 @@ pub async fn refresh_gallery(
         return Err("Gallery has no URL to refresh from".to_string());
     }
 
-    // Resolve cookie path: settings cookie_path > app_data_dir/cookie.txt
-    let cookie_path = {
-        let settings = state.settings.lock().unwrap();
-        if !settings.cookie_path.is_empty() {
-            PathBuf::from(&settings.cookie_path)
-        } else {
-            app.path()
-                .app_data_dir()
-                .map(|d| d.join("cookie.txt"))
-                .unwrap_or_else(|_| PathBuf::from("cookie.txt"))
-        }
-    };
-
-    log::info!("[refresh] Cookie path: {} (exists={})", cookie_path.display(), cookie_path.exists());
+    let cookie_path = require_cookie_file(&state)?;
 
-    if !cookie_path.exists() {
-        return Err(format!(
-            "Cookie file not found at: {}. Use Settings to select your cookie file.",
-            cookie_path.display()
-        ));
-    }
+    log::info!("[refresh] Cookie path: {}", cookie_path.display());
 
     // Fetch from ExHentai
     log::info!("[refresh] Fetching from URL: {}", gallery.url);
-    let fetched = fetcher::fetch_gallery_info(&gallery.url, &cookie_path)
+    let (fetched, newer) = fetch_refresh_data(&gallery, &cookie_path)
         .await
         .map_err(|e| format!("[refresh] Fetch failed: {}", e))?;
 
     log::info!("[refresh] Fetched title_en={}", fetched.title_en);
 
-    // Write updated info.txt
-    let info_path = Path::new(&gallery.path).join("info.txt");
-    fetcher::write_info_txt(&info_path, &fetched)
-        .map_err(|e| format!("[refresh] Write info.txt failed: {}", e))?;
+    let conflicts = apply_fetched_metadata(&state, &gallery, &fetched, force.unwrap_or(false))
+        .map_err(|e| format!("[refresh] {}", e))?;
+    let _ = state
+        .db
+        .set_newer_url(gallery.id, newer.as_deref().unwrap_or(""));
+    publish_change(&app, ChangeKind::TagsChanged, Some(gallery.id), &gallery.path);
+
+    log::info!(
+        "[refresh] Updated info.txt and DB for {} ({} conflicts kept local)",
+        gallery.path,
+        conflicts.len()
+    );
+
+    Ok(conflicts)
+}
+
+/// Current EH metadata for `gallery` and its newer version's URL, from the
+/// gdata API, scraping the gallery page only when the API can't answer for
+/// it. Fields the API doesn't carry keep their local values.
+async fn fetch_refresh_data(
+    gallery: &Gallery,
+    cookie_path: &Path,
+) -> Result<(ParsedGallery, Option<String>), String> {
+    let gid = fetcher::gallery_gid(&gallery.url);
+    match fetcher::fetch_gallery_api(std::slice::from_ref(&gallery.url), cookie_path).await {
+        Ok(mut found) => {
+            if let Some(mut hit) = gid.and_then(|gid| found.remove(&gid)) {
+                keep_fields_missing_from_api(&mut hit.0, gallery);
+                return Ok(hit);
+            }
+            log::info!("[refresh] API has no data for {}, scraping", gallery.url);
+        }
+        Err(e) => log::warn!("[refresh] API request failed, scraping {}: {}", gallery.url, e),
+    }
+    fetcher::fetch_gallery_versions(&gallery.url, cookie_path).await
+}
 
-    log::info!("[refresh] Wrote info.txt at {}", info_path.display());
+/// Carry over what gdata doesn't return: the favorite count and the
+/// uploader comment
+fn keep_fields_missing_from_api(fetched: &mut ParsedGallery, gallery: &Gallery) {
+    fetched.favorited = gallery.favorited;
+    fetched.uploader_comment = gallery.uploader_comment.clone();
+}
+
+/// Merge fetched metadata into a gallery's info.txt, re-parse it, regenerate
+/// the thumbnail, and upsert the DB row, all under the gallery lock so the
+/// watcher can't re-parse a half-written info.txt in between.
+///
+/// Locally edited fields survive unless `force` is set, locked fields always
+/// do; returns the fields whose remote value was not applied.
+pub(crate) fn apply_fetched_metadata(
+    state: &AppState,
+    gallery: &Gallery,
+    fetched: &ParsedGallery,
+    force: bool,
+) -> Result<Vec<FieldConflict>, String> {
+    let lock = state.gallery_locks.get(&gallery.path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
 
-    // Re-scan: parse info.txt and upsert to DB
-    let parsed = scanner::parse_info_txt(&info_path)
-        .ok_or_else(|| "[refresh] Failed to re-parse updated info.txt".to_string())?;
+    let gallery_path = Path::new(&gallery.path);
+    let info_path = scanner::gallery_info_path(gallery_path);
+    // The DB row is the local version: info.txt plus any DB-only edits
+    let local = state.db.get_parsed_gallery(gallery.id).ok();
+    let (merged, conflicts) = match local {
+        Some(local) => {
+            // Locked fields are protected even on a forced refresh
+            let mut protected = state.db.get_field_locks(gallery.id).unwrap_or_default();
+            let baseline: Option<ParsedGallery> = if force {
+                None
+            } else {
+                protected.extend(state.db.get_dirty_fields(gallery.id).unwrap_or_default());
+                state
+                    .db
+                    .get_remote_snapshot(gallery.id)
+                    .ok()
+                    .flatten()
+                    .and_then(|json| serde_json::from_str(&json).ok())
+            };
+            metadata::merge_remote(&local, baseline.as_ref(), fetched, &protected)
+        }
+        None => (fetched.clone(), Vec::new()),
+    };
 
-    let cache_dir = state.cache_dir.clone();
-    let thumb_width = state.settings.lock().unwrap().thumbnail_width;
+    // Archives are never rewritten, so their metadata lives in the DB only
+    let parsed = if archive::is_archive(gallery_path) {
+        merged
+    } else {
+        fetcher::write_info_txt(&info_path, &merged)
+            .map_err(|e| format!("Write info.txt failed: {}", e))?;
+        scanner::parse_info_txt(&info_path)
+            .ok_or_else(|| "Failed to re-parse updated info.txt".to_string())?
+    };
 
-    // Regenerate thumbnail
-    let thumb = scanner::get_first_image(Path::new(&gallery.path))
-        .and_then(|img| thumbnail::generate_thumbnail(&img, &cache_dir, thumb_width))
+    let (thumb_width, cover_crop) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.thumbnail_width, settings.cover_crop)
+    };
+    let extras = state.db.get_page_extras(gallery.id).unwrap_or_default();
+    let thumb = thumbnail::generate_gallery_thumbnail(gallery_path, &state.cache_dir(), thumb_width, cover_crop, &extras)
         .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_else(|| gallery.thumb_path.clone());
+        .unwrap_or_else(|_| gallery.thumb_path.clone());
+
+    // A forced refresh drops edit flags first so the upsert takes remote values
+    if force {
+        let _ = state.db.clear_dirty_fields(gallery.id);
+    }
 
     let info_mtime = scanner::get_file_mtime(&info_path);
-    let folder_str = normalize_path(Path::new(&gallery.path));
-    state
+    let folder_str = normalize_path(gallery_path);
+    let gallery_id = state
         .db
         .upsert_gallery(&folder_str, &parsed, &thumb, &info_mtime)
         .map_err(|e| e.to_string())?;
 
-    Ok(())
+    let resampled = scanner::detect_resampled(gallery_path, &parsed.file_size);
+    let _ = state.db.set_resampled(gallery_id, resampled);
+    let _ = state.db.set_page_stats(gallery_id, &scanner::page_stats(gallery_path));
+    let _ = state.db.set_disk_size(&folder_str, scanner::disk_size(gallery_path));
+
+    // Remember what EH said so the next refresh can tell local edits apart
+    if let Ok(json) = serde_json::to_string(fetched) {
+        let _ = state.db.set_remote_snapshot(gallery_id, &json);
+    }
+
+    Ok(conflicts)
 }
 
-/// Batch-refresh multiple galleries from ExHentai.
+/// Edit a gallery's metadata in the DB. Edited fields are flagged so a later
+/// refresh keeps them. The result is also written to info.txt when
+/// `write_info` is set, defaulting to the write-back setting.
 #[tauri::command]
-pub async fn batch_refresh_galleries(
-    ids: Vec<i64>,
+pub async fn update_gallery_metadata(
+    id: i64,
+    patch: MetadataPatch,
+    write_info: Option<bool>,
     state: State<'_, AppState>,
     app: AppHandle,
 ) -> Result<(), String> {
-    let total = ids.len();
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Gallery not found")?;
 
-    // Resolve cookie path once
-    let cookie_path = {
-        let settings = state.settings.lock().unwrap();
-        if !settings.cookie_path.is_empty() {
-            PathBuf::from(&settings.cookie_path)
-        } else {
-            app.path()
-                .app_data_dir()
-                .map(|d| d.join("cookie.txt"))
-                .unwrap_or_else(|_| PathBuf::from("cookie.txt"))
-        }
-    };
+    let lock = state.gallery_locks.get(&gallery.path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
 
-    if !cookie_path.exists() {
-        return Err(format!(
-            "Cookie file not found at: {}. Use Settings to select your cookie file.",
-            cookie_path.display()
-        ));
+    state
+        .db
+        .update_gallery_metadata(id, &patch)
+        .map_err(|e| e.to_string())?;
+    state
+        .db
+        .mark_fields_dirty(id, &patch.fields())
+        .map_err(|e| e.to_string())?;
+
+    let write_back = state.settings.lock().unwrap().write_back_info_txt;
+    if write_info.unwrap_or(write_back) {
+        write_db_metadata_to_info_txt(&state, &gallery)?;
     }
 
-    let cache_dir = state.cache_dir.clone();
-    let thumb_width = state.settings.lock().unwrap().thumbnail_width;
+    publish_change(&app, ChangeKind::GalleryUpserted, Some(id), &gallery.path);
+    Ok(())
+}
 
-    for (i, id) in ids.iter().enumerate() {
-        let gallery = match state.db.get_gallery_by_id(*id) {
-            Ok(Some(g)) => g,
-            _ => {
-                let _ = app.emit("batch-refresh-progress", serde_json::json!({
-                    "done": i + 1, "total": total, "current_title": "?"
-                }));
-                continue;
-            }
-        };
+/// Add tags to many galleries at once; galleries already carrying them are
+/// left as they are. See `edit_tags_batch` for `write_info`.
+#[tauri::command]
+pub async fn add_tags_to_galleries(
+    ids: Vec<i64>,
+    tags: Vec<TagEntry>,
+    write_info: Option<bool>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<TagEditReport, String> {
+    edit_tags_batch(&state, &app, &ids, &normalize_tag_entries(tags)?, &[], write_info)
+}
 
-        let title = if !gallery.title_en.is_empty() {
-            gallery.title_en.clone()
+/// Remove tags from many galleries at once.
+#[tauri::command]
+pub async fn remove_tags_from_galleries(
+    ids: Vec<i64>,
+    tags: Vec<TagEntry>,
+    write_info: Option<bool>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<TagEditReport, String> {
+    edit_tags_batch(&state, &app, &ids, &[], &normalize_tag_entries(tags)?, write_info)
+}
+
+/// Make `alias` (e.g. "artist:john doe") an alternate spelling of `target`
+/// ("artist:johndoe"): tag listings show it as the target and searching
+/// either finds both. Gallery tags are left as stored; see `migrate_tag`.
+/// Returns the target the alias now points to, after following aliases.
+#[tauri::command]
+pub async fn set_tag_alias(
+    alias: TagEntry,
+    target: TagEntry,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<TagEntry, String> {
+    let mut tags = normalize_tag_entries(vec![alias, target])?;
+    let target = state.db.resolve_tag(&tags.pop().unwrap()).map_err(|e| e.to_string())?;
+    let alias = tags.pop().unwrap();
+    if alias.namespace == target.namespace && alias.tag == target.tag {
+        return Err(format!("{}:{} can't be an alias of itself", alias.namespace, alias.tag));
+    }
+    state.db.set_tag_alias(&alias, &target).map_err(|e| e.to_string())?;
+    publish_change(&app, ChangeKind::TagsChanged, None, "");
+    Ok(target)
+}
+
+#[tauri::command]
+pub async fn remove_tag_alias(
+    alias: TagEntry,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let alias = normalize_tag_entries(vec![alias])?.remove(0);
+    state.db.remove_tag_alias(&alias).map_err(|e| e.to_string())?;
+    publish_change(&app, ChangeKind::TagsChanged, None, "");
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_tag_aliases(state: State<'_, AppState>) -> Result<Vec<TagAlias>, String> {
+    state.db.get_tag_aliases().map_err(|e| e.to_string())
+}
+
+/// Rewrite tag `from` to `to` (or `to`'s target, when it's an alias) on
+/// every gallery carrying it. See `edit_tags_batch` for `write_info`.
+#[tauri::command]
+pub async fn migrate_tag(
+    from: TagEntry,
+    to: TagEntry,
+    write_info: Option<bool>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<TagEditReport, String> {
+    let mut tags = normalize_tag_entries(vec![from, to])?;
+    let to = state.db.resolve_tag(&tags.pop().unwrap()).map_err(|e| e.to_string())?;
+    let from = tags.pop().unwrap();
+    if from.namespace == to.namespace && from.tag == to.tag {
+        return Err("Source and target tag are the same".to_string());
+    }
+    let ids = state.db.get_galleries_with_tag(&from).map_err(|e| e.to_string())?;
+    log::info!(
+        "[tags] Migrating {}:{} -> {}:{} on {} galleries",
+        from.namespace,
+        from.tag,
+        to.namespace,
+        to.tag,
+        ids.len()
+    );
+    edit_tags_batch(&state, &app, &ids, &[to], &[from], write_info)
+}
+
+/// Trimmed and NFC with a lowercase namespace, as info.txt tags are
+/// stored; every tag needs a namespace and a value
+fn normalize_tag_entries(tags: Vec<TagEntry>) -> Result<Vec<TagEntry>, String> {
+    let tags: Vec<TagEntry> = tags
+        .into_iter()
+        .map(|t| TagEntry {
+            namespace: scanner::to_nfc(t.namespace.trim()).to_lowercase(),
+            tag: scanner::to_nfc(t.tag.trim()),
+            translation: String::new(),
+        })
+        .collect();
+    if let Some(bad) = tags.iter().find(|t| t.namespace.is_empty() || t.tag.is_empty()) {
+        return Err(format!("Tags need a namespace and a value: \"{}:{}\"", bad.namespace, bad.tag));
+    }
+    Ok(tags)
+}
+
+/// Apply a tag edit to each gallery under its lock, marking its tags as
+/// locally edited. With `write_info` (default: the `write_back_info_txt`
+/// setting) each changed gallery's info.txt is rewritten too. Publishes a
+/// `TagsChanged` change per edited gallery; one gallery failing doesn't
+/// stop the rest.
+fn edit_tags_batch(
+    state: &AppState,
+    app: &AppHandle,
+    ids: &[i64],
+    add: &[TagEntry],
+    remove: &[TagEntry],
+    write_info: Option<bool>,
+) -> Result<TagEditReport, String> {
+    if add.is_empty() && remove.is_empty() {
+        return Err("No tags given".to_string());
+    }
+    let write_info = write_info.unwrap_or(state.settings.lock().unwrap().write_back_info_txt);
+    let mut report = TagEditReport::default();
+
+    for &id in ids {
+        let gallery = match state.db.get_gallery_by_id(id) {
+            Ok(Some(g)) => g,
+            Ok(None) => {
+                report.failures.push(ScanFailure {
+                    path: id.to_string(),
+                    reason: "Gallery not found".to_string(),
+                });
+                continue;
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        let lock = state.gallery_locks.get(&gallery.path);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        let changed = match state.db.edit_gallery_tags(id, add, remove) {
+            Ok(changed) => changed,
+            Err(e) => {
+                report.failures.push(ScanFailure {
+                    path: gallery.path.clone(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        if !changed {
+            report.unchanged += 1;
+            continue;
+        }
+        report.changed += 1;
+        let _ = state.db.mark_fields_dirty(id, &["tags".to_string()]);
+        if write_info {
+            if let Err(reason) = write_db_metadata_to_info_txt(state, &gallery) {
+                report.failures.push(ScanFailure {
+                    path: gallery.path.clone(),
+                    reason,
+                });
+            }
+        }
+        publish_change(app, ChangeKind::TagsChanged, Some(id), &gallery.path);
+    }
+    Ok(report)
+}
+
+/// Apply one bulk operation to a multi-selection: `operation` is a
+/// `SelectionOp` name ("set_label", "add_tags", "export", ...) and `params`
+/// its fields. Emits `selection-progress` per gallery; a gallery failing
+/// is reported and doesn't stop the rest. "refresh" is queued as a batch
+/// refresh job instead.
+#[tauri::command]
+pub async fn apply_to_selection(
+    ids: Vec<i64>,
+    operation: String,
+    params: Option<serde_json::Value>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<SelectionReport, String> {
+    let mut fields = match params {
+        Some(serde_json::Value::Object(map)) => map,
+        Some(serde_json::Value::Null) | None => serde_json::Map::new(),
+        Some(_) => return Err("Operation parameters must be an object".to_string()),
+    };
+    fields.insert("op".to_string(), serde_json::Value::String(operation.clone()));
+    let mut op: SelectionOp = serde_json::from_value(serde_json::Value::Object(fields))
+        .map_err(|e| format!("Invalid operation {}: {}", operation, e))?;
+
+    // Validate once up front rather than failing every gallery
+    match &mut op {
+        SelectionOp::SetLabel { label }
+            if !label.is_empty() && !COLOR_LABELS.contains(&label.as_str()) =>
+        {
+            return Err(format!("Unknown color label: {}", label));
+        }
+        SelectionOp::SetRating { rating: Some(r) }
+            if !(0.0..=5.0).contains(r) || (*r * 2.0).fract() != 0.0 =>
+        {
+            return Err(format!("Rating must be 0-5 in steps of 0.5, got {}", r));
+        }
+        SelectionOp::AddTags { tags, .. } | SelectionOp::RemoveTags { tags, .. } => {
+            *tags = normalize_tag_entries(std::mem::take(tags))?;
+            if tags.is_empty() {
+                return Err("No tags given".to_string());
+            }
+        }
+        SelectionOp::Export { dest } if !Path::new(dest.trim()).is_dir() => {
+            return Err(format!("Not a directory: {}", dest));
+        }
+        SelectionOp::Refresh => {
+            let job_id = submit_job(&state, JobSpec::BatchRefresh { ids }, JobPriority::Normal);
+            return Ok(SelectionReport {
+                job_id: Some(job_id),
+                ..Default::default()
+            });
+        }
+        _ => {}
+    }
+
+    let total = ids.len();
+    let mut report = SelectionReport::default();
+    for (i, &id) in ids.iter().enumerate() {
+        let _ = app.emit("selection-progress", serde_json::json!({
+            "operation": operation, "done": i, "total": total
+        }));
+        let gallery = match state.db.get_gallery_by_id(id) {
+            Ok(Some(g)) => g,
+            Ok(None) => {
+                report.failures.push(ScanFailure {
+                    path: id.to_string(),
+                    reason: "Gallery not found".to_string(),
+                });
+                continue;
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        match apply_selection_op(&state, &app, &gallery, &op) {
+            Ok(true) => report.changed += 1,
+            Ok(false) => report.unchanged += 1,
+            Err(reason) => report.failures.push(ScanFailure {
+                path: gallery.path.clone(),
+                reason,
+            }),
+        }
+    }
+    let _ = app.emit("selection-progress", serde_json::json!({
+        "operation": operation, "done": total, "total": total
+    }));
+    log::info!(
+        "[selection] {} on {} galleries: {} changed, {} failed",
+        operation,
+        total,
+        report.changed,
+        report.failures.len()
+    );
+    Ok(report)
+}
+
+/// Run `op` on one gallery. Returns whether it changed anything.
+fn apply_selection_op(
+    state: &AppState,
+    app: &AppHandle,
+    gallery: &Gallery,
+    op: &SelectionOp,
+) -> Result<bool, String> {
+    let id = gallery.id;
+    match op {
+        SelectionOp::SetLabel { label } => {
+            if gallery.color_label == *label {
+                return Ok(false);
+            }
+            state.db.set_color_label(id, label).map_err(|e| e.to_string())?;
+        }
+        SelectionOp::SetRating { rating } => {
+            if gallery.my_rating == *rating {
+                return Ok(false);
+            }
+            state.db.set_my_rating(id, *rating).map_err(|e| e.to_string())?;
+        }
+        SelectionOp::AddToCollection { collection_id } => {
+            let gid = fetcher::gallery_gid(&gallery.url).ok_or("Gallery has no EH URL")?;
+            let item = CollectionFileItem {
+                gid,
+                title: gallery.title_en.clone(),
+                url: gallery.url.clone(),
+            };
+            let added = state
+                .db
+                .add_collection_items(*collection_id, &[item])
+                .map_err(|e| e.to_string())?;
+            return Ok(added > 0);
+        }
+        SelectionOp::AddTags { tags, write_info } | SelectionOp::RemoveTags { tags, write_info } => {
+            let (add, remove): (&[TagEntry], &[TagEntry]) = match op {
+                SelectionOp::AddTags { .. } => (tags, &[]),
+                _ => (&[], tags),
+            };
+            let mut tag_report = edit_tags_batch(state, app, &[id], add, remove, *write_info)?;
+            if let Some(failure) = tag_report.failures.pop() {
+                return Err(failure.reason);
+            }
+            return Ok(tag_report.changed > 0);
+        }
+        SelectionOp::Export { dest } => {
+            let src = Path::new(&gallery.path);
+            let name = src.file_name().ok_or("Invalid gallery path")?;
+            let target = Path::new(dest.trim()).join(name);
+            let target_path = normalize_path(&target);
+            let locks = state.gallery_locks.get_many(&[&gallery.path, &target_path]);
+            let _guards: Vec<_> = locks
+                .iter()
+                .map(|l| l.lock().unwrap_or_else(|e| e.into_inner()))
+                .collect();
+            if target.exists() {
+                return Err(format!("Target already exists: {}", target.to_string_lossy()));
+            }
+            if src.is_dir() {
+                copy_dir(src, &target)?;
+            } else {
+                fs::copy(src, &target).map_err(|e| e.to_string())?;
+            }
+            return Ok(true);
+        }
+        SelectionOp::Refresh => return Ok(false),
+    }
+    publish_change(app, ChangeKind::GalleryUpserted, Some(id), "");
+    Ok(true)
+}
+
+/// Write a gallery's current DB metadata, including DB-only edits, to its info.txt.
+#[tauri::command]
+pub async fn sync_info_txt(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Gallery not found")?;
+
+    let lock = state.gallery_locks.get(&gallery.path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    write_db_metadata_to_info_txt(&state, &gallery)
+}
+
+/// Caller must hold the gallery lock. Archive galleries are left untouched;
+/// their metadata lives in the DB only.
+fn write_db_metadata_to_info_txt(state: &AppState, gallery: &Gallery) -> Result<(), String> {
+    if archive::is_archive(Path::new(&gallery.path)) {
+        return Ok(());
+    }
+    let parsed = state
+        .db
+        .get_parsed_gallery(gallery.id)
+        .map_err(|e| e.to_string())?;
+    let info_path = Path::new(&gallery.path).join("info.txt");
+    fetcher::write_info_txt(&info_path, &parsed)
+        .map_err(|e| format!("Write info.txt failed: {}", e))?;
+    state
+        .db
+        .update_info_modified(gallery.id, &scanner::get_file_mtime(&info_path))
+        .map_err(|e| e.to_string())
+}
+
+/// Set the per-gallery display title; an empty title falls back to the EH titles.
+#[tauri::command]
+pub async fn set_custom_title(
+    id: i64,
+    title: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .db
+        .set_custom_title(id, title.trim())
+        .map_err(|e| e.to_string())
+}
+
+/// Set or clear (empty string) a gallery's notes. Notes stay in the
+/// library database and are never written to info.txt.
+#[tauri::command]
+pub async fn set_gallery_note(
+    id: i64,
+    note: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .db
+        .set_gallery_note(id, note.trim())
+        .map_err(|e| e.to_string())
+}
+
+/// Set a custom field on a gallery; an empty value removes it.
+#[tauri::command]
+pub async fn set_gallery_field(
+    id: i64,
+    key: String,
+    value: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let key = key.trim();
+    if key.is_empty() || key.chars().count() > 64 {
+        return Err("Field names must be 1 to 64 characters".to_string());
+    }
+    state
+        .db
+        .set_gallery_field(id, key, value.trim())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_custom_field_keys(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    state.db.get_custom_field_keys().map_err(|e| e.to_string())
+}
+
+/// Lock or unlock a metadata field so scans and refreshes never change it.
+#[tauri::command]
+pub async fn set_field_lock(
+    id: i64,
+    field: String,
+    locked: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if !metadata::METADATA_FIELDS.contains(&field.as_str()) {
+        return Err(format!("Unknown metadata field: {}", field));
+    }
+    state
+        .db
+        .set_field_lock(id, &field, locked)
+        .map_err(|e| e.to_string())
+}
+
+/// Batch-refresh multiple galleries from ExHentai as a background job.
+/// Returns the job id.
+#[tauri::command]
+pub async fn batch_refresh_galleries(
+    ids: Vec<i64>,
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    Ok(submit_job(&state, JobSpec::BatchRefresh { ids }, JobPriority::Normal))
+}
+
+/// Attempts per gallery in a batch refresh when EH or the connection fails
+/// transiently
+const FETCH_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry; doubled for each one after
+const RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Throttled responses in a row after which a batch refresh pauses itself
+/// until the user resumes it
+const THROTTLES_BEFORE_PAUSE: u32 = 3;
+
+/// Most galleries a batch refresh fetches at once
+const MAX_REFRESH_CONCURRENCY: u32 = 3;
+
+/// Longest random wait allowed before a request
+const MAX_REFRESH_DELAY_MS: u64 = 60_000;
+
+/// What the workers of one batch refresh share
+struct RefreshRun<'a> {
+    ctx: &'a JobContext,
+    cookie_path: PathBuf,
+    pacing: RefreshPacing,
+    total: usize,
+    /// Galleries refreshed, failed or skipped so far
+    finished: AtomicUsize,
+    /// Throttled responses in a row
+    throttles: AtomicU32,
+}
+
+impl RefreshRun<'_> {
+    fn emit_progress(&self, done: usize, title: &str) {
+        let _ = self.ctx.app().emit("batch-refresh-progress", serde_json::json!({
+            "done": done, "total": self.total, "current_title": title
+        }));
+    }
+
+    /// Count a gallery as finished and report it
+    fn finish(&self, title: &str) {
+        let done = self.finished.fetch_add(1, Ordering::Relaxed) + 1;
+        self.emit_progress(done, title);
+    }
+}
+
+/// Refresh `ids[start..]`, checkpointing progress through the job context,
+/// held from the start when it was paused before a restart. Up to `RefreshPacing::concurrency` galleries are fetched at once, each
+/// request after a random delay and inside `fetcher::REQUEST_BUDGET`.
+/// Transient failures are retried with backoff; throttling cools every
+/// request down, and pauses the job if it keeps up. Every gallery's state is
+/// reported to the fetch queue.
+async fn run_batch_refresh(
+    ids: Vec<i64>,
+    start: usize,
+    paused: bool,
+    ctx: &JobContext,
+) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let total = ids.len();
+
+    let run = RefreshRun {
+        ctx,
+        // Resolve cookie path once
+        cookie_path: require_cookie_file(&state)?,
+        pacing: refresh_pacing(&state),
+        total,
+        finished: AtomicUsize::new(start),
+        throttles: AtomicU32::new(0),
+    };
+    state.fetch_queue.begin(ctx.id(), ids.get(start..).unwrap_or_default());
+    state.fetch_queue.set_paused(ctx.id(), paused);
+
+    // The gdata API answers a whole batch per request; gallery pages are
+    // only scraped for galleries it has no data for. Workers finish a batch
+    // before the next starts, and the checkpoint only covers galleries
+    // finished in order, so a restart never skips one still being fetched.
+    let batches = ids.get(start..).unwrap_or_default().chunks(fetcher::API_BATCH);
+    for (n, batch) in batches.enumerate() {
+        if ctx.is_cancelled() {
+            break;
+        }
+        let batch_start = start + n * fetcher::API_BATCH;
+        ctx.progress(batch_start as u64, total as u64, "");
+
+        let urls: Vec<String> = batch
+            .iter()
+            .filter_map(|id| state.db.get_gallery_by_id(*id).ok().flatten())
+            .map(|g| g.url)
+            .filter(|url| !url.is_empty())
+            .collect();
+        let mut from_api = HashMap::new();
+        if !urls.is_empty() {
+            if !pace_request(ctx, &run.pacing) {
+                break;
+            }
+            from_api = fetcher::fetch_gallery_api(&urls, &run.cookie_path)
+                .await
+                .unwrap_or_else(|e| {
+                    if fetcher::is_throttled(&e) {
+                        fetcher::note_throttled();
+                    }
+                    log::warn!("[batch-refresh] API request failed, scraping instead: {}", e);
+                    HashMap::new()
+                });
+        }
+
+        let from_api = Mutex::new(from_api);
+        let next = AtomicUsize::new(0);
+        let done = Mutex::new(vec![false; batch.len()]);
+        std::thread::scope(|scope| {
+            for _ in 0..run.pacing.concurrency {
+                scope.spawn(|| loop {
+                    let pos = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(&id) = batch.get(pos) else {
+                        break;
+                    };
+                    if !tauri::async_runtime::block_on(refresh_batch_item(&run, id, &from_api)) {
+                        break;
+                    }
+                    let mut done = done.lock().unwrap_or_else(|e| e.into_inner());
+                    done[pos] = true;
+                    let in_order = done.iter().take_while(|d| **d).count();
+                    ctx.progress((batch_start + in_order) as u64, total as u64, "");
+                });
+            }
+        });
+    }
+
+    state.fetch_queue.end(ctx.id());
+    let _ = app.emit("batch-refresh-complete", serde_json::json!({ "total": total }));
+    Ok(())
+}
+
+/// Refresh one gallery of a batch refresh, from the gdata API's answer when
+/// it had one and otherwise by scraping its page. Returns false if the job
+/// was cancelled before the gallery was done.
+async fn refresh_batch_item(
+    run: &RefreshRun<'_>,
+    id: i64,
+    from_api: &Mutex<HashMap<i64, (ParsedGallery, Option<String>)>>,
+) -> bool {
+    let ctx = run.ctx;
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let fetch_queue = &state.fetch_queue;
+
+    let gallery = match state.db.get_gallery_by_id(id) {
+        Ok(Some(g)) => g,
+        _ => {
+            fetch_queue.set(ctx.id(), id, "", FetchItemState::Failed {
+                reason: "Gallery not found".to_string(),
+            });
+            run.finish("?");
+            return true;
+        }
+    };
+
+    let title = if !gallery.title_en.is_empty() {
+        gallery.title_en.clone()
+    } else {
+        gallery.folder_name.clone()
+    };
+    run.emit_progress(run.finished.load(Ordering::Relaxed), &title);
+
+    if gallery.url.is_empty() {
+        fetch_queue.set(ctx.id(), id, &title, FetchItemState::Failed {
+            reason: "No gallery URL".to_string(),
+        });
+        run.finish(&title);
+        return true;
+    }
+
+    let mut api_hit = fetcher::gallery_gid(&gallery.url)
+        .and_then(|gid| from_api.lock().unwrap_or_else(|e| e.into_inner()).remove(&gid));
+    let mut attempt = 1;
+    let fetched = loop {
+        if let Some(mut hit) = api_hit.take() {
+            keep_fields_missing_from_api(&mut hit.0, &gallery);
+            break Ok(hit);
+        }
+        if !pace_request(ctx, &run.pacing) {
+            return false;
+        }
+        fetch_queue.set(ctx.id(), id, &title, FetchItemState::Fetching);
+        match fetcher::fetch_gallery_versions(&gallery.url, &run.cookie_path).await {
+            Err(e) if fetcher::is_throttled(&e) => {
+                // Retried once the cool-down, which every request waits
+                // out, is over
+                let delay = fetcher::note_throttled();
+                log::warn!("[batch-refresh] Throttled on gallery {}, cooling down for {:?}: {}", id, delay, e);
+                fetch_queue.set(ctx.id(), id, &title, FetchItemState::Retrying {
+                    attempt,
+                    next_attempt_at: unix_now() + delay.as_secs(),
+                    reason: e.clone(),
+                });
+                if run.throttles.fetch_add(1, Ordering::Relaxed) + 1 >= THROTTLES_BEFORE_PAUSE {
+                    run.throttles.store(0, Ordering::Relaxed);
+                    set_refresh_paused(&state, ctx.id(), true);
+                    let _ = app.emit("batch-refresh-paused", serde_json::json!({
+                        "job_id": ctx.id(), "reason": e
+                    }));
+                }
+            }
+            Err(e) if attempt < FETCH_ATTEMPTS && fetcher::is_transient(&e) => {
+                let delay = RETRY_DELAY * 2u32.pow(attempt - 1);
+                log::info!("[batch-refresh] Retrying gallery {} in {:?}: {}", id, delay, e);
+                fetch_queue.set(ctx.id(), id, &title, FetchItemState::Retrying {
+                    attempt,
+                    next_attempt_at: unix_now() + delay.as_secs(),
+                    reason: e,
+                });
+                if !wait_cancellable(ctx, delay) {
+                    return false;
+                }
+                attempt += 1;
+            }
+            result => {
+                if result.is_ok() {
+                    run.throttles.store(0, Ordering::Relaxed);
+                    fetcher::note_not_throttled();
+                }
+                break result;
+            }
+        }
+    };
+
+    let outcome = match fetched {
+        Ok((fetched, newer)) => {
+            match apply_fetched_metadata(&state, &gallery, &fetched, false) {
+                Ok(conflicts) => {
+                    let _ = state
+                        .db
+                        .set_newer_url(gallery.id, newer.as_deref().unwrap_or(""));
+                    publish_change(app, ChangeKind::TagsChanged, Some(gallery.id), &gallery.path);
+                    if !conflicts.is_empty() {
+                        log::info!(
+                            "[batch-refresh] Kept {} locally edited field(s) for gallery {}",
+                            conflicts.len(),
+                            id
+                        );
+                    }
+                    FetchItemState::Done
+                }
+                Err(e) => {
+                    log::warn!("[batch-refresh] Failed to apply gallery {}: {}", id, e);
+                    FetchItemState::Failed { reason: e }
+                }
+            }
+        }
+        Err(e) => {
+            log::warn!("[batch-refresh] Failed for gallery {}: {}", id, e);
+            FetchItemState::Failed { reason: e }
+        }
+    };
+    fetch_queue.set(ctx.id(), id, &title, outcome);
+    run.finish(&title);
+    true
+}
+
+/// Wait until a batch refresh may send its next request: not paused, past
+/// any throttling cool-down and inside the request budget, then a random
+/// delay on top. Returns false if the job was cancelled meanwhile.
+fn pace_request(ctx: &JobContext, pacing: &RefreshPacing) -> bool {
+    let state = ctx.app().state::<AppState>();
+    let step = std::time::Duration::from_secs(1);
+    loop {
+        let wait = if state.fetch_queue.is_paused(ctx.id()) {
+            step
         } else {
-            gallery.folder_name.clone()
+            fetcher::cooldown_remaining().max(fetcher::budget_wait())
         };
+        if wait.is_zero() {
+            break;
+        }
+        // In short steps, so pausing and resuming take effect promptly
+        if !wait_cancellable(ctx, wait.min(step)) {
+            return false;
+        }
+    }
+    wait_cancellable(
+        ctx,
+        fetcher::random_delay(
+            std::time::Duration::from_millis(pacing.min_delay_ms),
+            std::time::Duration::from_millis(pacing.max_delay_ms),
+        ),
+    )
+}
+
+fn refresh_pacing(state: &AppState) -> RefreshPacing {
+    let settings = state.settings.lock().unwrap();
+    RefreshPacing {
+        concurrency: settings.refresh_concurrency,
+        min_delay_ms: settings.refresh_min_delay_ms,
+        max_delay_ms: settings.refresh_max_delay_ms,
+    }
+}
+
+/// Hold a running batch refresh before its next request. It stays paused
+/// across restarts until resumed. Returns false if no bulk fetch with that
+/// job id is running.
+#[tauri::command]
+pub async fn pause_batch_refresh(job_id: u64, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(set_refresh_paused(&state, job_id, true))
+}
+
+/// Let a paused batch refresh carry on, including one that paused itself
+/// because EH kept throttling it.
+#[tauri::command]
+pub async fn resume_batch_refresh(job_id: u64, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(set_refresh_paused(&state, job_id, false))
+}
+
+/// Pause or resume a running batch refresh, and its stored job with it
+fn set_refresh_paused(state: &AppState, job_id: u64, paused: bool) -> bool {
+    if !state.fetch_queue.set_paused(job_id, paused) {
+        return false;
+    }
+    let persist_id = state
+        .jobs
+        .list()
+        .into_iter()
+        .find(|j| j.id == job_id)
+        .and_then(|j| j.persist_id);
+    if let Some(persist_id) = persist_id {
+        if let Err(e) = state.db.set_job_paused(persist_id, paused) {
+            log::warn!("[jobs] Failed to store pause of job {}: {}", job_id, e);
+        }
+    }
+    true
+}
+
+/// Set how many galleries batch refreshes fetch at once and the random wait
+/// before each request. Takes effect for the next batch refresh.
+#[tauri::command]
+pub async fn set_refresh_pacing(
+    pacing: RefreshPacing,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.refresh_concurrency = pacing.concurrency.clamp(1, MAX_REFRESH_CONCURRENCY);
+        let min_delay_ms = pacing.min_delay_ms.min(MAX_REFRESH_DELAY_MS);
+        settings.refresh_min_delay_ms = min_delay_ms;
+        settings.refresh_max_delay_ms = pacing.max_delay_ms.clamp(min_delay_ms, MAX_REFRESH_DELAY_MS);
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_refresh_pacing(state: State<'_, AppState>) -> Result<RefreshPacing, String> {
+    Ok(refresh_pacing(&state))
+}
+
+/// Per-gallery state of bulk refresh and identify jobs, the request budget
+/// and an estimated completion time.
+#[tauri::command]
+pub async fn get_fetch_queue_status(
+    state: State<'_, AppState>,
+) -> Result<FetchQueueStatus, String> {
+    Ok(state.fetch_queue.status())
+}
+
+/// Forget failed items of finished bulk fetches.
+#[tauri::command]
+pub async fn clear_fetch_failures(state: State<'_, AppState>) -> Result<(), String> {
+    state.fetch_queue.clear_failed();
+    Ok(())
+}
+
+/// Queue a long-running job that tries to identify every gallery without an
+/// EH URL, first by cover image hash and then by title search. Requests are
+/// spaced out by `fetcher::SEARCH_REQUEST_DELAY`. Returns the job id.
+#[tauri::command]
+pub async fn identify_all_galleries(state: State<'_, AppState>) -> Result<u64, String> {
+    let ids = state
+        .db
+        .get_unidentified_gallery_ids()
+        .map_err(|e| e.to_string())?;
+    Ok(submit_job(&state, JobSpec::Identify { ids }, JobPriority::Low))
+}
+
+/// Title-search hits from identification that need the user's
+/// confirmation before they're applied
+#[tauri::command]
+pub async fn get_identify_candidates(state: State<'_, AppState>) -> Result<Vec<IdentifyCandidate>, String> {
+    state.db.get_identify_candidates().map_err(|e| e.to_string())
+}
+
+/// Apply the held title-search hit for `id` as if identification had
+/// matched it.
+#[tauri::command]
+pub async fn confirm_identify_candidate(
+    id: i64,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let url = state
+        .db
+        .get_identify_candidate_url(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("No possible match for gallery {}", id))?;
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Gallery {} not found", id))?;
+    let cookie_path = require_cookie_file(&state)?;
+    let fetched = fetcher::fetch_gallery_info(&url, &cookie_path).await?;
+    apply_fetched_metadata(&state, &gallery, &fetched, false)?;
+    state.db.remove_identify_candidate(id).map_err(|e| e.to_string())?;
+    publish_change(&app, ChangeKind::TagsChanged, Some(id), &gallery.path);
+    Ok(())
+}
+
+/// Drop the held title-search hit for `id`
+#[tauri::command]
+pub async fn dismiss_identify_candidate(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    state.db.remove_identify_candidate(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_growth_stats(state: State<'_, AppState>) -> Result<Vec<GrowthPoint>, String> {
+    state.db.get_growth_stats().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_language_stats(state: State<'_, AppState>) -> Result<Vec<LanguageStat>, String> {
+    state.db.get_language_stats().map_err(|e| e.to_string())
+}
+
+/// Page width below which a gallery counts as low resolution by default
+const LOW_RES_WIDTH: u32 = 1200;
+
+/// Galleries worth re-downloading in original quality: low resolution
+/// (widest sampled page under `min_width`), resampled, or missing pages,
+/// ranked by own rating and then reading sessions. Galleries scanned before
+/// page stats were recorded are measured first.
+#[tauri::command]
+pub async fn get_quality_report(
+    min_width: Option<u32>,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<QualityReport, String> {
+    let unmeasured = state
+        .db
+        .get_galleries_without_page_stats()
+        .map_err(|e| e.to_string())?;
+    for (id, path) in unmeasured {
+        let path = Path::new(&path);
+        if path.exists() {
+            let _ = state.db.set_page_stats(id, &scanner::page_stats(path));
+        }
+    }
+    state
+        .db
+        .get_quality_report(min_width.unwrap_or(LOW_RES_WIDTH), limit.unwrap_or(200))
+        .map_err(|e| e.to_string())
+}
+
+/// Every tag in the library with its gallery count, for a tag index.
+/// `sort` is "count" (default, most used first) or "name".
+#[tauri::command]
+pub async fn get_all_tags(
+    namespace: Option<String>,
+    sort: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<TagCount>, String> {
+    let namespace = namespace.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    state
+        .db
+        .get_all_tags(namespace, sort.as_deref() == Some("name"))
+        .map_err(|e| e.to_string())
+}
+
+/// Pull favorite slot names from the EH account into settings.
+#[tauri::command]
+pub async fn sync_favorite_slots(
+    state: State<'_, AppState>,
+) -> Result<Vec<FavoriteSlot>, String> {
+    let cookie_path = require_cookie_file(&state)?;
+    let names = fetcher::fetch_favorite_slot_names(&cookie_path).await?;
+
+    let slots: Vec<FavoriteSlot> = default_favorite_slots()
+        .into_iter()
+        .zip(names.into_iter().map(Some).chain(std::iter::repeat(None)))
+        .map(|(slot, name)| FavoriteSlot {
+            name: name.filter(|n| !n.is_empty()).unwrap_or(slot.name),
+            ..slot
+        })
+        .collect();
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.favorite_slots = slots.clone();
+    }
+    save_settings(&state);
+    Ok(slots)
+}
+
+#[tauri::command]
+pub async fn get_favorite_slots(state: State<'_, AppState>) -> Result<Vec<FavoriteSlot>, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.favorite_slots.clone())
+}
+
+/// File a gallery under a favorite slot locally, or clear it with `None`.
+#[tauri::command]
+pub async fn set_gallery_favorite_slot(
+    id: i64,
+    slot: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if slot.is_some_and(|s| !(0..10).contains(&s)) {
+        return Err("Favorite slot must be between 0 and 9".to_string());
+    }
+    state
+        .db
+        .set_favorite_slot(id, slot)
+        .map_err(|e| e.to_string())
+}
+
+/// Bring over favorites, read history, and download dates from an EhViewer
+/// database, matched to local galleries by URL, folder name, or title.
+/// Local favorites are filed under `favorite_slot` (default 0) on galleries
+/// that aren't in a slot yet.
+#[tauri::command]
+pub async fn import_ehviewer_db(
+    path: String,
+    favorite_slot: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<ImportReport, String> {
+    let favorite_slot = favorite_slot.unwrap_or(0);
+    if !(0..10).contains(&favorite_slot) {
+        return Err(format!("Invalid favorite slot: {}", favorite_slot));
+    }
+    let items = importer::read_ehviewer_db(Path::new(&path))?;
+
+    let mut report = ImportReport {
+        total: items.len() as u64,
+        ..Default::default()
+    };
+    for item in &items {
+        let Some(id) = state
+            .db
+            .find_gallery_for_import(item)
+            .map_err(|e| e.to_string())?
+        else {
+            let label = if item.title.is_empty() { &item.url } else { &item.title };
+            report.unmatched.push(label.clone());
+            continue;
+        };
+        state
+            .db
+            .apply_imported_history(id, item, favorite_slot)
+            .map_err(|e| e.to_string())?;
+        report.matched += 1;
+        report.downloads += item.downloaded_at.is_some() as u64;
+        report.read += item.read_at.is_some() as u64;
+        report.favorites += item.favorited as u64;
+    }
+    Ok(report)
+}
+
+/// Associate URLs from an exported download list with local galleries that
+/// lack one, matching on title similarity and page count. Matched URLs are
+/// saved to the DB and info.txt unless `dry_run` is set.
+#[tauri::command]
+pub async fn match_url_list(
+    path: String,
+    dry_run: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<UrlListMatchReport, String> {
+    let content = scanner::read_text_file(Path::new(&path))
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let known = state.db.get_known_urls().map_err(|e| e.to_string())?;
+    let entries = matcher::parse_url_list(&content);
+
+    let mut report = UrlListMatchReport {
+        total: entries.len() as u64,
+        ..Default::default()
+    };
+    let entries: Vec<matcher::ListEntry> = entries
+        .into_iter()
+        .filter(|e| !known.contains(&e.url))
+        .collect();
+    report.already_known = report.total - entries.len() as u64;
+
+    let candidates = state
+        .db
+        .get_url_match_candidates()
+        .map_err(|e| e.to_string())?;
+    let matches = matcher::match_entries(&entries, &candidates);
+    let matched_entries: std::collections::HashSet<usize> =
+        matches.iter().map(|(ei, _, _)| *ei).collect();
+    report.unmatched = entries
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !matched_entries.contains(i))
+        .map(|(_, e)| e.url.clone())
+        .collect();
+
+    for (ei, id, score) in matches {
+        let url = &entries[ei].url;
+        let Some(gallery) = state.db.get_gallery_by_id(id).map_err(|e| e.to_string())? else {
+            continue;
+        };
+        if !dry_run.unwrap_or(false) {
+            let lock = state.gallery_locks.get(&gallery.path);
+            let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+            state
+                .db
+                .set_gallery_url(id, url)
+                .map_err(|e| e.to_string())?;
+            if let Err(reason) = write_db_metadata_to_info_txt(&state, &gallery) {
+                report.failures.push(ScanFailure {
+                    path: gallery.path.clone(),
+                    reason,
+                });
+            }
+        }
+        report.matched.push(UrlMatch {
+            gallery_id: id,
+            folder_name: gallery.folder_name,
+            url: url.clone(),
+            score,
+        });
+    }
+    Ok(report)
+}
+
+/// Job label for followed-artist checks, also used to avoid queueing twice
+pub const FOLLOW_CHECK_LABEL: &str = "Check followed artists";
+
+#[tauri::command]
+pub async fn follow_artist(artist: String, state: State<'_, AppState>) -> Result<(), String> {
+    let artist = artist.trim().to_lowercase();
+    if artist.is_empty() {
+        return Err("Artist name is required".to_string());
+    }
+    state.db.follow_artist(&artist).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn unfollow_artist(artist: String, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .db
+        .unfollow_artist(artist.trim())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_followed_artists(
+    state: State<'_, AppState>,
+) -> Result<Vec<FollowedArtist>, String> {
+    state.db.list_followed_artists().map_err(|e| e.to_string())
+}
+
+/// Check followed artists for new uploads now. Returns the job id.
+#[tauri::command]
+pub async fn check_followed_artists(state: State<'_, AppState>) -> Result<u64, String> {
+    Ok(submit_job(&state, JobSpec::CheckFollowedArtists, JobPriority::Low))
+}
+
+/// Whether a followed-artist check is already queued or running
+/// Whether a scan or any other job is queued or running
+pub fn jobs_busy(state: &AppState) -> bool {
+    state.scan_status.lock().unwrap().is_scanning
+        || state
+            .jobs
+            .list()
+            .iter()
+            .any(|j| matches!(j.status, JobStatus::Queued | JobStatus::Running))
+}
+
+pub fn follow_check_pending(state: &AppState) -> bool {
+    state.jobs.list().iter().any(|j| {
+        j.label == FOLLOW_CHECK_LABEL && matches!(j.status, JobStatus::Queued | JobStatus::Running)
+    })
+}
+
+/// Search each followed artist's tag and put uploads newer than the last
+/// check on the want-list, then emit a `follow-digest` with the new hits.
+/// An artist's first check only records a baseline, so following someone
+/// doesn't flood the want-list with their back catalogue.
+async fn run_check_followed_artists(ctx: &JobContext) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let cookie_path = require_cookie_file(&state)?;
+    let artists = state
+        .db
+        .list_followed_artists()
+        .map_err(|e| e.to_string())?;
+    let known = state.db.get_known_urls().map_err(|e| e.to_string())?;
+    let total = artists.len() as u64;
+    let mut hits: Vec<FollowHit> = Vec::new();
+
+    for (i, followed) in artists.iter().enumerate() {
+        ctx.progress(i as u64, total, &followed.artist);
+        let results = match fetcher::search_artist_uploads(&followed.artist, &cookie_path).await {
+            Ok(results) => results,
+            Err(e) => {
+                log::warn!("[follow] Search failed for {}: {}", followed.artist, e);
+                if !rate_limit_wait(ctx) {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        let newest = results
+            .iter()
+            .filter_map(|(url, _)| fetcher::gallery_gid(url))
+            .max()
+            .unwrap_or(0);
+        if followed.last_seen_gid > 0 {
+            for (url, title) in &results {
+                let Some(gid) = fetcher::gallery_gid(url) else {
+                    continue;
+                };
+                if gid <= followed.last_seen_gid || known.contains(url) {
+                    continue;
+                }
+                state
+                    .db
+                    .add_wanted(gid, url, title, &format!("artist:{}", followed.artist))
+                    .map_err(|e| e.to_string())?;
+                hits.push(FollowHit {
+                    artist: followed.artist.clone(),
+                    url: url.clone(),
+                    title: title.clone(),
+                });
+            }
+        }
+        state
+            .db
+            .set_artist_checked(&followed.artist, newest)
+            .map_err(|e| e.to_string())?;
+
+        if !rate_limit_wait(ctx) {
+            break;
+        }
+    }
+
+    if !hits.is_empty() {
+        let _ = app.emit("follow-digest", serde_json::json!({ "hits": hits }));
+    }
+    ctx.progress(total, total, &format!("{} new uploads", hits.len()));
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_follow_check_hours(
+    hours: u32,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.follow_check_hours = hours;
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_follow_check_hours(state: State<'_, AppState>) -> Result<u32, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.follow_check_hours)
+}
+
+/// Replace the scan schedules. Each root must be one of the library's
+/// roots, at most once.
+#[tauri::command]
+pub async fn set_scan_schedules(
+    schedules: Vec<ScanSchedule>,
+    skip_on_battery: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().unwrap();
+        let mut seen = std::collections::HashSet::new();
+        for schedule in &schedules {
+            if !settings.root_paths.contains(&schedule.root) {
+                return Err(format!("Not a library root: {}", schedule.root));
+            }
+            if !seen.insert(schedule.root.as_str()) {
+                return Err(format!("{} is scheduled twice", schedule.root));
+            }
+        }
+        settings.scan_schedules = schedules;
+        settings.scan_skip_on_battery = skip_on_battery;
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_scan_schedules(
+    state: State<'_, AppState>,
+) -> Result<(Vec<ScanSchedule>, bool), String> {
+    let settings = state.settings.lock().unwrap();
+    Ok((settings.scan_schedules.clone(), settings.scan_skip_on_battery))
+}
+
+/// Look up a batch of gallery URLs and/or bare gids in the library.
+/// Results are in input order.
+#[tauri::command]
+pub async fn check_owned(
+    urls_or_gids: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<OwnershipStatus>, String> {
+    lookup_owned(&state, urls_or_gids)
+}
+
+pub(crate) fn lookup_owned(
+    state: &AppState,
+    urls_or_gids: Vec<String>,
+) -> Result<Vec<OwnershipStatus>, String> {
+    let parsed: Vec<(String, Option<i64>)> = urls_or_gids
+        .into_iter()
+        .map(|input| {
+            let input = input.trim().to_string();
+            let gid = match input.parse::<i64>() {
+                Ok(gid) if gid > 0 => Some(gid),
+                _ => fetcher::gallery_gid(&input),
+            };
+            (input, gid)
+        })
+        .filter(|(input, _)| !input.is_empty())
+        .collect();
+    let gids: Vec<i64> = parsed.iter().filter_map(|(_, gid)| *gid).collect();
+    let owned = state
+        .db
+        .find_galleries_by_gid(&gids)
+        .map_err(|e| e.to_string())?;
+
+    Ok(parsed
+        .into_iter()
+        .map(|(input, gid)| {
+            let galleries = gid.and_then(|g| owned.get(&g)).cloned().unwrap_or_default();
+            OwnershipStatus {
+                input,
+                gid,
+                owned: !galleries.is_empty(),
+                galleries,
+            }
+        })
+        .collect())
+}
+
+/// New folders held back because their gid is already in the library.
+#[tauri::command]
+pub fn list_incoming_duplicates(state: State<'_, AppState>) -> Vec<IncomingDuplicate> {
+    state.incoming_duplicates.lock().unwrap().clone()
+}
+
+/// Settle a held duplicate: with `import`, index it as a gallery of its own;
+/// otherwise drop it from the list (a later scan flags it again while the
+/// folder is still there).
+#[tauri::command]
+pub async fn resolve_incoming_duplicate(
+    path: String,
+    import: bool,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let held = {
+        let mut pending = state.incoming_duplicates.lock().unwrap();
+        let before = pending.len();
+        pending.retain(|d| d.path != path);
+        pending.len() != before
+    };
+    if !held {
+        return Err(format!("{} isn't waiting as a duplicate", path));
+    }
+    if !import {
+        return Ok(());
+    }
+
+    let lock = state.gallery_locks.get(&path);
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    index_gallery_folder(&app, &state, &path)?;
+    Ok(())
+}
+
+/// Parse and thumbnail a single gallery folder and add it to the library.
+/// The caller holds the gallery lock. Returns the gallery id.
+fn index_gallery_folder(app: &AppHandle, state: &AppState, path: &str) -> Result<i64, String> {
+    let folder = Path::new(path);
+    let parsed = scanner::parse_gallery(folder)?;
+    let (thumb_width, cover_crop) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.thumbnail_width, settings.cover_crop)
+    };
+    let thumb = thumbnail::generate_gallery_thumbnail(folder, &state.cache_dir(), thumb_width, cover_crop, &[])
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let info_mtime = scanner::get_file_mtime(&scanner::gallery_info_path(folder));
+    let id = state
+        .db
+        .upsert_gallery(path, &parsed, &thumb, &info_mtime)
+        .map_err(|e| e.to_string())?;
+    let _ = state.db.set_resampled(id, scanner::detect_resampled(folder, &parsed.file_size));
+    let _ = state.db.set_page_stats(id, &scanner::page_stats(folder));
+    let _ = state.db.set_disk_size(path, scanner::disk_size(folder));
+    emit_scan_item(app, &state.db, "added", path);
+    publish_change(app, ChangeKind::GalleryUpserted, Some(id), path);
+    Ok(id)
+}
+
+/// Download the newer EH version of a gallery (see `Gallery::newer_url`)
+/// into a sibling folder, then carry over reading progress, notes, custom
+/// fields, ratings and collection entries. With `trash_old`, the old
+/// folder goes to the OS trash once the download has been verified.
+/// Returns the job id.
+#[tauri::command]
+pub async fn upgrade_gallery(
+    id: i64,
+    trash_old: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Gallery {} not found", id))?;
+    if gallery.newer_url.is_empty() {
+        return Err("No newer version known; refresh the gallery first".to_string());
+    }
+    require_cookie_file(&state)?;
+    let spec = JobSpec::UpgradeGallery {
+        id,
+        trash_old: trash_old.unwrap_or(false),
+    };
+    Ok(submit_job(&state, spec, JobPriority::Normal))
+}
+
+async fn run_upgrade_gallery(id: i64, trash_old: bool, ctx: &JobContext) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let cookie_path = require_cookie_file(&state)?;
+    let old = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Gallery {} not found", id))?;
+    let newer_url = old.newer_url.clone();
+    let gid = fetcher::gallery_gid(&newer_url).ok_or("Invalid newer version URL")?;
+    let (info, staging) = download_to_staging(ctx, &newer_url, &cookie_path).await?;
+
+    let old_path = Path::new(&old.path);
+    let parent = old_path.parent().ok_or("Invalid gallery path")?;
+    let title = if info.title_en.is_empty() { &info.title_jp } else { &info.title_en };
+    let new_path = normalize_path(&parent.join(gallery_folder_name(title, gid)));
+    let new_id = {
+        let lock = state.gallery_locks.get(&new_path);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        if Path::new(&new_path).exists() {
+            return Err(format!("Target already exists: {}", new_path));
+        }
+        move_dir(&staging, Path::new(&new_path))?;
+        index_gallery_folder(app, &state, &new_path)?
+    };
+    state
+        .db
+        .transfer_user_data(old.id, new_id)
+        .map_err(|e| e.to_string())?;
+    publish_change(app, ChangeKind::GalleryUpserted, Some(new_id), &new_path);
+    log::info!("[upgrade] {} -> {}", old.path, new_path);
+
+    if trash_old {
+        let lock = state.gallery_locks.get(&old.path);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        move_to_trash(old_path)?;
+        state.db.delete_gallery_by_path(&old.path).map_err(|e| e.to_string())?;
+        publish_change(app, ChangeKind::GalleryRemoved, Some(old.id), &old.path);
+        if !old.thumb_path.is_empty() {
+            let _ = fs::remove_file(&old.thumb_path);
+        }
+    }
+    Ok(())
+}
+
+/// Download a whole EH gallery into a new folder under `root`, which must be
+/// inside a library root, and add it to the library. Returns the job id.
+#[tauri::command]
+pub async fn download_gallery(
+    url: String,
+    root: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    queue_gallery_download(&state, &url, root)
+}
+
+/// Queue the download behind `download_gallery`, checked the same way; the
+/// first library root is used when `root` is `None`. Returns the job id.
+pub fn queue_gallery_download(state: &AppState, url: &str, root: Option<String>) -> Result<u64, String> {
+    let url = fetcher::canonical_gallery_url(url);
+    let gid = fetcher::gallery_gid(&url).ok_or_else(|| format!("Not a gallery URL: {}", url))?;
+    require_cookie_file(state)?;
+    let roots = state.settings.lock().unwrap().root_paths.clone();
+    let root = match root {
+        Some(root) if roots.iter().any(|r| Path::new(&root).starts_with(r)) => root,
+        Some(root) => return Err(format!("Not inside a library root: {}", root)),
+        None => roots.first().cloned().ok_or("No library root configured")?,
+    };
+    if !Path::new(&root).is_dir() {
+        return Err(format!("Folder not found: {}", root));
+    }
+    let owned = state.db.find_galleries_by_gid(&[gid]).map_err(|e| e.to_string())?;
+    if let Some(existing) = owned.get(&gid).and_then(|g| g.first()) {
+        return Err(format!("Already in the library: {}", existing.path));
+    }
+    Ok(submit_job(state, JobSpec::DownloadGallery { url, root }, JobPriority::Normal))
+}
+
+async fn run_download_gallery(url: &str, root: &str, ctx: &JobContext) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let cookie_path = require_cookie_file(&state)?;
+    let gid = fetcher::gallery_gid(url).ok_or("Invalid gallery URL")?;
+    let (info, staging) = download_to_staging(ctx, url, &cookie_path).await?;
+
+    let title = if info.title_en.is_empty() { &info.title_jp } else { &info.title_en };
+    let path = normalize_path(&Path::new(root).join(gallery_folder_name(title, gid)));
+    let id = {
+        let lock = state.gallery_locks.get(&path);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        if Path::new(&path).exists() {
+            return Err(format!("Target already exists: {}", path));
+        }
+        move_dir(&staging, Path::new(&path))?;
+        index_gallery_folder(app, &state, &path)?
+    };
+    publish_change(app, ChangeKind::GalleryUpserted, Some(id), &path);
+    let _ = app.emit("download-complete", serde_json::json!({
+        "job_id": ctx.id(), "id": id, "path": path
+    }));
+    log::info!("[download] {} -> {}", url, path);
+    Ok(())
+}
+
+/// Fetch the pages missing from a folder gallery (fewer images on disk than
+/// its page count) from EH, leaving the pages it has alone. Returns the job
+/// id.
+#[tauri::command]
+pub async fn repair_gallery(id: i64, state: State<'_, AppState>) -> Result<u64, String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Gallery {} not found", id))?;
+    if fetcher::gallery_gid(&gallery.url).is_none() {
+        return Err("Gallery has no EH URL".to_string());
+    }
+    if !Path::new(&gallery.path).is_dir() {
+        return Err("Only folder galleries can be repaired".to_string());
+    }
+    let missing = missing_pages(Path::new(&gallery.path), gallery.page_count.max(0) as usize)?;
+    if missing.is_empty() {
+        return Err("No pages are missing".to_string());
+    }
+    require_cookie_file(&state)?;
+    Ok(submit_job(&state, JobSpec::RepairGallery { id }, JobPriority::Normal))
+}
+
+async fn run_repair_gallery(id: i64, ctx: &JobContext) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let cookie_path = require_cookie_file(&state)?;
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Gallery {} not found", id))?;
+    let folder = Path::new(&gallery.path);
+    let page_count = gallery.page_count.max(0) as usize;
+    // Worked out again: pages may have been restored since it was queued
+    let missing = missing_pages(folder, page_count)?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let (prefix, width) = page_name_format(folder);
+    let pacing = refresh_pacing(&state);
+    let url = fetcher::canonical_gallery_url(&gallery.url);
+    let links = fetcher::fetch_page_links(&url, page_count, &cookie_path).await?;
+    if links.len() != page_count {
+        return Err(format!(
+            "EH lists {} pages, the library {}; the gallery may have changed",
+            links.len(),
+            page_count
+        ));
+    }
+
+    let title = if gallery.title_en.is_empty() { &gallery.title_jp } else { &gallery.title_en };
+    for (done, &page) in missing.iter().enumerate() {
+        ctx.progress(done as u64, missing.len() as u64, title);
+        let (ext, bytes) = fetch_page(ctx, &pacing, &links[page - 1], page, &cookie_path).await?;
+        let dimensions = image::ImageReader::new(std::io::Cursor::new(&bytes))
+            .with_guessed_format()
+            .ok()
+            .and_then(|r| r.into_dimensions().ok());
+        if dimensions.is_none() {
+            return Err(format!("Page {} is not a valid image", page));
+        }
+        let dest = folder.join(format!("{}{:0width$}.{}", prefix, page, ext, width = width));
+        let lock = state.gallery_locks.get(&gallery.path);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        fetcher::write_atomic(&dest, &bytes)
+            .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+    }
+    {
+        let lock = state.gallery_locks.get(&gallery.path);
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        index_gallery_folder(app, &state, &gallery.path)?;
+    }
+    ctx.progress(missing.len() as u64, missing.len() as u64, title);
+    log::info!("[repair] Fetched {} missing pages of {}", missing.len(), gallery.path);
+    Ok(())
+}
+
+/// A page image's name split around its page number, the last digit run:
+/// the text before it, the digits and the text after
+fn split_page_stem(stem: &str) -> Option<(&str, &str, &str)> {
+    let end = stem.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = stem[..end]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !c.is_ascii_digit())
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    Some((&stem[..start], &stem[start..end], &stem[end..]))
+}
+
+/// Names (without extension) of the images in `folder`
+fn image_stems(folder: &Path) -> Vec<String> {
+    scanner::get_all_images(folder)
+        .iter()
+        .map(|p| p.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default())
+        .collect()
+}
+
+/// Page number of each image in `folder`, from the last digit run in its
+/// name
+fn page_numbers(folder: &Path) -> Vec<Option<usize>> {
+    image_stems(folder)
+        .iter()
+        .map(|stem| split_page_stem(stem)?.1.parse().ok())
+        .collect()
+}
+
+/// Pages (1-based) of a `page_count`-page gallery that `folder` has no image
+/// for. Only works when the images are numbered by page; anything else
+/// can't be matched to EH's pages.
+fn missing_pages(folder: &Path, page_count: usize) -> Result<Vec<usize>, String> {
+    let mut present = std::collections::HashSet::new();
+    for number in page_numbers(folder) {
+        match number {
+            Some(n) if (1..=page_count).contains(&n) && present.insert(n) => {}
+            _ => return Err("Pages aren't numbered by page, so missing ones can't be told apart".to_string()),
+        }
+    }
+    Ok((1..=page_count).filter(|n| !present.contains(n)).collect())
+}
+
+/// Prefix and zero padding of the folder's page names, so fetched pages are
+/// named like the rest: the prefix most of them share, padded only if their
+/// numbers are. No prefix and 4 digits if it has no numbered pages.
+fn page_name_format(folder: &Path) -> (String, usize) {
+    let mut prefixes: HashMap<String, usize> = HashMap::new();
+    let mut width = 0;
+    for stem in image_stems(folder) {
+        let Some((prefix, digits, _)) = split_page_stem(&stem) else {
+            continue;
+        };
+        *prefixes.entry(prefix.to_string()).or_default() += 1;
+        if digits.starts_with('0') {
+            width = width.max(digits.len());
+        }
+    }
+    // Ties go to the first prefix in name order, so the result is stable
+    match prefixes.into_iter().max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0))) {
+        Some((prefix, _)) => (prefix, width),
+        None => (String::new(), 4),
+    }
+}
+
+/// Download every page of the EH gallery at `url`, with its info.txt, into
+/// a staging folder in the data dir, outside the roots so the watcher
+/// doesn't pick up a partial gallery. Pages staged by an interrupted run
+/// are kept for a retry, and every page must decode before the folder is
+/// handed back. A cancelled download's staging folder is removed.
+async fn download_to_staging(
+    ctx: &JobContext,
+    url: &str,
+    cookie_path: &Path,
+) -> Result<(ParsedGallery, PathBuf), String> {
+    let result = stage_gallery(ctx, url, cookie_path).await;
+    if result.is_err() && ctx.is_cancelled() {
+        if let Some(gid) = fetcher::gallery_gid(url) {
+            let staging = staging_dir(&ctx.app().state::<AppState>(), gid);
+            let _ = fs::remove_dir_all(&staging);
+        }
+    }
+    result
+}
+
+/// Staging folder of gallery `gid` in the active profile
+fn staging_dir(state: &AppState, gid: i64) -> PathBuf {
+    state.data_dir().join("downloads").join(gid.to_string())
+}
+
+/// Remove staging folders that no pending download or upgrade will resume,
+/// left by downloads that were given up on or by an earlier crash
+fn prune_staging(state: &AppState, pending: &[JobSpec]) {
+    let Ok(entries) = fs::read_dir(state.data_dir().join("downloads")) else {
+        return;
+    };
+    let keep: std::collections::HashSet<i64> = pending
+        .iter()
+        .filter_map(|spec| match spec {
+            JobSpec::DownloadGallery { url, .. } => fetcher::gallery_gid(url),
+            JobSpec::UpgradeGallery { id, .. } => state
+                .db
+                .get_gallery_by_id(*id)
+                .ok()
+                .flatten()
+                .and_then(|g| fetcher::gallery_gid(&g.newer_url)),
+            _ => None,
+        })
+        .collect();
+    for entry in entries.flatten() {
+        let gid = entry.file_name().to_string_lossy().parse::<i64>().ok();
+        if gid.is_some_and(|gid| keep.contains(&gid)) {
+            continue;
+        }
+        log::info!("[download] Removing stale staging folder {}", entry.path().display());
+        let _ = fs::remove_dir_all(entry.path());
+    }
+}
+
+async fn stage_gallery(
+    ctx: &JobContext,
+    url: &str,
+    cookie_path: &Path,
+) -> Result<(ParsedGallery, PathBuf), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let gid = fetcher::gallery_gid(url).ok_or("Invalid gallery URL")?;
+    let pacing = refresh_pacing(&state);
+
+    let info = fetcher::fetch_gallery_info(url, cookie_path).await?;
+    let page_count = info.page_count.max(0) as usize;
+    let links = fetcher::fetch_page_links(url, page_count, cookie_path).await?;
+    if links.len() != page_count {
+        return Err(format!("Found {} of {} pages", links.len(), page_count));
+    }
+    let title = if info.title_en.is_empty() { &info.title_jp } else { &info.title_en };
+
+    let staging = staging_dir(&state, gid);
+    fs::create_dir_all(&staging).map_err(|e| e.to_string())?;
+    for (i, link) in links.iter().enumerate() {
+        ctx.progress(i as u64, page_count as u64, title);
+        let _ = app.emit("download-progress", serde_json::json!({
+            "job_id": ctx.id(), "gid": gid, "done": i, "total": page_count, "title": title
+        }));
+        let stem = format!("{:04}", i + 1);
+        if page_staged(&staging, &stem) {
+            continue;
+        }
+        let (ext, bytes) = fetch_page(ctx, &pacing, link, i + 1, cookie_path).await?;
+        fs::write(staging.join(format!("{}.{}", stem, ext)), bytes).map_err(|e| e.to_string())?;
+    }
+    fetcher::write_info_txt(&scanner::gallery_info_path(&staging), &info)?;
+
+    // Every page must be there and decodable before anything is moved
+    let pages: Vec<PathBuf> = fs::read_dir(&staging)
+        .map_err(|e| e.to_string())?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| scanner::is_image_file(p))
+        .collect();
+    if pages.len() != page_count {
+        return Err(format!("Downloaded {} of {} pages", pages.len(), page_count));
+    }
+    if let Some(bad) = pages.iter().find(|p| image::image_dimensions(p).is_err()) {
+        return Err(format!("Downloaded page is not a valid image: {}", bad.display()));
+    }
+    ctx.progress(page_count as u64, page_count as u64, title);
+    Ok((info, staging))
+}
+
+/// Fetch page `page` (1-based) of a gallery through its viewer `link`,
+/// paced like a batch refresh and retried when throttled or on transient
+/// errors. Returns the image's extension and bytes.
+async fn fetch_page(
+    ctx: &JobContext,
+    pacing: &RefreshPacing,
+    link: &str,
+    page: usize,
+    cookie_path: &Path,
+) -> Result<(String, Vec<u8>), String> {
+    let mut attempt = 1;
+    let (image_url, bytes) = loop {
+        if !pace_request(ctx, pacing) {
+            return Err("Cancelled".to_string());
+        }
+        let fetched = match fetcher::fetch_image_url(link, cookie_path).await {
+            Ok(image_url) => fetcher::download_bytes(&image_url).await.map(|b| (image_url, b)),
+            Err(e) => Err(e),
+        };
+        match fetched {
+            Err(e) if attempt < FETCH_ATTEMPTS && fetcher::is_throttled(&e) => {
+                let delay = fetcher::note_throttled();
+                log::warn!("[download] Throttled on page {}, cooling down for {:?}: {}", page, delay, e);
+            }
+            Err(e) if attempt < FETCH_ATTEMPTS && fetcher::is_transient(&e) => {
+                log::info!("[download] Retrying page {}: {}", page, e);
+                if !wait_cancellable(ctx, RETRY_DELAY * 2u32.pow(attempt - 1)) {
+                    return Err("Cancelled".to_string());
+                }
+            }
+            Err(e) => return Err(format!("Page {}: {}", page, e)),
+            Ok(fetched) => {
+                fetcher::note_not_throttled();
+                break fetched;
+            }
+        }
+        attempt += 1;
+    };
+    let ext = Path::new(image_url.split('?').next().unwrap_or_default())
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("jpg")
+        .to_lowercase();
+    Ok((ext, bytes))
+}
+
+/// Whether the page named `stem` (any extension) is staged as a complete
+/// image. Anything else under that name is deleted so it's downloaded again.
+fn page_staged(staging: &Path, stem: &str) -> bool {
+    let Ok(entries) = fs::read_dir(staging) else {
+        return false;
+    };
+    let mut staged = false;
+    for path in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+        if path.file_stem().is_none_or(|s| s != stem) {
+            continue;
+        }
+        if !staged && scanner::is_image_file(&path) && image::image_dimensions(&path).is_ok() {
+            staged = true;
+        } else {
+            let _ = fs::remove_file(&path);
+        }
+    }
+    staged
+}
+
+/// Move a file or folder to the OS trash / recycle bin.
+fn move_to_trash(path: &Path) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    let status = std::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            "Add-Type -AssemblyName Microsoft.VisualBasic; \
+             [Microsoft.VisualBasic.FileIO.FileSystem]::DeleteDirectory($args[0], 'OnlyErrorDialogs', 'SendToRecycleBin')",
+        ])
+        .arg(path)
+        .status();
+    #[cfg(target_os = "macos")]
+    let status = std::process::Command::new("osascript")
+        .args([
+            "-e",
+            "on run argv\ntell application \"Finder\" to delete POSIX file (item 1 of argv)\nend run",
+        ])
+        .arg(path)
+        .status();
+    #[cfg(target_os = "linux")]
+    let status = std::process::Command::new("gio").arg("trash").arg(path).status();
+
+    match status {
+        Ok(s) if s.success() => Ok(()),
+        Ok(s) => Err(format!("Failed to trash {}: exit {}", path.display(), s)),
+        Err(e) => Err(format!("Failed to trash {}: {}", path.display(), e)),
+    }
+}
+
+/// Library changes after `cursor` (a `seq` from an earlier change or feed),
+/// for catching up on missed `library-changed` events.
+#[tauri::command]
+pub async fn get_changes_since(cursor: i64, state: State<'_, AppState>) -> Result<ChangeFeed, String> {
+    state.db.get_changes_since(cursor).map_err(|e| e.to_string())
+}
+
+/// Write a SHA-256 manifest of a gallery's pages and info.txt, for later
+/// bit-rot checks with `verify_manifest`. Returns the number of files listed.
+#[tauri::command]
+pub async fn create_manifest(id: i64, state: State<'_, AppState>) -> Result<usize, String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Gallery {} not found", id))?;
+    manifest::create(Path::new(&gallery.path))
+}
+
+/// Re-hash a gallery and compare it against the manifest written by
+/// `create_manifest`.
+#[tauri::command]
+pub async fn verify_manifest(id: i64, state: State<'_, AppState>) -> Result<ManifestReport, String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Gallery {} not found", id))?;
+    let report = manifest::verify(Path::new(&gallery.path))?;
+    if !report.corrupted.is_empty() || !report.missing.is_empty() {
+        log::warn!(
+            "[manifest] {}: {} corrupted, {} missing",
+            gallery.path,
+            report.corrupted.len(),
+            report.missing.len()
+        );
+    }
+    Ok(report)
+}
+
+/// Hash every page of galleries whose pages changed since they were last
+/// hashed, for `find_gallery_containing_image`. Returns the job id.
+#[tauri::command]
+pub async fn index_page_hashes(state: State<'_, AppState>) -> Result<u64, String> {
+    Ok(submit_job(&state, JobSpec::HashPages, JobPriority::Low))
+}
+
+#[tauri::command]
+pub async fn get_page_color_stats(state: State<'_, AppState>) -> Result<PageColorStats, String> {
+    state.db.get_page_color_stats().map_err(|e| e.to_string())
+}
+
+/// Rehash stale galleries. A gallery is stale when its folder or archive
+/// mtime differs from the one recorded with its hashes.
+fn run_hash_pages(ctx: &JobContext) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let stale: Vec<(i64, String, String)> = state
+        .db
+        .get_page_hash_states()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter_map(|(id, path, hashed_at)| {
+            let mtime = scanner::get_file_mtime(Path::new(&path));
+            (!mtime.is_empty() && mtime != hashed_at).then_some((id, path, mtime))
+        })
+        .collect();
+    let total = stale.len() as u64;
+
+    for (i, (id, path, mtime)) in stale.iter().enumerate() {
+        if ctx.is_cancelled() {
+            break;
+        }
+        ctx.progress(i as u64, total, path);
+        let order = state.db.get_page_order(*id).unwrap_or_default();
+        match hash_gallery_pages(Path::new(path), &order) {
+            Ok(pages) => {
+                if let Err(e) = state.db.replace_page_hashes(*id, mtime, &pages) {
+                    log::warn!("[hash] Failed to store page hashes for {}: {}", path, e);
+                }
+            }
+            Err(e) => log::warn!("[hash] Failed to hash {}: {}", path, e),
+        }
+    }
+    ctx.progress(total, total, "");
+    Ok(())
+}
+
+/// Hashes and decoded metadata of each page in reader order, following the gallery's page order
+/// override if it has one
+fn hash_gallery_pages(gallery: &Path, order: &[String]) -> Result<Vec<PageHash>, String> {
+    let page_hash = |filename: String, data: Vec<u8>| {
+        let sha1 = fetcher::data_sha1(&data);
+        let features = thumbnail::page_features(data).ok();
+        PageHash {
+            filename,
+            sha1,
+            phash: features.as_ref().map(|f| f.phash),
+            width: features.as_ref().map(|f| f.width),
+            height: features.as_ref().map(|f| f.height),
+            grayscale: features.as_ref().map(|f| f.grayscale),
+        }
+    };
+    if archive::is_archive(gallery) {
+        let mut archive = archive::open(gallery)?;
+        let names: Vec<String> = archive.image_entries().into_iter().map(|e| e.name.clone()).collect();
+        return scanner::apply_page_order(names, order, |n| n.as_str())
+            .into_iter()
+            .map(|name| {
+                let data = archive.read(&name)?;
+                let filename = name.rsplit('/').next().unwrap_or_default().to_string();
+                Ok(page_hash(filename, data))
+            })
+            .collect();
+    }
+    let images = scanner::get_all_images(gallery);
+    let images = scanner::apply_page_order(images, order, |p| {
+        p.file_name().and_then(|n| n.to_str()).unwrap_or_default()
+    });
+    images
+        .into_iter()
+        .map(|p| {
+            let data = fs::read(&p).map_err(|e| format!("Can't read {}: {}", p.display(), e))?;
+            let filename = p
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            Ok(page_hash(filename, data))
+        })
+        .collect()
+}
+
+/// Find the local galleries and pages an image came from, by exact SHA-1.
+/// Takes an image path or a 40-digit hex SHA-1; only galleries indexed by
+/// `index_page_hashes` are searched.
+#[tauri::command]
+pub async fn find_gallery_containing_image(
+    path_or_hash: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<PageMatch>, String> {
+    let input = path_or_hash.trim();
+    let sha1 = if input.len() == 40 && input.chars().all(|c| c.is_ascii_hexdigit()) {
+        input.to_ascii_lowercase()
+    } else {
+        fetcher::file_sha1(Path::new(input)).ok_or_else(|| format!("Can't read image: {}", input))?
+    };
+    state.db.find_pages_by_hash(&sha1).map_err(|e| e.to_string())
+}
+
+/// Default perceptual hash distance for `find_visually_similar`
+const SIMILAR_THRESHOLD: u32 = 10;
+
+/// Galleries with pages that look like a gallery's pages (`id_or_image` is
+/// a gallery id) or like an image file, by perceptual hash. Finds redraws,
+/// decensors and colourised versions that share no metadata. Only galleries
+/// indexed by `index_page_hashes` are searched; best matches come first.
+#[tauri::command]
+pub async fn find_visually_similar(
+    id_or_image: String,
+    threshold: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<SimilarGallery>, String> {
+    let threshold = threshold.unwrap_or(SIMILAR_THRESHOLD).min(64);
+    let input = id_or_image.trim();
+    let pages = state.db.get_page_phashes().map_err(|e| e.to_string())?;
+
+    let source_id = input.parse::<i64>().ok();
+    let query: Vec<u64> = match source_id {
+        Some(id) => {
+            let hashes: Vec<u64> = pages.iter().filter(|p| p.0 == id).map(|p| p.2).collect();
+            if hashes.is_empty() {
+                return Err(format!("Gallery {} has no indexed pages", id));
+            }
+            hashes
+        }
+        None => {
+            let data = fs::read(input).map_err(|e| format!("Can't read image: {}", e))?;
+            vec![thumbnail::perceptual_hash(data)?]
+        }
+    };
+    // Blank and flat pages hash to all zeros or ones and match each other
+    let informative = |h: &u64| *h != 0 && *h != u64::MAX;
+    let query: Vec<u64> = query.into_iter().filter(informative).collect();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // gallery id -> (best distance, matched pages)
+    let mut hits: HashMap<i64, (u32, usize)> = HashMap::new();
+    for (gallery_id, _, hash) in pages.iter().filter(|p| Some(p.0) != source_id && informative(&p.2)) {
+        let distance = query.iter().map(|q| (q ^ hash).count_ones()).min().unwrap_or(u32::MAX);
+        if distance <= threshold {
+            let hit = hits.entry(*gallery_id).or_insert((distance, 0));
+            hit.0 = hit.0.min(distance);
+            hit.1 += 1;
+        }
+    }
+
+    let ids: Vec<i64> = hits.keys().copied().collect();
+    let mut summaries = state.db.get_gallery_summaries(&ids).map_err(|e| e.to_string())?;
+    let mut similar: Vec<SimilarGallery> = hits
+        .into_iter()
+        .filter_map(|(id, (distance, matched_pages))| {
+            Some(SimilarGallery {
+                gallery: summaries.remove(&id)?,
+                distance,
+                matched_pages,
+            })
+        })
+        .collect();
+    similar.sort_by(|a, b| {
+        a.distance
+            .cmp(&b.distance)
+            .then(b.matched_pages.cmp(&a.matched_pages))
+    });
+    Ok(similar)
+}
+
+/// Start or stop the browser extension endpoint to match settings. Any
+/// running server is stopped first, so a port change takes effect.
+pub fn apply_companion(state: &AppState, app: &AppHandle) -> Result<(), String> {
+    let previous = state.companion.lock().unwrap().take();
+    // Dropping joins the server thread; no locks are held here
+    drop(previous);
+
+    let (enabled, port) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.companion_enabled, settings.companion_port)
+    };
+    if enabled {
+        let handle = companion::start_companion(port, app.clone())?;
+        *state.companion.lock().unwrap() = Some(handle);
+    }
+    Ok(())
+}
+
+fn companion_status(state: &AppState) -> CompanionStatus {
+    let running = state
+        .companion
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|h| h.is_running());
+    let settings = state.settings.lock().unwrap();
+    CompanionStatus {
+        enabled: settings.companion_enabled,
+        running,
+        port: settings.companion_port,
+        token: settings.companion_token.clone(),
+        origin: settings.companion_origin.clone(),
+    }
+}
+
+/// Enable or disable the localhost endpoint for the browser extension,
+/// optionally on a new port or pinned to the extension's origin (empty
+/// unpins it). A token is generated the first time it's enabled.
+#[tauri::command]
+pub async fn set_companion_enabled(
+    enabled: bool,
+    port: Option<u16>,
+    origin: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<CompanionStatus, String> {
+    let origin = origin.map(|o| o.trim().trim_end_matches('/').to_string());
+    if let Some(o) = origin.as_deref().filter(|o| !o.is_empty()) {
+        if !o.contains("-extension://") {
+            return Err(format!("Not a browser extension origin: {}", o));
+        }
+    }
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.companion_enabled = enabled;
+        if let Some(port) = port.filter(|p| *p > 0) {
+            settings.companion_port = port;
+        }
+        if let Some(origin) = origin {
+            settings.companion_origin = origin;
+        }
+        if enabled && settings.companion_token.is_empty() {
+            settings.companion_token = companion::new_token()?;
+        }
+    }
+    save_settings(&state);
+    apply_companion(&state, &app)?;
+    Ok(companion_status(&state))
+}
+
+#[tauri::command]
+pub async fn get_companion_status(state: State<'_, AppState>) -> Result<CompanionStatus, String> {
+    Ok(companion_status(&state))
+}
+
+/// Replace the extension token; the old one stops working immediately.
+#[tauri::command]
+pub async fn regenerate_companion_token(
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let token = companion::new_token()?;
+    state.settings.lock().unwrap().companion_token = token.clone();
+    save_settings(&state);
+    Ok(token)
+}
+
+/// Install an EhTagTranslation database (`db.text.json`) for tag display
+/// names and import it. `None` uninstalls it. Returns the import job id.
+#[tauri::command]
+pub async fn set_tag_translation_path(
+    path: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Option<u64>, String> {
+    let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if let Some(p) = &path {
+        // Fail now rather than in the job on a file that isn't a database
+        translation::load(Path::new(p))?;
+    }
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.tag_translation_path = path.clone().unwrap_or_default();
+    }
+    save_settings(&state);
+    if path.is_none() {
+        state.db.replace_tag_translations(&[]).map_err(|e| e.to_string())?;
+        return Ok(None);
+    }
+    Ok(Some(submit_job(&state, JobSpec::TranslateTags, JobPriority::Low)))
+}
+
+#[tauri::command]
+pub async fn get_tag_translation_path(state: State<'_, AppState>) -> Result<String, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.tag_translation_path.clone())
+}
+
+/// Re-import the installed translation database, e.g. after updating the
+/// file. Returns the job id.
+#[tauri::command]
+pub async fn backfill_tag_translations(state: State<'_, AppState>) -> Result<u64, String> {
+    if state.settings.lock().unwrap().tag_translation_path.is_empty() {
+        return Err("No tag translation database installed".to_string());
+    }
+    Ok(submit_job(&state, JobSpec::TranslateTags, JobPriority::Low))
+}
+
+/// Download the latest EhTagTranslation database into the data folder and
+/// install it as with `set_tag_translation_path`. Returns the import job id.
+#[tauri::command]
+pub async fn download_tag_translations(state: State<'_, AppState>) -> Result<u64, String> {
+    let bytes = fetcher::download_bytes(translation::DATABASE_URL).await?;
+    let content = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+    let names = translation::parse(&content, translation::DATABASE_URL)?;
+    let path = state.data_dir().join("db.text.json");
+    fs::write(&path, content).map_err(|e| format!("Can't save {}: {}", path.display(), e))?;
+    log::info!("[translate] Downloaded {} tag names to {}", names.len(), path.display());
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.tag_translation_path = path.to_string_lossy().to_string();
+    }
+    save_settings(&state);
+    Ok(submit_job(&state, JobSpec::TranslateTags, JobPriority::Low))
+}
+
+/// Display name of one tag from the installed translation database
+#[tauri::command]
+pub async fn translate_tag(
+    namespace: String,
+    tag: String,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, String> {
+    state
+        .db
+        .get_tag_translation(namespace.trim(), tag.trim())
+        .map_err(|e| e.to_string())
+}
+
+/// `tags` with their display names filled in, for tags that aren't in the
+/// library yet (search suggestions, EH results)
+#[tauri::command]
+pub async fn translate_tags(
+    mut tags: Vec<TagEntry>,
+    state: State<'_, AppState>,
+) -> Result<Vec<TagEntry>, String> {
+    state.db.translate_tags(&mut tags).map_err(|e| e.to_string())?;
+    Ok(tags)
+}
+
+/// Write an XMP sidecar next to each gallery (all of them when `ids` is
+/// `None`) so Spotlight or Windows Search find galleries by title and tags
+/// while the app is closed. Returns the job id.
+#[tauri::command]
+pub async fn export_search_sidecars(
+    ids: Option<Vec<i64>>,
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    let ids = match ids {
+        Some(ids) => ids,
+        None => state.db.get_present_gallery_ids().map_err(|e| e.to_string())?,
+    };
+    Ok(submit_job(&state, JobSpec::ExportSidecars { ids }, JobPriority::Low))
+}
+
+fn run_export_sidecars(ids: &[i64], start: usize, ctx: &JobContext) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let total = ids.len() as u64;
+    let mut written = 0;
+    for (i, id) in ids.iter().enumerate().skip(start) {
+        if ctx.is_cancelled() {
+            break;
+        }
+        ctx.progress(i as u64, total, "");
+        let Ok(Some(gallery)) = state.db.get_gallery_by_id(*id) else {
+            continue;
+        };
+        if gallery.missing {
+            continue;
+        }
+        let tags = state.db.get_tags_for_gallery(*id).unwrap_or_default();
+        match sidecar::write(&gallery, &tags) {
+            Ok(true) => written += 1,
+            Ok(false) => {}
+            Err(e) => log::warn!("[sidecar] {}", e),
+        }
+    }
+    ctx.progress(total, total, &format!("Wrote {} sidecars", written));
+    Ok(())
+}
+
+/// Import the installed translation database into SQLite, so tag lists
+/// and lookups don't read the JSON on every request
+fn run_translate_tags(ctx: &JobContext) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let path = state.settings.lock().unwrap().tag_translation_path.clone();
+    if path.is_empty() {
+        return Err("No tag translation database installed".to_string());
+    }
+    let names = translation::load(Path::new(&path))?;
+    if ctx.is_cancelled() {
+        return Ok(());
+    }
+    let total = names.len() as u64;
+    let names: Vec<(String, String, String)> = names
+        .into_iter()
+        .map(|((namespace, tag), name)| (namespace, tag, name))
+        .collect();
+    state.db.replace_tag_translations(&names).map_err(|e| e.to_string())?;
+    log::info!("[translate] Imported {} tag names", total);
+    publish_change(app, ChangeKind::TagsChanged, None, "");
+    ctx.progress(total, total, "");
+    Ok(())
+}
+
+/// Set the inbox folder whose loose images are listed as single-page
+/// entries, and scan it. `None` turns the inbox off.
+#[tauri::command]
+pub async fn set_inbox_path(
+    path: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if let Some(p) = &path {
+        if !Path::new(p).is_dir() {
+            return Err(format!("Not a directory: {}", p));
+        }
+    }
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.inbox_path = path.clone().unwrap_or_default();
+    }
+    save_settings(&state);
+    if path.is_some() {
+        submit_job(&state, JobSpec::ScanInbox, JobPriority::Normal);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_inbox_path(state: State<'_, AppState>) -> Result<Option<String>, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(Some(settings.inbox_path.clone()).filter(|p| !p.is_empty()))
+}
+
+/// Re-index the inbox folder as a background job. Returns the job id.
+#[tauri::command]
+pub async fn scan_inbox(state: State<'_, AppState>) -> Result<u64, String> {
+    Ok(submit_job(&state, JobSpec::ScanInbox, JobPriority::Normal))
+}
+
+#[tauri::command]
+pub async fn list_inbox(state: State<'_, AppState>) -> Result<Vec<InboxImage>, String> {
+    state.db.list_inbox().map_err(|e| e.to_string())
+}
+
+/// Index the images directly in the inbox folder, thumbnailing new or
+/// changed ones and dropping entries whose file is gone.
+fn run_scan_inbox(ctx: &JobContext) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let (inbox, thumb_width) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.inbox_path.clone(), settings.thumbnail_width)
+    };
+    if inbox.is_empty() {
+        return Err("No inbox folder set".to_string());
+    }
+
+    let images: Vec<PathBuf> = fs::read_dir(&inbox)
+        .map_err(|e| format!("Can't read inbox: {}", e))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && scanner::is_image_file(p))
+        .collect();
+    let mut known = state.db.get_inbox_mtimes().map_err(|e| e.to_string())?;
+    let total = images.len() as u64;
+
+    for (i, image) in images.iter().enumerate() {
+        if ctx.is_cancelled() {
+            return Ok(());
+        }
+        let path = normalize_path(image);
+        let mtime = scanner::get_file_mtime(image);
+        if known.remove(&path).is_some_and(|m| m == mtime) {
+            continue;
+        }
+        ctx.progress(i as u64, total, &path);
+        // Loose images are shown whole, never cover-cropped
+        let thumb = thumbnail::generate_thumbnail(image, &state.cache_dir(), thumb_width, false)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|e| {
+                log::warn!("[inbox] Thumbnail failed for {}: {}", path, e);
+                String::new()
+            });
+        let filename = image
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let size = fs::metadata(image).map(|m| m.len() as i64).unwrap_or(0);
+        if let Err(e) = state.db.upsert_inbox_image(&path, &filename, size, &mtime, &thumb) {
+            log::warn!("[inbox] Failed to index {}: {}", path, e);
+        }
+    }
+
+    // Whatever wasn't seen on disk has been moved or deleted
+    let gone: Vec<String> = known.into_keys().collect();
+    state.db.remove_inbox_images(&gone).map_err(|e| e.to_string())?;
+    ctx.progress(total, total, "");
+    let _ = app.emit("inbox-updated", ());
+    Ok(())
+}
+
+/// Move inbox images, in the given order, into a new gallery folder named
+/// after `title` under `destination` (the inbox folder by default). Pages
+/// are renamed 001, 002, ... and an info.txt with the title and page count
+/// is written. Returns the new gallery's id.
+#[tauri::command]
+pub async fn group_inbox_images(
+    ids: Vec<i64>,
+    title: String,
+    destination: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<i64, String> {
+    let title = title.trim().to_string();
+    if title.is_empty() {
+        return Err("A gallery title is required".to_string());
+    }
+    let mut images = Vec::new();
+    for id in &ids {
+        let image = state
+            .db
+            .get_inbox_image(*id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Inbox image {} not found", id))?;
+        images.push(image);
+    }
+    if images.is_empty() {
+        return Err("No images selected".to_string());
+    }
+
+    let (inbox, thumb_width, cover_crop) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.inbox_path.clone(), settings.thumbnail_width, settings.cover_crop)
+    };
+    let parent = PathBuf::from(destination.unwrap_or(inbox));
+    if !parent.is_dir() {
+        return Err(format!("Not a directory: {}", parent.display()));
+    }
+    let base = folder_safe_name(&title);
+    let target = std::iter::once(parent.join(&base))
+        .chain((2..).map(|n| parent.join(format!("{} ({})", base, n))))
+        .find(|p| !p.exists())
+        .ok_or("No free folder name")?;
+
+    fs::create_dir(&target).map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+    let width = images.len().to_string().len().max(3);
+    let mut moved: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for (i, image) in images.iter().enumerate() {
+        let src = PathBuf::from(&image.path);
+        let ext = src.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let dst = target.join(format!("{:0width$}.{}", i + 1, ext, width = width));
+        if let Err(e) = fs::rename(&src, &dst) {
+            for (from, to) in &moved {
+                let _ = fs::rename(to, from);
+            }
+            let _ = fs::remove_dir(&target);
+            return Err(format!("Failed to move {}: {}", image.filename, e));
+        }
+        moved.push((src, dst));
+    }
+
+    let info = ParsedGallery {
+        title_en: title,
+        category: "Image Set".to_string(),
+        page_count: images.len() as i64,
+        ..Default::default()
+    };
+    let info_path = target.join("info.txt");
+    fetcher::write_info_txt(&info_path, &info)?;
+
+    let thumb = thumbnail::generate_gallery_thumbnail(&target, &state.cache_dir(), thumb_width, cover_crop, &[])
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let gallery_id = state
+        .db
+        .upsert_gallery(&normalize_path(&target), &info, &thumb, &scanner::get_file_mtime(&info_path))
+        .map_err(|e| e.to_string())?;
+
+    let paths: Vec<String> = images.into_iter().map(|i| i.path).collect();
+    state.db.remove_inbox_images(&paths).map_err(|e| e.to_string())?;
+    let _ = app.emit("inbox-updated", ());
+    publish_change(&app, ChangeKind::GalleryUpserted, Some(gallery_id), &normalize_path(&target));
+    Ok(gallery_id)
+}
+
+/// `name` with characters that aren't allowed in folder names replaced
+fn folder_safe_name(name: &str) -> String {
+    let safe: String = name
+        .chars()
+        .map(|c| if c.is_control() || r#"<>:"/\|?*"#.contains(c) { '_' } else { c })
+        .collect();
+    let safe = safe.trim().trim_end_matches('.').trim().to_string();
+    if safe.is_empty() {
+        "Untitled".to_string()
+    } else {
+        safe
+    }
+}
+
+/// Longest folder name most filesystems allow, in bytes
+const MAX_FOLDER_NAME_BYTES: usize = 255;
+
+/// Folder name for a downloaded gallery, "<title> [<gid>]", with the title
+/// cut short on a char boundary so the name fits `MAX_FOLDER_NAME_BYTES`
+fn gallery_folder_name(title: &str, gid: i64) -> String {
+    let suffix = format!(" [{}]", gid);
+    let title = folder_safe_name(title);
+    let mut end = title.len().min(MAX_FOLDER_NAME_BYTES - suffix.len());
+    while !title.is_char_boundary(end) {
+        end -= 1;
+    }
+    let title = title[..end].trim_end().trim_end_matches('.').trim_end();
+    format!("{}{}", if title.is_empty() { "Untitled" } else { title }, suffix)
+}
+
+/// Add a gallery to the want-list by URL. Returns the entry id; adding a
+/// gallery that's already listed returns the existing entry.
+#[tauri::command]
+pub async fn add_wanted(
+    url: String,
+    title: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<i64, String> {
+    let gid = fetcher::gallery_gid(&url).ok_or_else(|| format!("Not a gallery URL: {}", url))?;
+    state
+        .db
+        .add_wanted(gid, &url, title.as_deref().unwrap_or("").trim(), "manual")
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_wanted(
+    status: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<WantedEntry>, String> {
+    state
+        .db
+        .list_wanted(status.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_wanted_status(
+    id: i64,
+    status: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if !WANTED_STATUSES.contains(&status.as_str()) {
+        return Err(format!("Unknown want-list status: {}", status));
+    }
+    state
+        .db
+        .set_wanted_status(id, &status)
+        .map_err(|e| e.to_string())
+}
 
-        let _ = app.emit("batch-refresh-progress", serde_json::json!({
-            "done": i, "total": total, "current_title": title
-        }));
+#[tauri::command]
+pub async fn remove_wanted(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    state.db.remove_wanted(id).map_err(|e| e.to_string())
+}
+
+/// Format tag for collection files, checked on import
+const COLLECTION_FORMAT: &str = "ehmaster-collection";
+
+#[tauri::command]
+pub async fn create_collection(
+    name: String,
+    description: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<i64, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Collection name is required".to_string());
+    }
+    state
+        .db
+        .create_collection(name, description.as_deref().unwrap_or("").trim())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_collections(state: State<'_, AppState>) -> Result<Vec<Collection>, String> {
+    state.db.list_collections().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_collection(
+    id: i64,
+    name: String,
+    description: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Collection name is required".to_string());
+    }
+    state
+        .db
+        .update_collection(id, name, description.as_deref().unwrap_or("").trim())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_collection(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    state.db.delete_collection(id).map_err(|e| e.to_string())
+}
+
+/// Save a search under `name`, replacing one of the same name. Paging state
+/// isn't kept, so a saved search always starts at the first page of a fresh
+/// result set. Returns its id.
+#[tauri::command]
+pub async fn save_search(
+    name: String,
+    query: SearchQuery,
+    state: State<'_, AppState>,
+) -> Result<i64, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Saved search name is required".to_string());
+    }
+    let query = SearchQuery {
+        offset: 0,
+        snapshot: None,
+        ..query
+    };
+    state.db.save_search(name, &query).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_saved_searches(state: State<'_, AppState>) -> Result<Vec<SavedSearch>, String> {
+    state.db.list_saved_searches().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_saved_search(id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    state.db.delete_saved_search(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_collection_items(
+    id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<CollectionItem>, String> {
+    state.db.get_collection_items(id).map_err(|e| e.to_string())
+}
+
+/// The local galleries of a collection, in collection order. Entries the
+/// library doesn't have are left out; `get_collection_items` lists them.
+#[tauri::command]
+pub async fn get_collection_galleries(
+    id: i64,
+    state: State<'_, AppState>,
+) -> Result<Vec<GallerySummary>, String> {
+    let items = state.db.get_collection_items(id).map_err(|e| e.to_string())?;
+    Ok(items.into_iter().filter_map(|item| item.gallery).collect())
+}
+
+/// Reorder a collection: `gids` come first, in that order, followed by any
+/// entries left out.
+#[tauri::command]
+pub async fn reorder_collection(
+    id: i64,
+    gids: Vec<i64>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.db.reorder_collection(id, &gids).map_err(|e| e.to_string())
+}
 
-        if gallery.url.is_empty() {
-            let _ = app.emit("batch-refresh-progress", serde_json::json!({
-                "done": i + 1, "total": total, "current_title": title
-            }));
+/// Add local galleries to a collection. Galleries without an EH URL have no
+/// shareable identity and are skipped. Returns how many were added.
+#[tauri::command]
+pub async fn add_to_collection(
+    id: i64,
+    gallery_ids: Vec<i64>,
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    let mut items = Vec::new();
+    for gallery_id in gallery_ids {
+        let Some(gallery) = state
+            .db
+            .get_gallery_by_id(gallery_id)
+            .map_err(|e| e.to_string())?
+        else {
             continue;
+        };
+        if let Some(gid) = fetcher::gallery_gid(&gallery.url) {
+            items.push(CollectionFileItem {
+                gid,
+                title: gallery.title_en,
+                url: gallery.url,
+            });
         }
+    }
+    state
+        .db
+        .add_collection_items(id, &items)
+        .map_err(|e| e.to_string())
+}
 
-        match fetcher::fetch_gallery_info(&gallery.url, &cookie_path).await {
-            Ok(fetched) => {
-                let info_path = Path::new(&gallery.path).join("info.txt");
-                let _ = fetcher::write_info_txt(&info_path, &fetched);
-
-                if let Some(parsed) = scanner::parse_info_txt(&info_path) {
-                    let thumb = scanner::get_first_image(Path::new(&gallery.path))
-                        .and_then(|img| thumbnail::generate_thumbnail(&img, &cache_dir, thumb_width))
-                        .map(|p| p.to_string_lossy().to_string())
-                        .unwrap_or_else(|| gallery.thumb_path.clone());
-
-                    let info_mtime = scanner::get_file_mtime(&info_path);
-                    let folder_str = normalize_path(Path::new(&gallery.path));
-                    let _ = state.db.upsert_gallery(&folder_str, &parsed, &thumb, &info_mtime);
+#[tauri::command]
+pub async fn remove_from_collection(
+    id: i64,
+    gid: i64,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .db
+        .remove_collection_item(id, gid)
+        .map_err(|e| e.to_string())
+}
+
+/// Write a collection to a JSON file another user can import.
+#[tauri::command]
+pub async fn export_collection(
+    id: i64,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let collection = state
+        .db
+        .get_collection(id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Collection not found")?;
+    let items = state
+        .db
+        .get_collection_items(id)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|item| CollectionFileItem {
+            gid: item.gid,
+            url: item.url,
+            title: item.title,
+        })
+        .collect();
+    let file = CollectionFile {
+        format: COLLECTION_FORMAT.to_string(),
+        version: 1,
+        name: collection.name,
+        description: collection.description,
+        items,
+    };
+    let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    fetcher::write_atomic(Path::new(&path), json.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Import a shared collection file as a new collection and report which
+/// entries aren't in the local library.
+#[tauri::command]
+pub async fn import_collection(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<CollectionImportReport, String> {
+    let content = scanner::read_text_file(Path::new(&path))
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let file: CollectionFile =
+        serde_json::from_str(&content).map_err(|e| format!("Invalid collection file: {}", e))?;
+    if file.format != COLLECTION_FORMAT {
+        return Err(format!("Not a collection file (format \"{}\")", file.format));
+    }
+
+    let id = state
+        .db
+        .create_collection(&file.name, &file.description)
+        .map_err(|e| e.to_string())?;
+    state
+        .db
+        .add_collection_items(id, &file.items)
+        .map_err(|e| e.to_string())?;
+
+    let items = state
+        .db
+        .get_collection_items(id)
+        .map_err(|e| e.to_string())?;
+    let missing: Vec<CollectionFileItem> = items
+        .iter()
+        .filter(|item| item.gallery.is_none())
+        .map(|item| CollectionFileItem {
+            gid: item.gid,
+            url: item.url.clone(),
+            title: item.title.clone(),
+        })
+        .collect();
+    Ok(CollectionImportReport {
+        collection_id: id,
+        total: items.len() as u64,
+        resolved: (items.len() - missing.len()) as u64,
+        missing,
+    })
+}
+
+const SHARE_FORMAT: &str = "ehmaster-gallery";
+
+/// Side length in pixels of rendered share QR codes
+const SHARE_QR_SIZE: u32 = 320;
+
+/// Build a shareable reference to a gallery, optionally with a QR code of it
+/// for scanning on another device.
+#[tauri::command]
+pub async fn share_gallery(
+    id: i64,
+    qr: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<ShareLink, String> {
+    let gallery = state
+        .db
+        .get_gallery_by_id(id)
+        .map_err(|e| e.to_string())?
+        .ok_or("Gallery not found")?;
+    let (gid, token) = fetcher::gallery_gid(&gallery.url)
+        .zip(fetcher::gallery_token(&gallery.url))
+        .ok_or("Gallery has no URL to share")?;
+    let title = [&gallery.custom_title, &gallery.title_en, &gallery.title_jp]
+        .into_iter()
+        .find(|t| !t.is_empty())
+        .cloned()
+        .unwrap_or(gallery.folder_name);
+
+    let share = GalleryShare {
+        format: SHARE_FORMAT.to_string(),
+        version: 1,
+        gid,
+        token,
+        title,
+    };
+    let descriptor = serde_json::to_string(&share).map_err(|e| e.to_string())?;
+    let qr_png = if qr.unwrap_or(false) {
+        Some(render_qr_png(&descriptor)?)
+    } else {
+        None
+    };
+    Ok(ShareLink {
+        share,
+        descriptor,
+        qr_png,
+    })
+}
+
+fn render_qr_png(text: &str) -> Result<String, String> {
+    let code = qrcode::QrCode::with_error_correction_level(text, qrcode::EcLevel::M)
+        .map_err(|e| format!("Failed to encode QR code: {}", e))?;
+    let img = code
+        .render::<image::Luma<u8>>()
+        .min_dimensions(SHARE_QR_SIZE, SHARE_QR_SIZE)
+        .build();
+    let mut png = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+
+    use base64::Engine;
+    let b64 = base64::engine::general_purpose::STANDARD.encode(png.into_inner());
+    Ok(format!("data:image/png;base64,{}", b64))
+}
+
+/// Read a share descriptor from another EhMaster and find the gallery
+/// locally. Bare gallery URLs are accepted too.
+#[tauri::command]
+pub async fn resolve_gallery_share(
+    descriptor: String,
+    state: State<'_, AppState>,
+) -> Result<ShareResolution, String> {
+    let descriptor = descriptor.trim();
+    let share = match serde_json::from_str::<GalleryShare>(descriptor) {
+        Ok(share) if share.format == SHARE_FORMAT => share,
+        Ok(share) => return Err(format!("Not a gallery share ({})", share.format)),
+        Err(_) => {
+            let url = fetcher::canonical_gallery_url(descriptor);
+            let (gid, token) = fetcher::gallery_gid(&url)
+                .zip(fetcher::gallery_token(&url))
+                .ok_or("Not a gallery share or URL")?;
+            GalleryShare {
+                format: SHARE_FORMAT.to_string(),
+                version: 1,
+                gid,
+                token,
+                title: String::new(),
+            }
+        }
+    };
+    let url = fetcher::canonical_gallery_url(&format!(
+        "https://exhentai.org/g/{}/{}/",
+        share.gid, share.token
+    ));
+    let gallery = state
+        .db
+        .find_galleries_by_gid(&[share.gid])
+        .map_err(|e| e.to_string())?
+        .remove(&share.gid)
+        .and_then(|copies| copies.into_iter().next());
+    Ok(ShareResolution {
+        share,
+        url,
+        gallery,
+    })
+}
+
+/// Import the account's EH favorites as a background job, filing matching
+/// local galleries under their slots. Returns the job id.
+#[tauri::command]
+pub async fn import_favorites(state: State<'_, AppState>) -> Result<u64, String> {
+    Ok(submit_job(&state, JobSpec::ImportFavorites, JobPriority::Normal))
+}
+
+/// Walk every favorite slot page by page, then replace local slot
+/// assignments in one go so a cancelled import leaves them untouched.
+async fn run_import_favorites(ctx: &JobContext) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let cookie_path = require_cookie_file(&state)?;
+    let mut slots: HashMap<String, i64> = HashMap::new();
+
+    for slot in 0..10i64 {
+        let mut next: Option<String> = None;
+        loop {
+            ctx.progress(
+                slot as u64,
+                10,
+                &format!("Favorites {} ({} found)", slot, slots.len()),
+            );
+            let (urls, cursor) =
+                fetcher::fetch_favorites_page(slot, next.as_deref(), &cookie_path).await?;
+            for url in urls {
+                slots.insert(url, slot);
+            }
+            if !rate_limit_wait(ctx) {
+                return Ok(());
+            }
+            match cursor {
+                Some(cursor) => next = Some(cursor),
+                None => break,
+            }
+        }
+    }
+
+    let matched = state
+        .db
+        .replace_favorite_slots(&slots)
+        .map_err(|e| e.to_string())?;
+    ctx.progress(
+        10,
+        10,
+        &format!("Matched {} of {} favorites", matched, slots.len()),
+    );
+    Ok(())
+}
+
+/// Identify `ids[start..]`, checkpointing progress through the job context.
+async fn run_identify(ids: Vec<i64>, start: usize, ctx: &JobContext) -> Result<(), String> {
+    let app = ctx.app();
+    let state = app.state::<AppState>();
+    let total = ids.len();
+    let cookie_path = require_cookie_file(&state)?;
+    let mut identified = 0u64;
+    let fetch_queue = &state.fetch_queue;
+    fetch_queue.begin(ctx.id(), ids.get(start..).unwrap_or_default());
+
+    for (i, id) in ids.iter().enumerate().skip(start) {
+        if ctx.is_cancelled() {
+            break;
+        }
+
+        // Skip anything identified by other means since the job was queued
+        let gallery = match state.db.get_gallery_by_id(*id) {
+            Ok(Some(g)) if g.url.is_empty() => g,
+            _ => {
+                fetch_queue.set(ctx.id(), *id, "", FetchItemState::Done);
+                continue;
+            }
+        };
+        ctx.progress(i as u64, total as u64, &gallery.folder_name);
+        fetch_queue.set(ctx.id(), *id, &gallery.folder_name, FetchItemState::Fetching);
+
+        let mut url = None;
+        // Only set for title-search hits, which have to be checked
+        let mut by_title = false;
+
+        if let Some(hash) = fetcher::first_page_sha1(Path::new(&gallery.path)) {
+            match fetcher::search_by_image_hash(&hash, &cookie_path).await {
+                Ok(found) => url = found,
+                Err(e) => log::warn!("[identify] Hash lookup failed for {}: {}", id, e),
+            }
+            if !rate_limit_wait(ctx) {
+                break;
+            }
+        }
+
+        let title = if !gallery.title_en.is_empty() {
+            &gallery.title_en
+        } else {
+            &gallery.folder_name
+        };
+        if url.is_none() && !title.is_empty() {
+            match fetcher::search_by_title(title, &cookie_path).await {
+                Ok(found) => {
+                    by_title = found.is_some();
+                    url = found;
+                }
+                Err(e) => log::warn!("[identify] Title search failed for {}: {}", id, e),
+            }
+            if !rate_limit_wait(ctx) {
+                break;
+            }
+        }
+
+        let Some(url) = url else {
+            fetch_queue.set(ctx.id(), *id, "", FetchItemState::Failed {
+                reason: "No match on EH".to_string(),
+            });
+            continue;
+        };
+        let outcome = match fetcher::fetch_gallery_info(&url, &cookie_path).await {
+            Ok(fetched) if by_title && !title_hit_matches(&gallery, &fetched) => {
+                // Common or short titles find the wrong gallery; let the
+                // user decide rather than overwrite the info.txt
+                let score = identify_title_score(&gallery, &fetched);
+                let fetched_title = if fetched.title_en.is_empty() {
+                    &fetched.title_jp
+                } else {
+                    &fetched.title_en
+                };
+                let held = state.db.set_identify_candidate(
+                    gallery.id,
+                    &url,
+                    fetched_title,
+                    fetched.page_count,
+                    score,
+                );
+                match held {
+                    Ok(()) => {
+                        log::info!("[identify] Gallery {} may be {} (score {:.2})", id, url, score);
+                        FetchItemState::Failed {
+                            reason: "Possible match saved for confirmation".to_string(),
+                        }
+                    }
+                    Err(e) => FetchItemState::Failed { reason: e.to_string() },
                 }
             }
+            Ok(fetched) => match apply_fetched_metadata(&state, &gallery, &fetched, false) {
+                Ok(_) => {
+                    identified += 1;
+                    let _ = state.db.remove_identify_candidate(gallery.id);
+                    publish_change(app, ChangeKind::TagsChanged, Some(gallery.id), &gallery.path);
+                    log::info!("[identify] Gallery {} matched {}", id, url);
+                    FetchItemState::Done
+                }
+                Err(e) => {
+                    log::warn!("[identify] Failed to apply gallery {}: {}", id, e);
+                    FetchItemState::Failed { reason: e }
+                }
+            },
             Err(e) => {
-                log::warn!("[batch-refresh] Failed for gallery {}: {}", id, e);
+                log::warn!("[identify] Fetch failed for {}: {}", url, e);
+                FetchItemState::Failed { reason: e }
             }
+        };
+        fetch_queue.set(ctx.id(), *id, "", outcome);
+        if !rate_limit_wait(ctx) {
+            break;
+        }
+    }
+    fetch_queue.end(ctx.id());
+
+    ctx.progress(total as u64, total as u64, &format!("Identified {}", identified));
+    Ok(())
+}
+
+/// Best title similarity between a local gallery and fetched metadata
+fn identify_title_score(gallery: &Gallery, fetched: &ParsedGallery) -> f64 {
+    matcher::title_similarity(
+        &[&gallery.title_en, &gallery.title_jp, &gallery.folder_name],
+        &[&fetched.title_en, &fetched.title_jp],
+    )
+}
+
+/// Whether a title-search hit is clearly the local gallery: the titles
+/// match as closely as list imports require, and the page counts agree
+/// when the local one is known
+fn title_hit_matches(gallery: &Gallery, fetched: &ParsedGallery) -> bool {
+    let pages_agree = gallery.page_count <= 0 || gallery.page_count == fetched.page_count;
+    pages_agree && identify_title_score(gallery, fetched) >= matcher::MATCH_THRESHOLD
+}
+
+/// Sleep for the search rate limit, waking early on cancellation.
+/// Returns false if the job was cancelled.
+fn rate_limit_wait(ctx: &JobContext) -> bool {
+    fetcher::start_cooldown(fetcher::SEARCH_REQUEST_DELAY);
+    wait_cancellable(ctx, fetcher::SEARCH_REQUEST_DELAY)
+}
+
+/// Sleep for `delay`, waking early on cancellation. Returns false if the
+/// job was cancelled.
+fn wait_cancellable(ctx: &JobContext, delay: std::time::Duration) -> bool {
+    let step = std::time::Duration::from_millis(250);
+    let mut waited = std::time::Duration::ZERO;
+    while waited < delay {
+        if ctx.is_cancelled() {
+            return false;
         }
+        std::thread::sleep(step.min(delay - waited));
+        waited += step;
+    }
+    !ctx.is_cancelled()
+}
+
+#[tauri::command]
+pub async fn set_title_pref(
+    pref: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.title_pref = pref;
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+/// Sort names and titles for `locale` ("ja", "sv-SE"...), or with an
+/// empty locale plain case-insensitively.
+#[tauri::command]
+pub async fn set_sort_locale(
+    locale: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let locale = locale.trim().to_string();
+    if locale.len() > 35 || !locale.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid locale: {}", locale));
+    }
+    collation::set_locale(&locale);
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.sort_locale = locale;
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_sort_locale(state: State<'_, AppState>) -> Result<String, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.sort_locale.clone())
+}
+
+#[tauri::command]
+pub async fn set_preferred_domain(
+    domain: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if domain != "exhentai" && domain != "e-hentai" {
+        return Err(format!("Unknown domain: {}", domain));
+    }
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.preferred_domain = domain;
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_write_back_info_txt(
+    enabled: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.write_back_info_txt = enabled;
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_write_back_info_txt(state: State<'_, AppState>) -> Result<bool, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.write_back_info_txt)
+}
+
+#[tauri::command]
+pub async fn get_preferred_domain(state: State<'_, AppState>) -> Result<String, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.preferred_domain.clone())
+}
+
+#[tauri::command]
+pub async fn get_title_pref(state: State<'_, AppState>) -> Result<String, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.title_pref.clone())
+}
+
+/// View preferences saved for `context`, or its built-in defaults.
+#[tauri::command]
+pub async fn get_view_prefs(
+    context: String,
+    state: State<'_, AppState>,
+) -> Result<ViewPrefs, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings
+        .view_prefs
+        .get(&context)
+        .cloned()
+        .unwrap_or_else(|| ViewPrefs::default_for(&context)))
+}
 
-        let _ = app.emit("batch-refresh-progress", serde_json::json!({
-            "done": i + 1, "total": total, "current_title": title
-        }));
+/// Save view preferences for `context`; `None` resets it to the defaults.
+/// Emits `view-prefs-changed` so every window picks them up.
+#[tauri::command]
+pub async fn set_view_prefs(
+    context: String,
+    prefs: Option<ViewPrefs>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let context = context.trim().to_string();
+    if context.is_empty() || context.len() > 64 {
+        return Err("View context must be 1 to 64 characters".to_string());
     }
-
-    let _ = app.emit("batch-refresh-complete", serde_json::json!({ "total": total }));
+    if let Some(prefs) = &prefs {
+        if prefs.sort_by.is_empty() || !matches!(prefs.sort_order.as_str(), "asc" | "desc") {
+            return Err(format!(
+                "Invalid sort \"{}:{}\"",
+                prefs.sort_by, prefs.sort_order
+            ));
+        }
+    }
+    let prefs = prefs.map(|mut p| {
+        p.card_width = p.card_width.map(|w| w.clamp(150, 400));
+        p
+    });
+    {
+        let mut settings = state.settings.lock().unwrap();
+        match &prefs {
+            Some(p) => settings.view_prefs.insert(context.clone(), p.clone()),
+            None => settings.view_prefs.remove(&context),
+        };
+    }
+    save_settings(&state);
+    let prefs = prefs.unwrap_or_else(|| ViewPrefs::default_for(&context));
+    let _ = app.emit(
+        "view-prefs-changed",
+        serde_json::json!({ "context": context, "prefs": prefs }),
+    );
     Ok(())
 }
 
+/// Remember the password of an encrypted archive gallery (`None` forgets
+/// it) and re-index the gallery with it. Stored sealed in settings.
 #[tauri::command]
-pub async fn set_title_pref(
-    pref: String,
+pub async fn set_archive_password(
+    path: String,
+    password: Option<String>,
     state: State<'_, AppState>,
     app: AppHandle,
 ) -> Result<(), String> {
+    let path = normalize_path(Path::new(path.trim()));
+    if !archive::has_archive_extension(Path::new(&path)) {
+        return Err(format!("Not an archive: {}", path));
+    }
+    let sealed = match password.filter(|p| !p.is_empty()) {
+        Some(p) => Some(secret::seal(&state.data_dir(), &p)?),
+        None => None,
+    };
     {
         let mut settings = state.settings.lock().unwrap();
-        settings.title_pref = pref;
+        match sealed {
+            Some(sealed) => settings.archive_passwords.insert(path.clone(), sealed),
+            None => settings.archive_passwords.remove(&path),
+        };
+    }
+    save_settings(&state);
+    apply_archive_passwords(&state);
+
+    // Listing, thumbnail and page count couldn't be read without it
+    // Reading a large RAR or 7z can take a while, so it runs off the runtime
+    if archive::is_archive(Path::new(&path)) {
+        tauri::async_runtime::spawn_blocking(move || {
+            let state = app.state::<AppState>();
+            let lock = state.gallery_locks.get(&path);
+            let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+            index_gallery_folder(&app, &state, &path).map(|_| ())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
     }
-    save_settings(&state, &app);
     Ok(())
 }
 
+/// Password tried for encrypted archives without one of their own; `None`
+/// clears it.
 #[tauri::command]
-pub async fn get_title_pref(state: State<'_, AppState>) -> Result<String, String> {
+pub async fn set_default_archive_password(
+    password: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let sealed = match password.filter(|p| !p.is_empty()) {
+        Some(p) => secret::seal(&state.data_dir(), &p)?,
+        None => String::new(),
+    };
+    state.settings.lock().unwrap().default_archive_password = sealed;
+    save_settings(&state);
+    apply_archive_passwords(&state);
+    Ok(())
+}
+
+/// Archives with a stored password, and whether a default password is set.
+/// Passwords themselves are never returned.
+#[tauri::command]
+pub async fn get_archive_passwords(state: State<'_, AppState>) -> Result<(Vec<String>, bool), String> {
     let settings = state.settings.lock().unwrap();
-    Ok(settings.title_pref.clone())
+    Ok((
+        settings.archive_passwords.keys().cloned().collect(),
+        !settings.default_archive_password.is_empty(),
+    ))
+}
+
+/// Hand the stored archive passwords to the archive readers
+pub fn apply_archive_passwords(state: &AppState) {
+    let dir = state.data_dir();
+    let (default, sealed) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.default_archive_password.clone(), settings.archive_passwords.clone())
+    };
+    let open = |sealed: &str| {
+        secret::open(&dir, sealed)
+            .map_err(|e| log::warn!("[archive] Couldn't unseal a stored password: {}", e))
+            .ok()
+    };
+    let default = Some(default).filter(|d| !d.is_empty()).and_then(|d| open(&d));
+    let by_path = sealed
+        .into_iter()
+        .filter_map(|(path, sealed)| Some((path, open(&sealed)?)))
+        .collect();
+    archive::set_passwords(default, by_path);
 }
 
 #[tauri::command]
 pub async fn set_grid_card_width(
     width: u32,
     state: State<'_, AppState>,
-    app: AppHandle,
 ) -> Result<(), String> {
     {
         let mut settings = state.settings.lock().unwrap();
         settings.grid_card_width = width.clamp(150, 400);
     }
-    save_settings(&state, &app);
+    save_settings(&state);
     Ok(())
 }
 
@@ -846,13 +5507,12 @@ pub async fn get_grid_card_width(state: State<'_, AppState>) -> Result<u32, Stri
 pub async fn set_gallery_card_width(
     width: u32,
     state: State<'_, AppState>,
-    app: AppHandle,
 ) -> Result<(), String> {
     {
         let mut settings = state.settings.lock().unwrap();
         settings.gallery_card_width = width.clamp(60, 300);
     }
-    save_settings(&state, &app);
+    save_settings(&state);
     Ok(())
 }
 
@@ -862,6 +5522,186 @@ pub async fn get_gallery_card_width(state: State<'_, AppState>) -> Result<u32, S
     Ok(settings.gallery_card_width)
 }
 
+/// Replace the uploader/artist blocklist. Entries are trimmed, lowercased
+/// and deduplicated.
+#[tauri::command]
+pub async fn set_blocklist(
+    blocklist: Blocklist,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let clean = |items: Vec<String>| {
+        let mut items: Vec<String> = items
+            .into_iter()
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+        items.sort();
+        items.dedup();
+        items
+    };
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.blocklist = Blocklist {
+            uploaders: clean(blocklist.uploaders),
+            artists: clean(blocklist.artists),
+        };
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_blocklist(state: State<'_, AppState>) -> Result<Blocklist, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.blocklist.clone())
+}
+
+#[tauri::command]
+pub async fn set_default_filters(
+    filters: DefaultFilters,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.default_filters = DefaultFilters {
+            min_rating: filters.min_rating.clamp(0.0, 5.0),
+            ..filters
+        };
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_default_filters(state: State<'_, AppState>) -> Result<DefaultFilters, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.default_filters.clone())
+}
+
+/// Set the namespace order and hidden namespaces for gallery tag lists.
+#[tauri::command]
+pub async fn set_tag_display(
+    display: TagDisplay,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let clean = |list: Vec<String>| -> Vec<String> {
+        list.into_iter()
+            .map(|n| n.trim().to_lowercase())
+            .filter(|n| !n.is_empty())
+            .collect()
+    };
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.tag_display = TagDisplay {
+            first: clean(display.first),
+            last: clean(display.last),
+            hidden: clean(display.hidden),
+        };
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_tag_display(state: State<'_, AppState>) -> Result<TagDisplay, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.tag_display.clone())
+}
+
+/// Toggle uniform smart-cropped covers. Existing thumbnails are kept; new
+/// ones are generated in the selected mode on the next scan or refresh.
+#[tauri::command]
+pub async fn set_cover_crop(
+    enabled: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.cover_crop = enabled;
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_cover_crop(state: State<'_, AppState>) -> Result<bool, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.cover_crop)
+}
+
+/// Toggle automatic contrast for grayscale pages in the reader.
+#[tauri::command]
+pub async fn set_reader_auto_contrast(
+    enabled: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.reader_auto_contrast = enabled;
+    }
+    save_settings(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_reader_auto_contrast(state: State<'_, AppState>) -> Result<bool, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.reader_auto_contrast)
+}
+
+/// Update scheduler limits (e.g. keep a NAS or a laptop on battery quiet).
+/// Takes effect for the next job the scheduler picks.
+#[tauri::command]
+pub async fn set_job_limits(
+    limits: JobLimits,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock().unwrap();
+        settings.max_thumbnail_workers = limits.thumbnail_workers.clamp(1, 16);
+        settings.max_network_jobs = limits.network_jobs.clamp(1, 8);
+        settings.max_disk_jobs = limits.disk_jobs.clamp(1, 8);
+    }
+    save_settings(&state);
+    apply_job_limits(&state);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_job_limits(state: State<'_, AppState>) -> Result<JobLimits, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(JobLimits {
+        thumbnail_workers: settings.max_thumbnail_workers,
+        network_jobs: settings.max_network_jobs,
+        disk_jobs: settings.max_disk_jobs,
+    })
+}
+
+/// Update which heavy jobs wait for mains power or an unmetered
+/// connection; queued jobs are held or released right away.
+#[tauri::command]
+pub async fn set_power_policy(
+    policy: PowerPolicy,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    state.settings.lock().unwrap().power_policy = policy;
+    save_settings(&state);
+    apply_power_policy(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_power_policy(state: State<'_, AppState>) -> Result<PowerPolicy, String> {
+    let settings = state.settings.lock().unwrap();
+    Ok(settings.power_policy.clone())
+}
+
+#[tauri::command]
+pub async fn get_power_state(state: State<'_, AppState>) -> Result<PowerState, String> {
+    Ok(*state.power.lock().unwrap())
+}
+
 fn urlencoding(s: &str) -> String {
     let mut encoded = String::new();
     for ch in s.chars() {
@@ -887,7 +5727,45 @@ fn urlencoding(s: &str) -> String {
 /// while WalkDir produces backslash-only paths. This ensures DB lookups match.
 fn normalize_path(p: &Path) -> String {
     let cleaned: PathBuf = p.components().collect();
-    cleaned.to_string_lossy().to_string()
+    scanner::to_nfc(&cleaned.to_string_lossy())
+}
+
+/// Find the configured root containing `path` (longest match wins for nested roots).
+fn root_for_path(path: &str, roots: &[String]) -> Option<String> {
+    let path = Path::new(path);
+    roots
+        .iter()
+        .filter(|r| path.starts_with(Path::new(r.as_str())))
+        .max_by_key(|r| r.len())
+        .cloned()
+}
+
+/// Rename a directory, copying then deleting when the rename crosses volumes.
+fn move_dir(src: &Path, dst: &Path) -> Result<(), String> {
+    if fs::rename(src, dst).is_ok() {
+        return Ok(());
+    }
+
+    copy_dir(src, dst)?;
+    fs::remove_dir_all(src)
+        .map_err(|e| format!("Copied but failed to remove source: {}", e))
+}
+
+/// Copy a directory tree to `dst`
+fn copy_dir(src: &Path, dst: &Path) -> Result<(), String> {
+    for entry in walkdir::WalkDir::new(src) {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let rel = entry.path().strip_prefix(src).map_err(|e| e.to_string())?;
+        let out = dst.join(rel);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&out).map_err(|e| e.to_string())?;
+        } else {
+            fs::copy(entry.path(), &out).map_err(|e| {
+                format!("Failed to copy '{}': {}", entry.path().display(), e)
+            })?;
+        }
+    }
+    Ok(())
 }
 
 fn has_subdirectories(path: &Path) -> bool {
@@ -901,36 +5779,331 @@ fn has_subdirectories(path: &Path) -> bool {
     false
 }
 
-fn start_watcher_for_path(path: &str, state: &AppState, app: &AppHandle) {
+pub(crate) fn start_watcher_for_path(path: &str, state: &AppState, app: &AppHandle) {
     let root = PathBuf::from(path);
     let db = Arc::clone(&state.db);
-    let cache_dir = state.cache_dir.clone();
-    let thumb_width = state.settings.lock().unwrap().thumbnail_width;
+    let cache_dir = state.cache_dir();
+    let (thumb_width, cover_crop) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.thumbnail_width, settings.cover_crop)
+    };
+
+    let locks = Arc::clone(&state.gallery_locks);
 
-    let handle = watcher::start_watcher(root, db, cache_dir, thumb_width, app.clone());
+    let handle = watcher::start_watcher(
+        root,
+        db,
+        cache_dir,
+        thumb_width,
+        cover_crop,
+        locks,
+        app.clone(),
+    );
     state.watchers.lock().unwrap().insert(path.to_string(), handle);
 }
 
-fn save_settings(state: &AppState, app: &AppHandle) {
+/// Resolve the cookie file: settings cookie_path > <profile dir>/cookie.txt
+fn cookie_file_path(state: &AppState) -> PathBuf {
     let settings = state.settings.lock().unwrap();
-    if let Some(data_dir) = app.path().app_data_dir().ok() {
-        let _ = fs::create_dir_all(&data_dir);
-        let settings_path = data_dir.join("settings.json");
-        let json = serde_json::to_string_pretty(&*settings).unwrap_or_default();
-        let _ = fs::write(settings_path, json);
+    if !settings.cookie_path.is_empty() {
+        PathBuf::from(&settings.cookie_path)
+    } else {
+        state.data_dir().join("cookie.txt")
     }
 }
 
-pub fn load_settings(app: &AppHandle) -> AppSettings {
-    if let Some(data_dir) = app.path().app_data_dir().ok() {
-        let settings_path = data_dir.join("settings.json");
-        if settings_path.exists() {
-            if let Ok(content) = fs::read_to_string(&settings_path) {
-                if let Ok(settings) = serde_json::from_str::<AppSettings>(&content) {
-                    return settings;
-                }
+/// Like `cookie_file_path`, but errors if the file doesn't exist.
+fn require_cookie_file(state: &AppState) -> Result<PathBuf, String> {
+    let cookie_path = cookie_file_path(state);
+    if !cookie_path.exists() {
+        return Err(format!(
+            "Cookie file not found at: {}. Use Settings to select your cookie file.",
+            cookie_path.display()
+        ));
+    }
+    Ok(cookie_path)
+}
+
+/// Library profiles in the app data directory; the default one is always
+/// listed
+#[tauri::command]
+pub async fn list_profiles(
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Vec<ProfileInfo>, String> {
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let active = state.profile.lock().unwrap().clone();
+    Ok(profile::list(&data_dir)
+        .into_iter()
+        .map(|name| ProfileInfo {
+            active: name == active,
+            name,
+        })
+        .collect())
+}
+
+/// Close the current library and open profile `name` in its place, creating
+/// it when new. Each profile has its own database, thumbnails and settings
+/// (roots included). Refused while jobs are queued or running, since they
+/// would carry on against the other library. Emits `profile-switched`.
+#[tauri::command]
+pub async fn switch_profile(
+    name: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let name = name.trim();
+    profile::validate_name(name)?;
+    if *state.profile.lock().unwrap() == name {
+        return Ok(());
+    }
+    if jobs_busy(&state) {
+        return Err("Finish or cancel running jobs before switching profiles".to_string());
+    }
+
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let dir = profile::profile_dir(&data_dir, name);
+    fs::create_dir_all(dir.join(profile::THUMBS_DIR))
+        .map_err(|e| format!("Failed to create profile {}: {}", name, e))?;
+
+    // Stop watching the old roots before their database goes away
+    state.watchers.lock().unwrap().clear();
+    state
+        .db
+        .reopen(&dir.join(profile::DB_NAME))
+        .map_err(|e| format!("Failed to open profile {}: {}", name, e))?;
+    state.set_data_dir(dir.clone());
+    *state.settings.lock().unwrap() = load_settings(&dir);
+    apply_archive_passwords(&state);
+    collation::set_locale(&state.settings.lock().unwrap().sort_locale);
+    *state.profile.lock().unwrap() = name.to_string();
+    state.session_filters.lock().unwrap().clear();
+    state.incoming_duplicates.lock().unwrap().clear();
+    profile::set_active(&data_dir, name)?;
+    log::info!("Switched to profile {}", name);
+
+    apply_job_limits(&state);
+    resume_persisted_jobs(&state);
+    let roots = state.settings.lock().unwrap().root_paths.clone();
+    for root in roots.iter().filter(|r| Path::new(r).exists()) {
+        start_watcher_for_path(root, &state, &app);
+    }
+    if let Err(e) = apply_companion(&state, &app) {
+        log::warn!("Browser extension endpoint not started: {}", e);
+    }
+
+    let _ = app.emit("profile-switched", name);
+    Ok(())
+}
+
+/// Copy an existing app data directory, e.g. from an older install, into
+/// profile `profile`, which must not be active or hold a library yet. The
+/// source is left as it is; check the report before removing it.
+#[tauri::command]
+pub async fn import_data_dir(
+    source: String,
+    profile: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<MigrationReport, String> {
+    let name = profile.trim();
+    profile::validate_name(name)?;
+    if *state.profile.lock().unwrap() == name {
+        return Err("Switch to another profile before importing into this one".to_string());
+    }
+    let data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let report = profile::copy_data(Path::new(source.trim()), &profile::profile_dir(&data_dir, name))?;
+    log::info!(
+        "[migrate] Imported {} into profile {}: {} files, {} galleries, verified: {}",
+        source,
+        name,
+        report.files,
+        report.galleries,
+        report.verified()
+    );
+    Ok(report)
+}
+
+/// Copy the active profile's data to `target`, e.g. to carry the library
+/// to another machine, and verify the copy. `target` can be imported there
+/// with `import_data_dir`.
+#[tauri::command]
+pub async fn export_profile(
+    target: String,
+    state: State<'_, AppState>,
+) -> Result<MigrationReport, String> {
+    let report = profile::copy_data(&state.data_dir(), Path::new(target.trim()))?;
+    log::info!(
+        "[migrate] Exported to {}: {} files, {} galleries, verified: {}",
+        target,
+        report.files,
+        report.galleries,
+        report.verified()
+    );
+    Ok(report)
+}
+
+fn save_settings(state: &AppState) {
+    let settings = state.settings.lock().unwrap();
+    let data_dir = state.data_dir();
+    let _ = fs::create_dir_all(&data_dir);
+    let settings_path = data_dir.join("settings.json");
+    let json = serde_json::to_string_pretty(&*settings).unwrap_or_default();
+    let _ = fs::write(settings_path, json);
+}
+
+/// Hold back a new gallery folder whose gid is already in the library, so a
+/// second copy doesn't silently become a second row. Emits
+/// `duplicate-incoming` the first time a folder is held; returns whether it
+/// was held.
+pub(crate) fn hold_incoming_duplicate(app: &AppHandle, path: &str, parsed: &ParsedGallery) -> bool {
+    let Some(gid) = fetcher::gallery_gid(&parsed.url) else {
+        return false;
+    };
+    let state = app.state::<AppState>();
+    let existing: Vec<GallerySummary> = state
+        .db
+        .find_galleries_by_gid(&[gid])
+        .unwrap_or_default()
+        .remove(&gid)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|g| g.path != path)
+        .collect();
+    if existing.is_empty() {
+        return false;
+    }
+
+    let mut pending = state.incoming_duplicates.lock().unwrap();
+    if pending.iter().any(|d| d.path == path) {
+        return true;
+    }
+    log::warn!("[scan] Holding {}: gid {} is already in the library", path, gid);
+    let incoming = IncomingDuplicate {
+        path: path.to_string(),
+        gid,
+        title: parsed.title_en.clone(),
+        existing,
+        detected_at: unix_now(),
+    };
+    let _ = app.emit("duplicate-incoming", &incoming);
+    pending.push(incoming);
+    true
+}
+
+/// Emit a `scan-item` event carrying the current DB summary for `path`.
+/// Log a library change and push it to the frontend as `library-changed`.
+/// Listeners that missed some (a gap in `seq`) catch up with
+/// `get_changes_since`.
+pub(crate) fn publish_change(app: &AppHandle, kind: ChangeKind, gallery_id: Option<i64>, path: &str) {
+    let state = app.state::<AppState>();
+    match state.db.record_change(kind, gallery_id, path) {
+        Ok(change) => {
+            let _ = app.emit("library-changed", &change);
+        }
+        Err(e) => log::warn!("Couldn't record library change: {}", e),
+    }
+}
+
+fn emit_scan_item(app: &AppHandle, db: &Database, kind: &str, path: &str) {
+    if let Ok(Some(gallery)) = db.get_gallery_by_path(path) {
+        let _ = app.emit(
+            "scan-item",
+            serde_json::json!({ "kind": kind, "gallery": gallery }),
+        );
+    }
+}
+
+fn save_scan_report(report: &ScanReport, state: &AppState) {
+    let data_dir = state.data_dir();
+    let _ = fs::create_dir_all(&data_dir);
+    let report_path = data_dir.join("last_scan_report.json");
+    let json = serde_json::to_string_pretty(report).unwrap_or_default();
+    if let Err(e) = fs::write(&report_path, json) {
+        log::warn!("Failed to write scan report: {}", e);
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Settings of the profile in `data_dir`, defaults if it has none yet
+pub fn load_settings(data_dir: &Path) -> AppSettings {
+    let settings_path = data_dir.join("settings.json");
+    if settings_path.exists() {
+        if let Ok(content) = fs::read_to_string(&settings_path) {
+            if let Ok(settings) = serde_json::from_str::<AppSettings>(&content) {
+                return settings;
             }
         }
     }
     AppSettings::default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gallery_folder(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ehmaster-pages-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for file in files {
+            fs::write(dir.join(file), b"").unwrap();
+        }
+        dir
+    }
+
+    fn missing_in(name: &str, files: &[&str], page_count: usize) -> Option<Vec<usize>> {
+        let dir = gallery_folder(name, files);
+        let missing = missing_pages(&dir, page_count).ok();
+        let _ = fs::remove_dir_all(&dir);
+        missing
+    }
+
+    #[test]
+    fn missing_pages_by_number() {
+        assert_eq!(missing_in("digits", &["0001.jpg", "0002.png", "0004.jpg"], 5), Some(vec![3, 5]));
+        assert_eq!(
+            missing_in("prefixed", &["page_001.jpg", "page_003.jpg", "ページ005.jpg"], 5),
+            Some(vec![2, 4])
+        );
+        assert_eq!(missing_in("unpadded", &["1.jpg", "2.jpg", "5.jpg"], 5), Some(vec![3, 4]));
+        assert_eq!(missing_in("duplicate", &["001.jpg", "1.png", "002.jpg"], 5), None);
+        assert_eq!(missing_in("unnumbered", &["cover.jpg", "001.jpg"], 5), None);
+        assert_eq!(missing_in("out-of-range", &["001.jpg", "006.jpg"], 5), None);
+    }
+
+    #[test]
+    fn page_name_format_follows_existing_names() {
+        let cases: [(&str, &[&str], (&str, usize)); 6] = [
+            ("digits", &["0001.jpg", "0003.jpg"], ("", 4)),
+            ("prefixed", &["page_001.jpg", "page_003.jpg", "page_010.jpg"], ("page_", 3)),
+            ("unpadded", &["1.jpg", "3.jpg", "10.jpg"], ("", 0)),
+            ("duplicate", &["p01.jpg", "p01.png", "p03.jpg"], ("p", 2)),
+            ("mixed", &["img01.jpg", "img02.jpg", "x03.jpg"], ("img", 2)),
+            ("empty", &[], ("", 4)),
+        ];
+        for (name, files, (prefix, width)) in cases {
+            let dir = gallery_folder(name, files);
+            assert_eq!(page_name_format(&dir), (prefix.to_string(), width), "{}", name);
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+
+    #[test]
+    fn gallery_folder_name_fits_with_gid() {
+        assert_eq!(gallery_folder_name("Title: Part 1?", 123), "Title_ Part 1_ [123]");
+        assert_eq!(gallery_folder_name(" . ", 123), "Untitled [123]");
+
+        let long = "長".repeat(200);
+        let name = gallery_folder_name(&long, 3123456);
+        assert!(name.len() <= MAX_FOLDER_NAME_BYTES);
+        assert!(name.ends_with(" [3123456]"));
+        assert!(name.starts_with("長長"));
+    }
+}
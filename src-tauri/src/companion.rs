@@ -0,0 +1,470 @@
+//! Localhost endpoint for the companion browser extension.
+//!
+//! Bound to 127.0.0.1 only and every request must carry the token from
+//! settings as `Authorization: Bearer <token>`. Browser requests are only
+//! answered for the extension's origin (`companion_origin`, or any extension
+//! origin while that's unset), so web pages can't call it. Routes:
+//!
+//! - `GET /owned?gid=1,2` — ownership of one or more gids
+//! - `POST /queue-download` `{url, title?}` — add a gallery to the want-list
+//!   and download it
+//! - `POST /save-metadata` `{url, html}` — apply a gallery page the browser
+//!   already loaded to the local copies, without fetching it again
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::commands;
+use crate::fetcher;
+use crate::state::AppState;
+
+/// Largest request body accepted; gallery pages are well under this
+const MAX_BODY_BYTES: usize = 4 * 1024 * 1024;
+
+/// Largest request line plus headers accepted
+const MAX_HEAD_BYTES: u64 = 16 * 1024;
+
+/// Most headers accepted in one request
+const MAX_HEADERS: usize = 64;
+
+/// How long a client may take to send its whole request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the accept loop checks whether it should stop
+const ACCEPT_POLL: Duration = Duration::from_millis(200);
+
+/// Connections served at once; more are closed unanswered
+const MAX_CONNECTIONS: usize = 16;
+
+/// Origin prefixes of browser extension pages
+const EXTENSION_SCHEMES: &[&str] = &["chrome-extension://", "moz-extension://", "safari-web-extension://"];
+
+pub struct CompanionHandle {
+    pub port: u16,
+    stop: Arc<AtomicBool>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl CompanionHandle {
+    pub fn is_running(&self) -> bool {
+        self.handle.as_ref().is_some_and(|h| !h.is_finished())
+    }
+}
+
+impl Drop for CompanionHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Bind the endpoint on `127.0.0.1:port` and serve it on a background thread
+/// until the returned handle is dropped.
+pub fn start_companion(port: u16, app_handle: AppHandle) -> Result<CompanionHandle, String> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| format!("Failed to bind 127.0.0.1:{}: {}", port, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_flag = Arc::clone(&stop);
+    let handle = std::thread::spawn(move || {
+        log::info!("[companion] Listening on 127.0.0.1:{}", port);
+        let active = Arc::new(AtomicUsize::new(0));
+        while !stop_flag.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    // Each client gets its own thread, so a slow one can't
+                    // hold up the rest
+                    if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                        active.fetch_sub(1, Ordering::SeqCst);
+                        log::warn!("[companion] Too many connections, dropping one");
+                        continue;
+                    }
+                    let active = Arc::clone(&active);
+                    let app = app_handle.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = handle_connection(stream, &app) {
+                            log::warn!("[companion] {}", e);
+                        }
+                        active.fetch_sub(1, Ordering::SeqCst);
+                    });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(ACCEPT_POLL);
+                }
+                Err(e) => log::warn!("[companion] Accept failed: {}", e),
+            }
+        }
+        log::info!("[companion] Stopped");
+    });
+
+    Ok(CompanionHandle {
+        port,
+        stop,
+        handle: Some(handle),
+    })
+}
+
+/// A random 128-bit hex token from the OS's secure random source
+pub fn new_token() -> Result<String, String> {
+    use ring::rand::{SecureRandom, SystemRandom};
+
+    let mut bytes = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| "No secure random source available".to_string())?;
+    Ok(hex::encode(bytes))
+}
+
+/// Compare without stopping at the first difference, so response times
+/// don't reveal how much of a guessed token was right
+fn tokens_match(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Whether a browser page from `origin` may call the endpoint: the
+/// configured extension origin, or any extension while none is configured
+fn origin_allowed(origin: &str, allowed: &str) -> bool {
+    if allowed.is_empty() {
+        EXTENSION_SCHEMES.iter().any(|s| origin.starts_with(s))
+    } else {
+        origin.trim_end_matches('/') == allowed.trim_end_matches('/')
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+fn handle_connection(mut stream: TcpStream, app: &AppHandle) -> Result<(), String> {
+    stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(READ_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+
+    let allowed = app.state::<AppState>().settings.lock().unwrap().companion_origin.clone();
+    // Requests without an Origin don't come from a web page
+    let (status, body, origin) = match read_request(&stream) {
+        Ok(request) => match request.headers.get("origin").cloned() {
+            Some(origin) if !origin_allowed(&origin, &allowed) => {
+                (403, json!({ "error": "Origin not allowed" }), None)
+            }
+            origin if request.method == "OPTIONS" => (204, Value::Null, origin),
+            origin => {
+                let (status, body) = route(&request, app);
+                (status, body, origin)
+            }
+        },
+        Err((status, e)) => (status, json!({ "error": e }), None),
+    };
+    write_response(&mut stream, status, &body, origin.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Reads from a stream until a deadline, so a client trickling bytes can't
+/// hold its connection open past it
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "Request took too long"));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+/// Read one request, failing with the status to answer: 431 when its head
+/// is over `MAX_HEAD_BYTES` or `MAX_HEADERS`, 400 for anything else
+fn read_request(stream: &TcpStream) -> Result<Request, (u16, String)> {
+    let bad = |e: std::io::Error| (400, e.to_string());
+    let too_large = || (431, "Request headers too large".to_string());
+    let reader = BufReader::new(DeadlineReader {
+        stream,
+        deadline: Instant::now() + READ_TIMEOUT,
+    });
+    let mut head = reader.take(MAX_HEAD_BYTES);
+    // A line cut off by the limit is missing its newline
+    let mut read_line = |line: &mut String| match head.read_line(line).map_err(bad)? {
+        0 => Ok(0),
+        _ if !line.ends_with('\n') && head.limit() == 0 => Err(too_large()),
+        n => Ok(n),
+    };
+
+    let mut line = String::new();
+    read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_uppercase();
+    let target = parts.next().ok_or((400, "Malformed request line".to_string()))?.to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut header = String::new();
+        if read_line(&mut header)? == 0 {
+            break;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(too_large());
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = headers
+        .get("content-length")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    if length > MAX_BODY_BYTES {
+        return Err((400, "Request body too large".to_string()));
+    }
+    let mut body = vec![0; length];
+    head.into_inner().read_exact(&mut body).map_err(bad)?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target.as_str(), ""));
+    let query = url::form_urlencoded::parse(query.as_bytes())
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query,
+        headers,
+        body,
+    })
+}
+
+fn route(request: &Request, app: &AppHandle) -> (u16, Value) {
+    let state = app.state::<AppState>();
+    let token = state.settings.lock().unwrap().companion_token.clone();
+    let given = request
+        .headers
+        .get("authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if token.is_empty() || !tokens_match(given, &token) {
+        return (401, json!({ "error": "Invalid or missing token" }));
+    }
+
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/owned") => owned(request, &state),
+        ("POST", "/queue-download") => queue_download(request, &state, app),
+        ("POST", "/save-metadata") => save_metadata(request, &state),
+        _ => return (404, json!({ "error": "Not found" })),
+    };
+    match result {
+        Ok(body) => (200, body),
+        Err(e) => (400, json!({ "error": e })),
+    }
+}
+
+fn owned(request: &Request, state: &AppState) -> Result<Value, String> {
+    let inputs: Vec<String> = request
+        .query
+        .get("gid")
+        .ok_or("Missing gid")?
+        .split(',')
+        .map(|s| s.to_string())
+        .collect();
+    let statuses = commands::lookup_owned(state, inputs)?;
+    serde_json::to_value(statuses).map_err(|e| e.to_string())
+}
+
+#[derive(Deserialize)]
+struct QueueDownload {
+    url: String,
+    #[serde(default)]
+    title: String,
+}
+
+/// A pushed gallery goes on the want-list, which closes itself once the
+/// gallery is in the library, and is downloaded into the first library root
+/// when the downloader can run (cookies set, not owned yet). `job_id` is
+/// null, with the reason in `download_error`, when it can't.
+fn queue_download(request: &Request, state: &AppState, app: &AppHandle) -> Result<Value, String> {
+    let body: QueueDownload = serde_json::from_slice(&request.body).map_err(|e| e.to_string())?;
+    let url = fetcher::canonical_gallery_url(&body.url);
+    let gid = fetcher::gallery_gid(&url).ok_or_else(|| format!("Not a gallery URL: {}", body.url))?;
+    let id = state
+        .db
+        .add_wanted(gid, &url, body.title.trim(), "extension")
+        .map_err(|e| e.to_string())?;
+    let _ = app.emit("wanted-added", json!({ "id": id, "url": url }));
+    match commands::queue_gallery_download(state, &url, None) {
+        Ok(job_id) => Ok(json!({ "id": id, "job_id": job_id })),
+        Err(e) => Ok(json!({ "id": id, "job_id": null, "download_error": e })),
+    }
+}
+
+#[derive(Deserialize)]
+struct SaveMetadata {
+    url: String,
+    html: String,
+}
+
+fn save_metadata(request: &Request, state: &AppState) -> Result<Value, String> {
+    let body: SaveMetadata = serde_json::from_slice(&request.body).map_err(|e| e.to_string())?;
+    let url = fetcher::canonical_gallery_url(&body.url);
+    let gid = fetcher::gallery_gid(&url).ok_or_else(|| format!("Not a gallery URL: {}", body.url))?;
+    let fetched = fetcher::parse_gallery_html(&body.html, &url)?;
+
+    let copies = state
+        .db
+        .find_galleries_by_gid(&[gid])
+        .map_err(|e| e.to_string())?
+        .remove(&gid)
+        .unwrap_or_default();
+    let mut updated = Vec::new();
+    for copy in copies {
+        let Some(gallery) = state.db.get_gallery_by_id(copy.id).map_err(|e| e.to_string())? else {
+            continue;
+        };
+        let conflicts = commands::apply_fetched_metadata(state, &gallery, &fetched, false)?;
+        updated.push(json!({ "id": gallery.id, "conflicts": conflicts }));
+    }
+    Ok(json!({ "updated": updated }))
+}
+
+/// Write the response; CORS headers are only sent back to an allowed `origin`
+fn write_response(
+    stream: &mut TcpStream,
+    status: u16,
+    body: &Value,
+    origin: Option<&str>,
+) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        204 => "No Content",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        _ => "Bad Request",
+    };
+    let body = if body.is_null() {
+        String::new()
+    } else {
+        body.to_string()
+    };
+    let cors = origin
+        .map(|origin| {
+            format!(
+                "Access-Control-Allow-Origin: {}\r\n\
+                 Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+                 Access-Control-Allow-Headers: Authorization, Content-Type\r\n\
+                 Vary: Origin\r\n",
+                origin
+            )
+        })
+        .unwrap_or_default();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         {}\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        cors,
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_extension_origins_are_allowed() {
+        assert!(origin_allowed("chrome-extension://abcdef", ""));
+        assert!(origin_allowed("moz-extension://1234-5678", ""));
+        assert!(!origin_allowed("https://evil.example", ""));
+        assert!(!origin_allowed("null", ""));
+        assert!(origin_allowed("chrome-extension://abcdef/", "chrome-extension://abcdef"));
+        assert!(!origin_allowed("chrome-extension://other", "chrome-extension://abcdef"));
+    }
+
+    #[test]
+    fn tokens_are_random_and_compared_exactly() {
+        let token = new_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert_ne!(new_token().unwrap(), token);
+        assert!(tokens_match(&token, &token));
+        assert!(!tokens_match(&token[..31], &token));
+        assert!(!tokens_match(&format!("{}0", &token[..31]), &format!("{}1", &token[..31])));
+    }
+
+    /// Send `raw` to a fresh connection and read it back as a request
+    fn parse(raw: Vec<u8>) -> Result<Request, (u16, String)> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            // The server may stop reading early and reset the connection
+            let _ = stream.write_all(&raw);
+            stream
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let request = read_request(&stream);
+        drop(client.join().unwrap());
+        request
+    }
+
+    #[test]
+    fn reads_requests_with_encoded_queries() {
+        let request = parse(
+            b"POST /owned?gid=1%2C2&title=a+b HTTP/1.1\r\nContent-Length: 4\r\nX-A: 1\r\n\r\nbody".to_vec(),
+        )
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/owned");
+        assert_eq!(request.query["gid"], "1,2");
+        assert_eq!(request.query["title"], "a b");
+        assert_eq!(request.headers["x-a"], "1");
+        assert_eq!(request.body, b"body");
+    }
+
+    #[test]
+    fn rejects_oversized_heads() {
+        let mut long_line = b"GET /".to_vec();
+        long_line.extend(vec![b'a'; MAX_HEAD_BYTES as usize]);
+        assert_eq!(parse(long_line).err().map(|e| e.0), Some(431));
+
+        let mut many = b"GET / HTTP/1.1\r\n".to_vec();
+        for i in 0..=MAX_HEADERS {
+            many.extend(format!("X-{}: 1\r\n", i).into_bytes());
+        }
+        many.extend(b"\r\n");
+        assert_eq!(parse(many).err().map(|e| e.0), Some(431));
+    }
+}
@@ -1,23 +1,158 @@
-use rusqlite::{params, Connection, Result as SqlResult};
-use std::path::Path;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Result as SqlResult};
+use unicode_normalization::is_nfc;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+use crate::collation;
+use crate::fetcher;
+use crate::importer::ImportedGallery;
+use crate::matcher::Candidate;
+use crate::metadata;
+use crate::scanner;
 use crate::models::*;
+use crate::query::{self, Condition, GalleryQuery};
+
+/// Columns (on alias `g`) read back by `summary_from_row`
+const SUMMARY_COLUMNS: &str = "g.id, g.title_en, g.title_jp, g.category, g.page_count, g.rating,
+     g.thumb_path, g.folder_name, g.path, g.custom_title, g.added_at, g.archive_label,
+     (SELECT percent FROM reading_progress WHERE gallery_id = g.id), g.color_label";
+
+/// Galleries shown in the library: present on disk, or archived to an
+/// offline drive (their files may be gone, their metadata stays searchable)
+const LISTED_EXPR: &str = "(g.missing = 0 OR g.archive_label != '')";
+
+/// Gallery id of a stored URL. Stored URLs are canonical
+/// (`https://exhentai.org/g/<gid>/<token>/`), so the gid starts at a fixed
+/// offset; `idx_galleries_gid` indexes this exact expression.
+const GID_EXPR: &str = "CAST(substr(g.url, 24) AS INTEGER)";
+
+/// Rows of `galleries_fts`: the titles plus the gallery's tag values,
+/// uploader and the user's notes and custom field values, so free text
+/// finds "glasses", an artist name or "needs TL"
+const FTS_SOURCE: &str = "SELECT id, title_en, title_jp, folder_name, custom_title,
+     (SELECT group_concat(tag, ' ') FROM gallery_tags WHERE gallery_id = galleries.id), uploader,
+     notes || ' ' || COALESCE((SELECT group_concat(value, ' ') FROM gallery_fields
+         WHERE gallery_id = galleries.id), '')
+     FROM galleries";
+
+/// Columns of `galleries_fts`, in `FTS_SOURCE` order
+const FTS_COLUMNS: &str = "title_en, title_jp, folder_name, custom_title, tags, uploader, notes";
+
+/// Reading state of a gallery: 0 never opened, 1 in progress, 2 finished
+const READ_STATE_EXPR: &str = "COALESCE((SELECT CASE WHEN percent >= 100 THEN 2 ELSE 1 END
+     FROM reading_progress WHERE gallery_id = g.id), 0)";
+
+/// Most tags listed in search facets
+const FACET_TAG_LIMIT: i64 = 30;
+
+/// A gallery opened again within this many minutes of its last page turn
+/// continues the same history entry
+const HISTORY_SESSION_MINUTES: i64 = 30;
+
+/// Entries kept in the library change log
+const CHANGE_LOG_SIZE: i64 = 10_000;
+
+/// Queries taking longer than this are logged with their parameters
+const SLOW_QUERY: Duration = Duration::from_millis(200);
+
+/// Log `sql` if it ran for longer than `SLOW_QUERY` since `started`. Debug
+/// builds also log its query plan, to spot scans that need an index.
+fn log_if_slow(conn: &Connection, sql: &str, params: &[Value], started: Instant) {
+    let elapsed = started.elapsed();
+    if elapsed < SLOW_QUERY {
+        return;
+    }
+    log::warn!(
+        "[db] Slow query ({} ms): {} -- params: {:?}",
+        elapsed.as_millis(),
+        sql.split_whitespace().collect::<Vec<_>>().join(" "),
+        params
+    );
+    if cfg!(debug_assertions) {
+        let plan = conn
+            .prepare(&format!("EXPLAIN QUERY PLAN {}", sql))
+            .and_then(|mut stmt| {
+                stmt.query_map(rusqlite::params_from_iter(params), |row| row.get::<_, String>(3))?
+                    .collect::<SqlResult<Vec<_>>>()
+            });
+        match plan {
+            Ok(steps) => log::warn!("[db] Query plan:\n  {}", steps.join("\n  ")),
+            Err(e) => log::warn!("[db] Couldn't explain slow query: {}", e),
+        }
+    }
+}
+
+fn summary_from_row(row: &rusqlite::Row) -> SqlResult<GallerySummary> {
+    Ok(GallerySummary {
+        id: row.get(0)?,
+        title_en: row.get(1)?,
+        title_jp: row.get(2)?,
+        category: row.get(3)?,
+        page_count: row.get(4)?,
+        rating: row.get(5)?,
+        thumb_path: row.get(6)?,
+        folder_name: row.get(7)?,
+        path: row.get(8)?,
+        custom_title: row.get(9)?,
+        added_at: row.get(10)?,
+        archive_label: row.get(11)?,
+        read_percent: row.get(12)?,
+        color_label: row.get(13)?,
+        date_modified: 0.0,
+        date_created: 0.0,
+    })
+}
+
+fn inbox_image_from_row(row: &rusqlite::Row) -> SqlResult<InboxImage> {
+    Ok(InboxImage {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        filename: row.get(2)?,
+        file_size: row.get(3)?,
+        thumb_path: row.get(4)?,
+        added_at: row.get(5)?,
+    })
+}
 
 pub struct Database {
     conn: Mutex<Connection>,
+    path: Mutex<PathBuf>,
 }
 
 impl Database {
     pub fn new(db_path: &Path) -> SqlResult<Self> {
-        let conn = Connection::open(db_path)?;
+        let conn = Self::open_file(db_path, OpenFlags::default())?;
         let db = Self {
             conn: Mutex::new(conn),
+            path: Mutex::new(db_path.to_path_buf()),
         };
         db.init_schema()?;
         Ok(db)
     }
 
+    /// Open a connection to the database file at `path` with the functions
+    /// its queries rely on, such as the LOCALE collation, registered. Every
+    /// connection this module opens goes through here.
+    fn open_file(path: &Path, flags: OpenFlags) -> SqlResult<Connection> {
+        let conn = Connection::open_with_flags(path, flags)?;
+        conn.create_collation(collation::SQL_NAME, collation::compare)?;
+        Ok(conn)
+    }
+
+    /// Switch this handle over to the database at `db_path` (created if
+    /// needed), for profile switches. Everyone sharing the handle sees the
+    /// new database from their next query on.
+    pub fn reopen(&self, db_path: &Path) -> SqlResult<()> {
+        let fresh = Self::new(db_path)?;
+        let conn = fresh.conn.into_inner().unwrap_or_else(|e| e.into_inner());
+        *self.conn.lock().unwrap() = conn;
+        *self.path.lock().unwrap() = db_path.to_path_buf();
+        Ok(())
+    }
+
     fn init_schema(&self) -> SqlResult<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute_batch("PRAGMA journal_mode=WAL;")?;
@@ -34,6 +169,7 @@ impl Database {
                 category      TEXT NOT NULL DEFAULT '',
                 uploader      TEXT NOT NULL DEFAULT '',
                 posted        TEXT NOT NULL DEFAULT '',
+                posted_ts     INTEGER,
                 language      TEXT NOT NULL DEFAULT '',
                 file_size     TEXT NOT NULL DEFAULT '',
                 page_count    INTEGER NOT NULL DEFAULT 0,
@@ -43,7 +179,24 @@ impl Database {
                 folder_name   TEXT NOT NULL DEFAULT '',
                 parent_path   TEXT NOT NULL DEFAULT '',
                 info_modified TEXT NOT NULL DEFAULT '',
-                scanned_at    TEXT NOT NULL DEFAULT (datetime('now'))
+                scanned_at    TEXT NOT NULL DEFAULT (datetime('now')),
+                custom_title  TEXT NOT NULL DEFAULT '',
+                uploader_comment TEXT NOT NULL DEFAULT '',
+                is_resampled  INTEGER NOT NULL DEFAULT 0,
+                favorite_slot INTEGER,
+                language_inferred INTEGER NOT NULL DEFAULT 0,
+                added_at      TEXT NOT NULL DEFAULT (datetime('now')),
+                missing       INTEGER NOT NULL DEFAULT 0,
+                archive_label TEXT NOT NULL DEFAULT '',
+                disk_size     INTEGER,
+                is_favorite   INTEGER NOT NULL DEFAULT 0,
+                my_rating     REAL,
+                color_label   TEXT NOT NULL DEFAULT '',
+                newer_url     TEXT NOT NULL DEFAULT '',
+                local_pages   INTEGER,
+                page_width    INTEGER,
+                notes         TEXT NOT NULL DEFAULT '',
+                last_read_at  TEXT NOT NULL DEFAULT ''
             );
 
             CREATE TABLE IF NOT EXISTS gallery_tags (
@@ -53,6 +206,14 @@ impl Database {
                 PRIMARY KEY (gallery_id, namespace, tag)
             );
 
+            -- User-defined key/value fields, e.g. translation status
+            CREATE TABLE IF NOT EXISTS gallery_fields (
+                gallery_id  INTEGER NOT NULL REFERENCES galleries(id) ON DELETE CASCADE,
+                key         TEXT NOT NULL,
+                value       TEXT NOT NULL,
+                PRIMARY KEY (gallery_id, key)
+            );
+
             CREATE TABLE IF NOT EXISTS folders (
                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
                 path        TEXT NOT NULL UNIQUE,
@@ -60,34 +221,418 @@ impl Database {
                 parent_path TEXT NOT NULL DEFAULT ''
             );
 
+            CREATE TABLE IF NOT EXISTS gallery_dirty_fields (
+                gallery_id  INTEGER NOT NULL REFERENCES galleries(id) ON DELETE CASCADE,
+                field       TEXT NOT NULL,
+                PRIMARY KEY (gallery_id, field)
+            );
+
+            CREATE TABLE IF NOT EXISTS field_locks (
+                gallery_id  INTEGER NOT NULL REFERENCES galleries(id) ON DELETE CASCADE,
+                field       TEXT NOT NULL,
+                PRIMARY KEY (gallery_id, field)
+            );
+
+            CREATE TABLE IF NOT EXISTS gallery_remote_snapshots (
+                gallery_id  INTEGER PRIMARY KEY REFERENCES galleries(id) ON DELETE CASCADE,
+                data        TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS jobs (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                spec        TEXT NOT NULL,
+                priority    TEXT NOT NULL DEFAULT 'normal',
+                done        INTEGER NOT NULL DEFAULT 0,
+                paused      INTEGER NOT NULL DEFAULT 0,
+                attempts    INTEGER NOT NULL DEFAULT 0,
+                last_error  TEXT NOT NULL DEFAULT '',
+                created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            CREATE TABLE IF NOT EXISTS collections (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                name        TEXT NOT NULL,
+                description TEXT NOT NULL DEFAULT '',
+                created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            -- Entries are keyed by gid so a shared list resolves against any
+            -- library, including galleries the user doesn't have
+            CREATE TABLE IF NOT EXISTS collection_items (
+                collection_id INTEGER NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+                gid           INTEGER NOT NULL,
+                url           TEXT NOT NULL,
+                title         TEXT NOT NULL DEFAULT '',
+                position      INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (collection_id, gid)
+            );
+
+            CREATE TABLE IF NOT EXISTS wanted (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                gid         INTEGER NOT NULL UNIQUE,
+                url         TEXT NOT NULL,
+                title       TEXT NOT NULL DEFAULT '',
+                status      TEXT NOT NULL DEFAULT 'wanted',
+                source      TEXT NOT NULL DEFAULT 'manual',
+                gallery_id  INTEGER REFERENCES galleries(id) ON DELETE SET NULL,
+                added_at    TEXT NOT NULL DEFAULT (datetime('now')),
+                acquired_at TEXT NOT NULL DEFAULT ''
+            );
+
+            -- Title-search hits for unidentified galleries too weak to apply
+            -- without the user's confirmation
+            CREATE TABLE IF NOT EXISTS identify_candidates (
+                gallery_id  INTEGER PRIMARY KEY REFERENCES galleries(id) ON DELETE CASCADE,
+                url         TEXT NOT NULL,
+                title       TEXT NOT NULL DEFAULT '',
+                page_count  INTEGER NOT NULL DEFAULT 0,
+                score       REAL NOT NULL DEFAULT 0.0,
+                found_at    TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            CREATE TABLE IF NOT EXISTS followed_artists (
+                artist          TEXT PRIMARY KEY,
+                last_seen_gid   INTEGER NOT NULL DEFAULT 0,
+                last_checked_at TEXT NOT NULL DEFAULT '',
+                followed_at     TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            -- Per-page SHA-1s and perceptual hashes for reverse image lookup;
+            -- `page_index` follows the reader's page order
+            CREATE TABLE IF NOT EXISTS page_hashes (
+                gallery_id  INTEGER NOT NULL REFERENCES galleries(id) ON DELETE CASCADE,
+                page_index  INTEGER NOT NULL,
+                filename    TEXT NOT NULL,
+                sha1        TEXT NOT NULL,
+                phash       INTEGER,
+                width       INTEGER,
+                height      INTEGER,
+                grayscale   INTEGER,
+                PRIMARY KEY (gallery_id, page_index)
+            );
+
+            -- Manual page order for galleries whose file names sort wrong;
+            -- `name` is the file name, or the entry name inside an archive
+            CREATE TABLE IF NOT EXISTS page_order (
+                gallery_id  INTEGER NOT NULL REFERENCES galleries(id) ON DELETE CASCADE,
+                position    INTEGER NOT NULL,
+                name        TEXT NOT NULL,
+                PRIMARY KEY (gallery_id, position)
+            );
+
+            -- Credit and extra pages, left out of reading progress and covers;
+            -- `name` as in `page_order`
+            CREATE TABLE IF NOT EXISTS page_extras (
+                gallery_id  INTEGER NOT NULL REFERENCES galleries(id) ON DELETE CASCADE,
+                name        TEXT NOT NULL,
+                PRIMARY KEY (gallery_id, name)
+            );
+
+            -- Where the reader left off; `total_pages` and `percent` leave out
+            -- extra pages
+            CREATE TABLE IF NOT EXISTS reading_progress (
+                gallery_id  INTEGER PRIMARY KEY REFERENCES galleries(id) ON DELETE CASCADE,
+                page        INTEGER NOT NULL,
+                total_pages INTEGER NOT NULL,
+                percent     INTEGER NOT NULL,
+                updated_at  TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            -- One row per reading session
+            CREATE TABLE IF NOT EXISTS reading_history (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                gallery_id  INTEGER NOT NULL REFERENCES galleries(id) ON DELETE CASCADE,
+                opened_at   TEXT NOT NULL DEFAULT (datetime('now')),
+                start_page  INTEGER NOT NULL,
+                pages_read  INTEGER NOT NULL DEFAULT 1,
+                updated_at  TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            -- Ordered log of library changes for `get_changes_since`
+            CREATE TABLE IF NOT EXISTS library_changes (
+                seq         INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind        TEXT NOT NULL,
+                gallery_id  INTEGER,
+                path        TEXT NOT NULL DEFAULT '',
+                created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            CREATE TABLE IF NOT EXISTS inbox_images (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                path        TEXT NOT NULL UNIQUE,
+                filename    TEXT NOT NULL,
+                file_size   INTEGER NOT NULL DEFAULT 0,
+                mtime       TEXT NOT NULL DEFAULT '',
+                thumb_path  TEXT NOT NULL DEFAULT '',
+                added_at    TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            -- Display names imported from the installed tag translation
+            -- database
+            CREATE TABLE IF NOT EXISTS tag_translations (
+                namespace   TEXT NOT NULL,
+                tag         TEXT NOT NULL,
+                name        TEXT NOT NULL,
+                PRIMARY KEY (namespace, tag)
+            );
+
+            -- Named searches; `query` is a serialized SearchQuery
+            CREATE TABLE IF NOT EXISTS saved_searches (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                name        TEXT NOT NULL UNIQUE,
+                query       TEXT NOT NULL,
+                created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            -- When each root last finished a full scan, for the scan scheduler
+            CREATE TABLE IF NOT EXISTS root_scans (
+                root        TEXT PRIMARY KEY,
+                scanned_at  TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+
+            -- Alternate spellings of a tag, shown and searched as their target
+            CREATE TABLE IF NOT EXISTS tag_aliases (
+                namespace        TEXT NOT NULL,
+                tag              TEXT NOT NULL,
+                target_namespace TEXT NOT NULL,
+                target_tag       TEXT NOT NULL,
+                PRIMARY KEY (namespace, tag)
+            );
+
+            -- Gallery tags with aliases replaced by their targets
+            CREATE VIEW IF NOT EXISTS resolved_tags AS
+                SELECT t.gallery_id,
+                       COALESCE(a.target_namespace, t.namespace) AS namespace,
+                       COALESCE(a.target_tag, t.tag) AS tag
+                FROM gallery_tags t
+                LEFT JOIN tag_aliases a ON a.namespace = t.namespace AND a.tag = t.tag;
+
+            CREATE INDEX IF NOT EXISTS idx_reading_history_gallery ON reading_history(gallery_id, updated_at);
             CREATE INDEX IF NOT EXISTS idx_galleries_parent ON galleries(parent_path);
+            CREATE INDEX IF NOT EXISTS idx_page_hashes_sha1 ON page_hashes(sha1);
             CREATE INDEX IF NOT EXISTS idx_gallery_tags_ns_tag ON gallery_tags(namespace, tag);
             CREATE INDEX IF NOT EXISTS idx_gallery_tags_tag ON gallery_tags(tag);
             CREATE INDEX IF NOT EXISTS idx_folders_parent ON folders(parent_path);
+            CREATE INDEX IF NOT EXISTS idx_galleries_url ON galleries(url);
+            CREATE INDEX IF NOT EXISTS idx_galleries_category ON galleries(category);
+            CREATE INDEX IF NOT EXISTS idx_galleries_language ON galleries(language);
+            CREATE INDEX IF NOT EXISTS idx_galleries_gid ON galleries(CAST(substr(url, 24) AS INTEGER));
             ",
         )?;
 
+        // Columns added after the initial schema
+        Self::add_column_if_missing(&conn, "galleries", "custom_title", "TEXT NOT NULL DEFAULT ''")?;
+        Self::add_column_if_missing(
+            &conn,
+            "galleries",
+            "uploader_comment",
+            "TEXT NOT NULL DEFAULT ''",
+        )?;
+        Self::add_column_if_missing(&conn, "galleries", "is_resampled", "INTEGER NOT NULL DEFAULT 0")?;
+        Self::add_column_if_missing(&conn, "galleries", "favorite_slot", "INTEGER")?;
+        Self::add_column_if_missing(
+            &conn,
+            "galleries",
+            "language_inferred",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+        Self::add_column_if_missing(&conn, "galleries", "missing", "INTEGER NOT NULL DEFAULT 0")?;
+        Self::add_column_if_missing(&conn, "galleries", "last_read_at", "TEXT NOT NULL DEFAULT ''")?;
+        Self::add_column_if_missing(&conn, "galleries", "pages_hashed_at", "TEXT NOT NULL DEFAULT ''")?;
+        if Self::has_column(&conn, "galleries", "read_page") {
+            // Progress used to be a bare page on the gallery row; move it over
+            // once (extras can't be told apart here, so they count as pages)
+            conn.execute_batch(
+                "INSERT OR IGNORE INTO reading_progress (gallery_id, page, total_pages, percent, updated_at)
+                 SELECT id, read_page, page_count,
+                        MIN(100, (read_page + 1) * 100 / MAX(page_count, 1)), last_read_at
+                 FROM galleries WHERE read_page IS NOT NULL;
+                 UPDATE galleries SET read_page = NULL WHERE read_page IS NOT NULL;",
+            )?;
+        }
+        Self::add_column_if_missing(&conn, "galleries", "archive_label", "TEXT NOT NULL DEFAULT ''")?;
+        Self::add_column_if_missing(&conn, "galleries", "disk_size", "INTEGER")?;
+        Self::add_column_if_missing(&conn, "galleries", "is_favorite", "INTEGER NOT NULL DEFAULT 0")?;
+        Self::add_column_if_missing(&conn, "galleries", "my_rating", "REAL")?;
+        Self::add_column_if_missing(&conn, "galleries", "notes", "TEXT NOT NULL DEFAULT ''")?;
+        Self::add_column_if_missing(&conn, "galleries", "color_label", "TEXT NOT NULL DEFAULT ''")?;
+        Self::add_column_if_missing(&conn, "galleries", "newer_url", "TEXT NOT NULL DEFAULT ''")?;
+        Self::add_column_if_missing(&conn, "galleries", "local_pages", "INTEGER")?;
+        Self::add_column_if_missing(&conn, "galleries", "page_width", "INTEGER")?;
+        Self::add_column_if_missing(&conn, "jobs", "paused", "INTEGER NOT NULL DEFAULT 0")?;
+        Self::add_column_if_missing(&conn, "jobs", "attempts", "INTEGER NOT NULL DEFAULT 0")?;
+        Self::add_column_if_missing(&conn, "jobs", "last_error", "TEXT NOT NULL DEFAULT ''")?;
+        if !Self::has_column(&conn, "page_hashes", "phash") {
+            // Galleries hashed before perceptual hashes existed need a rehash
+            conn.execute_batch(
+                "ALTER TABLE page_hashes ADD COLUMN phash INTEGER;
+                 UPDATE galleries SET pages_hashed_at = '';",
+            )?;
+        }
+        if !Self::has_column(&conn, "page_hashes", "grayscale") {
+            // Page dimensions and colour come from the same decode as the
+            // perceptual hash, so older hashes need a rehash too
+            conn.execute_batch(
+                "ALTER TABLE page_hashes ADD COLUMN width INTEGER;
+                 ALTER TABLE page_hashes ADD COLUMN height INTEGER;
+                 ALTER TABLE page_hashes ADD COLUMN grayscale INTEGER;
+                 UPDATE galleries SET pages_hashed_at = '';",
+            )?;
+        }
+        if !Self::has_column(&conn, "galleries", "posted_ts") {
+            // Unix time of `posted`, for range filters and sorting; NULL when
+            // the text isn't a date SQLite understands
+            conn.execute_batch(
+                "ALTER TABLE galleries ADD COLUMN posted_ts INTEGER;
+                 UPDATE galleries SET posted_ts = CAST(strftime('%s', posted) AS INTEGER)
+                 WHERE posted != '';",
+            )?;
+        }
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_galleries_posted_ts ON galleries(posted_ts);",
+        )?;
+        if !Self::has_column(&conn, "galleries", "added_at") {
+            // SQLite can't add a column with a non-constant default; backfill
+            // existing rows from their last scan, the best record we have.
+            conn.execute_batch(
+                "ALTER TABLE galleries ADD COLUMN added_at TEXT NOT NULL DEFAULT '';
+                 UPDATE galleries SET added_at = scanned_at;",
+            )?;
+        }
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_galleries_added_at ON galleries(added_at);",
+        )?;
+
+        Self::normalize_gallery_urls(&conn)?;
+
         // FTS5 table - create only if it doesn't exist
-        let fts_exists: bool = conn
+        let fts_sql: Option<String> = conn
             .query_row(
-                "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='galleries_fts'",
+                "SELECT sql FROM sqlite_master WHERE type='table' AND name='galleries_fts'",
                 [],
                 |row| row.get(0),
             )
-            .unwrap_or(false);
+            .ok();
+        let fts_exists = fts_sql.is_some();
 
-        if !fts_exists {
-            conn.execute_batch(
+        // Older databases index words rather than trigrams, only titles, or
+        // no notes; rebuild them
+        let fts_current = fts_sql.is_some_and(|sql| sql.contains("trigram") && sql.contains("notes"));
+        if fts_exists && !fts_current {
+            conn.execute_batch("DROP TABLE galleries_fts;")?;
+        }
+
+        if !fts_current {
+            // Tags live in their own table, so the index keeps its own copy
+            // of the text rather than reading galleries as external content.
+            // Trigrams match any substring, which word tokenizers can't do
+            // for Japanese and Chinese titles.
+            conn.execute_batch(&format!(
                 "
                 CREATE VIRTUAL TABLE galleries_fts USING fts5(
-                    title_en, title_jp, folder_name,
-                    content='galleries', content_rowid='id',
-                    tokenize='unicode61'
+                    {columns},
+                    tokenize='trigram'
                 );
+                INSERT INTO galleries_fts(rowid, {columns})
+                {source};
                 ",
+                columns = FTS_COLUMNS,
+                source = FTS_SOURCE
+            ))?;
+        }
+
+        // Reindexes FTS rows, so it runs once the FTS table is current
+        Self::normalize_unicode(&conn)?;
+
+        Ok(())
+    }
+
+    /// Rewrite stored URLs that predate canonicalization onto the canonical domain.
+    fn normalize_gallery_urls(conn: &Connection) -> SqlResult<()> {
+        let mut stmt = conn.prepare(
+            "SELECT id, url FROM galleries WHERE url != '' AND url NOT LIKE 'https://exhentai.org/g/%/'",
+        )?;
+        let rows: Vec<(i64, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        for (id, url) in rows {
+            let canonical = fetcher::canonical_gallery_url(&url);
+            if canonical != url {
+                conn.execute(
+                    "UPDATE galleries SET url = ?1 WHERE id = ?2",
+                    params![canonical, id],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-compose rows stored before paths and titles were NFC-normalized.
+    /// A row whose NFC path already exists is a duplicate from the same
+    /// mismatch and is left alone rather than clobbering the other copy.
+    fn normalize_unicode(conn: &Connection) -> SqlResult<()> {
+        let mut stmt = conn.prepare(
+            "SELECT id, path, parent_path, folder_name, title_en, title_jp, custom_title
+             FROM galleries",
+        )?;
+        let rows: Vec<(i64, [String; 6])> = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    [
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                        row.get(6)?,
+                    ],
+                ))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        for (id, fields) in rows {
+            if fields.iter().all(|f| is_nfc(f)) {
+                continue;
+            }
+            let [path, parent_path, folder_name, title_en, title_jp, custom_title] =
+                fields.map(|f| scanner::to_nfc(&f));
+            let updated = conn.execute(
+                "UPDATE OR IGNORE galleries SET path = ?1, parent_path = ?2, folder_name = ?3,
+                 title_en = ?4, title_jp = ?5, custom_title = ?6 WHERE id = ?7",
+                params![path, parent_path, folder_name, title_en, title_jp, custom_title, id],
             )?;
+            if updated == 0 {
+                log::warn!("Skipping NFC normalization of {}: path already exists", path);
+                continue;
+            }
+            Self::reindex_fts(conn, id)?;
         }
+        Ok(())
+    }
+
+    fn has_column(conn: &Connection, table: &str, column: &str) -> bool {
+        conn.prepare(&format!("PRAGMA table_info({})", table))
+            .and_then(|mut stmt| {
+                let names: Vec<String> = stmt
+                    .query_map([], |row| row.get(1))?
+                    .filter_map(|r| r.ok())
+                    .collect();
+                Ok(names.iter().any(|n| n == column))
+            })
+            .unwrap_or(false)
+    }
 
+    fn add_column_if_missing(
+        conn: &Connection,
+        table: &str,
+        column: &str,
+        decl: &str,
+    ) -> SqlResult<()> {
+        if !Self::has_column(conn, table, column) {
+            conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, decl))?;
+        }
         Ok(())
     }
 
@@ -99,6 +644,9 @@ impl Database {
         info_modified: &str,
     ) -> SqlResult<i64> {
         let conn = self.conn.lock().unwrap();
+        let path = &scanner::to_nfc(path);
+        let normalized = scanner::nfc_parsed(parsed);
+        let parsed = &normalized;
 
         let folder_name = Path::new(path)
             .file_name()
@@ -109,28 +657,75 @@ impl Database {
             .map(|p| p.to_string_lossy().to_string())
             .unwrap_or_default();
 
+        // Locked and locally edited fields keep their stored value no matter
+        // what info.txt says
+        let existing_id: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM galleries WHERE path = ?1",
+                params![path],
+                |row| row.get(0),
+            )
+            .ok();
+        let kept;
+        let parsed = match existing_id {
+            Some(id) => {
+                let mut locked = Self::field_locks_for(&conn, id)?;
+                let mut stmt =
+                    conn.prepare("SELECT field FROM gallery_dirty_fields WHERE gallery_id = ?1")?;
+                locked.extend(
+                    stmt.query_map(params![id], |row| row.get::<_, String>(0))?
+                        .filter_map(|r| r.ok()),
+                );
+                if locked.is_empty() {
+                    parsed
+                } else {
+                    let current = Self::load_parsed(&conn, id)?;
+                    let mut merged = parsed.clone();
+                    for field in &locked {
+                        metadata::copy_field(&mut merged, &current, field);
+                    }
+                    kept = merged;
+                    &kept
+                }
+            }
+            None => parsed,
+        };
+
+        // Fill a missing language from title markers, remembering it was a guess
+        let inferred_language = if parsed.language.is_empty() {
+            scanner::infer_language(&[&parsed.title_en, &parsed.title_jp, &folder_name])
+        } else {
+            None
+        };
+        let language = inferred_language.unwrap_or(&parsed.language);
+
         conn.execute(
             "INSERT INTO galleries (path, title_en, title_jp, url, category, uploader, posted,
              language, file_size, page_count, rating, favorited, thumb_path, folder_name,
-             parent_path, info_modified)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+             parent_path, info_modified, uploader_comment, language_inferred, added_at,
+             posted_ts)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+                     ?18, datetime('now'), CAST(strftime('%s', NULLIF(?7, '')) AS INTEGER))
              ON CONFLICT(path) DO UPDATE SET
                 title_en=excluded.title_en, title_jp=excluded.title_jp, url=excluded.url,
                 category=excluded.category, uploader=excluded.uploader, posted=excluded.posted,
+                posted_ts=excluded.posted_ts,
                 language=excluded.language, file_size=excluded.file_size,
                 page_count=excluded.page_count, rating=excluded.rating,
                 favorited=excluded.favorited, thumb_path=excluded.thumb_path,
                 folder_name=excluded.folder_name, parent_path=excluded.parent_path,
-                info_modified=excluded.info_modified, scanned_at=datetime('now')",
+                info_modified=excluded.info_modified, uploader_comment=excluded.uploader_comment,
+                language_inferred=excluded.language_inferred, missing=0,
+                scanned_at=datetime('now')",
             params![
                 path,
                 parsed.title_en,
                 parsed.title_jp,
-                parsed.url,
+                fetcher::canonical_gallery_url(&parsed.url),
                 parsed.category,
                 parsed.uploader,
                 parsed.posted,
-                parsed.language,
+                language,
                 parsed.file_size,
                 parsed.page_count,
                 parsed.rating,
@@ -139,6 +734,8 @@ impl Database {
                 folder_name,
                 parent_path,
                 info_modified,
+                parsed.uploader_comment,
+                inferred_language.is_some(),
             ],
         )?;
 
@@ -162,150 +759,549 @@ impl Database {
         }
 
         // Update FTS
-        conn.execute(
-            "INSERT OR REPLACE INTO galleries_fts(rowid, title_en, title_jp, folder_name)
-             VALUES (?1, ?2, ?3, ?4)",
-            params![gallery_id, parsed.title_en, parsed.title_jp, folder_name],
-        )?;
+        Self::reindex_fts(&conn, gallery_id)?;
+
+        Self::mark_wanted_acquired(&conn, gallery_id, &parsed.url)?;
 
         Ok(gallery_id)
     }
 
-    pub fn delete_gallery_by_path(&self, path: &str) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        // Get id first for FTS cleanup
-        let id: Option<i64> = conn
-            .query_row(
-                "SELECT id FROM galleries WHERE path = ?1",
-                params![path],
-                |row| row.get(0),
-            )
-            .ok();
-
-        if let Some(id) = id {
+    /// Close out a want-list entry once a gallery with its gid is in the library.
+    fn mark_wanted_acquired(conn: &Connection, gallery_id: i64, url: &str) -> SqlResult<()> {
+        if let Some(gid) = fetcher::gallery_gid(url) {
             conn.execute(
-                "DELETE FROM galleries_fts WHERE rowid = ?1",
-                params![id],
+                "UPDATE wanted SET status = 'acquired', gallery_id = ?1, acquired_at = datetime('now')
+                 WHERE gid = ?2 AND status != 'acquired'",
+                params![gallery_id, gid],
             )?;
-            conn.execute("DELETE FROM galleries WHERE id = ?1", params![id])?;
         }
         Ok(())
     }
 
-    pub fn get_gallery_by_id(&self, id: i64) -> SqlResult<Option<Gallery>> {
+    /// Add a gallery to the want-list, or return the existing entry's id.
+    /// Entries already in the library start out acquired.
+    pub fn add_wanted(&self, gid: i64, url: &str, title: &str, source: &str) -> SqlResult<i64> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, path, title_en, title_jp, url, category, uploader, posted,
-                    language, file_size, page_count, rating, favorited, thumb_path,
-                    folder_name, parent_path
-             FROM galleries WHERE id = ?1",
+        let url = fetcher::canonical_gallery_url(url);
+        conn.execute(
+            "INSERT OR IGNORE INTO wanted (gid, url, title, source) VALUES (?1, ?2, ?3, ?4)",
+            params![gid, url, title, source],
         )?;
-
-        let gallery = stmt
-            .query_row(params![id], |row| {
-                Ok(Gallery {
-                    id: row.get(0)?,
-                    path: row.get(1)?,
-                    title_en: row.get(2)?,
-                    title_jp: row.get(3)?,
-                    url: row.get(4)?,
-                    category: row.get(5)?,
-                    uploader: row.get(6)?,
-                    posted: row.get(7)?,
-                    language: row.get(8)?,
-                    file_size: row.get(9)?,
-                    page_count: row.get(10)?,
-                    rating: row.get(11)?,
-                    favorited: row.get(12)?,
-                    thumb_path: row.get(13)?,
-                    folder_name: row.get(14)?,
-                    parent_path: row.get(15)?,
-                })
-            })
+        let owned: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM galleries WHERE url LIKE 'https://exhentai.org/g/' || ?1 || '/%'",
+                params![gid],
+                |row| row.get(0),
+            )
             .ok();
-
-        Ok(gallery)
+        if let Some(gallery_id) = owned {
+            Self::mark_wanted_acquired(&conn, gallery_id, &url)?;
+        }
+        conn.query_row("SELECT id FROM wanted WHERE gid = ?1", params![gid], |row| row.get(0))
     }
 
-    pub fn get_gallery_by_path(&self, path: &str) -> SqlResult<Option<GallerySummary>> {
+    pub fn list_wanted(&self, status: Option<&str>) -> SqlResult<Vec<WantedEntry>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, title_en, title_jp, category, page_count, rating, thumb_path, folder_name, path
-             FROM galleries WHERE path = ?1",
+            "SELECT id, gid, url, title, status, source, gallery_id, added_at, acquired_at
+             FROM wanted WHERE ?1 IS NULL OR status = ?1 ORDER BY added_at DESC, id DESC",
         )?;
-
-        let gallery = stmt
-            .query_row(params![path], |row| {
-                Ok(GallerySummary {
+        let entries = stmt
+            .query_map(params![status], |row| {
+                Ok(WantedEntry {
                     id: row.get(0)?,
-                    title_en: row.get(1)?,
-                    title_jp: row.get(2)?,
-                    category: row.get(3)?,
-                    page_count: row.get(4)?,
-                    rating: row.get(5)?,
-                    thumb_path: row.get(6)?,
-                    folder_name: row.get(7)?,
-                    path: row.get(8)?,
-                    date_modified: 0.0,
-                    date_created: 0.0,
+                    gid: row.get(1)?,
+                    url: row.get(2)?,
+                    title: row.get(3)?,
+                    status: row.get(4)?,
+                    source: row.get(5)?,
+                    gallery_id: row.get(6)?,
+                    added_at: row.get(7)?,
+                    acquired_at: row.get(8)?,
                 })
-            })
-            .ok();
-
-        Ok(gallery)
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(entries)
     }
 
-    pub fn get_tags_for_gallery(&self, gallery_id: i64) -> SqlResult<Vec<TagEntry>> {
+    pub fn set_wanted_status(&self, id: i64, status: &str) -> SqlResult<()> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT namespace, tag FROM gallery_tags WHERE gallery_id = ?1 ORDER BY namespace, tag",
+        conn.execute(
+            "UPDATE wanted SET status = ?1,
+                acquired_at = CASE WHEN ?1 = 'acquired' THEN datetime('now') ELSE '' END
+             WHERE id = ?2",
+            params![status, id],
         )?;
+        Ok(())
+    }
 
-        let tags = stmt
-            .query_map(params![gallery_id], |row| {
-                Ok(TagEntry {
-                    namespace: row.get(0)?,
-                    tag: row.get(1)?,
-                })
-            })?
-            .filter_map(|r| r.ok())
-            .collect();
-
-        Ok(tags)
+    /// Keep a title-search hit for `gallery_id` for the user to confirm,
+    /// replacing any earlier one
+    pub fn set_identify_candidate(
+        &self,
+        gallery_id: i64,
+        url: &str,
+        title: &str,
+        page_count: i64,
+        score: f64,
+    ) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO identify_candidates (gallery_id, url, title, page_count, score)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![gallery_id, url, title, page_count, score],
+        )?;
+        Ok(())
     }
 
-    pub fn get_galleries_in_folder(&self, parent_path: &str) -> SqlResult<Vec<GallerySummary>> {
+    /// Unconfirmed title-search hits for galleries that are still
+    /// unidentified, best matches first
+    pub fn get_identify_candidates(&self) -> SqlResult<Vec<IdentifyCandidate>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, title_en, title_jp, category, page_count, rating, thumb_path, folder_name, path
-             FROM galleries WHERE parent_path = ?1
-             ORDER BY folder_name COLLATE NOCASE",
+            "SELECT c.gallery_id, g.path, g.folder_name, g.page_count, c.url, c.title,
+                    c.page_count, c.score, c.found_at
+             FROM identify_candidates c JOIN galleries g ON g.id = c.gallery_id
+             WHERE g.url = ''
+             ORDER BY c.score DESC, c.gallery_id",
         )?;
-
-        let galleries = stmt
-            .query_map(params![parent_path], |row| {
-                Ok(GallerySummary {
-                    id: row.get(0)?,
-                    title_en: row.get(1)?,
-                    title_jp: row.get(2)?,
-                    category: row.get(3)?,
-                    page_count: row.get(4)?,
-                    rating: row.get(5)?,
-                    thumb_path: row.get(6)?,
-                    folder_name: row.get(7)?,
-                    path: row.get(8)?,
-                    date_modified: 0.0,
-                    date_created: 0.0,
+        let candidates = stmt
+            .query_map([], |row| {
+                Ok(IdentifyCandidate {
+                    gallery_id: row.get(0)?,
+                    path: row.get(1)?,
+                    folder_name: row.get(2)?,
+                    local_page_count: row.get(3)?,
+                    url: row.get(4)?,
+                    title: row.get(5)?,
+                    page_count: row.get(6)?,
+                    score: row.get(7)?,
+                    found_at: row.get(8)?,
                 })
             })?
             .filter_map(|r| r.ok())
             .collect();
-
-        Ok(galleries)
+        Ok(candidates)
     }
 
-    pub fn get_info_modified(&self, path: &str) -> SqlResult<Option<String>> {
+    pub fn get_identify_candidate_url(&self, gallery_id: i64) -> SqlResult<Option<String>> {
         let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT url FROM identify_candidates WHERE gallery_id = ?1",
+            params![gallery_id],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    pub fn remove_identify_candidate(&self, gallery_id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM identify_candidates WHERE gallery_id = ?1",
+            params![gallery_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn follow_artist(&self, artist: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO followed_artists (artist) VALUES (?1)",
+            params![artist],
+        )?;
+        Ok(())
+    }
+
+    pub fn unfollow_artist(&self, artist: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM followed_artists WHERE artist = ?1", params![artist])?;
+        Ok(())
+    }
+
+    pub fn list_followed_artists(&self) -> SqlResult<Vec<FollowedArtist>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT artist, last_seen_gid, last_checked_at, followed_at
+             FROM followed_artists ORDER BY artist COLLATE LOCALE",
+        )?;
+        let artists = stmt
+            .query_map([], |row| {
+                Ok(FollowedArtist {
+                    artist: row.get(0)?,
+                    last_seen_gid: row.get(1)?,
+                    last_checked_at: row.get(2)?,
+                    followed_at: row.get(3)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(artists)
+    }
+
+    /// Whether any followed artist was last checked more than `hours` ago
+    pub fn followed_artists_due(&self, hours: u32) -> SqlResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) > 0 FROM followed_artists
+             WHERE last_checked_at = '' OR last_checked_at <= datetime('now', ?1)",
+            params![format!("-{} hours", hours)],
+            |row| row.get(0),
+        )
+    }
+
+    /// Whether `root` hasn't finished a scan in the last `hours`
+    pub fn root_scan_due(&self, root: &str, hours: u32) -> SqlResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT NOT EXISTS (SELECT 1 FROM root_scans
+                 WHERE root = ?1 AND scanned_at > datetime('now', ?2))",
+            params![root, format!("-{} hours", hours)],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn set_root_scanned(&self, root: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO root_scans (root, scanned_at) VALUES (?1, datetime('now'))
+             ON CONFLICT(root) DO UPDATE SET scanned_at = excluded.scanned_at",
+            params![root],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_artist_checked(&self, artist: &str, last_seen_gid: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE followed_artists SET last_seen_gid = MAX(last_seen_gid, ?1),
+                last_checked_at = datetime('now')
+             WHERE artist = ?2",
+            params![last_seen_gid, artist],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_wanted(&self, id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM wanted WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn delete_gallery_by_path(&self, path: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let path = scanner::to_nfc(path);
+        // Get id first for FTS cleanup
+        let id: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM galleries WHERE path = ?1",
+                params![path],
+                |row| row.get(0),
+            )
+            .ok();
+
+        if let Some(id) = id {
+            conn.execute(
+                "DELETE FROM galleries_fts WHERE rowid = ?1",
+                params![id],
+            )?;
+            conn.execute("DELETE FROM galleries WHERE id = ?1", params![id])?;
+        }
+        Ok(())
+    }
+
+    /// Flag a gallery whose folder has disappeared. The row (and everything
+    /// hanging off it) is kept so it can come back intact if the path
+    /// reappears. Returns false if it was already missing or unknown.
+    pub fn mark_gallery_missing(&self, path: &str) -> SqlResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let path = scanner::to_nfc(path);
+        let changed = conn.execute(
+            "UPDATE galleries SET missing = 1 WHERE path = ?1 AND missing = 0",
+            params![path],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Clear the missing flag for a path found on disk again. Returns true if
+    /// the gallery was missing.
+    pub fn restore_gallery(&self, path: &str) -> SqlResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let path = scanner::to_nfc(path);
+        let changed = conn.execute(
+            "UPDATE galleries SET missing = 0 WHERE path = ?1 AND missing = 1",
+            params![path],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Set or clear (empty string) the offline drive a gallery is archived to.
+    pub fn set_archive_label(&self, gallery_id: i64, label: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET archive_label = ?1 WHERE id = ?2",
+            params![label.trim(), gallery_id],
+        )?;
+        Ok(())
+    }
+
+    /// Flip a gallery's personal favorite flag, returning the new value.
+    /// Metadata refreshes never touch it.
+    pub fn toggle_favorite(&self, gallery_id: i64) -> SqlResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "UPDATE galleries SET is_favorite = 1 - is_favorite WHERE id = ?1 RETURNING is_favorite",
+            params![gallery_id],
+            |row| row.get(0),
+        )
+    }
+
+    /// Set or clear (`None`) the user's own rating. Like the favorite flag,
+    /// it's not in info.txt, so refreshes and rescans leave it alone.
+    pub fn set_my_rating(&self, gallery_id: i64, rating: Option<f64>) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET my_rating = ?1 WHERE id = ?2",
+            params![rating, gallery_id],
+        )?;
+        Ok(())
+    }
+
+    /// Set or clear (empty string) a gallery's color label. Kept out of
+    /// info.txt like the favorite flag.
+    pub fn set_color_label(&self, gallery_id: i64, label: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET color_label = ?1 WHERE id = ?2",
+            params![label, gallery_id],
+        )?;
+        Ok(())
+    }
+
+    /// Record the newest EH version of a gallery seen on refresh; empty when
+    /// the gallery is current
+    pub fn set_newer_url(&self, gallery_id: i64, url: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET newer_url = ?1 WHERE id = ?2",
+            params![url, gallery_id],
+        )?;
+        Ok(())
+    }
+
+    /// Carry the user's own data from gallery `from` over to `to`, a newer
+    /// version of it: reading progress and history, notes and custom
+    /// fields, favorite flag, own rating, color label and display title.
+    /// Values `to` already has win. Collection entries for `from`'s gid are
+    /// pointed at `to`'s.
+    pub fn transfer_user_data(&self, from: i64, to: i64) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR IGNORE INTO reading_progress (gallery_id, page, total_pages, percent, updated_at)
+             SELECT ?2, page, total_pages, percent, updated_at FROM reading_progress WHERE gallery_id = ?1",
+            params![from, to],
+        )?;
+        tx.execute(
+            "INSERT INTO reading_history (gallery_id, opened_at, start_page, pages_read, updated_at)
+             SELECT ?2, opened_at, start_page, pages_read, updated_at FROM reading_history
+             WHERE gallery_id = ?1 ORDER BY id",
+            params![from, to],
+        )?;
+        tx.execute(
+            "INSERT OR IGNORE INTO gallery_fields (gallery_id, key, value)
+             SELECT ?2, key, value FROM gallery_fields WHERE gallery_id = ?1",
+            params![from, to],
+        )?;
+        tx.execute(
+            "UPDATE galleries SET
+                 notes = CASE WHEN galleries.notes = '' THEN o.notes ELSE galleries.notes END,
+                 is_favorite = MAX(galleries.is_favorite, o.is_favorite),
+                 my_rating = COALESCE(galleries.my_rating, o.my_rating),
+                 color_label = CASE WHEN galleries.color_label = '' THEN o.color_label ELSE galleries.color_label END,
+                 custom_title = CASE WHEN galleries.custom_title = '' THEN o.custom_title ELSE galleries.custom_title END,
+                 last_read_at = MAX(galleries.last_read_at, o.last_read_at)
+             FROM (SELECT * FROM galleries WHERE id = ?1) AS o
+             WHERE galleries.id = ?2",
+            params![from, to],
+        )?;
+        tx.execute(
+            &format!(
+                "UPDATE OR IGNORE collection_items
+                 SET gid = (SELECT {gid} FROM galleries g WHERE g.id = ?2),
+                     url = (SELECT url FROM galleries WHERE id = ?2)
+                 WHERE gid = (SELECT {gid} FROM galleries g WHERE g.id = ?1)",
+                gid = GID_EXPR
+            ),
+            params![from, to],
+        )?;
+        Self::reindex_fts(&tx, to)?;
+        tx.commit()
+    }
+
+    /// Permanently delete every gallery flagged missing, except those
+    /// archived offline, returning the ids and thumbnail paths removed.
+    pub fn purge_missing_galleries(&self) -> SqlResult<Vec<(i64, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, thumb_path FROM galleries WHERE missing = 1 AND archive_label = ''")?;
+        let rows: Vec<(i64, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        for (id, _) in &rows {
+            conn.execute("DELETE FROM galleries_fts WHERE rowid = ?1", params![id])?;
+            conn.execute("DELETE FROM galleries WHERE id = ?1", params![id])?;
+        }
+        Ok(rows)
+    }
+
+    pub fn get_gallery_by_id(&self, id: i64) -> SqlResult<Option<Gallery>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, path, title_en, title_jp, url, category, uploader, posted,
+                    language, file_size, page_count, rating, favorited, thumb_path,
+                    folder_name, parent_path, custom_title, uploader_comment, is_resampled,
+                    favorite_slot, language_inferred, missing, last_read_at, archive_label,
+                    is_favorite, my_rating, color_label, newer_url
+             FROM galleries WHERE id = ?1",
+        )?;
+
+        let gallery = stmt
+            .query_row(params![id], |row| {
+                Ok(Gallery {
+                    id: row.get(0)?,
+                    path: row.get(1)?,
+                    title_en: row.get(2)?,
+                    title_jp: row.get(3)?,
+                    url: row.get(4)?,
+                    category: row.get(5)?,
+                    uploader: row.get(6)?,
+                    posted: row.get(7)?,
+                    language: row.get(8)?,
+                    file_size: row.get(9)?,
+                    page_count: row.get(10)?,
+                    rating: row.get(11)?,
+                    favorited: row.get(12)?,
+                    thumb_path: row.get(13)?,
+                    folder_name: row.get(14)?,
+                    parent_path: row.get(15)?,
+                    custom_title: row.get(16)?,
+                    uploader_comment: row.get(17)?,
+                    is_resampled: row.get(18)?,
+                    favorite_slot: row.get(19)?,
+                    language_inferred: row.get(20)?,
+                    missing: row.get(21)?,
+                    last_read_at: row.get(22)?,
+                    archive_label: row.get(23)?,
+                    is_favorite: row.get(24)?,
+                    my_rating: row.get(25)?,
+                    color_label: row.get(26)?,
+                    newer_url: row.get(27)?,
+                })
+            })
+            .ok();
+
+        Ok(gallery)
+    }
+
+    pub fn get_gallery_by_path(&self, path: &str) -> SqlResult<Option<GallerySummary>> {
+        let conn = self.conn.lock().unwrap();
+        let path = scanner::to_nfc(path);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM galleries g WHERE path = ?1",
+            SUMMARY_COLUMNS
+        ))?;
+
+        let gallery = stmt
+            .query_row(params![path], summary_from_row)
+            .ok();
+
+        Ok(gallery)
+    }
+
+    pub fn get_tags_for_gallery(&self, gallery_id: i64) -> SqlResult<Vec<TagEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT t.namespace, t.tag, COALESCE(tr.name, '') FROM resolved_tags t
+             LEFT JOIN tag_translations tr ON tr.namespace = t.namespace AND tr.tag = t.tag
+             WHERE t.gallery_id = ?1 ORDER BY t.namespace, t.tag",
+        )?;
+
+        let tags = stmt
+            .query_map(params![gallery_id], |row| {
+                Ok(TagEntry {
+                    namespace: row.get(0)?,
+                    tag: row.get(1)?,
+                    translation: row.get(2)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(tags)
+    }
+
+    /// Replace the imported tag display names with `names`, as
+    /// `(namespace, tag, name)`.
+    pub fn replace_tag_translations(&self, names: &[(String, String, String)]) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM tag_translations", [])?;
+        {
+            let mut stmt =
+                tx.prepare("INSERT INTO tag_translations (namespace, tag, name) VALUES (?1, ?2, ?3)")?;
+            for (namespace, tag, name) in names {
+                stmt.execute(params![namespace, tag, name])?;
+            }
+        }
+        tx.commit()
+    }
+
+    pub fn get_tag_translation(&self, namespace: &str, tag: &str) -> SqlResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        let name = conn
+            .query_row(
+                "SELECT name FROM tag_translations WHERE namespace = ?1 AND tag = ?2",
+                params![namespace, tag],
+                |row| row.get(0),
+            )
+            .ok();
+        Ok(name)
+    }
+
+    /// Fill in `translation` for each of `tags`; left empty without one
+    pub fn translate_tags(&self, tags: &mut [TagEntry]) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare_cached("SELECT name FROM tag_translations WHERE namespace = ?1 AND tag = ?2")?;
+        for entry in tags.iter_mut() {
+            entry.translation = stmt
+                .query_row(params![entry.namespace, entry.tag], |row| row.get(0))
+                .unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    pub fn get_galleries_in_folder(&self, parent_path: &str) -> SqlResult<Vec<GallerySummary>> {
+        let conn = self.conn.lock().unwrap();
+        let parent_path = scanner::to_nfc(parent_path);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM galleries g WHERE parent_path = ?1 AND {}
+             ORDER BY folder_name COLLATE LOCALE",
+            SUMMARY_COLUMNS, LISTED_EXPR
+        ))?;
+
+        let galleries = stmt
+            .query_map(params![parent_path], summary_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(galleries)
+    }
+
+    pub fn get_info_modified(&self, path: &str) -> SqlResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        let path = scanner::to_nfc(path);
         conn.query_row(
             "SELECT info_modified FROM galleries WHERE path = ?1",
             params![path],
@@ -315,316 +1311,2152 @@ impl Database {
         .map_or(Ok(None), |v| Ok(Some(v)))
     }
 
-    pub fn search_galleries(&self, query: &SearchQuery) -> SqlResult<SearchResult> {
+    pub fn search_galleries(
+        &self,
+        query: &SearchQuery,
+        blocklist: &Blocklist,
+        defaults: &DefaultFilters,
+        session: &[SessionFilter],
+    ) -> SqlResult<SearchResult> {
+        let mut q = Self::search_filters(query, blocklist, defaults, session);
+        let conn = self.conn.lock().unwrap();
+
+        // New galleries always get a higher id, so capping the id keeps a
+        // result set stable while the library grows
+        let snapshot = match query.snapshot {
+            Some(snapshot) => snapshot,
+            None => conn.query_row("SELECT COALESCE(MAX(id), 0) FROM galleries", [], |row| {
+                row.get(0)
+            })?,
+        };
+        q.filter(Condition::new("g.id <= ?", vec![Value::Integer(snapshot)]));
+
+        let count_sql = q.sql("COUNT(*)", "");
+        let count_params = q.values(vec![]);
+        let started = Instant::now();
+        let total_count: i64 = conn
+            .query_row(&count_sql, rusqlite::params_from_iter(&count_params), |row| row.get(0))
+            .unwrap_or(0);
+        log_if_slow(&conn, &count_sql, &count_params, started);
+
+        // Sort; the column comes from a fixed list, never from the query
+        let sort_col = match query.sort_by.as_deref() {
+            Some("rating") => "g.rating",
+            Some("my_rating") => "g.my_rating",
+            Some("pages") => "g.page_count",
+            Some("posted") => "g.posted_ts",
+            Some("title") => "COALESCE(NULLIF(g.custom_title, ''), g.title_en) COLLATE LOCALE",
+            Some("scanned") => "g.scanned_at",
+            Some("read") => "g.last_read_at",
+            Some("size") => "g.disk_size",
+            Some("progress") => READ_STATE_EXPR,
+            _ => "g.added_at",
+        };
+        let order = match query.sort_order.as_deref() {
+            Some("asc") => "ASC",
+            _ => "DESC",
+        };
+        let tail = format!("ORDER BY {} {} LIMIT ? OFFSET ?", sort_col, order);
+
+        let sql = q.sql(SUMMARY_COLUMNS, &tail);
+        let params = q.values(vec![Value::Integer(query.limit), Value::Integer(query.offset)]);
+        let started = Instant::now();
+        let mut stmt = conn.prepare(&sql)?;
+        let galleries = stmt
+            .query_map(rusqlite::params_from_iter(&params), summary_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+        log_if_slow(&conn, &sql, &params, started);
+
+        let facets = if query.include_facets {
+            Some(Self::search_facets(&conn, &q)?)
+        } else {
+            None
+        };
+
+        Ok(SearchResult {
+            galleries,
+            total_count,
+            snapshot,
+            facets,
+        })
+    }
+
+    /// Up to `count` random galleries matching `query`'s filters; paging and
+    /// sorting are ignored.
+    pub fn get_random_galleries(
+        &self,
+        query: &SearchQuery,
+        blocklist: &Blocklist,
+        defaults: &DefaultFilters,
+        session: &[SessionFilter],
+        count: i64,
+    ) -> SqlResult<Vec<GallerySummary>> {
+        let q = Self::search_filters(query, blocklist, defaults, session);
+        let conn = self.conn.lock().unwrap();
+        let sql = q.sql(SUMMARY_COLUMNS, "ORDER BY RANDOM() LIMIT ?");
+        let params = q.values(vec![Value::Integer(count)]);
+        let started = Instant::now();
+        let mut stmt = conn.prepare(&sql)?;
+        let galleries = stmt
+            .query_map(rusqlite::params_from_iter(&params), summary_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+        log_if_slow(&conn, &sql, &params, started);
+        Ok(galleries)
+    }
+
+    /// Conditions for every filter of `query`, plus the blocklist, default
+    /// and session filters unless it opts out of them
+    fn search_filters(
+        query: &SearchQuery,
+        blocklist: &Blocklist,
+        defaults: &DefaultFilters,
+        session: &[SessionFilter],
+    ) -> GalleryQuery {
+        let mut q = GalleryQuery::new();
+        q.filter(Condition::new(LISTED_EXPR, vec![]));
+
+        // Text search via FTS5
+        if let Some(cond) = query.text.as_deref().and_then(query::fts_match) {
+            q.join(query::FTS_JOIN).filter(cond);
+        }
+
+        for tf in &query.tags {
+            q.filter(query::has_tag(tf));
+        }
+        for tf in &query.excluded_tags {
+            q.filter(query::lacks_tag(tf));
+        }
+        for group in &query.tag_groups {
+            q.filter_opt(Condition::any(group.iter().map(query::has_tag).collect()));
+        }
+        for word in &query.excluded_words {
+            q.filter_opt(query::fts_exclude(word));
+        }
+        if let Some(cat) = query.category.as_deref().filter(|c| !c.is_empty()) {
+            q.filter(Condition::new("g.category = ?", vec![query::text(cat)]));
+        }
+        if let Some(lang) = query.language.as_deref().filter(|l| !l.is_empty()) {
+            q.filter(Condition::new("g.language = ?", vec![query::text(lang)]));
+        }
+        if let Some(uploader) = query.uploader.as_deref().filter(|u| !u.trim().is_empty()) {
+            q.filter(Condition::new("LOWER(g.uploader) = ?", vec![query::folded(uploader)]));
+        }
+        // Original vs resampled filter
+        if let Some(resampled) = query.resampled {
+            q.filter(Condition::new("g.is_resampled = ?", vec![Value::Integer(resampled as i64)]));
+        }
+        if let Some(favorite) = query.favorite {
+            q.filter(Condition::new("g.is_favorite = ?", vec![Value::Integer(favorite as i64)]));
+        }
+        if let Some(label) = &query.color_label {
+            q.filter(Condition::new("g.color_label = ?", vec![Value::Text(label.clone())]));
+        }
+        if let Some(slot) = query.favorite_slot {
+            q.filter(Condition::new("g.favorite_slot = ?", vec![Value::Integer(slot)]));
+        }
+        if let Some(rating) = query.min_rating.filter(|r| *r > 0.0) {
+            q.filter(Condition::new("g.rating >= ?", vec![Value::Real(rating)]));
+        }
+        if let Some(pages) = query.min_pages {
+            q.filter(Condition::new("g.page_count >= ?", vec![Value::Integer(pages)]));
+        }
+        if let Some(pages) = query.max_pages {
+            q.filter(Condition::new("g.page_count <= ?", vec![Value::Integer(pages)]));
+        }
+        if let Some(from) = query.posted_from {
+            q.filter(Condition::new("g.posted_ts >= ?", vec![Value::Integer(from)]));
+        }
+        if let Some(to) = query.posted_to {
+            q.filter(Condition::new("g.posted_ts <= ?", vec![Value::Integer(to)]));
+        }
+        let read_state = match query.read_state.as_deref() {
+            Some("unread") => Some(0),
+            Some("reading") => Some(1),
+            Some("finished") => Some(2),
+            _ => None,
+        };
+        if let Some(state) = read_state {
+            q.filter(Condition::new(
+                format!("{} = ?", READ_STATE_EXPR),
+                vec![Value::Integer(state)],
+            ));
+        }
+
+        if !query.include_blocked {
+            q.filter_opt(query::blocklist_condition(blocklist));
+        }
+        if !query.ignore_defaults {
+            q.filter_opt(query::default_filter_condition(defaults));
+        }
+        if !query.ignore_session {
+            q.filter_opt(query::session_filter_condition(session));
+        }
+        q
+    }
+
+    /// Category, language and top tag counts over every gallery matching `q`
+    fn search_facets(conn: &Connection, q: &GalleryQuery) -> SqlResult<SearchFacets> {
+        let matches = q.sql("g.id", "");
+        let params = q.values(vec![]);
+        let count_by = |column: &str| -> SqlResult<Vec<FacetCount>> {
+            let sql = format!(
+                "SELECT {col}, COUNT(*) FROM galleries WHERE id IN ({matches})
+                 GROUP BY {col} ORDER BY COUNT(*) DESC, {col}",
+                col = column,
+                matches = matches
+            );
+            let started = Instant::now();
+            let mut stmt = conn.prepare(&sql)?;
+            let counts = stmt
+                .query_map(rusqlite::params_from_iter(&params), |row| {
+                    Ok(FacetCount {
+                        value: row.get(0)?,
+                        count: row.get(1)?,
+                    })
+                })?
+                .filter_map(|r| r.ok())
+                .collect();
+            log_if_slow(conn, &sql, &params, started);
+            Ok(counts)
+        };
+        let categories = count_by("category")?;
+        let languages = count_by("language")?;
+
+        let sql = format!(
+            "SELECT namespace, tag, COUNT(DISTINCT gallery_id) FROM resolved_tags WHERE gallery_id IN ({})
+             GROUP BY namespace, tag ORDER BY COUNT(DISTINCT gallery_id) DESC, namespace, tag LIMIT {}",
+            matches, FACET_TAG_LIMIT
+        );
+        let started = Instant::now();
+        let mut stmt = conn.prepare(&sql)?;
+        let tags = stmt
+            .query_map(rusqlite::params_from_iter(&params), |row| {
+                Ok(TagCount {
+                    namespace: row.get(0)?,
+                    tag: row.get(1)?,
+                    count: row.get(2)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        log_if_slow(conn, &sql, &params, started);
+
+        Ok(SearchFacets {
+            categories,
+            languages,
+            tags,
+        })
+    }
+
+    pub fn get_all_gallery_paths(&self) -> SqlResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT path FROM galleries")?;
+        let paths = stmt
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(paths)
+    }
+
+    pub fn find_duplicates_by_url(&self) -> SqlResult<Vec<Vec<GallerySummary>>> {
+        let conn = self.conn.lock().unwrap();
+        // Find URLs that appear more than once (ignoring empty URLs)
+        let mut group_stmt = conn.prepare(
+            "SELECT url FROM galleries WHERE url != '' AND missing = 0
+             GROUP BY url HAVING COUNT(*) > 1",
+        )?;
+        let urls: Vec<String> = group_stmt
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        let mut results = Vec::new();
+        let mut detail_stmt = conn.prepare(&format!(
+            "SELECT {} FROM galleries g WHERE url = ?1 AND missing = 0 ORDER BY id",
+            SUMMARY_COLUMNS
+        ))?;
+        for url in &urls {
+            let group: Vec<GallerySummary> = detail_stmt
+                .query_map(params![url], summary_from_row)?
+                .filter_map(|r| r.ok())
+                .collect();
+            if group.len() > 1 {
+                results.push(group);
+            }
+        }
+        Ok(results)
+    }
+
+    pub fn find_duplicates_by_name(&self) -> SqlResult<Vec<Vec<GallerySummary>>> {
+        let conn = self.conn.lock().unwrap();
+        // Find title_en values that appear more than once (ignoring empty titles)
+        let mut group_stmt = conn.prepare(
+            "SELECT title_en FROM galleries WHERE title_en != '' AND missing = 0
+             GROUP BY title_en HAVING COUNT(*) > 1",
+        )?;
+        let names: Vec<String> = group_stmt
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        let mut results = Vec::new();
+        let mut detail_stmt = conn.prepare(&format!(
+            "SELECT {} FROM galleries g WHERE title_en = ?1 AND missing = 0 ORDER BY id",
+            SUMMARY_COLUMNS
+        ))?;
+        for name in &names {
+            let group: Vec<GallerySummary> = detail_stmt
+                .query_map(params![name], summary_from_row)?
+                .filter_map(|r| r.ok())
+                .collect();
+            if group.len() > 1 {
+                results.push(group);
+            }
+        }
+        Ok(results)
+    }
+
+    /// Move all galleries whose path starts with `old_prefix` to `new_prefix`.
+    /// Updates path, parent_path, folder_name, and FTS index.
+    pub fn move_gallery_paths(&self, old_prefix: &str, new_prefix: &str) -> SqlResult<u64> {
+        let conn = self.conn.lock().unwrap();
+
+        // Find affected galleries — try both separator styles to handle
+        // any inconsistency between how paths were stored vs passed in.
+        let mut stmt = conn.prepare(
+            "SELECT id, path FROM galleries WHERE path = ?1 OR path LIKE ?2
+             OR path = ?3 OR path LIKE ?4",
+        )?;
+        let old_prefix = scanner::to_nfc(old_prefix);
+        let new_prefix = scanner::to_nfc(new_prefix);
+        let fwd = old_prefix.replace('\\', "/");
+        let bck = old_prefix.replace('/', "\\");
+        let fwd_pattern = format!("{}/%", fwd);
+        let bck_pattern = format!("{}\\%", bck);
+        let rows: Vec<(i64, String)> = stmt
+            .query_map(params![fwd, fwd_pattern, bck, bck_pattern], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        let mut count = 0u64;
+        for (id, old_path) in &rows {
+            // Determine which prefix variant matches this stored path
+            let suffix = if old_path.starts_with(&fwd) {
+                &old_path[fwd.len()..]
+            } else if old_path.starts_with(&bck) {
+                &old_path[bck.len()..]
+            } else {
+                continue;
+            };
+            let new_path = format!("{}{}", new_prefix, suffix);
+            let new_parent = std::path::Path::new(&new_path)
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let new_folder_name = std::path::Path::new(&new_path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            conn.execute(
+                "UPDATE galleries SET path = ?1, parent_path = ?2, folder_name = ?3 WHERE id = ?4",
+                params![new_path, new_parent, new_folder_name, id],
+            )?;
+
+            // Update FTS
+            Self::reindex_fts(&conn, *id)?;
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Delete all galleries under a given path (exact match or children).
+    /// Returns the list of (id, thumb_path) for cleanup.
+    pub fn delete_galleries_under_path(&self, path: &str) -> SqlResult<Vec<(i64, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let norm = scanner::to_nfc(path).replace('\\', "/");
+        let pattern = format!("{}/%", norm);
+
+        // Collect galleries to delete
+        let mut stmt = conn.prepare(
+            "SELECT id, thumb_path FROM galleries WHERE path = ?1 OR path LIKE ?2 OR parent_path = ?3 OR parent_path LIKE ?4",
+        )?;
+        let deleted: Vec<(i64, String)> = stmt
+            .query_map(params![norm, pattern, norm, pattern], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        for (id, _) in &deleted {
+            conn.execute("DELETE FROM galleries_fts WHERE rowid = ?1", params![id])?;
+            conn.execute("DELETE FROM galleries WHERE id = ?1", params![id])?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Galleries first seen per month, with the running library size.
+    pub fn get_growth_stats(&self) -> SqlResult<Vec<GrowthPoint>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT substr(added_at, 1, 7) AS month, COUNT(*) FROM galleries
+             GROUP BY month ORDER BY month",
+        )?;
+        let mut total = 0;
+        let points = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?
+            .filter_map(|r| r.ok())
+            .map(|(month, added)| {
+                total += added;
+                GrowthPoint { month, added, total }
+            })
+            .collect();
+        Ok(points)
+    }
+
+    /// Gallery counts per language, with how many of those were inferred.
+    pub fn get_language_stats(&self) -> SqlResult<Vec<LanguageStat>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT language, COUNT(*), SUM(language_inferred) FROM galleries WHERE missing = 0
+             GROUP BY language ORDER BY COUNT(*) DESC",
+        )?;
+        let stats = stmt
+            .query_map([], |row| {
+                Ok(LanguageStat {
+                    language: row.get(0)?,
+                    count: row.get(1)?,
+                    inferred: row.get(2)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(stats)
+    }
+
+    /// Every tag with its gallery count, optionally only those in
+    /// `namespace`. Sorted by count, most used first, or with `by_name` by
+    /// namespace and tag.
+    pub fn get_all_tags(&self, namespace: Option<&str>, by_name: bool) -> SqlResult<Vec<TagCount>> {
+        let conn = self.conn.lock().unwrap();
+        let order = if by_name {
+            "t.namespace, t.tag"
+        } else {
+            "COUNT(DISTINCT t.gallery_id) DESC, t.namespace, t.tag"
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT t.namespace, t.tag, COUNT(DISTINCT t.gallery_id) FROM resolved_tags t
+             JOIN galleries g ON g.id = t.gallery_id
+             WHERE {} AND (?1 IS NULL OR t.namespace = ?1)
+             GROUP BY t.namespace, t.tag ORDER BY {}",
+            LISTED_EXPR, order
+        ))?;
+        let tags = stmt
+            .query_map(params![namespace], |row| {
+                Ok(TagCount {
+                    namespace: row.get(0)?,
+                    tag: row.get(1)?,
+                    count: row.get(2)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(tags)
+    }
+
+    /// Local gallery for a row imported from another app: by URL first, then
+    /// download folder name, then exact title.
+    pub fn find_gallery_for_import(&self, item: &ImportedGallery) -> SqlResult<Option<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let id = conn
+            .query_row(
+                "SELECT id FROM galleries
+                 WHERE missing = 0 AND ((?1 != '' AND url = ?1)
+                    OR (?2 != '' AND folder_name = ?2)
+                    OR (?3 != '' AND (title_en = ?3 OR title_jp = ?3))
+                    OR (?4 != '' AND title_jp = ?4))
+                 ORDER BY CASE WHEN url = ?1 THEN 0 WHEN folder_name = ?2 THEN 1 ELSE 2 END
+                 LIMIT 1",
+                params![
+                    item.url,
+                    scanner::to_nfc(&item.dirname),
+                    scanner::to_nfc(&item.title),
+                    scanner::to_nfc(&item.title_jpn)
+                ],
+                |row| row.get(0),
+            )
+            .ok();
+        Ok(id)
+    }
+
+    /// Merge another app's history into a gallery: an earlier download time
+    /// moves `added_at` back, a later read time advances `last_read_at`, and
+    /// a favorite lands in `favorite_slot` unless one is already set.
+    pub fn apply_imported_history(
+        &self,
+        gallery_id: i64,
+        item: &ImportedGallery,
+        favorite_slot: i64,
+    ) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        if let Some(ms) = item.downloaded_at.filter(|&ms| ms > 0) {
+            conn.execute(
+                "UPDATE galleries SET added_at = MIN(added_at, datetime(?1 / 1000, 'unixepoch'))
+                 WHERE id = ?2",
+                params![ms, gallery_id],
+            )?;
+        }
+        if let Some(ms) = item.read_at.filter(|&ms| ms > 0) {
+            conn.execute(
+                "UPDATE galleries SET last_read_at = MAX(last_read_at, datetime(?1 / 1000, 'unixepoch'))
+                 WHERE id = ?2",
+                params![ms, gallery_id],
+            )?;
+        }
+        if item.favorited {
+            conn.execute(
+                "UPDATE galleries SET favorite_slot = ?1 WHERE id = ?2 AND favorite_slot IS NULL",
+                params![favorite_slot, gallery_id],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn set_favorite_slot(&self, gallery_id: i64, slot: Option<i64>) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET favorite_slot = ?1 WHERE id = ?2",
+            params![slot, gallery_id],
+        )?;
+        Ok(())
+    }
+
+    /// Replace all favorite slot assignments with `slots` (canonical URL ->
+    /// slot). Returns how many local galleries were matched.
+    pub fn replace_favorite_slots(&self, slots: &HashMap<String, i64>) -> SqlResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("UPDATE galleries SET favorite_slot = NULL", [])?;
+        let mut matched = 0;
+        {
+            let mut stmt = tx.prepare("UPDATE galleries SET favorite_slot = ?1 WHERE url = ?2")?;
+            for (url, slot) in slots {
+                matched += stmt.execute(params![slot, url])?;
+            }
+        }
+        tx.commit()?;
+        Ok(matched)
+    }
+
+    pub fn set_resampled(&self, gallery_id: i64, resampled: bool) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET is_resampled = ?1 WHERE id = ?2",
+            params![resampled, gallery_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_page_stats(&self, gallery_id: i64, stats: &PageStats) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET local_pages = ?1, page_width = ?2 WHERE id = ?3",
+            params![stats.pages, stats.max_width, gallery_id],
+        )?;
+        Ok(())
+    }
+
+    /// Present galleries scanned before page stats were recorded
+    pub fn get_galleries_without_page_stats(&self) -> SqlResult<Vec<(i64, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT id, path FROM galleries WHERE missing = 0 AND local_pages IS NULL")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(rows)
+    }
+
+    /// Galleries narrower than `min_width`, resampled, or with fewer pages
+    /// on disk than EH lists, best rated and most read first; plus the
+    /// library's own-rating distribution.
+    pub fn get_quality_report(&self, min_width: u32, limit: usize) -> SqlResult<QualityReport> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT CAST(ROUND(g.my_rating) AS INTEGER) AS stars, COUNT(*) FROM galleries g
+             WHERE {} GROUP BY stars ORDER BY stars DESC",
+            LISTED_EXPR
+        ))?;
+        let rating_distribution = stmt
+            .query_map([], |row| {
+                Ok(RatingBucket {
+                    stars: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {cols}, g.local_pages, g.page_width, g.is_resampled, g.my_rating,
+                    (SELECT COUNT(*) FROM reading_history h WHERE h.gallery_id = g.id) AS views
+             FROM galleries g
+             WHERE {listed} AND (g.is_resampled = 1 OR g.page_width < ?1
+                OR (g.page_count > 0 AND g.local_pages < g.page_count))
+             ORDER BY g.my_rating IS NULL, g.my_rating DESC, views DESC, g.id
+             LIMIT ?2",
+            cols = SUMMARY_COLUMNS,
+            listed = LISTED_EXPR
+        ))?;
+        let items = stmt
+            .query_map(params![min_width, limit as i64], |row| {
+                let gallery = summary_from_row(row)?;
+                let local_pages: Option<i64> = row.get(14)?;
+                let max_width: Option<u32> = row.get(15)?;
+                let is_resampled: bool = row.get(16)?;
+                let mut issues = Vec::new();
+                if max_width.is_some_and(|w| w < min_width) {
+                    issues.push("low_res".to_string());
+                }
+                if is_resampled {
+                    issues.push("resampled".to_string());
+                }
+                if gallery.page_count > 0 && local_pages.is_some_and(|n| n < gallery.page_count) {
+                    issues.push("missing_pages".to_string());
+                }
+                Ok(QualityItem {
+                    gallery,
+                    local_pages: local_pages.unwrap_or(0),
+                    max_width,
+                    is_resampled,
+                    my_rating: row.get(17)?,
+                    views: row.get(18)?,
+                    issues,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(QualityReport {
+            rating_distribution,
+            items,
+        })
+    }
+
+    /// Record the bytes a gallery takes on disk
+    pub fn set_disk_size(&self, path: &str, size: u64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET disk_size = ?1 WHERE path = ?2",
+            params![size as i64, scanner::to_nfc(path)],
+        )?;
+        Ok(())
+    }
+
+    /// Paths of galleries whose size on disk hasn't been recorded yet
+    pub fn get_unsized_gallery_paths(&self) -> SqlResult<HashSet<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT path FROM galleries WHERE disk_size IS NULL AND missing = 0")?;
+        let paths = stmt
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(paths)
+    }
+
+    /// Record the info.txt mtime after the app itself rewrote the file, so the
+    /// next scan doesn't treat it as changed.
+    pub fn update_info_modified(&self, gallery_id: i64, info_modified: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET info_modified = ?1 WHERE id = ?2",
+            params![info_modified, gallery_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn update_thumb_path(&self, gallery_id: i64, thumb_path: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET thumb_path = ?1 WHERE id = ?2",
+            params![thumb_path, gallery_id],
+        )?;
+        Ok(())
+    }
+
+    /// Persist a job descriptor (JSON `JobSpec`). Returns the row id.
+    pub fn insert_job(&self, spec: &str, priority: &str) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO jobs (spec, priority) VALUES (?1, ?2)",
+            params![spec, priority],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn update_job_progress(&self, id: i64, done: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE jobs SET done = ?1 WHERE id = ?2", params![done, id])?;
+        Ok(())
+    }
+
+    pub fn delete_job(&self, id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM jobs WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Note a failed run of a stored job. Returns how many runs have failed.
+    pub fn record_job_failure(&self, id: i64, error: &str) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE jobs SET attempts = attempts + 1, last_error = ?1 WHERE id = ?2",
+            params![error, id],
+        )?;
+        conn.query_row("SELECT attempts FROM jobs WHERE id = ?1", params![id], |row| row.get(0))
+    }
+
+    pub fn set_job_paused(&self, id: i64, paused: bool) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE jobs SET paused = ?1 WHERE id = ?2", params![paused, id])?;
+        Ok(())
+    }
+
+    pub fn is_job_paused(&self, id: i64) -> bool {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT paused FROM jobs WHERE id = ?1", params![id], |row| row.get(0))
+            .unwrap_or(false)
+    }
+
+    /// When each stored job was queued and whether it's paused, by row id.
+    pub fn get_job_states(&self) -> SqlResult<HashMap<i64, (String, bool)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, created_at, paused FROM jobs")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(rows)
+    }
+
+    /// Jobs left over from a previous session as (id, spec, priority, done), oldest first.
+    pub fn get_pending_jobs(&self) -> SqlResult<Vec<(i64, String, String, i64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, spec, priority, done FROM jobs ORDER BY id")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(rows)
+    }
+
+    /// Every gallery that's on disk.
+    pub fn get_present_gallery_ids(&self) -> SqlResult<Vec<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id FROM galleries WHERE missing = 0 ORDER BY id")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(ids)
+    }
+
+    /// Galleries with no EH URL, i.e. still needing metadata identification.
+    pub fn get_unidentified_gallery_ids(&self) -> SqlResult<Vec<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id FROM galleries WHERE url = '' AND missing = 0 ORDER BY id")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(ids)
+    }
+
+    pub fn create_collection(&self, name: &str, description: &str) -> SqlResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO collections (name, description) VALUES (?1, ?2)",
+            params![name, description],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn update_collection(&self, id: i64, name: &str, description: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE collections SET name = ?1, description = ?2 WHERE id = ?3",
+            params![name, description, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_collection(&self, id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM collections WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn list_collections(&self) -> SqlResult<Vec<Collection>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT c.id, c.name, c.description, c.created_at, COUNT(i.gid)
+             FROM collections c LEFT JOIN collection_items i ON i.collection_id = c.id
+             GROUP BY c.id ORDER BY c.name COLLATE LOCALE",
+        )?;
+        let collections = stmt
+            .query_map([], |row| {
+                Ok(Collection {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    description: row.get(2)?,
+                    created_at: row.get(3)?,
+                    item_count: row.get(4)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(collections)
+    }
+
+    /// Save `query` under `name`, replacing a saved search of the same name.
+    /// Returns its id.
+    pub fn save_search(&self, name: &str, query: &SearchQuery) -> SqlResult<i64> {
+        let json = serde_json::to_string(query)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "INSERT INTO saved_searches (name, query) VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE SET query = excluded.query
+             RETURNING id",
+            params![name, json],
+            |row| row.get(0),
+        )
+    }
+
+    /// Saved searches by name. Rows whose query no longer deserializes are
+    /// skipped.
+    pub fn list_saved_searches(&self) -> SqlResult<Vec<SavedSearch>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, query, created_at FROM saved_searches ORDER BY name COLLATE LOCALE",
+        )?;
+        let searches = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get(1)?, row.get::<_, String>(2)?, row.get(3)?))
+            })?
+            .filter_map(|r| r.ok())
+            .filter_map(|(id, name, json, created_at)| match serde_json::from_str(&json) {
+                Ok(query) => Some(SavedSearch {
+                    id,
+                    name,
+                    query,
+                    created_at,
+                }),
+                Err(e) => {
+                    log::warn!("Skipping unreadable saved search {}: {}", id, e);
+                    None
+                }
+            })
+            .collect();
+        Ok(searches)
+    }
+
+    pub fn delete_saved_search(&self, id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM saved_searches WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn get_collection(&self, id: i64) -> SqlResult<Option<Collection>> {
+        Ok(self.list_collections()?.into_iter().find(|c| c.id == id))
+    }
+
+    /// Append entries to a collection, skipping gids already in it.
+    /// Returns how many were added.
+    pub fn add_collection_items(
+        &self,
+        collection_id: i64,
+        items: &[CollectionFileItem],
+    ) -> SqlResult<u64> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut added = 0;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO collection_items (collection_id, gid, url, title, position)
+                 VALUES (?1, ?2, ?3, ?4,
+                         (SELECT COALESCE(MAX(position), -1) + 1 FROM collection_items
+                          WHERE collection_id = ?1))",
+            )?;
+            for item in items {
+                added += stmt.execute(params![
+                    collection_id,
+                    item.gid,
+                    fetcher::canonical_gallery_url(&item.url),
+                    item.title
+                ])? as u64;
+            }
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
+    pub fn remove_collection_item(&self, collection_id: i64, gid: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM collection_items WHERE collection_id = ?1 AND gid = ?2",
+            params![collection_id, gid],
+        )?;
+        Ok(())
+    }
+
+    /// Put the listed gids first, in the given order; entries not listed
+    /// follow in their previous order.
+    pub fn reorder_collection(&self, collection_id: i64, gids: &[i64]) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE collection_items SET position = position + ?2 WHERE collection_id = ?1",
+            params![collection_id, gids.len() as i64],
+        )?;
+        {
+            let mut stmt = tx.prepare(
+                "UPDATE collection_items SET position = ?3 WHERE collection_id = ?1 AND gid = ?2",
+            )?;
+            for (position, gid) in gids.iter().enumerate() {
+                stmt.execute(params![collection_id, gid, position as i64])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Collection entries in order, each resolved to the local gallery with
+    /// the same gid if there is one.
+    pub fn get_collection_items(&self, collection_id: i64) -> SqlResult<Vec<CollectionItem>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT gid, url, title FROM collection_items WHERE collection_id = ?1 ORDER BY position",
+        )?;
+        let entries: Vec<(i64, String, String)> = stmt
+            .query_map(params![collection_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        let mut local = conn.prepare(&format!(
+            "SELECT {} FROM galleries g
+             WHERE g.missing = 0 AND (g.url = ?1 OR g.url LIKE 'https://exhentai.org/g/' || ?2 || '/%')
+             ORDER BY g.url = ?1 DESC LIMIT 1",
+            SUMMARY_COLUMNS
+        ))?;
+        let items = entries
+            .into_iter()
+            .map(|(gid, url, title)| {
+                let gallery = local.query_row(params![url, gid], summary_from_row).ok();
+                CollectionItem {
+                    gid,
+                    url,
+                    title,
+                    gallery,
+                }
+            })
+            .collect();
+        Ok(items)
+    }
+
+    /// Unidentified galleries with the titles they can be matched on.
+    pub fn get_url_match_candidates(&self) -> SqlResult<Vec<Candidate>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, title_en, title_jp, folder_name, page_count FROM galleries
+             WHERE url = '' AND missing = 0",
+        )?;
+        let candidates = stmt
+            .query_map([], |row| {
+                let titles: Vec<String> = [row.get(1)?, row.get(2)?, row.get(3)?]
+                    .into_iter()
+                    .filter(|t: &String| !t.is_empty())
+                    .collect();
+                Ok(Candidate {
+                    id: row.get(0)?,
+                    titles,
+                    page_count: row.get(4)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(candidates)
+    }
+
+    /// Local copies of each gid, in one lookup on the gid index. Gids
+    /// without a copy are absent from the map.
+    pub fn find_galleries_by_gid(&self, gids: &[i64]) -> SqlResult<HashMap<i64, Vec<GallerySummary>>> {
+        let mut owned: HashMap<i64, Vec<GallerySummary>> = HashMap::new();
+        if gids.is_empty() {
+            return Ok(owned);
+        }
+        let conn = self.conn.lock().unwrap();
+        let list = gids.iter().map(|g| g.to_string()).collect::<Vec<_>>().join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {cols}, {gid} FROM galleries g
+             WHERE {gid} IN ({list}) AND g.url != '' AND {listed}
+             ORDER BY g.path",
+            cols = SUMMARY_COLUMNS,
+            gid = GID_EXPR,
+            listed = LISTED_EXPR,
+            list = list
+        ))?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(14)?, summary_from_row(row)?)))?;
+        for (gid, summary) in rows.filter_map(|r| r.ok()) {
+            owned.entry(gid).or_default().push(summary);
+        }
+        Ok(owned)
+    }
+
+    /// Present galleries with the mtime their page hashes were taken at
+    /// (empty when never hashed).
+    pub fn get_page_hash_states(&self) -> SqlResult<Vec<(i64, String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, path, pages_hashed_at FROM galleries WHERE missing = 0 ORDER BY id",
+        )?;
+        let states = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(states)
+    }
+
+    /// Replace a gallery's page hashes, recording the mtime they match.
+    pub fn replace_page_hashes(
+        &self,
+        gallery_id: i64,
+        hashed_at: &str,
+        pages: &[PageHash],
+    ) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM page_hashes WHERE gallery_id = ?1", params![gallery_id])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO page_hashes
+                 (gallery_id, page_index, filename, sha1, phash, width, height, grayscale)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            for (i, page) in pages.iter().enumerate() {
+                stmt.execute(params![
+                    gallery_id,
+                    i as i64,
+                    page.filename,
+                    page.sha1,
+                    page.phash.map(|h| h as i64),
+                    page.width,
+                    page.height,
+                    page.grayscale
+                ])?;
+            }
+        }
+        tx.execute(
+            "UPDATE galleries SET pages_hashed_at = ?1 WHERE id = ?2",
+            params![hashed_at, gallery_id],
+        )?;
+        tx.commit()
+    }
+
+    /// A gallery's hashed pages in reader order; empty until they're hashed.
+    pub fn get_page_hashes(&self, gallery_id: i64) -> SqlResult<Vec<PageHash>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT filename, sha1, phash, width, height, grayscale FROM page_hashes
+             WHERE gallery_id = ?1 ORDER BY page_index",
+        )?;
+        let pages = stmt
+            .query_map(params![gallery_id], |row| {
+                Ok(PageHash {
+                    filename: row.get(0)?,
+                    sha1: row.get(1)?,
+                    phash: row.get::<_, Option<i64>>(2)?.map(|h| h as u64),
+                    width: row.get(3)?,
+                    height: row.get(4)?,
+                    grayscale: row.get(5)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(pages)
+    }
+
+    /// Colour and orientation page counts over present galleries.
+    pub fn get_page_color_stats(&self) -> SqlResult<PageColorStats> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COALESCE(SUM(p.grayscale = 0), 0), COALESCE(SUM(p.grayscale = 1), 0),
+                    COALESCE(SUM(p.width > p.height), 0), COALESCE(SUM(p.grayscale IS NULL), 0),
+                    COUNT(DISTINCT CASE WHEN p.grayscale = 0 THEN p.gallery_id END)
+             FROM page_hashes p JOIN galleries g ON g.id = p.gallery_id
+             WHERE g.missing = 0",
+            [],
+            |row| {
+                Ok(PageColorStats {
+                    color_pages: row.get(0)?,
+                    grayscale_pages: row.get(1)?,
+                    landscape_pages: row.get(2)?,
+                    unknown_pages: row.get(3)?,
+                    color_galleries: row.get(4)?,
+                })
+            },
+        )
+    }
+
+    /// Every local page whose SHA-1 is `sha1`.
+    pub fn find_pages_by_hash(&self, sha1: &str) -> SqlResult<Vec<PageMatch>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, p.page_index, p.filename FROM page_hashes p
+             JOIN galleries g ON g.id = p.gallery_id
+             WHERE p.sha1 = ?1 AND g.missing = 0
+             ORDER BY g.path, p.page_index",
+            SUMMARY_COLUMNS
+        ))?;
+        let matches = stmt
+            .query_map(params![sha1.to_ascii_lowercase()], |row| {
+                Ok(PageMatch {
+                    gallery: summary_from_row(row)?,
+                    page_index: row.get::<_, i64>(14)? as usize,
+                    filename: row.get(15)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(matches)
+    }
+
+    /// A gallery's page order override; empty when it has none.
+    pub fn get_page_order(&self, gallery_id: i64) -> SqlResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT name FROM page_order WHERE gallery_id = ?1 ORDER BY position")?;
+        let names = stmt
+            .query_map(params![gallery_id], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(names)
+    }
+
+    /// Replace a gallery's page order override; an empty list removes it.
+    /// Page hashes are indexed by position, so the gallery is marked for a
+    /// rehash.
+    pub fn set_page_order(&self, gallery_id: i64, names: &[String]) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM page_order WHERE gallery_id = ?1", params![gallery_id])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO page_order (gallery_id, position, name) VALUES (?1, ?2, ?3)",
+            )?;
+            for (i, name) in names.iter().enumerate() {
+                stmt.execute(params![gallery_id, i as i64, name])?;
+            }
+        }
+        tx.execute(
+            "UPDATE galleries SET pages_hashed_at = '' WHERE id = ?1",
+            params![gallery_id],
+        )?;
+        tx.commit()
+    }
+
+    /// Follow renamed page files in a gallery's order override.
+    pub fn rename_page_order_entries(
+        &self,
+        gallery_id: i64,
+        renames: &[(String, String)],
+    ) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt =
+                tx.prepare("UPDATE page_order SET name = ?1 WHERE gallery_id = ?2 AND name = ?3")?;
+            for (from, to) in renames {
+                stmt.execute(params![to, gallery_id, from])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Pages of a gallery marked as credits or extras.
+    pub fn get_page_extras(&self, gallery_id: i64) -> SqlResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT name FROM page_extras WHERE gallery_id = ?1 ORDER BY name")?;
+        let names = stmt
+            .query_map(params![gallery_id], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(names)
+    }
+
+    /// Extra pages of the gallery at `path`; empty for galleries not yet in
+    /// the DB.
+    pub fn get_page_extras_by_path(&self, path: &str) -> SqlResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let path = scanner::to_nfc(path);
+        let mut stmt = conn.prepare(
+            "SELECT e.name FROM page_extras e JOIN galleries g ON g.id = e.gallery_id
+             WHERE g.path = ?1 ORDER BY e.name",
+        )?;
+        let names = stmt
+            .query_map(params![path], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(names)
+    }
+
+    /// Replace the set of extra pages of a gallery.
+    pub fn set_page_extras(&self, gallery_id: i64, names: &[String]) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM page_extras WHERE gallery_id = ?1", params![gallery_id])?;
+        {
+            let mut stmt =
+                tx.prepare("INSERT OR IGNORE INTO page_extras (gallery_id, name) VALUES (?1, ?2)")?;
+            for name in names {
+                stmt.execute(params![gallery_id, name])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Record where the reader is in a gallery, which also counts as reading
+    /// it now.
+    pub fn set_reading_progress(
+        &self,
+        gallery_id: i64,
+        page: usize,
+        total_pages: usize,
+        percent: u32,
+    ) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO reading_progress (gallery_id, page, total_pages, percent, updated_at)
+             VALUES (?1, ?2, ?3, ?4, datetime('now'))",
+            params![gallery_id, page as i64, total_pages as i64, percent],
+        )?;
+        conn.execute(
+            "UPDATE galleries SET last_read_at = datetime('now') WHERE id = ?1",
+            params![gallery_id],
+        )?;
+        Ok(())
+    }
+
+    /// Count a page turn towards the gallery's reading history: it extends
+    /// the current session, or opens a new one after `HISTORY_SESSION_MINUTES`
+    /// without reading it. `pages_read` is the furthest page reached past
+    /// where the session started.
+    pub fn log_reading(&self, gallery_id: i64, page: usize) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let page = page as i64;
+        let updated = conn.execute(
+            "UPDATE reading_history
+             SET pages_read = MAX(pages_read, ?2 - start_page + 1), updated_at = datetime('now')
+             WHERE id = (SELECT id FROM reading_history WHERE gallery_id = ?1
+                         AND updated_at >= datetime('now', ?3)
+                         ORDER BY updated_at DESC LIMIT 1)",
+            params![gallery_id, page, format!("-{} minutes", HISTORY_SESSION_MINUTES)],
+        )?;
+        if updated == 0 {
+            conn.execute(
+                "INSERT INTO reading_history (gallery_id, start_page) VALUES (?1, ?2)",
+                params![gallery_id, page],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reading sessions, most recent first
+    pub fn get_history(&self, limit: i64) -> SqlResult<Vec<HistoryEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, h.opened_at, h.pages_read FROM reading_history h
+             JOIN galleries g ON g.id = h.gallery_id
+             WHERE {}
+             ORDER BY h.updated_at DESC, h.id DESC LIMIT ?1",
+            SUMMARY_COLUMNS, LISTED_EXPR
+        ))?;
+        let entries = stmt
+            .query_map(params![limit], |row| {
+                Ok(HistoryEntry {
+                    gallery: summary_from_row(row)?,
+                    opened_at: row.get(14)?,
+                    pages_read: row.get(15)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(entries)
+    }
+
+    /// Started but unfinished galleries, most recently read first
+    pub fn get_continue_reading(&self, limit: i64) -> SqlResult<Vec<GallerySummary>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM reading_progress p
+             JOIN galleries g ON g.id = p.gallery_id
+             WHERE p.percent < 100 AND {}
+             ORDER BY p.updated_at DESC LIMIT ?1",
+            SUMMARY_COLUMNS, LISTED_EXPR
+        ))?;
+        let galleries = stmt
+            .query_map(params![limit], summary_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(galleries)
+    }
+
+    /// Forget where the reader was, making the gallery unread again. Its
+    /// history is kept.
+    pub fn clear_reading_progress(&self, gallery_id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM reading_progress WHERE gallery_id = ?1", params![gallery_id])?;
+        Ok(())
+    }
+
+    /// The last page read in a gallery and when, `None` if it was never
+    /// opened.
+    pub fn get_reading_progress(&self, gallery_id: i64) -> SqlResult<Option<(usize, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let progress = conn
+            .query_row(
+                "SELECT page, updated_at FROM reading_progress WHERE gallery_id = ?1",
+                params![gallery_id],
+                |row| Ok((row.get::<_, i64>(0)? as usize, row.get(1)?)),
+            )
+            .ok();
+        Ok(progress)
+    }
+
+    /// Append to the change log, dropping entries beyond `CHANGE_LOG_SIZE`
+    pub fn record_change(
+        &self,
+        kind: ChangeKind,
+        gallery_id: Option<i64>,
+        path: &str,
+    ) -> SqlResult<LibraryChange> {
+        let kind_name = serde_json::to_value(kind)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let conn = self.conn.lock().unwrap();
+        let seq: i64 = conn.query_row(
+            "INSERT INTO library_changes (kind, gallery_id, path) VALUES (?1, ?2, ?3) RETURNING seq",
+            params![kind_name, gallery_id, path],
+            |row| row.get(0),
+        )?;
+        conn.execute(
+            "DELETE FROM library_changes WHERE seq <= ?1",
+            params![seq - CHANGE_LOG_SIZE],
+        )?;
+        Ok(LibraryChange {
+            seq,
+            kind,
+            gallery_id,
+            path: path.to_string(),
+        })
+    }
+
+    /// Changes logged after `cursor`, oldest first
+    pub fn get_changes_since(&self, cursor: i64) -> SqlResult<ChangeFeed> {
+        let conn = self.conn.lock().unwrap();
+        let oldest: Option<i64> =
+            conn.query_row("SELECT MIN(seq) FROM library_changes", [], |row| row.get(0))?;
+        // AUTOINCREMENT never reuses a seq, so this is the latest even when
+        // pruning left nothing after it
+        let latest: i64 = conn.query_row(
+            "SELECT COALESCE((SELECT seq FROM sqlite_sequence WHERE name = 'library_changes'), 0)",
+            [],
+            |row| row.get(0),
+        )?;
+        let reset = cursor > latest || oldest.is_some_and(|oldest| cursor < oldest - 1);
+
+        let mut stmt = conn.prepare(
+            "SELECT seq, kind, gallery_id, path FROM library_changes WHERE seq > ?1 ORDER BY seq",
+        )?;
+        let changes = stmt
+            .query_map(params![cursor], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .filter_map(|r| r.ok())
+            .filter_map(|(seq, kind, gallery_id, path)| {
+                let kind = serde_json::from_value(serde_json::Value::String(kind)).ok()?;
+                Some(LibraryChange {
+                    seq,
+                    kind,
+                    gallery_id,
+                    path,
+                })
+            })
+            .collect();
+        Ok(ChangeFeed {
+            changes,
+            cursor: latest,
+            reset,
+        })
+    }
+
+    /// Modification time of each indexed inbox image, by path.
+    pub fn get_inbox_mtimes(&self) -> SqlResult<HashMap<String, String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT path, mtime FROM inbox_images")?;
+        let mtimes = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(mtimes)
+    }
+
+    pub fn upsert_inbox_image(
+        &self,
+        path: &str,
+        filename: &str,
+        file_size: i64,
+        mtime: &str,
+        thumb_path: &str,
+    ) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO inbox_images (path, filename, file_size, mtime, thumb_path)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(path) DO UPDATE SET
+                file_size = excluded.file_size, mtime = excluded.mtime,
+                thumb_path = excluded.thumb_path",
+            params![scanner::to_nfc(path), filename, file_size, mtime, thumb_path],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_inbox_images(&self, paths: &[String]) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("DELETE FROM inbox_images WHERE path = ?1")?;
+        for path in paths {
+            stmt.execute(params![path])?;
+        }
+        Ok(())
+    }
+
+    /// Inbox images, newest first.
+    pub fn list_inbox(&self) -> SqlResult<Vec<InboxImage>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, path, filename, file_size, thumb_path, added_at FROM inbox_images
+             ORDER BY added_at DESC, filename",
+        )?;
+        let images = stmt
+            .query_map([], inbox_image_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(images)
+    }
+
+    pub fn get_inbox_image(&self, id: i64) -> SqlResult<Option<InboxImage>> {
+        let conn = self.conn.lock().unwrap();
+        let image = conn
+            .query_row(
+                "SELECT id, path, filename, file_size, thumb_path, added_at FROM inbox_images
+                 WHERE id = ?1",
+                params![id],
+                inbox_image_from_row,
+            )
+            .ok();
+        Ok(image)
+    }
+
+    /// Bytes on disk: the database file plus its WAL and shared-memory files.
+    pub fn file_size(&self) -> u64 {
+        ["", "-wal", "-shm"]
+            .iter()
+            .map(|suffix| {
+                let mut name = self.path.lock().unwrap().clone().into_os_string();
+                name.push(suffix);
+                std::fs::metadata(PathBuf::from(name)).map(|m| m.len()).unwrap_or(0)
+            })
+            .sum()
+    }
+
+    /// Compact and re-analyze the database. Everything else waits on the
+    /// connection until this finishes.
+    pub fn run_maintenance(&self) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch(
+            "PRAGMA wal_checkpoint(TRUNCATE);
+             INSERT INTO galleries_fts(galleries_fts) VALUES('optimize');
+             ANALYZE;
+             VACUUM;",
+        )?;
+        // VACUUM writes the new pages through the WAL; fold them back in so
+        // the freed space actually leaves the disk
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        Ok(())
+    }
+
+    /// Write a consistent, compacted copy of the database file at `src` to
+    /// `dst`, which must not exist yet. Safe while the app has `src` open.
+    pub fn copy_file(src: &Path, dst: &Path) -> SqlResult<()> {
+        let conn = Self::open_file(src, OpenFlags::default())?;
+        conn.execute("VACUUM INTO ?1", params![dst.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Point thumbnail paths under `old_dir` at `new_dir` in the database file
+    /// at `path`, after its thumbnails were moved. Returns the rows changed.
+    pub fn rebase_thumb_paths(path: &Path, old_dir: &str, new_dir: &str) -> SqlResult<usize> {
+        let conn = Self::open_file(path, OpenFlags::default())?;
+        let mut changed = 0;
+        for table in ["galleries", "inbox_images"] {
+            changed += conn.execute(
+                &format!(
+                    "UPDATE {} SET thumb_path = ?2 || substr(thumb_path, length(?1) + 1)
+                     WHERE substr(thumb_path, 1, length(?1)) = ?1",
+                    table
+                ),
+                params![old_dir, new_dir],
+            )?;
+        }
+        Ok(changed)
+    }
+
+    /// Whether the database file at `path` passes `PRAGMA integrity_check`,
+    /// and how many galleries it holds
+    pub fn check_file(path: &Path) -> SqlResult<(bool, i64)> {
+        let conn = Self::open_file(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let result: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+        let galleries = conn.query_row("SELECT COUNT(*) FROM galleries", [], |row| row.get(0))?;
+        Ok((result == "ok", galleries))
+    }
+
+    /// Run one statement on a separate read-only connection, returning at
+    /// most `max_rows` rows as JSON values. Blobs come back as their size;
+    /// anything after the first statement is ignored. A statement still
+    /// running after `time_limit` is interrupted and fails with
+    /// `ErrorCode::OperationInterrupted`.
+    pub fn run_readonly_query(
+        &self,
+        sql: &str,
+        max_rows: usize,
+        time_limit: Duration,
+    ) -> SqlResult<QueryResult> {
+        let path = self.path.lock().unwrap().clone();
+        let conn = Self::open_file(&path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+        conn.execute_batch("PRAGMA query_only = ON;")?;
+
+        // Dropping `_finished` on return wakes the watchdog before its deadline
+        let (_finished, watched) = std::sync::mpsc::channel::<()>();
+        let interrupt = conn.get_interrupt_handle();
+        std::thread::spawn(move || {
+            if watched.recv_timeout(time_limit) == Err(std::sync::mpsc::RecvTimeoutError::Timeout) {
+                interrupt.interrupt();
+            }
+        });
+        let mut stmt = conn.prepare(sql)?;
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+
+        let mut rows = Vec::new();
+        let mut truncated = false;
+        let mut cursor = stmt.query([])?;
+        while let Some(row) = cursor.next()? {
+            if rows.len() == max_rows {
+                truncated = true;
+                break;
+            }
+            let values = (0..columns.len())
+                .map(|i| {
+                    Ok(match row.get_ref(i)? {
+                        ValueRef::Null => serde_json::Value::Null,
+                        ValueRef::Integer(n) => n.into(),
+                        ValueRef::Real(f) => f.into(),
+                        ValueRef::Text(t) => String::from_utf8_lossy(t).into(),
+                        ValueRef::Blob(b) => format!("<{} bytes>", b.len()).into(),
+                    })
+                })
+                .collect::<SqlResult<Vec<_>>>()?;
+            rows.push(values);
+        }
+        Ok(QueryResult {
+            columns,
+            rows,
+            truncated,
+        })
+    }
+
+    /// Perceptual hashes of every indexed page, as
+    /// `(gallery_id, page_index, phash)`.
+    pub fn get_page_phashes(&self) -> SqlResult<Vec<(i64, usize, u64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT p.gallery_id, p.page_index, p.phash FROM page_hashes p
+             JOIN galleries g ON g.id = p.gallery_id
+             WHERE p.phash IS NOT NULL AND g.missing = 0",
+        )?;
+        let hashes = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get::<_, i64>(1)? as usize,
+                    row.get::<_, i64>(2)? as u64,
+                ))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(hashes)
+    }
+
+    /// Summaries of the galleries in `ids`; unknown ids are absent.
+    pub fn get_gallery_summaries(&self, ids: &[i64]) -> SqlResult<HashMap<i64, GallerySummary>> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let conn = self.conn.lock().unwrap();
+        let list = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM galleries g WHERE g.id IN ({})",
+            SUMMARY_COLUMNS, list
+        ))?;
+        let summaries = stmt
+            .query_map([], summary_from_row)?
+            .filter_map(|r| r.ok())
+            .map(|g| (g.id, g))
+            .collect();
+        Ok(summaries)
+    }
+
+    pub fn get_known_urls(&self) -> SqlResult<HashSet<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT url FROM galleries WHERE url != ''")?;
+        let urls = stmt
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(urls)
+    }
+
+    pub fn set_gallery_url(&self, gallery_id: i64, url: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET url = ?1 WHERE id = ?2",
+            params![fetcher::canonical_gallery_url(url), gallery_id],
+        )?;
+        Self::mark_wanted_acquired(&conn, gallery_id, url)
+    }
+
+    /// Fields explicitly marked as locally edited for a gallery.
+    pub fn get_dirty_fields(&self, gallery_id: i64) -> SqlResult<HashSet<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT field FROM gallery_dirty_fields WHERE gallery_id = ?1")?;
+        let fields = stmt
+            .query_map(params![gallery_id], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(fields)
+    }
+
+    pub fn mark_fields_dirty(&self, gallery_id: i64, fields: &[String]) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "INSERT OR IGNORE INTO gallery_dirty_fields (gallery_id, field) VALUES (?1, ?2)",
+        )?;
+        for field in fields {
+            stmt.execute(params![gallery_id, field])?;
+        }
+        Ok(())
+    }
+
+    pub fn clear_dirty_fields(&self, gallery_id: i64) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM gallery_dirty_fields WHERE gallery_id = ?1",
+            params![gallery_id],
+        )?;
+        Ok(())
+    }
+
+    /// JSON of the metadata as last fetched from EH, the merge baseline for refresh.
+    pub fn get_remote_snapshot(&self, gallery_id: i64) -> SqlResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT data FROM gallery_remote_snapshots WHERE gallery_id = ?1",
+            params![gallery_id],
+            |row| row.get(0),
+        )
+        .ok()
+        .map_or(Ok(None), |v| Ok(Some(v)))
+    }
+
+    pub fn set_remote_snapshot(&self, gallery_id: i64, data: &str) -> SqlResult<()> {
         let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO gallery_remote_snapshots (gallery_id, data) VALUES (?1, ?2)",
+            params![gallery_id, data],
+        )?;
+        Ok(())
+    }
 
-        let mut sql = String::from(
-            "SELECT g.id, g.title_en, g.title_jp, g.category, g.page_count,
-                    g.rating, g.thumb_path, g.folder_name, g.path
-             FROM galleries g",
-        );
-        let mut count_sql = String::from("SELECT COUNT(DISTINCT g.id) FROM galleries g");
-        let mut conditions: Vec<String> = Vec::new();
-        let _param_values: Vec<String> = Vec::new();
-        let mut join_idx = 0;
+    pub fn get_field_locks(&self, gallery_id: i64) -> SqlResult<HashSet<String>> {
+        let conn = self.conn.lock().unwrap();
+        Self::field_locks_for(&conn, gallery_id)
+    }
 
-        // Text search via FTS5
-        if let Some(ref text) = query.text {
-            let text = text.trim();
-            if !text.is_empty() {
-                sql.push_str(
-                    " INNER JOIN galleries_fts fts ON fts.rowid = g.id",
-                );
-                count_sql.push_str(
-                    " INNER JOIN galleries_fts fts ON fts.rowid = g.id",
-                );
-                // Escape FTS5 special chars and wrap each word in quotes
-                let fts_query: String = text
-                    .split_whitespace()
-                    .map(|w| {
-                        let escaped = w.replace('"', "\"\"");
-                        format!("\"{}\"", escaped)
-                    })
-                    .collect::<Vec<_>>()
-                    .join(" ");
-                conditions.push(format!(
-                    "galleries_fts MATCH '{}'",
-                    fts_query.replace('\'', "''")
-                ));
-            }
+    pub fn set_field_lock(&self, gallery_id: i64, field: &str, locked: bool) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        if locked {
+            conn.execute(
+                "INSERT OR IGNORE INTO field_locks (gallery_id, field) VALUES (?1, ?2)",
+                params![gallery_id, field],
+            )?;
+        } else {
+            conn.execute(
+                "DELETE FROM field_locks WHERE gallery_id = ?1 AND field = ?2",
+                params![gallery_id, field],
+            )?;
         }
+        Ok(())
+    }
 
-        // Tag filters
-        for tf in &query.tags {
-            join_idx += 1;
-            let alias = format!("t{}", join_idx);
-            let join = format!(
-                " INNER JOIN gallery_tags {} ON {}.gallery_id = g.id",
-                alias, alias
-            );
-            sql.push_str(&join);
-            count_sql.push_str(&join);
-            conditions.push(format!(
-                "{}.namespace = '{}' AND {}.tag = '{}'",
-                alias,
-                tf.namespace.replace('\'', "''"),
-                alias,
-                tf.tag.replace('\'', "''")
-            ));
-        }
+    fn field_locks_for(conn: &Connection, gallery_id: i64) -> SqlResult<HashSet<String>> {
+        let mut stmt = conn.prepare("SELECT field FROM field_locks WHERE gallery_id = ?1")?;
+        let fields = stmt
+            .query_map(params![gallery_id], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(fields)
+    }
 
-        // Category filter
-        if let Some(ref cat) = query.category {
-            if !cat.is_empty() {
-                conditions.push(format!("g.category = '{}'", cat.replace('\'', "''")));
-            }
-        }
+    /// Current stored metadata of a gallery, in info.txt shape. Inferred
+    /// languages are left out since they never came from metadata.
+    pub fn get_parsed_gallery(&self, gallery_id: i64) -> SqlResult<ParsedGallery> {
+        let conn = self.conn.lock().unwrap();
+        Self::load_parsed(&conn, gallery_id)
+    }
+
+    /// Apply a partial metadata edit to the gallery row, its tags and FTS.
+    pub fn update_gallery_metadata(&self, gallery_id: i64, patch: &MetadataPatch) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
 
-        // Language filter
-        if let Some(ref lang) = query.language {
-            if !lang.is_empty() {
-                conditions.push(format!("g.language = '{}'", lang.replace('\'', "''")));
+        let columns = [
+            ("title_en", &patch.title_en),
+            ("title_jp", &patch.title_jp),
+            ("category", &patch.category),
+            ("language", &patch.language),
+        ];
+        for (column, value) in columns {
+            if let Some(value) = value {
+                tx.execute(
+                    &format!("UPDATE galleries SET {} = ?1 WHERE id = ?2", column),
+                    params![scanner::to_nfc(value.trim()), gallery_id],
+                )?;
             }
         }
 
-        if !conditions.is_empty() {
-            let where_clause = format!(" WHERE {}", conditions.join(" AND "));
-            sql.push_str(&where_clause);
-            count_sql.push_str(&where_clause);
+        if let Some(tags) = &patch.tags {
+            tx.execute(
+                "DELETE FROM gallery_tags WHERE gallery_id = ?1",
+                params![gallery_id],
+            )?;
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO gallery_tags (gallery_id, namespace, tag) VALUES (?1, ?2, ?3)",
+            )?;
+            for t in tags {
+                let tag = scanner::to_nfc(t.tag.trim());
+                if !tag.is_empty() {
+                    stmt.execute(params![gallery_id, scanner::to_nfc(t.namespace.trim()), tag])?;
+                }
+            }
         }
 
-        // Get total count
-        let total_count: i64 = conn
-            .query_row(&count_sql, [], |row| row.get(0))
-            .unwrap_or(0);
+        Self::reindex_fts(&tx, gallery_id)?;
+        tx.commit()
+    }
 
-        // Sort
-        let sort_col = match query.sort_by.as_deref() {
-            Some("rating") => "g.rating",
-            Some("pages") => "g.page_count",
-            Some("posted") => "g.posted",
-            Some("title") => "g.title_en",
-            _ => "g.scanned_at",
-        };
-        let order = match query.sort_order.as_deref() {
-            Some("asc") => "ASC",
-            _ => "DESC",
-        };
-        sql.push_str(&format!(" ORDER BY {} {}", sort_col, order));
-        sql.push_str(&format!(" LIMIT {} OFFSET {}", query.limit, query.offset));
+    /// Add `add` and drop `remove` from a gallery's tags, leaving the rest.
+    /// Returns whether any tag changed.
+    /// `tag`, or its target when it's an alias
+    pub fn resolve_tag(&self, tag: &TagEntry) -> SqlResult<TagEntry> {
+        let conn = self.conn.lock().unwrap();
+        let (namespace, tag_name) = conn
+            .query_row(
+                "SELECT target_namespace, target_tag FROM tag_aliases WHERE namespace = ?1 AND tag = ?2",
+                params![tag.namespace, tag.tag],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .unwrap_or_else(|| (tag.namespace.clone(), tag.tag.clone()));
+        Ok(TagEntry {
+            namespace,
+            tag: tag_name,
+            translation: String::new(),
+        })
+    }
 
-        let mut stmt = conn.prepare(&sql)?;
-        let galleries = stmt
-            .query_map([], |row| {
-                Ok(GallerySummary {
-                    id: row.get(0)?,
-                    title_en: row.get(1)?,
-                    title_jp: row.get(2)?,
-                    category: row.get(3)?,
-                    page_count: row.get(4)?,
-                    rating: row.get(5)?,
-                    thumb_path: row.get(6)?,
-                    folder_name: row.get(7)?,
-                    path: row.get(8)?,
-                    date_modified: 0.0,
-                    date_created: 0.0,
-                })
-            })?
-            .filter_map(|r| r.ok())
-            .collect();
+    /// Make `alias` an alternate spelling of `target`, which must not be an
+    /// alias itself. Aliases of `alias` are repointed to `target` so aliases
+    /// never chain.
+    pub fn set_tag_alias(&self, alias: &TagEntry, target: &TagEntry) -> SqlResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE tag_aliases SET target_namespace = ?3, target_tag = ?4
+             WHERE target_namespace = ?1 AND target_tag = ?2",
+            params![alias.namespace, alias.tag, target.namespace, target.tag],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO tag_aliases (namespace, tag, target_namespace, target_tag)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![alias.namespace, alias.tag, target.namespace, target.tag],
+        )?;
+        tx.commit()
+    }
 
-        Ok(SearchResult {
-            galleries,
-            total_count,
-        })
+    pub fn remove_tag_alias(&self, alias: &TagEntry) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM tag_aliases WHERE namespace = ?1 AND tag = ?2",
+            params![alias.namespace, alias.tag],
+        )?;
+        Ok(())
     }
 
-    pub fn get_all_gallery_paths(&self) -> SqlResult<Vec<String>> {
+    pub fn get_tag_aliases(&self) -> SqlResult<Vec<TagAlias>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT path FROM galleries")?;
-        let paths = stmt
-            .query_map([], |row| row.get(0))?
+        let mut stmt = conn.prepare(
+            "SELECT namespace, tag, target_namespace, target_tag FROM tag_aliases
+             ORDER BY target_namespace, target_tag, namespace, tag",
+        )?;
+        let aliases = stmt
+            .query_map([], |row| {
+                Ok(TagAlias {
+                    namespace: row.get(0)?,
+                    tag: row.get(1)?,
+                    target_namespace: row.get(2)?,
+                    target_tag: row.get(3)?,
+                })
+            })?
             .filter_map(|r| r.ok())
             .collect();
-        Ok(paths)
+        Ok(aliases)
     }
 
-    pub fn find_duplicates_by_url(&self) -> SqlResult<Vec<Vec<GallerySummary>>> {
+    /// Ids of the galleries carrying exactly `tag`, as stored
+    pub fn get_galleries_with_tag(&self, tag: &TagEntry) -> SqlResult<Vec<i64>> {
         let conn = self.conn.lock().unwrap();
-        // Find URLs that appear more than once (ignoring empty URLs)
-        let mut group_stmt = conn.prepare(
-            "SELECT url FROM galleries WHERE url != '' GROUP BY url HAVING COUNT(*) > 1",
+        let mut stmt = conn.prepare(
+            "SELECT gallery_id FROM gallery_tags WHERE namespace = ?1 AND tag = ?2 ORDER BY gallery_id",
         )?;
-        let urls: Vec<String> = group_stmt
-            .query_map([], |row| row.get(0))?
+        let ids = stmt
+            .query_map(params![tag.namespace, tag.tag], |row| row.get(0))?
             .filter_map(|r| r.ok())
             .collect();
+        Ok(ids)
+    }
 
-        let mut results = Vec::new();
-        let mut detail_stmt = conn.prepare(
-            "SELECT id, title_en, title_jp, category, page_count, rating, thumb_path, folder_name, path
-             FROM galleries WHERE url = ?1 ORDER BY id",
-        )?;
-        for url in &urls {
-            let group: Vec<GallerySummary> = detail_stmt
-                .query_map(params![url], |row| {
-                    Ok(GallerySummary {
-                        id: row.get(0)?,
-                        title_en: row.get(1)?,
-                        title_jp: row.get(2)?,
-                        category: row.get(3)?,
-                        page_count: row.get(4)?,
-                        rating: row.get(5)?,
-                        thumb_path: row.get(6)?,
-                        folder_name: row.get(7)?,
-                        path: row.get(8)?,
-                        date_modified: 0.0,
-                    date_created: 0.0,
-                    })
-                })?
-                .filter_map(|r| r.ok())
-                .collect();
-            if group.len() > 1 {
-                results.push(group);
+    pub fn edit_gallery_tags(
+        &self,
+        gallery_id: i64,
+        add: &[TagEntry],
+        remove: &[TagEntry],
+    ) -> SqlResult<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut changed = 0;
+        {
+            let mut insert = tx.prepare(
+                "INSERT OR IGNORE INTO gallery_tags (gallery_id, namespace, tag) VALUES (?1, ?2, ?3)",
+            )?;
+            for t in add {
+                changed += insert.execute(params![gallery_id, t.namespace, t.tag])?;
+            }
+            let mut delete = tx.prepare(
+                "DELETE FROM gallery_tags WHERE gallery_id = ?1 AND namespace = ?2 AND tag = ?3",
+            )?;
+            for t in remove {
+                changed += delete.execute(params![gallery_id, t.namespace, t.tag])?;
             }
         }
-        Ok(results)
+        if changed > 0 {
+            Self::reindex_fts(&tx, gallery_id)?;
+        }
+        tx.commit()?;
+        Ok(changed > 0)
     }
 
-    pub fn find_duplicates_by_name(&self) -> SqlResult<Vec<Vec<GallerySummary>>> {
-        let conn = self.conn.lock().unwrap();
-        // Find title_en values that appear more than once (ignoring empty titles)
-        let mut group_stmt = conn.prepare(
-            "SELECT title_en FROM galleries WHERE title_en != '' GROUP BY title_en HAVING COUNT(*) > 1",
+    fn load_parsed(conn: &Connection, gallery_id: i64) -> SqlResult<ParsedGallery> {
+        let mut parsed = conn.query_row(
+            "SELECT title_en, title_jp, url, category, uploader, posted,
+                    CASE WHEN language_inferred THEN '' ELSE language END,
+                    file_size, page_count, rating, favorited, uploader_comment
+             FROM galleries WHERE id = ?1",
+            params![gallery_id],
+            |row| {
+                Ok(ParsedGallery {
+                    title_en: row.get(0)?,
+                    title_jp: row.get(1)?,
+                    url: row.get(2)?,
+                    category: row.get(3)?,
+                    uploader: row.get(4)?,
+                    posted: row.get(5)?,
+                    language: row.get(6)?,
+                    file_size: row.get(7)?,
+                    page_count: row.get(8)?,
+                    rating: row.get(9)?,
+                    favorited: row.get(10)?,
+                    tags: Vec::new(),
+                    uploader_comment: row.get(11)?,
+                })
+            },
         )?;
-        let names: Vec<String> = group_stmt
-            .query_map([], |row| row.get(0))?
+        let mut stmt =
+            conn.prepare("SELECT namespace, tag FROM gallery_tags WHERE gallery_id = ?1")?;
+        parsed.tags = stmt
+            .query_map(params![gallery_id], |row| Ok((row.get(0)?, row.get(1)?)))?
             .filter_map(|r| r.ok())
             .collect();
+        Ok(parsed)
+    }
 
-        let mut results = Vec::new();
-        let mut detail_stmt = conn.prepare(
-            "SELECT id, title_en, title_jp, category, page_count, rating, thumb_path, folder_name, path
-             FROM galleries WHERE title_en = ?1 ORDER BY id",
+    /// Set or clear (empty string) the user's display title for a gallery.
+    pub fn set_custom_title(&self, gallery_id: i64, title: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE galleries SET custom_title = ?1 WHERE id = ?2",
+            params![scanner::to_nfc(title), gallery_id],
         )?;
-        for name in &names {
-            let group: Vec<GallerySummary> = detail_stmt
-                .query_map(params![name], |row| {
-                    Ok(GallerySummary {
-                        id: row.get(0)?,
-                        title_en: row.get(1)?,
-                        title_jp: row.get(2)?,
-                        category: row.get(3)?,
-                        page_count: row.get(4)?,
-                        rating: row.get(5)?,
-                        thumb_path: row.get(6)?,
-                        folder_name: row.get(7)?,
-                        path: row.get(8)?,
-                        date_modified: 0.0,
-                    date_created: 0.0,
-                    })
-                })?
-                .filter_map(|r| r.ok())
-                .collect();
-            if group.len() > 1 {
-                results.push(group);
-            }
-        }
-        Ok(results)
+        Self::reindex_fts(&conn, gallery_id)
     }
 
-    /// Move all galleries whose path starts with `old_prefix` to `new_prefix`.
-    /// Updates path, parent_path, folder_name, and FTS index.
-    pub fn move_gallery_paths(&self, old_prefix: &str, new_prefix: &str) -> SqlResult<u64> {
+    /// Set or clear (empty string) the user's free-form notes on a gallery
+    pub fn set_gallery_note(&self, gallery_id: i64, note: &str) -> SqlResult<()> {
         let conn = self.conn.lock().unwrap();
-
-        // Find affected galleries — try both separator styles to handle
-        // any inconsistency between how paths were stored vs passed in.
-        let mut stmt = conn.prepare(
-            "SELECT id, path FROM galleries WHERE path = ?1 OR path LIKE ?2
-             OR path = ?3 OR path LIKE ?4",
+        conn.execute(
+            "UPDATE galleries SET notes = ?1 WHERE id = ?2",
+            params![scanner::to_nfc(note), gallery_id],
         )?;
-        let fwd = old_prefix.replace('\\', "/");
-        let bck = old_prefix.replace('/', "\\");
-        let fwd_pattern = format!("{}/%", fwd);
-        let bck_pattern = format!("{}\\%", bck);
-        let rows: Vec<(i64, String)> = stmt
-            .query_map(params![fwd, fwd_pattern, bck, bck_pattern], |row| {
-                Ok((row.get(0)?, row.get(1)?))
-            })?
-            .filter_map(|r| r.ok())
-            .collect();
-
-        let mut count = 0u64;
-        for (id, old_path) in &rows {
-            // Determine which prefix variant matches this stored path
-            let suffix = if old_path.starts_with(&fwd) {
-                &old_path[fwd.len()..]
-            } else if old_path.starts_with(&bck) {
-                &old_path[bck.len()..]
-            } else {
-                continue;
-            };
-            let new_path = format!("{}{}", new_prefix, suffix);
-            let new_parent = std::path::Path::new(&new_path)
-                .parent()
-                .map(|p| p.to_string_lossy().to_string())
-                .unwrap_or_default();
-            let new_folder_name = std::path::Path::new(&new_path)
-                .file_name()
-                .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_default();
+        Self::reindex_fts(&conn, gallery_id)
+    }
 
+    /// Set a custom field, or remove it when `value` is empty
+    pub fn set_gallery_field(&self, gallery_id: i64, key: &str, value: &str) -> SqlResult<()> {
+        let conn = self.conn.lock().unwrap();
+        if value.is_empty() {
             conn.execute(
-                "UPDATE galleries SET path = ?1, parent_path = ?2, folder_name = ?3 WHERE id = ?4",
-                params![new_path, new_parent, new_folder_name, id],
+                "DELETE FROM gallery_fields WHERE gallery_id = ?1 AND key = ?2",
+                params![gallery_id, key],
             )?;
-
-            // Update FTS
+        } else {
             conn.execute(
-                "INSERT OR REPLACE INTO galleries_fts(rowid, title_en, title_jp, folder_name)
-                 SELECT id, title_en, title_jp, folder_name FROM galleries WHERE id = ?1",
-                params![id],
+                "INSERT INTO gallery_fields (gallery_id, key, value) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(gallery_id, key) DO UPDATE SET value = excluded.value",
+                params![gallery_id, key, scanner::to_nfc(value)],
             )?;
-
-            count += 1;
         }
-
-        Ok(count)
+        Self::reindex_fts(&conn, gallery_id)
     }
 
-    /// Delete all galleries under a given path (exact match or children).
-    /// Returns the list of (id, thumb_path) for cleanup.
-    pub fn delete_galleries_under_path(&self, path: &str) -> SqlResult<Vec<(i64, String)>> {
+    /// A gallery's notes and its custom fields by key
+    pub fn get_gallery_notes(&self, gallery_id: i64) -> SqlResult<(String, Vec<CustomField>)> {
         let conn = self.conn.lock().unwrap();
-        let norm = path.replace('\\', "/");
-        let pattern = format!("{}/%", norm);
-
-        // Collect galleries to delete
+        let notes: String = conn
+            .query_row(
+                "SELECT notes FROM galleries WHERE id = ?1",
+                params![gallery_id],
+                |row| row.get(0),
+            )
+            .unwrap_or_default();
         let mut stmt = conn.prepare(
-            "SELECT id, thumb_path FROM galleries WHERE path = ?1 OR path LIKE ?2 OR parent_path = ?3 OR parent_path LIKE ?4",
+            "SELECT key, value FROM gallery_fields WHERE gallery_id = ?1 ORDER BY key COLLATE LOCALE",
         )?;
-        let deleted: Vec<(i64, String)> = stmt
-            .query_map(params![norm, pattern, norm, pattern], |row| {
-                Ok((row.get(0)?, row.get(1)?))
+        let fields = stmt
+            .query_map(params![gallery_id], |row| {
+                Ok(CustomField {
+                    key: row.get(0)?,
+                    value: row.get(1)?,
+                })
             })?
-            .filter_map(|r| r.ok())
-            .collect();
-
-        for (id, _) in &deleted {
-            conn.execute("DELETE FROM galleries_fts WHERE rowid = ?1", params![id])?;
-            conn.execute("DELETE FROM galleries WHERE id = ?1", params![id])?;
-        }
-
-        Ok(deleted)
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok((notes, fields))
     }
 
-    pub fn update_thumb_path(&self, gallery_id: i64, thumb_path: &str) -> SqlResult<()> {
+    /// Every custom field key in use, for suggesting keys
+    pub fn get_custom_field_keys(&self) -> SqlResult<Vec<String>> {
         let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT key FROM gallery_fields ORDER BY key COLLATE LOCALE",
+        )?;
+        let keys = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<SqlResult<Vec<String>>>()?;
+        Ok(keys)
+    }
+
+    fn reindex_fts(conn: &Connection, gallery_id: i64) -> SqlResult<()> {
         conn.execute(
-            "UPDATE galleries SET thumb_path = ?1 WHERE id = ?2",
-            params![thumb_path, gallery_id],
+            &format!(
+                "INSERT OR REPLACE INTO galleries_fts(rowid, {}) {} WHERE id = ?1",
+                FTS_COLUMNS, FTS_SOURCE
+            ),
+            params![gallery_id],
         )?;
         Ok(())
     }
+
+    /// Ids of galleries hidden by the blocklist, default filters and/or
+    /// session filters, for filtering folder listings.
+    pub fn get_hidden_gallery_ids(
+        &self,
+        blocklist: Option<&Blocklist>,
+        defaults: Option<&DefaultFilters>,
+        session: &[SessionFilter],
+    ) -> SqlResult<HashSet<i64>> {
+        let conds: Vec<Condition> = blocklist
+            .and_then(query::blocklist_condition)
+            .into_iter()
+            .chain(defaults.and_then(query::default_filter_condition))
+            .chain(query::session_filter_condition(session))
+            .collect();
+        let Some(visible) = Condition::all(conds) else {
+            return Ok(HashSet::new());
+        };
+        let mut q = GalleryQuery::new();
+        q.filter(visible.negate());
+        let conn = self.conn.lock().unwrap();
+        let sql = q.sql("g.id", "");
+        let params = q.values(vec![]);
+        let started = Instant::now();
+        let mut stmt = conn.prepare(&sql)?;
+        let ids = stmt
+            .query_map(rusqlite::params_from_iter(&params), |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        log_if_slow(&conn, &sql, &params, started);
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The schema as first released, before any migration
+    const BASELINE_SCHEMA: &str = "
+        CREATE TABLE galleries (
+            id            INTEGER PRIMARY KEY AUTOINCREMENT,
+            path          TEXT NOT NULL UNIQUE,
+            title_en      TEXT NOT NULL DEFAULT '',
+            title_jp      TEXT NOT NULL DEFAULT '',
+            url           TEXT NOT NULL DEFAULT '',
+            category      TEXT NOT NULL DEFAULT '',
+            uploader      TEXT NOT NULL DEFAULT '',
+            posted        TEXT NOT NULL DEFAULT '',
+            language      TEXT NOT NULL DEFAULT '',
+            file_size     TEXT NOT NULL DEFAULT '',
+            page_count    INTEGER NOT NULL DEFAULT 0,
+            rating        REAL NOT NULL DEFAULT 0.0,
+            favorited     INTEGER NOT NULL DEFAULT 0,
+            thumb_path    TEXT NOT NULL DEFAULT '',
+            folder_name   TEXT NOT NULL DEFAULT '',
+            parent_path   TEXT NOT NULL DEFAULT '',
+            info_modified TEXT NOT NULL DEFAULT '',
+            scanned_at    TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE TABLE gallery_tags (
+            gallery_id  INTEGER NOT NULL REFERENCES galleries(id) ON DELETE CASCADE,
+            namespace   TEXT NOT NULL,
+            tag         TEXT NOT NULL,
+            PRIMARY KEY (gallery_id, namespace, tag)
+        );
+        CREATE TABLE folders (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            path        TEXT NOT NULL UNIQUE,
+            name        TEXT NOT NULL,
+            parent_path TEXT NOT NULL DEFAULT ''
+        );
+        CREATE INDEX idx_galleries_parent ON galleries(parent_path);
+        CREATE INDEX idx_gallery_tags_ns_tag ON gallery_tags(namespace, tag);
+        CREATE INDEX idx_gallery_tags_tag ON gallery_tags(tag);
+        CREATE INDEX idx_folders_parent ON folders(parent_path);
+        CREATE VIRTUAL TABLE galleries_fts USING fts5(
+            title_en, title_jp, folder_name,
+            content='galleries', content_rowid='id',
+            tokenize='unicode61'
+        );
+        INSERT INTO galleries (path, title_en, url, folder_name, parent_path, scanned_at)
+        VALUES ('/lib/Title [1]', 'Title', 'https://e-hentai.org/g/1/abc/', 'Title [1]', '/lib',
+                '2020-01-02 03:04:05');
+    ";
+
+    #[test]
+    fn upgrades_baseline_database() {
+        let path = std::env::temp_dir().join(format!("ehmaster-upgrade-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        Connection::open(&path).unwrap().execute_batch(BASELINE_SCHEMA).unwrap();
+
+        let db = Database::new(&path).unwrap();
+        let conn = db.conn.lock().unwrap();
+        let (added_at, url): (String, String) = conn
+            .query_row("SELECT added_at, url FROM galleries", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(added_at, "2020-01-02 03:04:05");
+        assert_eq!(url, "https://exhentai.org/g/1/abc/");
+        let indexed: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'idx_galleries_added_at'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(indexed);
+        drop(conn);
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+    #[test]
+    fn readonly_queries_can_sort_by_locale() {
+        let path = std::env::temp_dir().join(format!("ehmaster-readonly-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = Database::new(&path).unwrap();
+        let result = db
+            .run_readonly_query(
+                "SELECT name FROM sqlite_master ORDER BY name COLLATE LOCALE",
+                10,
+                Duration::from_secs(5),
+            )
+            .unwrap();
+        assert!(!result.rows.is_empty());
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn readonly_queries_time_out() {
+        let path = std::env::temp_dir().join(format!("ehmaster-timeout-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = Database::new(&path).unwrap();
+        let started = Instant::now();
+        let err = db
+            .run_readonly_query(
+                "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n)
+                 SELECT COUNT(*) FROM n",
+                10,
+                Duration::from_millis(200),
+            )
+            .unwrap_err();
+        assert_eq!(err.sqlite_error_code(), Some(rusqlite::ErrorCode::OperationInterrupted));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
 }
@@ -0,0 +1,160 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::fetcher;
+use crate::models::{FetchItem, FetchItemState, FetchQueueStatus};
+
+/// Most failed items kept from finished jobs
+const FAILED_HISTORY: usize = 500;
+
+struct ActiveJob {
+    id: u64,
+    started: Instant,
+    /// Items done or failed so far, for the estimate
+    completed: u32,
+    /// Held before its next request until resumed
+    paused: bool,
+}
+
+#[derive(Default)]
+struct Inner {
+    jobs: Vec<ActiveJob>,
+    items: Vec<FetchItem>,
+}
+
+/// Per-gallery state of the bulk fetch jobs, for `get_fetch_queue_status`.
+/// Jobs register their galleries when they start and report each one as
+/// they go; done items are dropped right away.
+#[derive(Default)]
+pub struct FetchQueue {
+    inner: Mutex<Inner>,
+}
+
+impl FetchQueue {
+    /// Register a job's galleries, all queued
+    pub fn begin(&self, job_id: u64, gallery_ids: &[i64]) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.jobs.push(ActiveJob {
+            id: job_id,
+            started: Instant::now(),
+            completed: 0,
+            paused: false,
+        });
+        inner.items.extend(gallery_ids.iter().map(|&gallery_id| FetchItem {
+            job_id,
+            gallery_id,
+            title: String::new(),
+            state: FetchItemState::Queued,
+        }));
+    }
+
+    /// Move a gallery to `state`, naming it when `title` isn't empty
+    pub fn set(&self, job_id: u64, gallery_id: i64, title: &str, state: FetchItemState) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let finished = matches!(state, FetchItemState::Done | FetchItemState::Failed { .. });
+        if finished {
+            if let Some(job) = inner.jobs.iter_mut().find(|j| j.id == job_id) {
+                job.completed += 1;
+            }
+        }
+        let Some(pos) = inner
+            .items
+            .iter()
+            .position(|i| i.job_id == job_id && i.gallery_id == gallery_id)
+        else {
+            return;
+        };
+        if state == FetchItemState::Done {
+            inner.items.remove(pos);
+            return;
+        }
+        let item = &mut inner.items[pos];
+        if !title.is_empty() {
+            item.title = title.to_string();
+        }
+        item.state = state;
+    }
+
+    /// Forget a finished job's remaining items, keeping its failures
+    pub fn end(&self, job_id: u64) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.jobs.retain(|j| j.id != job_id);
+        inner.items.retain(|i| {
+            i.job_id != job_id || matches!(i.state, FetchItemState::Failed { .. })
+        });
+        let failed = inner
+            .items
+            .iter()
+            .filter(|i| matches!(i.state, FetchItemState::Failed { .. }))
+            .count();
+        let mut excess = failed.saturating_sub(FAILED_HISTORY);
+        inner.items.retain(|i| {
+            if excess > 0 && matches!(i.state, FetchItemState::Failed { .. }) {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Hold or release an active job; false if no active job has that id
+    pub fn set_paused(&self, job_id: u64, paused: bool) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        match inner.jobs.iter_mut().find(|j| j.id == job_id) {
+            Some(job) => {
+                job.paused = paused;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_paused(&self, job_id: u64) -> bool {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.jobs.iter().any(|j| j.id == job_id && j.paused)
+    }
+
+    /// Drop failures kept from finished jobs
+    pub fn clear_failed(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let active: Vec<u64> = inner.jobs.iter().map(|j| j.id).collect();
+        inner.items.retain(|i| {
+            active.contains(&i.job_id) || !matches!(i.state, FetchItemState::Failed { .. })
+        });
+    }
+
+    pub fn status(&self) -> FetchQueueStatus {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Each job at its own pace so far, but never faster than the
+        // request budget allows
+        let mut eta = Some(Duration::ZERO);
+        for job in &inner.jobs {
+            let remaining = inner
+                .items
+                .iter()
+                .filter(|i| i.job_id == job.id && !matches!(i.state, FetchItemState::Failed { .. }))
+                .count() as u32;
+            if remaining == 0 {
+                continue;
+            }
+            let job_eta = (job.completed > 0).then(|| {
+                let paced = job.started.elapsed() / job.completed * remaining;
+                let budgeted = fetcher::BUDGET_WINDOW * remaining / fetcher::REQUEST_BUDGET as u32;
+                paced.max(budgeted)
+            });
+            eta = eta.zip(job_eta).map(|(a, b)| a + b);
+        }
+
+        FetchQueueStatus {
+            items: inner.items.clone(),
+            paused_jobs: inner.jobs.iter().filter(|j| j.paused).map(|j| j.id).collect(),
+            budget_used: fetcher::budget_used(),
+            budget_limit: fetcher::REQUEST_BUDGET,
+            budget_window_secs: fetcher::BUDGET_WINDOW.as_secs(),
+            cooldown_secs: fetcher::cooldown_remaining().as_secs_f64().ceil() as u64,
+            eta_secs: eta.map(|d| d.as_secs_f64().ceil() as u64),
+        }
+    }
+}
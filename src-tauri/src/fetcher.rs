@@ -1,11 +1,176 @@
 use regex::Regex;
 use reqwest::header;
-use scraper::{Html, Selector};
-use std::collections::HashMap;
+use scraper::{Html, Node, Selector};
+use sha1::{Digest, Sha1};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::BuildHasher;
 use std::fs;
+use std::io::Write;
 use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+use crate::archive;
 use crate::models::ParsedGallery;
+use crate::scanner;
+
+/// Gallery search endpoint used for identification lookups
+const SEARCH_BASE_URL: &str = "https://exhentai.org/";
+
+/// Favorites listing, one page per slot via `favcat`
+const FAVORITES_URL: &str = "https://exhentai.org/favorites.php";
+
+/// Minimum spacing between search requests in bulk jobs, to stay well
+/// inside EH's rate limits
+pub const SEARCH_REQUEST_DELAY: Duration = Duration::from_secs(10);
+
+/// When the current bulk-request cool-down ends, if one is in progress
+static COOLDOWN_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Record that bulk requests are paused for `delay` from now, unless a
+/// longer cool-down is already running.
+pub fn start_cooldown(delay: Duration) {
+    let until = Instant::now() + delay;
+    let mut cooldown = COOLDOWN_UNTIL.lock().unwrap_or_else(|e| e.into_inner());
+    *cooldown = Some(cooldown.map_or(until, |current| current.max(until)));
+}
+
+/// Time left before the next bulk request may be sent.
+pub fn cooldown_remaining() -> Duration {
+    COOLDOWN_UNTIL
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .map(|until| until.saturating_duration_since(Instant::now()))
+        .unwrap_or_default()
+}
+
+/// Most EH requests sent per `BUDGET_WINDOW`; bulk jobs wait for a free
+/// slot before each request
+pub const REQUEST_BUDGET: usize = 20;
+
+pub const BUDGET_WINDOW: Duration = Duration::from_secs(60);
+
+/// When each request inside the current budget window was sent
+static RECENT_REQUESTS: Mutex<VecDeque<Instant>> = Mutex::new(VecDeque::new());
+
+/// Requests sent in the last `BUDGET_WINDOW`, dropping older ones
+fn recent_requests() -> std::sync::MutexGuard<'static, VecDeque<Instant>> {
+    let mut recent = RECENT_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+    while recent
+        .front()
+        .is_some_and(|sent| sent.elapsed() >= BUDGET_WINDOW)
+    {
+        recent.pop_front();
+    }
+    recent
+}
+
+/// How many requests of the budget were used in the last window
+pub fn budget_used() -> usize {
+    recent_requests().len()
+}
+
+/// Time until a request fits in the budget; zero when one can go now
+pub fn budget_wait() -> Duration {
+    let recent = recent_requests();
+    if recent.len() < REQUEST_BUDGET {
+        return Duration::ZERO;
+    }
+    // The slot frees up when the request that filled it leaves the window
+    BUDGET_WINDOW.saturating_sub(recent[recent.len() - REQUEST_BUDGET].elapsed())
+}
+
+/// Whether a failed request is worth retrying: the connection failed, EH is
+/// throttling (429) or the server had an error (5xx)
+pub fn is_transient(error: &str) -> bool {
+    if error.starts_with("HTTP request failed") {
+        return true;
+    }
+    http_status(error).is_some_and(|code| code == 429 || code >= 500)
+}
+
+/// Status code of an "HTTP <code> ..." error
+fn http_status(error: &str) -> Option<u16> {
+    error
+        .strip_prefix("HTTP ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|code| code.parse().ok())
+}
+
+/// Whether EH answered a request by throttling it (429) or with the sad
+/// panda page, which it also serves to clients it thinks are scraping
+pub fn is_throttled(error: &str) -> bool {
+    http_status(error) == Some(429) || error.contains("sad panda")
+}
+
+/// First cool-down after EH throttles bulk requests; doubled for each
+/// throttled response in a row
+const THROTTLE_BACKOFF: Duration = Duration::from_secs(60);
+
+const MAX_THROTTLE_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+/// Throttled responses since the last request that went through
+static THROTTLE_STREAK: AtomicU32 = AtomicU32::new(0);
+
+/// Record a throttled response and start a cool-down, longer for each one
+/// in a row. Returns the cool-down.
+pub fn note_throttled() -> Duration {
+    let streak = THROTTLE_STREAK.fetch_add(1, Ordering::Relaxed).min(10);
+    let delay = (THROTTLE_BACKOFF * 2u32.pow(streak)).min(MAX_THROTTLE_BACKOFF);
+    start_cooldown(delay);
+    delay
+}
+
+/// Record a request that went through, so the next throttle starts the
+/// backoff over
+pub fn note_not_throttled() {
+    THROTTLE_STREAK.store(0, Ordering::Relaxed);
+}
+
+/// A random duration between `min` and `max`, so bulk requests don't go out
+/// on a fixed beat
+pub fn random_delay(min: Duration, max: Duration) -> Duration {
+    let spread = max.saturating_sub(min).as_millis() as u64;
+    if spread == 0 {
+        return min;
+    }
+    let random = RandomState::new().hash_one(std::time::SystemTime::now());
+    min + Duration::from_millis(random % (spread + 1))
+}
+
+/// Host gallery URLs are stored under in the DB
+const CANONICAL_HOST: &str = "exhentai.org";
+
+/// Normalize a gallery URL to `https://exhentai.org/g/{gid}/{token}/`.
+/// Anything that isn't a recognizable gallery URL is returned trimmed.
+pub fn canonical_gallery_url(url: &str) -> String {
+    with_domain(url, CANONICAL_HOST)
+}
+
+/// Gallery id from an EH gallery URL
+pub fn gallery_gid(url: &str) -> Option<i64> {
+    let re = Regex::new(r"(?:exhentai|e-hentai)\.org/g/(\d+)/").unwrap();
+    re.captures(url)?[1].parse().ok()
+}
+
+/// Gallery token from an EH gallery URL
+pub fn gallery_token(url: &str) -> Option<String> {
+    let re = Regex::new(r"(?:exhentai|e-hentai)\.org/g/\d+/([0-9a-f]+)").unwrap();
+    Some(re.captures(url)?[1].to_string())
+}
+
+/// Rewrite a gallery URL onto `host` (e.g. "e-hentai.org").
+pub fn with_domain(url: &str, host: &str) -> String {
+    let re = Regex::new(r"^https?://(?:[a-z]+\.)?(?:exhentai|e-hentai)\.org/g/(\d+)/([0-9a-f]+)")
+        .unwrap();
+    let url = url.trim();
+    match re.captures(url) {
+        Some(caps) => format!("https://{}/g/{}/{}/", host, &caps[1], &caps[2]),
+        None => url.to_string(),
+    }
+}
 
 /// Load cookies from a Netscape cookie file.
 fn load_cookies(path: &Path) -> Result<HashMap<String, String>, String> {
@@ -36,6 +201,427 @@ pub async fn fetch_gallery_info(
     url: &str,
     cookie_path: &Path,
 ) -> Result<ParsedGallery, String> {
+    fetch_gallery_versions(url, cookie_path).await.map(|(info, _)| info)
+}
+
+/// Like `fetch_gallery_info`, plus the canonical URL of the newest version
+/// when EH lists newer versions of the gallery.
+pub async fn fetch_gallery_versions(
+    url: &str,
+    cookie_path: &Path,
+) -> Result<(ParsedGallery, Option<String>), String> {
+    let html = fetch_html(url, &[], cookie_path).await?;
+    let info = parse_gallery_html(&html, url)?;
+    Ok((info, parse_newer_version(&html)))
+}
+
+/// EH's JSON API endpoint
+const API_URL: &str = "https://api.e-hentai.org/api.php";
+
+/// Most galleries the gdata API answers in one request
+pub const API_BATCH: usize = 25;
+
+/// Metadata for up to `API_BATCH` galleries per request from the gdata API,
+/// by gid, each with the canonical URL of its newest version when there is
+/// a newer one. Galleries the API reports an error for are left out.
+///
+/// The API has no favorite count or uploader comment: those come back as
+/// 0 and empty.
+pub async fn fetch_gallery_api(
+    urls: &[String],
+    cookie_path: &Path,
+) -> Result<HashMap<i64, (ParsedGallery, Option<String>)>, String> {
+    let gidlist: Vec<(i64, String)> = urls
+        .iter()
+        .filter_map(|url| Some((gallery_gid(url)?, gallery_token(url)?)))
+        .collect();
+    let cookies = load_cookies(cookie_path)?;
+    let cookie_str: String = cookies
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    let mut galleries = HashMap::new();
+    for batch in gidlist.chunks(API_BATCH) {
+        let body = serde_json::json!({
+            "method": "gdata",
+            "gidlist": batch.iter().map(|(gid, token)| serde_json::json!([gid, token])).collect::<Vec<_>>(),
+            "namespace": 1,
+        });
+        recent_requests().push_back(Instant::now());
+        let response = reqwest::Client::new()
+            .post(API_URL)
+            .header(
+                header::USER_AGENT,
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36",
+            )
+            .header(header::COOKIE, &cookie_str)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| format!("HTTP request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        let text = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read response body: {}", e))?;
+        let json: serde_json::Value =
+            serde_json::from_str(&text).map_err(|e| format!("Invalid API response: {}", e))?;
+        let entries = json
+            .get("gmetadata")
+            .and_then(|m| m.as_array())
+            .ok_or("API response has no gmetadata")?;
+        for entry in entries {
+            if let Some((gid, parsed)) = parse_gdata_entry(entry) {
+                galleries.insert(gid, parsed);
+            }
+        }
+    }
+    Ok(galleries)
+}
+
+/// One `gmetadata` entry as a ParsedGallery, or `None` for an error entry
+fn parse_gdata_entry(entry: &serde_json::Value) -> Option<(i64, (ParsedGallery, Option<String>))> {
+    if entry.get("error").is_some() {
+        return None;
+    }
+    // Numbers like `filecount` and `posted` come quoted; accept either form
+    let str_field = |key: &str| match entry.get(key) {
+        Some(serde_json::Value::String(s)) => unescape_html(s).trim().to_string(),
+        Some(serde_json::Value::Number(n)) => n.to_string(),
+        _ => String::new(),
+    };
+    let gid = entry.get("gid")?.as_i64()?;
+    let token = entry.get("token")?.as_str()?;
+
+    let mut tags = Vec::new();
+    for tag in entry.get("tags").and_then(|t| t.as_array()).into_iter().flatten() {
+        let Some(tag) = tag.as_str() else { continue };
+        let (namespace, name) = tag.split_once(':').unwrap_or(("misc", tag));
+        tags.push((namespace.to_string(), name.to_string()));
+    }
+    // The page shows the language tag capitalized, minus "translated"/"rewrite"
+    let language = tags
+        .iter()
+        .filter(|(ns, t)| ns == "language" && t != "translated" && t != "rewrite")
+        .map(|(_, t)| {
+            let mut chars = t.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .next()
+        .unwrap_or_default();
+
+    let posted = str_field("posted")
+        .parse::<i64>()
+        .map(format_posted)
+        .unwrap_or_default();
+    let file_size = entry
+        .get("filesize")
+        .and_then(|v| v.as_u64())
+        .map(format_file_size)
+        .unwrap_or_default();
+
+    // `current_gid`/`current_key` name the newest version when it differs
+    let current = str_field("current_gid");
+    let current_key = str_field("current_key");
+    let newer = (!current.is_empty() && !current_key.is_empty() && current != gid.to_string())
+        .then(|| format!("https://{}/g/{}/{}/", CANONICAL_HOST, current, current_key));
+
+    let parsed = ParsedGallery {
+        title_en: str_field("title"),
+        title_jp: str_field("title_jpn"),
+        url: format!("https://{}/g/{}/{}/", CANONICAL_HOST, gid, token),
+        category: str_field("category"),
+        uploader: str_field("uploader"),
+        posted,
+        language,
+        file_size,
+        page_count: str_field("filecount").parse().unwrap_or(0),
+        rating: str_field("rating").parse().unwrap_or(0.0),
+        favorited: 0,
+        tags,
+        uploader_comment: String::new(),
+    };
+    Some((gid, (parsed, newer)))
+}
+
+/// Unix time as the "YYYY-MM-DD HH:MM" (UTC) the gallery page shows
+fn format_posted(ts: i64) -> String {
+    let days = ts.div_euclid(86_400);
+    let secs = ts.rem_euclid(86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60
+    )
+}
+
+/// Bytes as the page shows them, e.g. "45.62 MiB"
+fn format_file_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.2} {}", size, UNITS[unit])
+    }
+}
+
+/// Decode the HTML entities the API leaves in titles
+fn unescape_html(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let re = Regex::new(r"&(#x[0-9a-fA-F]+|#\d+|amp|lt|gt|quot|apos);").unwrap();
+    re.replace_all(s, |caps: &regex::Captures| {
+        let entity = &caps[1];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => {
+                u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32)
+            }
+            _ => entity[1..].parse().ok().and_then(char::from_u32),
+        };
+        decoded.map(String::from).unwrap_or_else(|| caps[0].to_string())
+    })
+    .to_string()
+}
+
+/// Last link of the "newer versions" box (`#gnd`), which EH lists oldest
+/// first
+fn parse_newer_version(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let sel = Selector::parse("#gnd a[href]").unwrap();
+    document
+        .select(&sel)
+        .filter_map(|a| a.value().attr("href"))
+        .rfind(|href| gallery_gid(href).is_some())
+        .map(canonical_gallery_url)
+}
+
+/// Links to every page viewer (`/s/...`) of a gallery, in page order. The
+/// gallery's thumbnail pages are walked until `page_count` links are found
+/// or a page adds none.
+pub async fn fetch_page_links(
+    url: &str,
+    page_count: usize,
+    cookie_path: &Path,
+) -> Result<Vec<String>, String> {
+    let re = Regex::new(r"https://(?:exhentai|e-hentai)\.org/s/[0-9a-f]+/\d+-(\d+)").unwrap();
+    let mut pages: BTreeMap<usize, String> = BTreeMap::new();
+    for p in 0.. {
+        // Only runs inside jobs, which sleep on their own thread
+        std::thread::sleep(budget_wait());
+        let html = fetch_html(url, &[("p", &p.to_string())], cookie_path).await?;
+        let before = pages.len();
+        for caps in re.captures_iter(&html) {
+            if let Ok(n) = caps[1].parse() {
+                pages.entry(n).or_insert_with(|| caps[0].to_string());
+            }
+        }
+        if pages.len() >= page_count || pages.len() == before {
+            break;
+        }
+    }
+    Ok(pages.into_values().collect())
+}
+
+/// URL of the full image shown on a page viewer
+pub async fn fetch_image_url(page_url: &str, cookie_path: &Path) -> Result<String, String> {
+    let html = fetch_html(page_url, &[], cookie_path).await?;
+    let document = Html::parse_document(&html);
+    let sel = Selector::parse("img#img").unwrap();
+    let src = document
+        .select(&sel)
+        .next()
+        .and_then(|img| img.value().attr("src"))
+        .ok_or_else(|| format!("No image on {}", page_url))?;
+    // EH swaps in this placeholder once the image quota is used up
+    if src.ends_with("/509.gif") {
+        return Err("Image viewing limit reached".to_string());
+    }
+    Ok(src.to_string())
+}
+
+/// Download a file to memory
+pub async fn download_bytes(url: &str) -> Result<Vec<u8>, String> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .header(
+            header::USER_AGENT,
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36",
+        )
+        .send()
+        .await
+        .map_err(|e| format!("HTTP request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    response
+        .bytes()
+        .await
+        .map(|b| b.to_vec())
+        .map_err(|e| format!("Failed to read response body: {}", e))
+}
+
+/// Search EH for galleries containing an image with the given SHA-1 and
+/// return the first hit's URL.
+pub async fn search_by_image_hash(
+    sha1_hex: &str,
+    cookie_path: &Path,
+) -> Result<Option<String>, String> {
+    let html = fetch_html(SEARCH_BASE_URL, &[("f_shash", sha1_hex)], cookie_path).await?;
+    Ok(first_gallery_link(&html))
+}
+
+/// Search EH by title and return the first hit's URL.
+pub async fn search_by_title(title: &str, cookie_path: &Path) -> Result<Option<String>, String> {
+    let html = fetch_html(SEARCH_BASE_URL, &[("f_search", title)], cookie_path).await?;
+    Ok(first_gallery_link(&html))
+}
+
+/// Galleries uploaded under an artist tag, newest first, from the first
+/// page of results as (canonical URL, title).
+pub async fn search_artist_uploads(
+    artist: &str,
+    cookie_path: &Path,
+) -> Result<Vec<(String, String)>, String> {
+    let query = format!("artist:\"{}$\"", artist);
+    let html = fetch_html(SEARCH_BASE_URL, &[("f_search", query.as_str())], cookie_path).await?;
+    Ok(parse_search_results(&html))
+}
+
+fn parse_search_results(html: &str) -> Vec<(String, String)> {
+    let document = Html::parse_document(html);
+    let sel_link = Selector::parse("a[href]").unwrap();
+    let sel_title = Selector::parse(".glink").unwrap();
+    let mut results: Vec<(String, String)> = Vec::new();
+    for link in document.select(&sel_link) {
+        let Some(title) = link.select(&sel_title).next() else {
+            continue;
+        };
+        let url = canonical_gallery_url(link.value().attr("href").unwrap_or_default());
+        if gallery_gid(&url).is_some() && !results.iter().any(|(u, _)| *u == url) {
+            results.push((url, title.text().collect::<String>().trim().to_string()));
+        }
+    }
+    results
+}
+
+/// Names of the account's 10 favorite slots, in slot order.
+pub async fn fetch_favorite_slot_names(cookie_path: &Path) -> Result<Vec<String>, String> {
+    let html = fetch_html(FAVORITES_URL, &[], cookie_path).await?;
+    let names = parse_favorite_slot_names(&html);
+    if names.is_empty() {
+        return Err("No favorite slots found — check cookies".to_string());
+    }
+    Ok(names)
+}
+
+/// One page of a favorite slot: canonical gallery URLs on the page and the
+/// cursor for the next page, if any.
+pub async fn fetch_favorites_page(
+    slot: i64,
+    next: Option<&str>,
+    cookie_path: &Path,
+) -> Result<(Vec<String>, Option<String>), String> {
+    let slot = slot.to_string();
+    let mut query = vec![("favcat", slot.as_str())];
+    if let Some(next) = next {
+        query.push(("next", next));
+    }
+    let html = fetch_html(FAVORITES_URL, &query, cookie_path).await?;
+
+    let re_link = Regex::new(r"https://(?:exhentai|e-hentai)\.org/g/\d+/[0-9a-f]+/").unwrap();
+    let mut urls: Vec<String> = Vec::new();
+    for m in re_link.find_iter(&html) {
+        let url = canonical_gallery_url(m.as_str());
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+
+    let re_next = Regex::new(r#"id="unext"[^>]*href="[^"]*[?&;]next=(\d+)"#).unwrap();
+    let next = re_next.captures(&html).map(|caps| caps[1].to_string());
+    Ok((urls, next))
+}
+
+fn parse_favorite_slot_names(html: &str) -> Vec<String> {
+    let document = Html::parse_document(html);
+    let sel_fp = Selector::parse("div.fp:not(.fps)").unwrap();
+    let sel_div = Selector::parse("div").unwrap();
+    document
+        .select(&sel_fp)
+        .take(10)
+        .filter_map(|fp| {
+            fp.select(&sel_div)
+                .last()
+                .map(|el| el.text().collect::<String>().trim().to_string())
+        })
+        .collect()
+}
+
+/// SHA-1 of a file's contents as lowercase hex (the hash EH's file search uses).
+pub fn file_sha1(path: &Path) -> Option<String> {
+    let data = fs::read(path).ok()?;
+    Some(data_sha1(&data))
+}
+
+/// Lowercase hex SHA-1 of `data`
+pub fn data_sha1(data: &[u8]) -> String {
+    hex::encode(Sha1::digest(data))
+}
+
+/// SHA-1 of a gallery's first page, for a folder or an archive
+pub fn first_page_sha1(gallery: &Path) -> Option<String> {
+    if archive::is_archive(gallery) {
+        let data = archive::read_first_image(gallery).ok()?;
+        return Some(data_sha1(&data));
+    }
+    file_sha1(&scanner::get_first_image(gallery)?)
+}
+
+/// Extract the first gallery URL from a search results page.
+fn first_gallery_link(html: &str) -> Option<String> {
+    let re = Regex::new(r"https://(?:exhentai|e-hentai)\.org/g/\d+/[0-9a-f]+/").unwrap();
+    re.find(html).map(|m| m.as_str().to_string())
+}
+
+/// GET a page with the user's cookies and return the body.
+async fn fetch_html(
+    url: &str,
+    query: &[(&str, &str)],
+    cookie_path: &Path,
+) -> Result<String, String> {
     let cookies = load_cookies(cookie_path)?;
 
     // Build cookie header string
@@ -45,9 +631,11 @@ pub async fn fetch_gallery_info(
         .collect::<Vec<_>>()
         .join("; ");
 
+    recent_requests().push_back(Instant::now());
     let client = reqwest::Client::new();
     let response = client
         .get(url)
+        .query(query)
         .header(
             header::USER_AGENT,
             "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36",
@@ -61,16 +649,14 @@ pub async fn fetch_gallery_info(
         return Err(format!("HTTP {}", response.status()));
     }
 
-    let html = response
+    response
         .text()
         .await
-        .map_err(|e| format!("Failed to read response body: {}", e))?;
-
-    parse_gallery_html(&html, url)
+        .map_err(|e| format!("Failed to read response body: {}", e))
 }
 
 /// Parse the ExHentai gallery page HTML into a ParsedGallery.
-fn parse_gallery_html(html: &str, url: &str) -> Result<ParsedGallery, String> {
+pub fn parse_gallery_html(html: &str, url: &str) -> Result<ParsedGallery, String> {
     let document = Html::parse_document(html);
 
     // Check for sad panda (empty/blocked page)
@@ -188,6 +774,24 @@ fn parse_gallery_html(html: &str, url: &str) -> Result<ParsedGallery, String> {
         }
     }
 
+    // Uploader comment (#comment_0), keeping <br> line breaks
+    let sel_comment = Selector::parse("#comment_0").unwrap();
+    let uploader_comment = document
+        .select(&sel_comment)
+        .next()
+        .map(|el| {
+            let mut text = String::new();
+            for node in el.descendants() {
+                match node.value() {
+                    Node::Text(t) => text.push_str(t),
+                    Node::Element(e) if e.name() == "br" => text.push('\n'),
+                    _ => {}
+                }
+            }
+            text.trim().to_string()
+        })
+        .unwrap_or_default();
+
     Ok(ParsedGallery {
         title_en,
         title_jp,
@@ -201,6 +805,7 @@ fn parse_gallery_html(html: &str, url: &str) -> Result<ParsedGallery, String> {
         rating,
         favorited,
         tags,
+        uploader_comment,
     })
 }
 
@@ -266,5 +871,32 @@ pub fn write_info_txt(path: &Path, info: &ParsedGallery) -> Result<(), String> {
         }
     }
 
-    fs::write(path, content).map_err(|e| format!("Failed to write info.txt: {}", e))
+    if !info.uploader_comment.is_empty() {
+        content.push_str("\nUploader Comment:\n");
+        content.push_str(&info.uploader_comment);
+        content.push('\n');
+    }
+
+    write_atomic(path, content.as_bytes()).map_err(|e| format!("Failed to write info.txt: {}", e))
+}
+
+/// Write via a sibling temp file and rename, so a crash mid-write never
+/// leaves a truncated file behind.
+pub fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name));
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
 }
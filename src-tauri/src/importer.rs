@@ -0,0 +1,97 @@
+use rusqlite::{Connection, OpenFlags};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::fetcher;
+
+/// One gallery as recorded by another app, merged across its tables.
+/// Timestamps are Unix milliseconds, as EhViewer stores them.
+#[derive(Debug, Clone, Default)]
+pub struct ImportedGallery {
+    pub url: String,
+    pub title: String,
+    pub title_jpn: String,
+    /// Download folder name, used to match galleries without a stored URL
+    pub dirname: String,
+    pub downloaded_at: Option<i64>,
+    pub read_at: Option<i64>,
+    pub favorited: bool,
+}
+
+/// Read downloads, history, and local favorites from an EhViewer `eh.db`.
+/// The file is opened read-only; tables missing from older or trimmed
+/// databases are skipped.
+pub fn read_ehviewer_db(path: &Path) -> Result<Vec<ImportedGallery>, String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+
+    if !has_table(&conn, "DOWNLOADS") && !has_table(&conn, "HISTORY") {
+        return Err("Not an EhViewer database (no DOWNLOADS or HISTORY table)".to_string());
+    }
+
+    let mut by_gid: BTreeMap<i64, ImportedGallery> = BTreeMap::new();
+    let mut merge = |table: &str, apply: &dyn Fn(&mut ImportedGallery, i64)| -> Result<(), String> {
+        if !has_table(&conn, table) {
+            return Ok(());
+        }
+        let sql = format!("SELECT GID, TOKEN, TITLE, TITLE_JPN, TIME FROM {}", table);
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                    row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                    row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                    row.get::<_, Option<i64>>(4)?.unwrap_or(0),
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        for (gid, token, title, title_jpn, time) in rows.filter_map(|r| r.ok()) {
+            let entry = by_gid.entry(gid).or_default();
+            if entry.url.is_empty() && !token.is_empty() {
+                entry.url = fetcher::canonical_gallery_url(&format!(
+                    "https://exhentai.org/g/{}/{}/",
+                    gid, token
+                ));
+            }
+            if entry.title.is_empty() {
+                entry.title = title;
+            }
+            if entry.title_jpn.is_empty() {
+                entry.title_jpn = title_jpn;
+            }
+            apply(entry, time);
+        }
+        Ok(())
+    };
+
+    merge("DOWNLOADS", &|g, time| g.downloaded_at = Some(time))?;
+    merge("HISTORY", &|g, time| g.read_at = Some(time))?;
+    merge("LOCAL_FAVORITES", &|g, _| g.favorited = true)?;
+
+    if has_table(&conn, "DOWNLOAD_DIRNAME") {
+        let mut stmt = conn
+            .prepare("SELECT GID, DIRNAME FROM DOWNLOAD_DIRNAME")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| e.to_string())?;
+        for (gid, dirname) in rows.filter_map(|r| r.ok()) {
+            if let Some(g) = by_gid.get_mut(&gid) {
+                g.dirname = dirname;
+            }
+        }
+    }
+
+    Ok(by_gid.into_values().collect())
+}
+
+fn has_table(conn: &Connection, name: &str) -> bool {
+    conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?1 COLLATE NOCASE",
+        [name],
+        |row| row.get(0),
+    )
+    .unwrap_or(false)
+}
@@ -0,0 +1,393 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::models::{JobInfo, JobKind, JobPriority, JobResource, JobStatus};
+use crate::state::AppState;
+
+/// How many finished jobs are kept around for `list_jobs`
+const FINISHED_HISTORY: usize = 100;
+
+type JobFn = Box<dyn FnOnce(&JobContext) -> Result<(), String> + Send>;
+
+struct QueuedJob {
+    id: u64,
+    run: JobFn,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    jobs: Vec<JobInfo>,
+    pending: Vec<QueuedJob>,
+    running: HashMap<JobKind, usize>,
+    running_by_resource: HashMap<JobResource, usize>,
+    cancel_flags: HashMap<u64, Arc<AtomicBool>>,
+    resource_limits: HashMap<JobResource, usize>,
+    held: HashSet<JobKind>,
+}
+
+/// Shared background job queue. Jobs are picked by priority (then age) as
+/// long as both their kind and their resource class are below their
+/// concurrency limits, and each runs on its own thread. Every state change
+/// is broadcast as a `job-progress` event.
+#[derive(Default)]
+pub struct JobQueue {
+    inner: Mutex<Inner>,
+    wake: Condvar,
+}
+
+/// Handle passed to a running job for reporting progress and polling cancellation.
+pub struct JobContext {
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+    queue: Arc<JobQueue>,
+    app: AppHandle,
+}
+
+impl JobContext {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn app(&self) -> &AppHandle {
+        &self.app
+    }
+
+    /// Whether `cancel_job` was called; long-running jobs should check this
+    /// between items and return early.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Report progress. Persisted jobs also checkpoint `done` to the DB so a
+    /// restart can resume where they left off.
+    pub fn progress(&self, done: u64, total: u64, message: &str) {
+        let info = self.queue.update(self.id, |job| {
+            job.done = done;
+            job.total = total;
+            job.message = message.to_string();
+        });
+        if let Some(info) = info {
+            if let Some(persist_id) = info.persist_id {
+                let state = self.app.state::<AppState>();
+                if let Err(e) = state.db.update_job_progress(persist_id, done as i64) {
+                    log::warn!("[jobs] Failed to checkpoint job {}: {}", info.id, e);
+                }
+            }
+            let _ = self.app.emit("job-progress", info);
+        }
+    }
+}
+
+impl JobQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn the dispatcher thread. Must be called once after app setup.
+    pub fn start(self: &Arc<Self>, app: AppHandle) {
+        let queue = Arc::clone(self);
+        std::thread::spawn(move || loop {
+            let (job, info, cancelled) = {
+                let mut inner = queue.inner.lock().unwrap_or_else(|e| e.into_inner());
+                loop {
+                    if let Some(claimed) = Self::claim_next(&mut inner) {
+                        break claimed;
+                    }
+                    inner = queue.wake.wait(inner).unwrap_or_else(|e| e.into_inner());
+                }
+            };
+
+            let _ = app.emit("job-progress", info.clone());
+
+            let ctx = JobContext {
+                id: job.id,
+                cancelled,
+                queue: Arc::clone(&queue),
+                app: app.clone(),
+            };
+            std::thread::spawn(move || {
+                let result = run_caught(|| (job.run)(&ctx));
+                ctx.queue.finish(&ctx, info.kind, result);
+            });
+        });
+    }
+
+    /// Queue a job and return its id.
+    pub fn enqueue<F>(
+        &self,
+        kind: JobKind,
+        priority: JobPriority,
+        label: impl Into<String>,
+        run: F,
+    ) -> u64
+    where
+        F: FnOnce(&JobContext) -> Result<(), String> + Send + 'static,
+    {
+        self.enqueue_persisted(kind, priority, label, None, run)
+    }
+
+    /// Queue a job backed by a row in the `jobs` table (see `Database::insert_job`).
+    pub fn enqueue_persisted<F>(
+        &self,
+        kind: JobKind,
+        priority: JobPriority,
+        label: impl Into<String>,
+        persist_id: Option<i64>,
+        run: F,
+    ) -> u64
+    where
+        F: FnOnce(&JobContext) -> Result<(), String> + Send + 'static,
+    {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.next_id += 1;
+        let id = inner.next_id;
+        let deferred = inner.held.contains(&kind);
+        inner.jobs.push(JobInfo {
+            id,
+            kind,
+            priority,
+            status: JobStatus::Queued,
+            label: label.into(),
+            done: 0,
+            total: 0,
+            message: String::new(),
+            error: None,
+            persist_id,
+            deferred,
+        });
+        inner.pending.push(QueuedJob {
+            id,
+            run: Box::new(run),
+        });
+        drop(inner);
+        self.wake.notify_all();
+        id
+    }
+
+    /// Show where a resumed job left off while it waits to run
+    pub fn set_checkpoint(&self, id: u64, done: u64, total: u64) {
+        self.update(id, |job| {
+            job.done = done;
+            job.total = total;
+        });
+    }
+
+    /// Snapshot of queued, running, and recently finished jobs.
+    pub fn list(&self) -> Vec<JobInfo> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.jobs.clone()
+    }
+
+    /// Cancel a job. Queued jobs are dropped immediately; running jobs are
+    /// flagged and stop at their next cancellation check.
+    pub fn cancel(&self, id: u64) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pos) = inner.pending.iter().position(|j| j.id == id) {
+            inner.pending.remove(pos);
+            if let Some(job) = inner.jobs.iter_mut().find(|j| j.id == id) {
+                job.status = JobStatus::Cancelled;
+            }
+            return true;
+        }
+        match inner.cancel_flags.get(&id) {
+            Some(flag) => {
+                flag.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set how many jobs contending for `resource` may run at once.
+    pub fn set_resource_limit(&self, resource: JobResource, limit: usize) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.resource_limits.insert(resource, limit.max(1));
+        drop(inner);
+        self.wake.notify_all();
+    }
+
+    /// Hold queued jobs of these kinds until a later call releases them.
+    /// Returns the queued jobs whose `deferred` flag changed.
+    pub fn set_held(&self, kinds: &[JobKind]) -> Vec<JobInfo> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.held = kinds.iter().copied().collect();
+        let held = inner.held.clone();
+        let changed = inner
+            .jobs
+            .iter_mut()
+            .filter(|j| j.status == JobStatus::Queued && j.deferred != held.contains(&j.kind))
+            .map(|job| {
+                job.deferred = !job.deferred;
+                job.clone()
+            })
+            .collect();
+        drop(inner);
+        self.wake.notify_all();
+        changed
+    }
+
+    fn update<F: FnOnce(&mut JobInfo)>(&self, id: u64, f: F) -> Option<JobInfo> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let job = inner.jobs.iter_mut().find(|j| j.id == id)?;
+        f(job);
+        Some(job.clone())
+    }
+
+    fn finish(&self, ctx: &JobContext, kind: JobKind, result: Result<(), String>) {
+        let info = self.settle(ctx.id, kind, ctx.is_cancelled(), result);
+        self.wake.notify_all();
+
+        if let Some(info) = info {
+            if let Some(err) = &info.error {
+                log::warn!("[jobs] Job {} ({}) failed: {}", info.id, info.label, err);
+            }
+            let _ = ctx.app.emit("job-progress", info);
+        }
+    }
+
+    /// Take the next runnable job off `pending` and mark it running. Returns
+    /// it with its info and cancel flag.
+    fn claim_next(inner: &mut Inner) -> Option<(QueuedJob, JobInfo, Arc<AtomicBool>)> {
+        loop {
+            let job = inner.pending.remove(Self::next_runnable(inner)?);
+            let Some(info) = inner.jobs.iter_mut().find(|j| j.id == job.id) else {
+                continue;
+            };
+            info.status = JobStatus::Running;
+            info.deferred = false;
+            let info = info.clone();
+            let cancelled = inner
+                .cancel_flags
+                .entry(job.id)
+                .or_insert_with(|| Arc::new(AtomicBool::new(false)))
+                .clone();
+            *inner.running.entry(info.kind).or_default() += 1;
+            *inner
+                .running_by_resource
+                .entry(info.kind.resource())
+                .or_default() += 1;
+            return Some((job, info, cancelled));
+        }
+    }
+
+    /// Free the slot of finished job `id` and record how it ended
+    fn settle(
+        &self,
+        id: u64,
+        kind: JobKind,
+        cancelled: bool,
+        result: Result<(), String>,
+    ) -> Option<JobInfo> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(n) = inner.running.get_mut(&kind) {
+            *n = n.saturating_sub(1);
+        }
+        if let Some(n) = inner.running_by_resource.get_mut(&kind.resource()) {
+            *n = n.saturating_sub(1);
+        }
+        inner.cancel_flags.remove(&id);
+
+        let info = inner.jobs.iter_mut().find(|j| j.id == id).map(|job| {
+            job.status = match &result {
+                _ if cancelled => JobStatus::Cancelled,
+                Ok(()) => JobStatus::Completed,
+                Err(_) => JobStatus::Failed,
+            };
+            job.error = result.err();
+            job.clone()
+        });
+
+        // Trim the oldest finished jobs beyond the history limit
+        let finished = inner
+            .jobs
+            .iter()
+            .filter(|j| !matches!(j.status, JobStatus::Queued | JobStatus::Running))
+            .count();
+        let mut excess = finished.saturating_sub(FINISHED_HISTORY);
+        inner.jobs.retain(|j| {
+            if excess > 0 && !matches!(j.status, JobStatus::Queued | JobStatus::Running) {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+        info
+    }
+
+    /// Index into `pending` of the highest-priority job whose kind has a free
+    /// slot and isn't held.
+    fn next_runnable(inner: &Inner) -> Option<usize> {
+        inner
+            .pending
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, job)| {
+                let info = inner.jobs.iter().find(|j| j.id == job.id)?;
+                if inner.held.contains(&info.kind) {
+                    return None;
+                }
+                let resource = info.kind.resource();
+                let kind_running = inner.running.get(&info.kind).copied().unwrap_or(0);
+                let resource_running = inner
+                    .running_by_resource
+                    .get(&resource)
+                    .copied()
+                    .unwrap_or(0);
+                let resource_limit = inner
+                    .resource_limits
+                    .get(&resource)
+                    .copied()
+                    .unwrap_or(usize::MAX);
+                (kind_running < info.kind.default_limit() && resource_running < resource_limit)
+                    .then_some((idx, info.priority, job.id))
+            })
+            // Highest priority first, then oldest (lowest id)
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.2.cmp(&a.2)))
+            .map(|(idx, _, _)| idx)
+    }
+}
+
+/// Run a job body, turning a panic into an error so the job still finishes
+/// and frees its slot
+pub fn run_caught<F: FnOnce() -> Result<(), String>>(run: F) -> Result<(), String> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(run)).unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Err(format!("Job panicked: {}", message))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panicking_job_frees_its_slot() {
+        let queue = JobQueue::new();
+        let first = queue.enqueue(JobKind::Scan, JobPriority::Normal, "first", |_| Ok(()));
+        let second = queue.enqueue(JobKind::Scan, JobPriority::Normal, "second", |_| Ok(()));
+
+        let mut inner = queue.inner.lock().unwrap();
+        let (job, info, _) = JobQueue::claim_next(&mut inner).unwrap();
+        assert_eq!(job.id, first);
+        // One scan at a time: the second waits for the first
+        assert!(JobQueue::claim_next(&mut inner).is_none());
+        drop(inner);
+
+        let result = run_caught(|| -> Result<(), String> { panic!("bad image") });
+        assert_eq!(result, Err("Job panicked: bad image".to_string()));
+        let info = queue.settle(info.id, info.kind, false, result).unwrap();
+        assert_eq!(info.status, JobStatus::Failed);
+
+        let mut inner = queue.inner.lock().unwrap();
+        let (job, _, _) = JobQueue::claim_next(&mut inner).unwrap();
+        assert_eq!(job.id, second);
+    }
+}
@@ -1,8 +1,24 @@
+pub mod archive;
+pub mod collation;
 pub mod commands;
+pub mod companion;
 pub mod db;
+pub mod fetch_queue;
 pub mod fetcher;
+pub mod importer;
+pub mod matcher;
+pub mod jobs;
+pub mod manifest;
+pub mod metadata;
 pub mod models;
+pub mod power;
+pub mod profile;
+pub mod query;
 pub mod scanner;
+pub mod search;
+pub mod secret;
+pub mod sidecar;
 pub mod state;
 pub mod thumbnail;
+pub mod translation;
 pub mod watcher;
@@ -6,8 +6,10 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use tauri::Manager;
 
+use eh_master_lib::collation;
 use eh_master_lib::commands;
 use eh_master_lib::db::Database;
+use eh_master_lib::profile;
 use eh_master_lib::state::AppState;
 
 fn main() {
@@ -15,6 +17,21 @@ fn main() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_window_state::Builder::new().build())
+        // Pages of archive and folder galleries, converted to sRGB when they
+        // carry a wide-gamut profile. Served off the main thread, as a page
+        // may have to be decoded and re-encoded.
+        .register_asynchronous_uri_scheme_protocol("archive", |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            std::thread::spawn(move || {
+                responder.respond(commands::archive_page_response(&app, request.uri()))
+            });
+        })
+        .register_asynchronous_uri_scheme_protocol("page", |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            std::thread::spawn(move || {
+                responder.respond(commands::folder_page_response(&app, request.uri()))
+            });
+        })
         .setup(|app| {
             // Get app data directory for DB and cache
             let data_dir = app
@@ -23,8 +40,11 @@ fn main() {
                 .expect("Failed to get app data directory");
             fs::create_dir_all(&data_dir).expect("Failed to create data directory");
 
-            let db_path = data_dir.join("manga_viewer.db");
-            let cache_dir = data_dir.join("thumbs");
+            // Everything below belongs to the library profile opened last
+            let profile_name = profile::active(&data_dir);
+            let profile_dir = profile::profile_dir(&data_dir, &profile_name);
+            let db_path = profile_dir.join(profile::DB_NAME);
+            let cache_dir = profile_dir.join(profile::THUMBS_DIR);
             fs::create_dir_all(&cache_dir).expect("Failed to create cache directory");
 
             // Initialize database
@@ -32,20 +52,23 @@ fn main() {
                 Database::new(&db_path).expect("Failed to initialize database");
 
             // Load settings
-            let settings = commands::load_settings(&app.handle());
+            let settings = commands::load_settings(&profile_dir);
 
             // Create app state
-            let state = AppState::new(db, cache_dir.clone());
+            let state = AppState::new(db, profile_name, profile_dir);
             {
                 let mut s = state.settings.lock().unwrap_or_else(|e| e.into_inner());
                 *s = settings;
             }
+            commands::apply_archive_passwords(&state);
+            collation::set_locale(&state.settings.lock().unwrap_or_else(|e| e.into_inner()).sort_locale);
 
             // Start file watchers for configured root paths
             {
                 let settings = state.settings.lock().unwrap_or_else(|e| e.into_inner());
                 let paths = settings.root_paths.clone();
                 let thumb_width = settings.thumbnail_width;
+                let cover_crop = settings.cover_crop;
                 drop(settings);
 
                 for path in &paths {
@@ -57,6 +80,8 @@ fn main() {
                             db,
                             cache_dir.clone(),
                             thumb_width,
+                            cover_crop,
+                            Arc::clone(&state.gallery_locks),
                             app.handle().clone(),
                         );
                         state.watchers.lock().unwrap_or_else(|e| e.into_inner()).insert(path.clone(), handle);
@@ -66,6 +91,26 @@ fn main() {
 
             app.manage(state);
 
+            // Jobs look up AppState when they run, so start only once it's managed
+            let state = app.state::<AppState>();
+            commands::apply_job_limits(&state);
+            // Hold heavy jobs before any start if on battery or a metered link
+            commands::refresh_power_state(app.handle());
+            eh_master_lib::watcher::start_power_monitor(app.handle().clone());
+            state.jobs.start(app.handle().clone());
+            commands::resume_persisted_jobs(&state);
+
+            // Reconcile roots after sleep or a drive being reconnected
+            eh_master_lib::watcher::start_resume_monitor(app.handle().clone());
+
+            if let Err(e) = commands::apply_companion(&state, app.handle()) {
+                log::warn!("Browser extension endpoint not started: {}", e);
+            }
+
+            // Periodic new-upload checks for followed artists
+            eh_master_lib::watcher::start_follow_scheduler(app.handle().clone());
+            eh_master_lib::watcher::start_scan_scheduler(app.handle().clone());
+
             // Open DevTools in debug builds
             #[cfg(debug_assertions)]
             {
@@ -84,27 +129,179 @@ fn main() {
             commands::get_folder_children,
             commands::get_gallery,
             commands::get_gallery_pages,
+            commands::get_page_order,
+            commands::set_page_order,
+            commands::fix_page_padding,
+            commands::get_page_extras,
+            commands::set_page_extras,
+            commands::set_reading_progress,
+            commands::get_reading_progress,
+            commands::mark_read,
+            commands::get_history,
+            commands::get_continue_reading,
             commands::open_file,
+            commands::open_gallery_url,
             commands::search_galleries,
+            commands::get_random_gallery,
+            commands::parse_search_query,
+            commands::save_search,
+            commands::list_saved_searches,
+            commands::delete_saved_search,
+            commands::push_session_filter,
+            commands::pop_session_filter,
+            commands::clear_session_filters,
+            commands::get_session_filters,
             commands::start_scan,
+            commands::get_app_status,
             commands::get_scan_status,
+            commands::list_jobs,
+            commands::get_jobs,
+            commands::cancel_job,
+            commands::cancel_scan,
+            commands::get_last_scan_report,
             commands::get_asset_url,
             commands::get_duplicate_galleries,
+            commands::migrate_gallery,
             commands::delete_gallery,
+            commands::archive_gallery,
+            commands::toggle_favorite,
+            commands::set_my_rating,
+            commands::set_label,
             commands::move_folders,
+            commands::split_gallery,
             commands::delete_gallery_folder,
+            commands::purge_missing_galleries,
             commands::clear_cache,
+            commands::run_db_maintenance,
+            commands::run_readonly_query,
             commands::read_thumb,
             commands::refresh_gallery,
+            commands::set_field_lock,
             commands::set_cookie_file,
             commands::get_cookie_status,
             commands::batch_refresh_galleries,
+            commands::pause_batch_refresh,
+            commands::resume_batch_refresh,
+            commands::get_fetch_queue_status,
+            commands::clear_fetch_failures,
+            commands::identify_all_galleries,
+            commands::get_identify_candidates,
+            commands::confirm_identify_candidate,
+            commands::dismiss_identify_candidate,
+            commands::get_growth_stats,
+            commands::get_language_stats,
+            commands::get_all_tags,
+            commands::get_quality_report,
+            commands::sync_favorite_slots,
+            commands::get_favorite_slots,
+            commands::set_gallery_favorite_slot,
+            commands::import_favorites,
+            commands::import_ehviewer_db,
+            commands::match_url_list,
+            commands::follow_artist,
+            commands::unfollow_artist,
+            commands::list_followed_artists,
+            commands::check_followed_artists,
+            commands::set_follow_check_hours,
+            commands::get_follow_check_hours,
+            commands::set_scan_schedules,
+            commands::get_scan_schedules,
+            commands::check_owned,
+            commands::list_incoming_duplicates,
+            commands::resolve_incoming_duplicate,
+            commands::upgrade_gallery,
+            commands::download_gallery,
+            commands::repair_gallery,
+            commands::export_search_sidecars,
+            commands::get_changes_since,
+            commands::list_profiles,
+            commands::switch_profile,
+            commands::import_data_dir,
+            commands::export_profile,
+            commands::create_manifest,
+            commands::verify_manifest,
+            commands::index_page_hashes,
+            commands::get_page_color_stats,
+            commands::find_gallery_containing_image,
+            commands::find_visually_similar,
+            commands::set_companion_enabled,
+            commands::get_companion_status,
+            commands::regenerate_companion_token,
+            commands::set_inbox_path,
+            commands::get_inbox_path,
+            commands::scan_inbox,
+            commands::list_inbox,
+            commands::group_inbox_images,
+            commands::add_wanted,
+            commands::list_wanted,
+            commands::set_wanted_status,
+            commands::remove_wanted,
+            commands::share_gallery,
+            commands::resolve_gallery_share,
+            commands::create_collection,
+            commands::list_collections,
+            commands::update_collection,
+            commands::delete_collection,
+            commands::get_collection_items,
+            commands::get_collection_galleries,
+            commands::reorder_collection,
+            commands::add_to_collection,
+            commands::remove_from_collection,
+            commands::export_collection,
+            commands::import_collection,
             commands::set_title_pref,
             commands::get_title_pref,
+            commands::set_sort_locale,
+            commands::get_sort_locale,
+            commands::set_preferred_domain,
+            commands::get_preferred_domain,
+            commands::set_write_back_info_txt,
+            commands::get_write_back_info_txt,
+            commands::set_custom_title,
+            commands::set_gallery_note,
+            commands::set_gallery_field,
+            commands::get_custom_field_keys,
+            commands::update_gallery_metadata,
+            commands::add_tags_to_galleries,
+            commands::remove_tags_from_galleries,
+            commands::apply_to_selection,
+            commands::set_tag_alias,
+            commands::remove_tag_alias,
+            commands::get_tag_aliases,
+            commands::migrate_tag,
+            commands::sync_info_txt,
             commands::set_grid_card_width,
             commands::get_grid_card_width,
+            commands::get_view_prefs,
+            commands::set_view_prefs,
+            commands::set_archive_password,
+            commands::set_default_archive_password,
+            commands::get_archive_passwords,
             commands::set_gallery_card_width,
             commands::get_gallery_card_width,
+            commands::set_cover_crop,
+            commands::get_cover_crop,
+            commands::set_reader_auto_contrast,
+            commands::get_reader_auto_contrast,
+            commands::set_blocklist,
+            commands::get_blocklist,
+            commands::set_default_filters,
+            commands::get_default_filters,
+            commands::set_tag_display,
+            commands::set_tag_translation_path,
+            commands::get_tag_translation_path,
+            commands::backfill_tag_translations,
+            commands::download_tag_translations,
+            commands::translate_tag,
+            commands::translate_tags,
+            commands::get_tag_display,
+            commands::set_job_limits,
+            commands::get_job_limits,
+            commands::set_refresh_pacing,
+            commands::get_refresh_pacing,
+            commands::set_power_policy,
+            commands::get_power_policy,
+            commands::get_power_state,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,111 @@
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::archive;
+use crate::models::ManifestReport;
+use crate::scanner;
+
+/// Name of the checksum file inside a folder gallery
+pub const MANIFEST_NAME: &str = "checksums.sha256";
+
+/// Where a gallery's manifest lives: inside a folder gallery, or beside an
+/// archive as `<archive>.sha256`. Either way it's in `sha256sum` format, so
+/// `sha256sum -c` can check a folder gallery without the app.
+pub fn manifest_path(gallery: &Path) -> PathBuf {
+    if archive::has_archive_extension(gallery) {
+        let mut name = gallery.as_os_str().to_owned();
+        name.push(".sha256");
+        PathBuf::from(name)
+    } else {
+        gallery.join(MANIFEST_NAME)
+    }
+}
+
+/// SHA-256 of every page and the info.txt, by name relative to the gallery
+/// (the entry name for archives)
+fn checksums(gallery: &Path) -> Result<BTreeMap<String, String>, String> {
+    let mut sums = BTreeMap::new();
+    if archive::is_archive(gallery) {
+        let mut archive = archive::open(gallery)?;
+        let names: Vec<String> = archive
+            .entries()
+            .iter()
+            .filter(|e| !e.name.starts_with("__MACOSX/"))
+            .filter(|e| {
+                let name = e.name.rsplit('/').next().unwrap_or_default();
+                scanner::is_image_file(Path::new(&e.name)) || name.eq_ignore_ascii_case("info.txt")
+            })
+            .map(|e| e.name.clone())
+            .collect();
+        for name in names {
+            let data = archive.read(&name)?;
+            sums.insert(name, hex::encode(Sha256::digest(&data)));
+        }
+        return Ok(sums);
+    }
+
+    let mut files = scanner::get_all_images(gallery);
+    let info = gallery.join("info.txt");
+    if info.is_file() {
+        files.push(info);
+    }
+    for path in files {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        sums.insert(name, file_sha256(&path)?);
+    }
+    Ok(sums)
+}
+
+pub fn file_sha256(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Hash the gallery and write its manifest, replacing any previous one.
+/// Returns the number of files listed.
+pub fn create(gallery: &Path) -> Result<usize, String> {
+    let sums = checksums(gallery)?;
+    let content: String = sums
+        .iter()
+        .map(|(name, sum)| format!("{}  {}\n", sum, name))
+        .collect();
+    let path = manifest_path(gallery);
+    fs::write(&path, content).map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+    Ok(sums.len())
+}
+
+/// Re-hash the gallery and compare it against its manifest
+pub fn verify(gallery: &Path) -> Result<ManifestReport, String> {
+    let path = manifest_path(gallery);
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("No readable manifest at {}: {}", path.display(), e))?;
+    // `sha256sum` writes `<hash>  <name>`, or `<hash> *<name>` in binary mode
+    let listed: BTreeMap<&str, &str> = content
+        .lines()
+        .filter_map(|line| {
+            let (sum, name) = line.split_once(' ')?;
+            let name = name.strip_prefix([' ', '*']).unwrap_or(name);
+            Some((name, sum))
+        })
+        .collect();
+
+    let mut actual = checksums(gallery)?;
+    let mut report = ManifestReport::default();
+    for (name, sum) in listed {
+        match actual.remove(name) {
+            Some(found) if found.eq_ignore_ascii_case(sum) => report.verified += 1,
+            Some(_) => report.corrupted.push(name.to_string()),
+            None => report.missing.push(name.to_string()),
+        }
+    }
+    report.unlisted = actual.into_keys().collect();
+    Ok(report)
+}
@@ -0,0 +1,185 @@
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashSet;
+
+use crate::fetcher;
+
+/// Minimum title similarity for an export entry to claim a local folder
+pub const MATCH_THRESHOLD: f64 = 0.8;
+
+/// A gallery listed in an exported download list
+#[derive(Debug, Clone, Default)]
+pub struct ListEntry {
+    pub url: String,
+    pub title: String,
+    pub title_jp: String,
+    pub page_count: Option<i64>,
+}
+
+/// A local gallery still missing its URL
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub id: i64,
+    pub titles: Vec<String>,
+    pub page_count: i64,
+}
+
+/// Parse an exported gallery list. JSON exports may be an array of URL
+/// strings or of objects with `url`/`title`/`pages`-style keys (optionally
+/// wrapped in an object); anything else is read as text with one gallery per
+/// line, the rest of the line being its title.
+pub fn parse_url_list(content: &str) -> Vec<ListEntry> {
+    let content = content.trim_start_matches('\u{feff}');
+    let entries = match serde_json::from_str::<Value>(content) {
+        Ok(value) => parse_json_list(&value),
+        Err(_) => parse_text_list(content),
+    };
+    entries.into_iter().filter(|e| !e.url.is_empty()).collect()
+}
+
+fn parse_json_list(value: &Value) -> Vec<ListEntry> {
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Object(map) => match map.values().find_map(|v| v.as_array()) {
+            Some(items) => items,
+            None => return vec![json_entry(value)],
+        },
+        _ => return Vec::new(),
+    };
+    items.iter().map(json_entry).collect()
+}
+
+fn json_entry(item: &Value) -> ListEntry {
+    if let Some(url) = item.as_str() {
+        return ListEntry {
+            url: fetcher::canonical_gallery_url(url.trim()),
+            ..Default::default()
+        };
+    }
+    let text = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| item.get(*k).and_then(|v| v.as_str()))
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    };
+    let page_count = ["pages", "page_count", "filecount", "length"]
+        .iter()
+        .find_map(|k| {
+            let v = item.get(*k)?;
+            v.as_i64()
+                .or_else(|| v.as_str().and_then(|s| s.split_whitespace().next()?.parse().ok()))
+        });
+    ListEntry {
+        url: fetcher::canonical_gallery_url(&text(&["url", "link", "href"])),
+        title: text(&["title", "name", "title_en"]),
+        title_jp: text(&["title_jpn", "title_jp"]),
+        page_count,
+    }
+}
+
+fn parse_text_list(content: &str) -> Vec<ListEntry> {
+    let url_re = Regex::new(r"https?://(?:exhentai|e-hentai)\.org/g/\d+/[0-9a-f]+/?").unwrap();
+    content
+        .lines()
+        .filter_map(|line| {
+            let m = url_re.find(line)?;
+            let title = format!("{} {}", &line[..m.start()], &line[m.end()..]);
+            let title = title.trim_matches(|c: char| c.is_whitespace() || "-|,;:".contains(c));
+            Some(ListEntry {
+                url: fetcher::canonical_gallery_url(m.as_str()),
+                title: title.to_string(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Lowercased letters and digits only, so punctuation and spacing
+/// differences between downloaders don't count against a match
+fn fold(title: &str) -> Vec<char> {
+    title
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+fn bigrams(title: &str) -> HashSet<(char, char)> {
+    let chars = fold(title);
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// Pair export entries with local candidates one-to-one, best scores first.
+/// A known page count that disagrees rules a pair out. Returns
+/// `(entry index, candidate id, score)` for every pair above the threshold.
+pub fn match_entries(entries: &[ListEntry], candidates: &[Candidate]) -> Vec<(usize, i64, f64)> {
+    let candidate_grams: Vec<Vec<HashSet<(char, char)>>> = candidates
+        .iter()
+        .map(|c| c.titles.iter().map(|t| bigrams(t)).collect())
+        .collect();
+
+    let mut pairs: Vec<(usize, usize, f64)> = Vec::new();
+    for (ei, entry) in entries.iter().enumerate() {
+        let entry_grams: Vec<HashSet<(char, char)>> = [&entry.title, &entry.title_jp]
+            .iter()
+            .filter(|t| !t.is_empty())
+            .map(|t| bigrams(t))
+            .collect();
+        if entry_grams.is_empty() {
+            continue;
+        }
+        for (ci, candidate) in candidates.iter().enumerate() {
+            if let Some(pages) = entry.page_count {
+                if candidate.page_count > 0 && pages != candidate.page_count {
+                    continue;
+                }
+            }
+            let score = entry_grams
+                .iter()
+                .flat_map(|a| candidate_grams[ci].iter().map(move |b| dice(a, b)))
+                .fold(0.0, f64::max);
+            if score >= MATCH_THRESHOLD {
+                pairs.push((ei, ci, score));
+            }
+        }
+    }
+
+    pairs.sort_by(|a, b| b.2.total_cmp(&a.2));
+    let mut used_entries = HashSet::new();
+    let mut used_candidates = HashSet::new();
+    pairs
+        .into_iter()
+        .filter(|(ei, ci, _)| {
+            if used_entries.contains(ei) || used_candidates.contains(ci) {
+                return false;
+            }
+            used_entries.insert(*ei);
+            used_candidates.insert(*ci);
+            true
+        })
+        .map(|(ei, ci, score)| (ei, candidates[ci].id, score))
+        .collect()
+}
+
+/// Best similarity between any of `a` and any of `b` (0.0 to 1.0), for
+/// checking a single search hit against a local gallery
+pub fn title_similarity(a: &[&str], b: &[&str]) -> f64 {
+    let grams = |titles: &[&str]| -> Vec<HashSet<(char, char)>> {
+        titles.iter().filter(|t| !t.is_empty()).map(|t| bigrams(t)).collect()
+    };
+    let b = grams(b);
+    grams(a)
+        .iter()
+        .flat_map(|x| b.iter().map(move |y| dice(x, y)))
+        .fold(0.0, f64::max)
+}
+
+/// Dice coefficient over character bigrams. Works for both spaced and
+/// unspaced (CJK) titles; 1.0 means identical after folding.
+fn dice(a: &HashSet<(char, char)>, b: &HashSet<(char, char)>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(b).count() as f64 / (a.len() + b.len()) as f64
+}
@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+
+use crate::models::{FieldConflict, ParsedGallery};
+
+/// Metadata fields that can be edited locally and are protected on refresh
+pub const METADATA_FIELDS: &[&str] = &[
+    "title_en",
+    "title_jp",
+    "category",
+    "uploader",
+    "posted",
+    "language",
+    "file_size",
+    "page_count",
+    "rating",
+    "favorited",
+    "tags",
+    "uploader_comment",
+];
+
+/// Render a field of `g` as a string for comparison and conflict reporting.
+pub fn field_value(g: &ParsedGallery, field: &str) -> String {
+    match field {
+        "title_en" => g.title_en.clone(),
+        "title_jp" => g.title_jp.clone(),
+        "category" => g.category.clone(),
+        "uploader" => g.uploader.clone(),
+        "posted" => g.posted.clone(),
+        "language" => g.language.clone(),
+        "file_size" => g.file_size.clone(),
+        "page_count" => g.page_count.to_string(),
+        "rating" => format!("{:.2}", g.rating),
+        "favorited" => g.favorited.to_string(),
+        "uploader_comment" => g.uploader_comment.clone(),
+        "tags" => {
+            let mut tags: Vec<String> = g
+                .tags
+                .iter()
+                .map(|(ns, tag)| format!("{}:{}", ns, tag))
+                .collect();
+            tags.sort();
+            tags.join(", ")
+        }
+        _ => String::new(),
+    }
+}
+
+/// Copy `field` from `src` into `dst`.
+pub fn copy_field(dst: &mut ParsedGallery, src: &ParsedGallery, field: &str) {
+    match field {
+        "title_en" => dst.title_en = src.title_en.clone(),
+        "title_jp" => dst.title_jp = src.title_jp.clone(),
+        "category" => dst.category = src.category.clone(),
+        "uploader" => dst.uploader = src.uploader.clone(),
+        "posted" => dst.posted = src.posted.clone(),
+        "language" => dst.language = src.language.clone(),
+        "file_size" => dst.file_size = src.file_size.clone(),
+        "page_count" => dst.page_count = src.page_count,
+        "rating" => dst.rating = src.rating,
+        "favorited" => dst.favorited = src.favorited,
+        "tags" => dst.tags = src.tags.clone(),
+        "uploader_comment" => dst.uploader_comment = src.uploader_comment.clone(),
+        _ => {}
+    }
+}
+
+/// Three-way merge of freshly fetched metadata into the local copy.
+///
+/// A field counts as locally edited when it is in `dirty` or differs from
+/// `baseline` (the metadata as last fetched). Edited fields keep the local
+/// value and are reported as conflicts when the remote value differs;
+/// everything else takes the remote value. The URL always comes from remote.
+pub fn merge_remote(
+    local: &ParsedGallery,
+    baseline: Option<&ParsedGallery>,
+    remote: &ParsedGallery,
+    dirty: &HashSet<String>,
+) -> (ParsedGallery, Vec<FieldConflict>) {
+    let mut merged = remote.clone();
+    let mut conflicts = Vec::new();
+
+    for &field in METADATA_FIELDS {
+        let local_value = field_value(local, field);
+        let edited = dirty.contains(field)
+            || baseline.is_some_and(|b| field_value(b, field) != local_value);
+        if !edited {
+            continue;
+        }
+
+        copy_field(&mut merged, local, field);
+        let remote_value = field_value(remote, field);
+        if remote_value != local_value {
+            conflicts.push(FieldConflict {
+                field: field.to_string(),
+                local: local_value,
+                remote: remote_value,
+            });
+        }
+    }
+
+    (merged, conflicts)
+}
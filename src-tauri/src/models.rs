@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Gallery {
@@ -18,6 +19,99 @@ pub struct Gallery {
     pub thumb_path: String,
     pub folder_name: String,
     pub parent_path: String,
+    #[serde(default)]
+    pub custom_title: String,
+    #[serde(default)]
+    pub uploader_comment: String,
+    #[serde(default)]
+    pub is_resampled: bool,
+    /// EH favorite slot (0-9) the gallery is filed under, if any
+    #[serde(default)]
+    pub favorite_slot: Option<i64>,
+    /// `language` was guessed from title markers rather than metadata
+    #[serde(default)]
+    pub language_inferred: bool,
+    /// Folder was not found on the last scan; hidden until it reappears
+    #[serde(default)]
+    pub missing: bool,
+    /// Last time the gallery was opened (UTC), empty if never
+    #[serde(default)]
+    pub last_read_at: String,
+    /// See `GallerySummary::archive_label`
+    #[serde(default)]
+    pub archive_label: String,
+    /// On the user's own favorites shelf; unrelated to `favorited`, the EH
+    /// favorite count
+    #[serde(default)]
+    pub is_favorite: bool,
+    /// The user's own 0-5 rating; `rating` is EH's
+    #[serde(default)]
+    pub my_rating: Option<f64>,
+    /// One of `COLOR_LABELS`, or empty
+    #[serde(default)]
+    pub color_label: String,
+    /// Newest EH version of this gallery, as of the last refresh; empty when
+    /// it's current
+    #[serde(default)]
+    pub newer_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootPathStatus {
+    pub path: String,
+    /// Volume is mounted and the folder is readable
+    pub online: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrowthPoint {
+    /// `YYYY-MM`
+    pub month: String,
+    pub added: i64,
+    pub total: i64,
+}
+
+/// Pages found on disk for a gallery and how wide they are
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageStats {
+    pub pages: i64,
+    pub max_width: Option<u32>,
+}
+
+/// A gallery worth re-downloading, with what's wrong with it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityItem {
+    pub gallery: GallerySummary,
+    /// Images on disk, against `gallery.page_count` from info.txt
+    pub local_pages: i64,
+    pub max_width: Option<u32>,
+    pub is_resampled: bool,
+    pub my_rating: Option<f64>,
+    /// Reading sessions
+    pub views: i64,
+    /// "low_res", "resampled" and/or "missing_pages"
+    pub issues: Vec<String>,
+}
+
+/// Galleries carrying `my_rating` rounded to `stars`; `None` is unrated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatingBucket {
+    pub stars: Option<i64>,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityReport {
+    pub rating_distribution: Vec<RatingBucket>,
+    /// Highest own rating first, then most read
+    pub items: Vec<QualityItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageStat {
+    pub language: String,
+    pub count: i64,
+    pub inferred: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +126,22 @@ pub struct GallerySummary {
     pub folder_name: String,
     pub path: String,
     #[serde(default)]
+    pub custom_title: String,
+    /// When the gallery was first indexed (UTC `YYYY-MM-DD HH:MM:SS`); never
+    /// changes on rescan
+    #[serde(default)]
+    pub added_at: String,
+    /// Label of the offline drive the gallery was archived to; empty when
+    /// its files are kept locally
+    #[serde(default)]
+    pub archive_label: String,
+    /// Reading progress, `None` if never opened
+    #[serde(default)]
+    pub read_percent: Option<u32>,
+    /// One of `COLOR_LABELS`, or empty
+    #[serde(default)]
+    pub color_label: String,
+    #[serde(default)]
     pub date_modified: f64,
     #[serde(default)]
     pub date_created: f64,
@@ -41,12 +151,46 @@ pub struct GallerySummary {
 pub struct GalleryDetail {
     pub gallery: Gallery,
     pub tags: Vec<TagEntry>,
+    #[serde(default)]
+    pub locked_fields: Vec<String>,
+    /// The user's free-form notes
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub custom_fields: Vec<CustomField>,
+}
+
+/// User-defined field on a gallery, e.g. `tl_status: in progress`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomField {
+    pub key: String,
+    pub value: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TagEntry {
     pub namespace: String,
     pub tag: String,
+    /// Display name from the installed tag translations; empty without one
+    #[serde(default)]
+    pub translation: String,
+}
+
+/// `namespace:tag` is shown and searched as `target_namespace:target_tag`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagAlias {
+    pub namespace: String,
+    pub tag: String,
+    pub target_namespace: String,
+    pub target_tag: String,
+}
+
+/// A tag with the number of galleries carrying it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagCount {
+    pub namespace: String,
+    pub tag: String,
+    pub count: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,8 +209,20 @@ pub struct FolderChildren {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PageInfo {
     pub filename: String,
+    /// The image file, or the archive it's read from
     pub path: String,
     pub index: usize,
+    /// Entry name within the archive at `path`, for archive galleries
+    #[serde(default)]
+    pub archive_entry: Option<String>,
+    /// Recorded by `index_page_hashes`; `None` until the gallery's pages are
+    /// hashed, or when the page couldn't be decoded
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub grayscale: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,18 +235,97 @@ pub struct SearchQuery {
     pub sort_order: Option<String>,
     pub offset: i64,
     pub limit: i64,
+    /// Include galleries hidden by the uploader/artist blocklist
+    #[serde(default)]
+    pub include_blocked: bool,
+    /// Only resampled (`true`) or only original (`false`) copies
+    #[serde(default)]
+    pub resampled: Option<bool>,
+    #[serde(default)]
+    pub favorite_slot: Option<i64>,
+    /// Only personal favorites (`true`) or only the rest (`false`)
+    #[serde(default)]
+    pub favorite: Option<bool>,
+    /// Only galleries with this color label, or `""` for unlabeled ones
+    #[serde(default)]
+    pub color_label: Option<String>,
+    /// Skip the persistent default filters from settings
+    #[serde(default)]
+    pub ignore_defaults: bool,
+    /// Skip the session filter stack
+    #[serde(default)]
+    pub ignore_session: bool,
+    /// Galleries with any of these tags are left out
+    #[serde(default)]
+    pub excluded_tags: Vec<TagFilter>,
+    /// Also count categories, languages and top tags over all matches
+    #[serde(default)]
+    pub include_facets: bool,
+    /// `snapshot` of the first page's result. Galleries added since are
+    /// left out, so later pages don't shift under offset pagination.
+    #[serde(default)]
+    pub snapshot: Option<i64>,
+    /// Each group needs at least one of its tags; groups are ANDed with
+    /// each other and with `tags`
+    #[serde(default)]
+    pub tag_groups: Vec<Vec<TagFilter>>,
+    /// Case-insensitive exact uploader name
+    #[serde(default)]
+    pub uploader: Option<String>,
+    #[serde(default)]
+    pub min_rating: Option<f64>,
+    #[serde(default)]
+    pub min_pages: Option<i64>,
+    #[serde(default)]
+    pub max_pages: Option<i64>,
+    /// Posted on or after this Unix time
+    #[serde(default)]
+    pub posted_from: Option<i64>,
+    /// Posted on or before this Unix time
+    #[serde(default)]
+    pub posted_to: Option<i64>,
+    /// Galleries whose titles contain any of these words or quoted phrases
+    /// are left out
+    #[serde(default)]
+    pub excluded_words: Vec<String>,
+    /// `unread`, `reading` or `finished`
+    #[serde(default)]
+    pub read_state: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TagFilter {
     pub namespace: String,
     pub tag: String,
+    /// Match every tag starting with `tag`, as the site does without `$`
+    #[serde(default)]
+    pub prefix: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
     pub galleries: Vec<GallerySummary>,
     pub total_count: i64,
+    /// Pass back as `SearchQuery::snapshot` to page through this result set
+    pub snapshot: i64,
+    /// Counts over the whole result set, when `SearchQuery::include_facets`
+    /// is set
+    pub facets: Option<SearchFacets>,
+}
+
+/// Refinement counts for a search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchFacets {
+    pub categories: Vec<FacetCount>,
+    pub languages: Vec<FacetCount>,
+    /// Most common tags, most used first
+    pub tags: Vec<TagCount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -101,6 +336,48 @@ pub struct ScanStatus {
     pub current_folder: String,
 }
 
+/// A gallery path paired with the reason a scan step failed for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanFailure {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Persisted outcome of the most recent scan, written to app-data as JSON
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub root_path: String,
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub total_scanned: i64,
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    /// Galleries no longer on disk, now flagged missing
+    pub removed: Vec<String>,
+    /// Previously missing galleries found again
+    #[serde(default)]
+    pub restored: Vec<String>,
+    pub parse_failures: Vec<ScanFailure>,
+    pub thumbnail_failures: Vec<ScanFailure>,
+    #[serde(default)]
+    pub cancelled: bool,
+    /// New folders held back because their gid is already in the library
+    #[serde(default)]
+    pub held_duplicates: Vec<String>,
+}
+
+/// A new gallery folder whose gid is already in the library, held back from
+/// indexing until resolved with `resolve_incoming_duplicate`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncomingDuplicate {
+    pub path: String,
+    pub gid: i64,
+    pub title: String,
+    /// Library galleries with the same gid
+    pub existing: Vec<GallerySummary>,
+    pub detected_at: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
     pub root_paths: Vec<String>,
@@ -114,12 +391,319 @@ pub struct AppSettings {
     pub grid_card_width: u32,
     #[serde(default = "default_gallery_card_width")]
     pub gallery_card_width: u32,
+    #[serde(default)]
+    pub cover_crop: bool,
+    #[serde(default = "default_max_thumbnail_workers")]
+    pub max_thumbnail_workers: u32,
+    #[serde(default = "default_max_network_jobs")]
+    pub max_network_jobs: u32,
+    #[serde(default = "default_max_disk_jobs")]
+    pub max_disk_jobs: u32,
+    #[serde(default)]
+    pub blocklist: Blocklist,
+    #[serde(default)]
+    pub default_filters: DefaultFilters,
+    /// "exhentai" or "e-hentai"; the site gallery links are opened on
+    #[serde(default = "default_preferred_domain")]
+    pub preferred_domain: String,
+    /// Persist metadata edits into info.txt instead of keeping them DB-only
+    #[serde(default)]
+    pub write_back_info_txt: bool,
+    #[serde(default = "default_favorite_slots")]
+    pub favorite_slots: Vec<FavoriteSlot>,
+    /// Hours between automatic followed-artist checks; 0 disables them
+    #[serde(default = "default_follow_check_hours")]
+    pub follow_check_hours: u32,
+    /// Serve the browser extension endpoint on localhost
+    #[serde(default)]
+    pub companion_enabled: bool,
+    #[serde(default = "default_companion_port")]
+    pub companion_port: u16,
+    /// Bearer token the extension must send; generated on first enable
+    #[serde(default)]
+    pub companion_token: String,
+    /// Origin of the companion extension ("chrome-extension://<id>"); empty
+    /// accepts any browser extension
+    #[serde(default)]
+    pub companion_origin: String,
+    /// Folder whose loose images are indexed as single-page entries; empty
+    /// when the inbox is off
+    #[serde(default)]
+    pub inbox_path: String,
+    #[serde(default)]
+    pub tag_display: TagDisplay,
+    /// EhTagTranslation database used for tag display names; empty when none
+    /// is installed
+    #[serde(default)]
+    pub tag_translation_path: String,
+    /// Roots rescanned automatically, each on its own interval
+    #[serde(default)]
+    pub scan_schedules: Vec<ScanSchedule>,
+    /// Hold scheduled scans while the machine runs on battery
+    #[serde(default = "default_scan_skip_on_battery")]
+    pub scan_skip_on_battery: bool,
+    #[serde(default)]
+    pub power_policy: PowerPolicy,
+    /// Saved view preferences by context ("folder", "search", "artist"...);
+    /// contexts without an entry use `ViewPrefs::default_for`
+    #[serde(default)]
+    pub view_prefs: BTreeMap<String, ViewPrefs>,
+    /// Passwords for encrypted archive galleries by archive path, sealed
+    /// with `secret::seal`
+    #[serde(default)]
+    pub archive_passwords: BTreeMap<String, String>,
+    /// Sealed password tried for encrypted archives without their own;
+    /// empty when none
+    #[serde(default)]
+    pub default_archive_password: String,
+    /// Have the reader raise contrast on grayscale pages, for faded scans
+    #[serde(default)]
+    pub reader_auto_contrast: bool,
+    /// Locale names and titles are sorted for; empty sorts them plain
+    /// case-insensitively
+    #[serde(default)]
+    pub sort_locale: String,
+    #[serde(default = "default_refresh_concurrency")]
+    pub refresh_concurrency: u32,
+    #[serde(default = "default_refresh_min_delay_ms")]
+    pub refresh_min_delay_ms: u64,
+    #[serde(default = "default_refresh_max_delay_ms")]
+    pub refresh_max_delay_ms: u64,
+}
+
+/// How one kind of listing is shown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewPrefs {
+    pub sort_by: String,
+    /// "asc" or "desc"
+    pub sort_order: String,
+    /// Grid card width for this context; `None` uses `grid_card_width`
+    #[serde(default)]
+    pub card_width: Option<u32>,
+}
+
+impl ViewPrefs {
+    /// Built-in preferences: folders by name, searches and artist pages
+    /// newest posted first, anything else most recently added first
+    pub fn default_for(context: &str) -> Self {
+        let (sort_by, sort_order) = match context {
+            "folder" => ("title", "asc"),
+            "search" | "artist" => ("posted", "desc"),
+            _ => ("added", "desc"),
+        };
+        Self {
+            sort_by: sort_by.to_string(),
+            sort_order: sort_order.to_string(),
+            card_width: None,
+        }
+    }
+}
+
+/// Automatic incremental scan of one root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanSchedule {
+    pub root: String,
+    /// Hours between scans; 0 pauses the schedule
+    pub every_hours: u32,
+}
+
+/// One of EH's 10 favorite categories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FavoriteSlot {
+    pub index: i64,
+    pub name: String,
+    pub color: String,
+}
+
+/// EH's fixed favorite slot colors, in slot order
+pub const FAVORITE_SLOT_COLORS: [&str; 10] = [
+    "#000000", "#f00000", "#f0a000", "#d0d000", "#008000", "#90f030", "#00d0d0", "#0000f0",
+    "#a000f0", "#f040f0",
+];
+
+pub fn default_favorite_slots() -> Vec<FavoriteSlot> {
+    FAVORITE_SLOT_COLORS
+        .iter()
+        .enumerate()
+        .map(|(i, color)| FavoriteSlot {
+            index: i as i64,
+            name: format!("Favorites {}", i),
+            color: color.to_string(),
+        })
+        .collect()
+}
+
+/// Filters applied to search and folder listing unless a query opts out
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DefaultFilters {
+    #[serde(default)]
+    pub excluded_categories: Vec<String>,
+    #[serde(default)]
+    pub min_rating: f64,
+    /// Allowed languages; empty allows all. Galleries without a language always pass.
+    #[serde(default)]
+    pub languages: Vec<String>,
+}
+
+impl DefaultFilters {
+    pub fn is_empty(&self) -> bool {
+        self.excluded_categories.is_empty() && self.min_rating <= 0.0 && self.languages.is_empty()
+    }
+}
+
+/// Which heavy jobs wait for mains power or an unmetered connection. Jobs
+/// already running carry on; held ones stay queued until conditions allow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerPolicy {
+    /// Hold thumbnail passes and conversions while on battery
+    #[serde(default)]
+    pub defer_on_battery: bool,
+    /// Hold metadata fetches and downloads on a metered connection
+    #[serde(default)]
+    pub defer_on_metered: bool,
+    /// Run everything regardless, keeping the two settings above for later
+    #[serde(default)]
+    pub ignore: bool,
+}
+
+impl Default for PowerPolicy {
+    fn default() -> Self {
+        Self {
+            defer_on_battery: true,
+            defer_on_metered: true,
+            ignore: false,
+        }
+    }
+}
+
+impl PowerPolicy {
+    /// Job kinds to hold under `power`
+    pub fn held_kinds(&self, power: PowerState) -> Vec<JobKind> {
+        let mut kinds = Vec::new();
+        if self.ignore {
+            return kinds;
+        }
+        if self.defer_on_battery && power.on_battery {
+            kinds.extend([JobKind::Thumbnail, JobKind::Conversion]);
+        }
+        if self.defer_on_metered && power.metered {
+            kinds.extend([JobKind::Refresh, JobKind::Download]);
+        }
+        kinds
+    }
+}
+
+/// Power source and connection cost, as of the last check; also the payload
+/// of `power-state` events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowerState {
+    pub on_battery: bool,
+    pub metered: bool,
+}
+
+/// Order in which a gallery's tag namespaces are listed: `first` in the
+/// given order, then any others alphabetically, then `last` in the given
+/// order. Namespaces in `hidden` aren't returned at all.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagDisplay {
+    #[serde(default)]
+    pub first: Vec<String>,
+    #[serde(default)]
+    pub last: Vec<String>,
+    #[serde(default)]
+    pub hidden: Vec<String>,
+}
+
+impl Default for TagDisplay {
+    fn default() -> Self {
+        let names = |list: &[&str]| list.iter().map(|n| n.to_string()).collect();
+        Self {
+            first: names(&["language", "parody", "character", "group", "artist", "cosplayer"]),
+            last: names(&["female", "male", "mixed", "other"]),
+            hidden: names(&["temp"]),
+        }
+    }
+}
+
+impl TagDisplay {
+    /// Drop hidden namespaces and order the rest, keeping tags within a
+    /// namespace in their existing order
+    pub fn apply(&self, tags: Vec<TagEntry>) -> Vec<TagEntry> {
+        let position = |list: &[String], ns: &str| list.iter().position(|n| n.eq_ignore_ascii_case(ns));
+        let mut tags: Vec<TagEntry> = tags
+            .into_iter()
+            .filter(|t| position(&self.hidden, &t.namespace).is_none())
+            .collect();
+        tags.sort_by_cached_key(|t| {
+            match (position(&self.first, &t.namespace), position(&self.last, &t.namespace)) {
+                (Some(i), _) => (0, i, String::new()),
+                (None, Some(i)) => (2, i, String::new()),
+                (None, None) => (1, 0, t.namespace.clone()),
+            }
+        });
+        tags
+    }
+}
+
+/// One layer of the session ("focus mode") filter stack. Layers are ANDed
+/// together and apply to search and browse until the stack is cleared; they
+/// are never saved to settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionFilter {
+    #[serde(default)]
+    pub tags: Vec<TagFilter>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub min_rating: f64,
+}
+
+impl SessionFilter {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && self.category.as_deref().unwrap_or_default().is_empty()
+            && self.language.as_deref().unwrap_or_default().is_empty()
+            && self.min_rating <= 0.0
+    }
+}
+
+/// Uploaders and artist tags hidden from search and browse by default
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Blocklist {
+    #[serde(default)]
+    pub uploaders: Vec<String>,
+    #[serde(default)]
+    pub artists: Vec<String>,
+}
+
+impl Blocklist {
+    pub fn is_empty(&self) -> bool {
+        self.uploaders.is_empty() && self.artists.is_empty()
+    }
 }
 
 fn default_title_pref() -> String {
     "en".to_string()
 }
 
+fn default_preferred_domain() -> String {
+    "exhentai".to_string()
+}
+
+fn default_companion_port() -> u16 {
+    17828
+}
+
+fn default_follow_check_hours() -> u32 {
+    24
+}
+
+fn default_scan_skip_on_battery() -> bool {
+    true
+}
+
 fn default_grid_card_width() -> u32 {
     200
 }
@@ -128,6 +712,30 @@ fn default_gallery_card_width() -> u32 {
     150
 }
 
+fn default_max_thumbnail_workers() -> u32 {
+    4
+}
+
+fn default_max_network_jobs() -> u32 {
+    2
+}
+
+fn default_max_disk_jobs() -> u32 {
+    1
+}
+
+fn default_refresh_concurrency() -> u32 {
+    1
+}
+
+fn default_refresh_min_delay_ms() -> u64 {
+    1500
+}
+
+fn default_refresh_max_delay_ms() -> u64 {
+    4000
+}
+
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
@@ -138,14 +746,428 @@ impl Default for AppSettings {
             title_pref: "en".to_string(),
             grid_card_width: 200,
             gallery_card_width: 150,
+            cover_crop: false,
+            max_thumbnail_workers: 4,
+            max_network_jobs: 2,
+            max_disk_jobs: 1,
+            blocklist: Blocklist::default(),
+            default_filters: DefaultFilters::default(),
+            preferred_domain: "exhentai".to_string(),
+            write_back_info_txt: false,
+            favorite_slots: default_favorite_slots(),
+            follow_check_hours: default_follow_check_hours(),
+            companion_enabled: false,
+            companion_port: default_companion_port(),
+            companion_token: String::new(),
+            companion_origin: String::new(),
+            inbox_path: String::new(),
+            tag_display: TagDisplay::default(),
+            tag_translation_path: String::new(),
+            scan_schedules: Vec::new(),
+            scan_skip_on_battery: default_scan_skip_on_battery(),
+            power_policy: PowerPolicy::default(),
+            view_prefs: BTreeMap::new(),
+            archive_passwords: BTreeMap::new(),
+            default_archive_password: String::new(),
+            reader_auto_contrast: false,
+            sort_locale: String::new(),
+            refresh_concurrency: default_refresh_concurrency(),
+            refresh_min_delay_ms: default_refresh_min_delay_ms(),
+            refresh_max_delay_ms: default_refresh_max_delay_ms(),
         }
     }
 }
 
+/// One copy within a duplicate group, annotated with the root it lives under
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateCopy {
+    #[serde(flatten)]
+    pub gallery: GallerySummary,
+    pub root: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DuplicateResult {
-    pub by_url: Vec<Vec<GallerySummary>>,
-    pub by_name: Vec<Vec<GallerySummary>>,
+    pub by_url: Vec<Vec<DuplicateCopy>>,
+    pub by_name: Vec<Vec<DuplicateCopy>>,
+}
+
+/// Outcome of importing another app's database
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// Galleries found in the source database
+    pub total: u64,
+    pub matched: u64,
+    /// Titles (or URLs) of source galleries with no local folder
+    pub unmatched: Vec<String>,
+    pub downloads: u64,
+    pub read: u64,
+    pub favorites: u64,
+}
+
+/// Color labels a gallery can carry, in display order
+pub const COLOR_LABELS: &[&str] = &["red", "orange", "yellow", "green", "blue", "purple", "gray"];
+
+/// Want-list states, in lifecycle order
+pub const WANTED_STATUSES: &[&str] = &["wanted", "downloading", "acquired"];
+
+/// Whether a pasted gallery URL or gid is already in the library
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnershipStatus {
+    /// The identifier as given
+    pub input: String,
+    /// `None` when the input is neither a gallery URL nor a gid
+    pub gid: Option<i64>,
+    pub owned: bool,
+    /// Every local copy; more than one means duplicates
+    pub galleries: Vec<GallerySummary>,
+}
+
+/// Hashes of one gallery page, as stored for image lookups, with what
+/// decoding it showed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageHash {
+    pub filename: String,
+    pub sha1: String,
+    /// `None` when the page couldn't be decoded, as are the fields below
+    pub phash: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Black and white page, e.g. a scanned manga page
+    pub grayscale: Option<bool>,
+}
+
+/// Page counts by colour and orientation over hashed galleries
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageColorStats {
+    pub color_pages: i64,
+    pub grayscale_pages: i64,
+    /// Pages wider than tall, mostly two-page spreads
+    pub landscape_pages: i64,
+    /// Pages that couldn't be decoded
+    pub unknown_pages: i64,
+    /// Galleries with at least one colour page
+    pub color_galleries: i64,
+}
+
+/// How far the user has read a gallery. Pages marked as extras don't count,
+/// so trailing credits don't hold a finished gallery below 100%.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadProgress {
+    pub gallery_id: i64,
+    /// Last page reached (zero-based); `None` if never opened
+    pub page: Option<usize>,
+    pub page_count: usize,
+    /// Pages that aren't marked as extras
+    pub content_pages: usize,
+    pub percent: u32,
+    /// When the page was recorded (UTC); empty if never opened
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+/// One reading session from the history log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub gallery: GallerySummary,
+    pub opened_at: String,
+    /// Furthest page reached past where the session started
+    pub pages_read: i64,
+}
+
+/// Zero-based, inclusive range of pages in reader order
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PageRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl PageRange {
+    pub fn contains(&self, index: usize) -> bool {
+        (self.start..=self.end).contains(&index)
+    }
+}
+
+/// A page file renamed by `fix_page_padding`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageRename {
+    pub from: String,
+    pub to: String,
+}
+
+/// A local page matching a reverse image lookup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageMatch {
+    pub gallery: GallerySummary,
+    /// Zero-based, in reader order
+    pub page_index: usize,
+    pub filename: String,
+}
+
+/// A gallery whose pages look like the query's
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarGallery {
+    pub gallery: GallerySummary,
+    /// Smallest perceptual hash distance (0-64) between any two pages
+    pub distance: u32,
+    /// Pages of this gallery within the threshold of a query page
+    pub matched_pages: usize,
+}
+
+/// A loose image in the inbox folder, listed as a single-page entry until
+/// it's grouped into a gallery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboxImage {
+    pub id: i64,
+    pub path: String,
+    pub filename: String,
+    pub file_size: i64,
+    pub thumb_path: String,
+    pub added_at: String,
+}
+
+/// A title-search hit for an unidentified gallery, held for the user to
+/// confirm because the titles or page counts don't clearly agree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentifyCandidate {
+    pub gallery_id: i64,
+    pub path: String,
+    pub folder_name: String,
+    pub local_page_count: i64,
+    pub url: String,
+    /// Title of the EH gallery
+    pub title: String,
+    pub page_count: i64,
+    /// Best title similarity, 0.0 to 1.0
+    pub score: f64,
+    pub found_at: String,
+}
+
+/// A gallery the user wants but doesn't have yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WantedEntry {
+    pub id: i64,
+    pub gid: i64,
+    pub url: String,
+    pub title: String,
+    /// One of `WANTED_STATUSES`
+    pub status: String,
+    /// What put it on the list, e.g. "manual"
+    pub source: String,
+    /// Local gallery that satisfied the entry, once acquired
+    pub gallery_id: Option<i64>,
+    pub added_at: String,
+    pub acquired_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowedArtist {
+    pub artist: String,
+    /// Newest gid seen on the last check; later uploads count as new
+    pub last_seen_gid: i64,
+    pub last_checked_at: String,
+    pub followed_at: String,
+}
+
+/// A new upload by a followed artist, part of the `follow-digest` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowHit {
+    pub artist: String,
+    pub url: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+    pub description: String,
+    pub created_at: String,
+    pub item_count: i64,
+}
+
+/// A named `SearchQuery` kept for reuse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: String,
+    pub query: SearchQuery,
+    pub created_at: String,
+}
+
+/// A collection entry, with the matching local gallery if the user has it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionItem {
+    pub gid: i64,
+    pub url: String,
+    pub title: String,
+    pub gallery: Option<GallerySummary>,
+}
+
+/// A "read this" reference to one gallery, as produced by `share_gallery`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GalleryShare {
+    pub format: String,
+    pub version: u32,
+    pub gid: i64,
+    pub token: String,
+    #[serde(default)]
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub share: GalleryShare,
+    /// `share` as compact JSON, the text to send or encode
+    pub descriptor: String,
+    /// PNG data URL of `descriptor` as a QR code, when requested
+    pub qr_png: Option<String>,
+}
+
+/// A received share, resolved against the local library
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareResolution {
+    pub share: GalleryShare,
+    pub url: String,
+    pub gallery: Option<GallerySummary>,
+}
+
+/// Shareable collection file written by `export_collection`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionFile {
+    pub format: String,
+    pub version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub items: Vec<CollectionFileItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionFileItem {
+    pub gid: i64,
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionImportReport {
+    pub collection_id: i64,
+    pub total: u64,
+    /// Entries found in the local library
+    pub resolved: u64,
+    /// Entries the user doesn't have locally
+    pub missing: Vec<CollectionFileItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UrlMatch {
+    pub gallery_id: i64,
+    pub folder_name: String,
+    pub url: String,
+    /// Title similarity, 0.0-1.0
+    pub score: f64,
+}
+
+/// Outcome of matching an exported URL list against local folders
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UrlListMatchReport {
+    /// Galleries found in the list
+    pub total: u64,
+    /// Entries whose URL is already on a local gallery
+    pub already_known: u64,
+    pub matched: Vec<UrlMatch>,
+    /// URLs with no confident local match
+    pub unmatched: Vec<String>,
+    /// Matches whose info.txt could not be written
+    pub failures: Vec<ScanFailure>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheUsage {
+    pub thumbnail_files: u64,
+    pub thumbnail_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatcherStatus {
+    pub path: String,
+    /// A watcher thread is alive for this root
+    pub watching: bool,
+    pub online: bool,
+}
+
+/// Aggregate of scan, job, watcher, fetcher, and cache state for the status bar
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppStatus {
+    pub scan: ScanStatus,
+    /// Queued and running jobs only
+    pub jobs: Vec<JobInfo>,
+    pub watcher_enabled: bool,
+    pub watchers: Vec<WatcherStatus>,
+    /// Seconds until bulk EH requests resume; 0 when not cooling down
+    pub fetch_cooldown_secs: u64,
+    pub cache: CacheUsage,
+}
+
+/// Where one gallery is in a bulk fetch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum FetchItemState {
+    Queued,
+    Fetching,
+    /// Failed with a transient error; tried again at `next_attempt_at` (unix
+    /// seconds)
+    Retrying {
+        attempt: u32,
+        next_attempt_at: u64,
+        reason: String,
+    },
+    Failed {
+        reason: String,
+    },
+    Done,
+}
+
+/// One gallery of a bulk refresh or identify job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchItem {
+    pub job_id: u64,
+    pub gallery_id: i64,
+    /// Filled in once the job reaches the gallery
+    pub title: String,
+    #[serde(flatten)]
+    pub state: FetchItemState,
+}
+
+/// Bulk fetches in progress, plus failures from finished ones
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchQueueStatus {
+    /// Items not yet done, in job then queue order; failed ones are kept
+    /// until cleared
+    pub items: Vec<FetchItem>,
+    /// Jobs held by `pause_batch_refresh` or after EH kept throttling them
+    pub paused_jobs: Vec<u64>,
+    /// Requests sent in the last `budget_window_secs`, out of `budget_limit`
+    pub budget_used: usize,
+    pub budget_limit: usize,
+    pub budget_window_secs: u64,
+    /// Seconds until bulk EH requests resume; 0 when not cooling down
+    pub cooldown_secs: u64,
+    /// Estimated seconds until every active fetch finishes; `None` before
+    /// any item has completed
+    pub eta_secs: Option<u64>,
+}
+
+/// Browser extension endpoint settings and whether it's serving
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompanionStatus {
+    pub enabled: bool,
+    pub running: bool,
+    pub port: u16,
+    pub token: String,
+    pub origin: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -154,8 +1176,274 @@ pub struct CacheCleanResult {
     pub freed_bytes: u64,
 }
 
+/// A bulk operation for `apply_to_selection`, named by `op` with its
+/// parameters alongside
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum SelectionOp {
+    /// One of `COLOR_LABELS`, or empty to clear
+    SetLabel { label: String },
+    SetRating { rating: Option<f64> },
+    AddToCollection { collection_id: i64 },
+    AddTags {
+        tags: Vec<TagEntry>,
+        #[serde(default)]
+        write_info: Option<bool>,
+    },
+    RemoveTags {
+        tags: Vec<TagEntry>,
+        #[serde(default)]
+        write_info: Option<bool>,
+    },
+    /// Queued as one batch refresh job
+    Refresh,
+    /// Copy each gallery folder or archive into `dest`
+    Export { dest: String },
+}
+
+/// Outcome of `apply_to_selection`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SelectionReport {
+    /// Galleries the operation changed
+    pub changed: u64,
+    /// Galleries already in the requested state
+    pub unchanged: u64,
+    /// Galleries the operation failed on; the rest still ran
+    pub failures: Vec<ScanFailure>,
+    /// The job running the operation, for operations that run as one
+    pub job_id: Option<u64>,
+}
+
+/// Outcome of adding or removing tags across many galleries
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TagEditReport {
+    /// Galleries whose tags changed
+    pub changed: u64,
+    /// Galleries already in the requested state
+    pub unchanged: u64,
+    /// Galleries that couldn't be edited or whose info.txt couldn't be
+    /// written
+    pub failures: Vec<ScanFailure>,
+}
+
+/// Outcome of `run_db_maintenance`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbMaintenanceResult {
+    /// Database, WAL and shared-memory file bytes
+    pub size_before: u64,
+    pub size_after: u64,
+    pub duration_ms: u64,
+}
+
+/// Outcome of `verify_manifest`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManifestReport {
+    /// Listed files whose checksum still matches
+    pub verified: usize,
+    /// Listed files whose contents changed
+    pub corrupted: Vec<String>,
+    /// Listed files that are gone
+    pub missing: Vec<String>,
+    /// Pages or info.txt not in the manifest
+    pub unlisted: Vec<String>,
+}
+
+/// Rows returned by `run_readonly_query`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// More rows matched than were returned
+    pub truncated: bool,
+}
+
+/// Category of background job; each kind has its own concurrency limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Scan,
+    Thumbnail,
+    Refresh,
+    Download,
+    Conversion,
+}
+
+impl JobKind {
+    /// Hard per-kind cap, independent of the user-tunable resource limits
+    pub fn default_limit(self) -> usize {
+        match self {
+            JobKind::Scan => 1,
+            JobKind::Thumbnail => 4,
+            JobKind::Refresh => 1,
+            JobKind::Download => 2,
+            JobKind::Conversion => 1,
+        }
+    }
+
+    pub fn resource(self) -> JobResource {
+        match self {
+            JobKind::Thumbnail => JobResource::Thumbnail,
+            JobKind::Refresh | JobKind::Download => JobResource::Network,
+            JobKind::Scan | JobKind::Conversion => JobResource::Disk,
+        }
+    }
+}
+
+/// What a job mostly contends for; limits are shared by all kinds in a class
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobResource {
+    Thumbnail,
+    Network,
+    Disk,
+}
+
+/// User-tunable scheduler limits, one per `JobResource`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobLimits {
+    pub thumbnail_workers: u32,
+    pub network_jobs: u32,
+    pub disk_jobs: u32,
+}
+
+/// How batch refreshes spread out their requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshPacing {
+    /// Galleries fetched at the same time
+    pub concurrency: u32,
+    /// Random wait before each request, between these bounds
+    pub min_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobPriority {
+    Low,
+    Normal,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Snapshot of a background job, also the payload of `job-progress` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: u64,
+    pub kind: JobKind,
+    pub priority: JobPriority,
+    pub status: JobStatus,
+    pub label: String,
+    pub done: u64,
+    pub total: u64,
+    pub message: String,
+    pub error: Option<String>,
+    /// Row in the `jobs` table for jobs that survive restarts
+    pub persist_id: Option<i64>,
+    /// Queued but held back by the power policy
+    #[serde(default)]
+    pub deferred: bool,
+}
+
+/// A job as the jobs panel lists it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    #[serde(flatten)]
+    pub info: JobInfo,
+    /// When the job was first queued, for jobs that survive restarts
+    pub queued_at: Option<String>,
+    /// Batch refresh held by `pause_batch_refresh` or by throttling
+    pub paused: bool,
+}
+
+/// Durable description of a job, stored as JSON so it can be rebuilt and
+/// resumed after the app restarts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobSpec {
+    Scan { root_path: String },
+    BatchRefresh { ids: Vec<i64> },
+    Identify { ids: Vec<i64> },
+    ImportFavorites,
+    CheckFollowedArtists,
+    HashPages,
+    ScanInbox,
+    TranslateTags,
+    UpgradeGallery { id: i64, trash_old: bool },
+    DownloadGallery { url: String, root: String },
+    RepairGallery { id: i64 },
+    ExportSidecars { ids: Vec<i64> },
+}
+
+impl JobSpec {
+    pub fn kind(&self) -> JobKind {
+        match self {
+            JobSpec::Scan { .. } => JobKind::Scan,
+            JobSpec::BatchRefresh { .. }
+            | JobSpec::Identify { .. }
+            | JobSpec::ImportFavorites
+            | JobSpec::CheckFollowedArtists => JobKind::Refresh,
+            JobSpec::HashPages | JobSpec::TranslateTags | JobSpec::ExportSidecars { .. } => {
+                JobKind::Conversion
+            }
+            JobSpec::ScanInbox => JobKind::Thumbnail,
+            JobSpec::UpgradeGallery { .. }
+            | JobSpec::DownloadGallery { .. }
+            | JobSpec::RepairGallery { .. } => JobKind::Download,
+        }
+    }
+}
+
+/// Partial metadata edit from the UI; `None` fields are left unchanged
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetadataPatch {
+    #[serde(default)]
+    pub title_en: Option<String>,
+    #[serde(default)]
+    pub title_jp: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub tags: Option<Vec<TagEntry>>,
+}
+
+impl MetadataPatch {
+    /// Names of the fields this patch sets, as used by `metadata::METADATA_FIELDS`.
+    pub fn fields(&self) -> Vec<String> {
+        [
+            ("title_en", self.title_en.is_some()),
+            ("title_jp", self.title_jp.is_some()),
+            ("category", self.category.is_some()),
+            ("language", self.language.is_some()),
+            ("tags", self.tags.is_some()),
+        ]
+        .iter()
+        .filter(|(_, set)| *set)
+        .map(|(name, _)| name.to_string())
+        .collect()
+    }
+}
+
+/// A locally edited field whose remote value differs, kept local on refresh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldConflict {
+    pub field: String,
+    pub local: String,
+    pub remote: String,
+}
+
 /// Parsed info.txt data before insertion into DB
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ParsedGallery {
     pub title_en: String,
     pub title_jp: String,
@@ -169,4 +1457,67 @@ pub struct ParsedGallery {
     pub rating: f64,
     pub favorited: i64,
     pub tags: Vec<(String, String)>, // (namespace, tag)
+    #[serde(default)]
+    pub uploader_comment: String,
+}
+
+/// What happened to the library in a `LibraryChange`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    GalleryUpserted,
+    GalleryRemoved,
+    /// Tags or their display names changed; without a gallery, library-wide
+    TagsChanged,
+    ProgressUpdated,
+}
+
+/// One entry of the ordered change log, also the payload of
+/// `library-changed` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryChange {
+    /// Increases by one per change; the cursor for `get_changes_since`
+    pub seq: i64,
+    pub kind: ChangeKind,
+    pub gallery_id: Option<i64>,
+    #[serde(default)]
+    pub path: String,
+}
+
+/// Changes after a cursor. `reset` means some were already pruned from the
+/// log and the caller has to reload everything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeFeed {
+    pub changes: Vec<LibraryChange>,
+    /// Latest `seq`, to pass as the next cursor
+    pub cursor: i64,
+    pub reset: bool,
+}
+
+/// A library profile: its own database, thumbnails and settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileInfo {
+    pub name: String,
+    pub active: bool,
+}
+
+/// Outcome of copying a profile's data to another directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MigrationReport {
+    /// Files copied besides the database
+    pub files: usize,
+    pub bytes: u64,
+    /// Galleries in the copied database
+    pub galleries: i64,
+    /// Copied files whose checksum differs from the original
+    pub mismatched: Vec<String>,
+    /// The copied database passed SQLite's integrity check and holds as many
+    /// galleries as the original
+    pub database_ok: bool,
+}
+
+impl MigrationReport {
+    pub fn verified(&self) -> bool {
+        self.database_ok && self.mismatched.is_empty()
+    }
 }
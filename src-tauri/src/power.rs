@@ -0,0 +1,92 @@
+//! Power source and network cost detection, so heavy background work can
+//! wait for mains power or an unmetered connection. Every check answers
+//! false when it can't tell.
+
+/// Whether the machine is running on battery rather than mains power.
+/// False on desktops without a battery.
+#[cfg(target_os = "windows")]
+pub fn on_battery() -> bool {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status = SYSTEM_POWER_STATUS::default();
+    // ACLineStatus: 0 offline, 1 online, 255 unknown
+    unsafe { GetSystemPowerStatus(&mut status) }.is_ok() && status.ACLineStatus == 0
+}
+
+#[cfg(target_os = "macos")]
+pub fn on_battery() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).contains("'Battery Power'"))
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+pub fn on_battery() -> bool {
+    use std::path::Path;
+
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let read = |dir: &Path, name: &str| {
+        std::fs::read_to_string(dir.join(name))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut mains_online = false;
+    let mut discharging = false;
+    for supply in supplies.filter_map(|e| e.ok()) {
+        let dir = supply.path();
+        match read(&dir, "type").as_str() {
+            "Mains" => mains_online |= read(&dir, "online") == "1",
+            "Battery" => discharging |= read(&dir, "status") == "Discharging",
+            _ => {}
+        }
+    }
+    discharging && !mains_online
+}
+
+/// Whether the internet connection is metered (mobile data, a tethered
+/// phone, or one the user marked as metered)
+#[cfg(target_os = "windows")]
+pub fn on_metered_connection() -> bool {
+    use windows::Networking::Connectivity::{NetworkCostType, NetworkInformation};
+
+    let metered = || -> windows::core::Result<bool> {
+        let cost = NetworkInformation::GetInternetConnectionProfile()?.GetConnectionCost()?;
+        let cost_type = cost.NetworkCostType()?;
+        Ok(cost.Roaming()?
+            || cost.OverDataLimit()?
+            || cost_type == NetworkCostType::Fixed
+            || cost_type == NetworkCostType::Variable)
+    };
+    metered().unwrap_or(false)
+}
+
+/// macOS has no command-line view of Low Data Mode, so connections are
+/// never treated as metered there
+#[cfg(target_os = "macos")]
+pub fn on_metered_connection() -> bool {
+    false
+}
+
+/// Asks NetworkManager, which reports 1 (yes) or 3 (guessed yes) for
+/// metered connections
+#[cfg(target_os = "linux")]
+pub fn on_metered_connection() -> bool {
+    std::process::Command::new("busctl")
+        .args([
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ])
+        .output()
+        .map(|out| {
+            let reply = String::from_utf8_lossy(&out.stdout);
+            matches!(reply.trim(), "u 1" | "u 3")
+        })
+        .unwrap_or(false)
+}
@@ -0,0 +1,184 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::db::Database;
+use crate::manifest;
+use crate::models::MigrationReport;
+use crate::secret;
+
+/// Profile using the app data directory itself, so libraries from before
+/// profiles existed open as this one
+pub const DEFAULT_PROFILE: &str = "main";
+
+/// File in the app data directory naming the profile to open on startup
+const ACTIVE_FILE: &str = "active_profile";
+
+/// Other profiles live in their own directories under this one
+const PROFILES_DIR: &str = "profiles";
+
+/// Library database inside a profile directory
+pub const DB_NAME: &str = "manga_viewer.db";
+
+/// Thumbnail cache inside a profile directory
+pub const THUMBS_DIR: &str = "thumbs";
+
+/// Files carried along with the database and thumbnails when copying a
+/// profile. The secret key goes with the settings it opens; `fs::copy`
+/// keeps its owner-only permissions.
+const DATA_FILES: &[&str] = &[
+    "settings.json",
+    secret::KEY_FILE,
+    "cookie.txt",
+    "last_scan_report.json",
+];
+
+/// Directory holding a profile's database, thumbnails and settings
+pub fn profile_dir(data_dir: &Path, name: &str) -> PathBuf {
+    if name == DEFAULT_PROFILE {
+        data_dir.to_path_buf()
+    } else {
+        data_dir.join(PROFILES_DIR).join(name)
+    }
+}
+
+/// Profile names become directory names: letters, digits, spaces, `-` and
+/// `_` only
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.trim() == name
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid profile name \"{}\": use letters, digits, spaces, - and _",
+            name
+        ))
+    }
+}
+
+/// Every profile, the default first, then the rest by name
+pub fn list(data_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(data_dir.join(PROFILES_DIR))
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().is_dir())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|name| validate_name(name).is_ok() && name != DEFAULT_PROFILE)
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort_by_key(|n| n.to_lowercase());
+    names.insert(0, DEFAULT_PROFILE.to_string());
+    names
+}
+
+/// The profile opened last, falling back to the default when it's unset or
+/// its directory is gone
+pub fn active(data_dir: &Path) -> String {
+    fs::read_to_string(data_dir.join(ACTIVE_FILE))
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| validate_name(name).is_ok() && profile_dir(data_dir, name).is_dir())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+pub fn set_active(data_dir: &Path, name: &str) -> Result<(), String> {
+    fs::write(data_dir.join(ACTIVE_FILE), name)
+        .map_err(|e| format!("Failed to save active profile: {}", e))
+}
+
+/// Copy the data directory `src` (database, thumbnails, settings, cookies)
+/// to `dst`, then check the copy: every file against its original's
+/// checksum, and the database for integrity and its gallery count.
+/// Thumbnail paths in the copied database are rewritten to the new
+/// location; root paths in the settings are kept as they are. `src` is left
+/// untouched, and `dst` must not hold a library yet.
+pub fn copy_data(src: &Path, dst: &Path) -> Result<MigrationReport, String> {
+    let src_db = src.join(DB_NAME);
+    if !src_db.is_file() {
+        return Err(format!("No library database in {}", src.display()));
+    }
+    let dst_db = dst.join(DB_NAME);
+    if dst_db.exists() {
+        return Err(format!("{} already holds a library", dst.display()));
+    }
+    fs::create_dir_all(dst).map_err(|e| format!("Can't create {}: {}", dst.display(), e))?;
+
+    let mut files: Vec<PathBuf> = DATA_FILES
+        .iter()
+        .map(PathBuf::from)
+        .filter(|name| src.join(name).is_file())
+        .collect();
+    files.extend(
+        WalkDir::new(src.join(THUMBS_DIR))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter_map(|e| e.path().strip_prefix(src).ok().map(Path::to_path_buf)),
+    );
+
+    let mut report = MigrationReport::default();
+    for name in &files {
+        let to = dst.join(name);
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Can't create {}: {}", parent.display(), e))?;
+        }
+        report.bytes += fs::copy(src.join(name), &to)
+            .map_err(|e| format!("Can't copy {}: {}", name.display(), e))?;
+        report.files += 1;
+    }
+
+    Database::copy_file(&src_db, &dst_db).map_err(|e| format!("Can't copy the database: {}", e))?;
+    Database::rebase_thumb_paths(
+        &dst_db,
+        &src.join(THUMBS_DIR).to_string_lossy(),
+        &dst.join(THUMBS_DIR).to_string_lossy(),
+    )
+    .map_err(|e| format!("Can't update thumbnail paths: {}", e))?;
+
+    for name in &files {
+        let original = manifest::file_sha256(&src.join(name));
+        let copy = manifest::file_sha256(&dst.join(name));
+        if !matches!((original, copy), (Ok(a), Ok(b)) if a == b) {
+            report.mismatched.push(name.to_string_lossy().to_string());
+        }
+    }
+    let (_, expected) = Database::check_file(&src_db).map_err(|e| e.to_string())?;
+    let (intact, galleries) = Database::check_file(&dst_db).map_err(|e| e.to_string())?;
+    report.galleries = galleries;
+    report.database_ok = intact && galleries == expected;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copied_profile_opens_sealed_values() {
+        let base = std::env::temp_dir().join(format!("ehmaster-profile-copy-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let (src, dst) = (base.join("src"), base.join("dst"));
+        fs::create_dir_all(&src).unwrap();
+        drop(Database::new(&src.join(DB_NAME)).unwrap());
+        let sealed = secret::seal(&src, "hunter2").unwrap();
+
+        let report = copy_data(&src, &dst).unwrap();
+        assert!(report.mismatched.is_empty());
+        assert_eq!(secret::open(&dst, &sealed).unwrap(), "hunter2");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dst.join(secret::KEY_FILE)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = fs::remove_dir_all(&base);
+    }
+}
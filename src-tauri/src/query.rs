@@ -0,0 +1,350 @@
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, ParamsFromIter};
+
+use crate::models::*;
+
+/// A SQL boolean expression with its `?` parameters, in order of appearance.
+/// User text only ever reaches SQLite as a parameter.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+impl Condition {
+    pub fn new(sql: impl Into<String>, params: Vec<Value>) -> Self {
+        Condition {
+            sql: sql.into(),
+            params,
+        }
+    }
+
+    /// `expr IN (?, ?, ...)`, or `expr NOT IN (...)` when `negate` is set
+    pub fn in_list(expr: &str, values: Vec<Value>, negate: bool) -> Self {
+        let placeholders = vec!["?"; values.len()].join(", ");
+        let op = if negate { "NOT IN" } else { "IN" };
+        Condition::new(format!("{} {} ({})", expr, op, placeholders), values)
+    }
+
+    /// All of `conds`, or `None` when there are none
+    pub fn all(conds: Vec<Condition>) -> Option<Condition> {
+        if conds.is_empty() {
+            return None;
+        }
+        let sql = conds
+            .iter()
+            .map(|c| format!("({})", c.sql))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let params = conds.into_iter().flat_map(|c| c.params).collect();
+        Some(Condition::new(sql, params))
+    }
+
+    /// Any of `conds`, or `None` when there are none
+    pub fn any(conds: Vec<Condition>) -> Option<Condition> {
+        if conds.is_empty() {
+            return None;
+        }
+        let sql = conds
+            .iter()
+            .map(|c| format!("({})", c.sql))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        let params = conds.into_iter().flat_map(|c| c.params).collect();
+        Some(Condition::new(sql, params))
+    }
+
+    pub fn negate(self) -> Condition {
+        Condition::new(format!("NOT ({})", self.sql), self.params)
+    }
+}
+
+/// A `SELECT` over `galleries g` assembled from joins and conditions
+#[derive(Debug, Default)]
+pub struct GalleryQuery {
+    joins: Vec<&'static str>,
+    conditions: Vec<Condition>,
+}
+
+impl GalleryQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn join(&mut self, join: &'static str) -> &mut Self {
+        self.joins.push(join);
+        self
+    }
+
+    pub fn filter(&mut self, cond: Condition) -> &mut Self {
+        self.conditions.push(cond);
+        self
+    }
+
+    pub fn filter_opt(&mut self, cond: Option<Condition>) -> &mut Self {
+        self.conditions.extend(cond);
+        self
+    }
+
+    /// `SELECT {columns} FROM galleries g ... WHERE ...` followed by `tail`,
+    /// which may use further parameters (see `params`)
+    pub fn sql(&self, columns: &str, tail: &str) -> String {
+        let mut sql = format!("SELECT {} FROM galleries g", columns);
+        for join in &self.joins {
+            sql.push(' ');
+            sql.push_str(join);
+        }
+        if !self.conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(
+                &self
+                    .conditions
+                    .iter()
+                    .map(|c| format!("({})", c.sql))
+                    .collect::<Vec<_>>()
+                    .join(" AND "),
+            );
+        }
+        if !tail.is_empty() {
+            sql.push(' ');
+            sql.push_str(tail);
+        }
+        sql
+    }
+
+    /// The conditions' parameters followed by those of the `tail`
+    pub fn values(&self, tail: Vec<Value>) -> Vec<Value> {
+        let mut params: Vec<Value> = self
+            .conditions
+            .iter()
+            .flat_map(|c| c.params.iter().cloned())
+            .collect();
+        params.extend(tail);
+        params
+    }
+
+    /// `values` ready to bind
+    pub fn params(&self, tail: Vec<Value>) -> ParamsFromIter<Vec<Value>> {
+        params_from_iter(self.values(tail))
+    }
+}
+
+pub fn text(s: &str) -> Value {
+    Value::Text(s.to_string())
+}
+
+/// Trimmed and lowercased, for case-insensitive comparisons against `LOWER(...)`
+pub fn folded(s: &str) -> Value {
+    Value::Text(s.trim().to_lowercase())
+}
+
+/// `s` with `LIKE` wildcards escaped for `ESCAPE '\'`
+fn like_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// `{alias}.namespace` and `{alias}.tag` match `tf`, exactly or as a prefix
+fn tag_match(alias: &str, tf: &TagFilter) -> Condition {
+    if tf.prefix {
+        let pattern = like_escape(&tf.tag);
+        Condition::new(
+            format!("{a}.namespace = ? AND {a}.tag LIKE ? ESCAPE '\\'", a = alias),
+            vec![text(&tf.namespace), Value::Text(format!("{}%", pattern))],
+        )
+    } else {
+        // An alias searches as its target
+        Condition::new(
+            format!(
+                "{a}.namespace = COALESCE((SELECT target_namespace FROM tag_aliases WHERE namespace = ? AND tag = ?), ?)
+                 AND {a}.tag = COALESCE((SELECT target_tag FROM tag_aliases WHERE namespace = ? AND tag = ?), ?)",
+                a = alias
+            ),
+            vec![
+                text(&tf.namespace),
+                text(&tf.tag),
+                text(&tf.namespace),
+                text(&tf.namespace),
+                text(&tf.tag),
+                text(&tf.tag),
+            ],
+        )
+    }
+}
+
+/// Gallery has the tag `tf`
+pub fn has_tag(tf: &TagFilter) -> Condition {
+    let m = tag_match("st", tf);
+    Condition::new(
+        format!("EXISTS (SELECT 1 FROM resolved_tags st WHERE st.gallery_id = g.id AND {})", m.sql),
+        m.params,
+    )
+}
+
+/// Gallery doesn't have the tag `tf`
+pub fn lacks_tag(tf: &TagFilter) -> Condition {
+    let m = tag_match("xt", tf);
+    Condition::new(
+        format!("NOT EXISTS (SELECT 1 FROM resolved_tags xt WHERE xt.gallery_id = g.id AND {})", m.sql),
+        m.params,
+    )
+}
+
+/// Words of `text`, with phrases in double quotes kept together
+fn fts_terms(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let (term, tail) = match rest.strip_prefix('"') {
+            Some(phrase) => phrase.split_once('"').unwrap_or((phrase, "")),
+            None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+        };
+        if !term.trim().is_empty() {
+            terms.push(term.trim().to_string());
+        }
+        rest = tail.trim_start();
+    }
+    terms
+}
+
+/// Indexed columns of `galleries_fts`
+const FTS_COLUMNS: &[&str] = &[
+    "title_en",
+    "title_jp",
+    "folder_name",
+    "custom_title",
+    "tags",
+    "uploader",
+    "notes",
+];
+
+/// Every term of `text` appears somewhere in the `galleries_fts` row
+/// aliased `alias`. The trigram index matches substrings of three or more
+/// characters; shorter terms fall back to `LIKE` over each column.
+fn fts_condition(text: &str, alias: &str) -> Option<Condition> {
+    let (long, short): (Vec<String>, Vec<String>) =
+        fts_terms(text).into_iter().partition(|t| t.chars().count() >= 3);
+    let mut parts = Vec::new();
+    if !long.is_empty() {
+        // Quoted so FTS syntax in the input is matched literally
+        let fts_query = long
+            .iter()
+            .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        parts.push(Condition::new(
+            format!("{}.galleries_fts MATCH ?", alias),
+            vec![Value::Text(fts_query)],
+        ));
+    }
+    for term in short {
+        let pattern = Value::Text(format!("%{}%", like_escape(&term)));
+        let sql = FTS_COLUMNS
+            .iter()
+            .map(|c| format!("{}.{} LIKE ? ESCAPE '\\'", alias, c))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        parts.push(Condition::new(sql, vec![pattern; FTS_COLUMNS.len()]));
+    }
+    Condition::all(parts)
+}
+
+/// Full-text match on `text`: every word, or phrase in double quotes, must
+/// appear. Needs `FTS_JOIN`.
+pub fn fts_match(text: &str) -> Option<Condition> {
+    fts_condition(text, "fts")
+}
+
+/// Gallery's titles, tags, uploader and notes don't contain the word or quoted
+/// phrase `text`
+pub fn fts_exclude(text: &str) -> Option<Condition> {
+    fts_condition(text, "xf").map(|cond| {
+        Condition::new(
+            format!("g.id NOT IN (SELECT xf.rowid FROM galleries_fts xf WHERE {})", cond.sql),
+            cond.params,
+        )
+    })
+}
+
+pub const FTS_JOIN: &str = "INNER JOIN galleries_fts fts ON fts.rowid = g.id";
+
+/// Condition excluding blocked uploaders and artists. Matching is
+/// case-insensitive; `None` when the blocklist is empty.
+pub fn blocklist_condition(blocklist: &Blocklist) -> Option<Condition> {
+    let mut parts = Vec::new();
+    if !blocklist.uploaders.is_empty() {
+        parts.push(Condition::in_list(
+            "LOWER(g.uploader)",
+            blocklist.uploaders.iter().map(|u| folded(u)).collect(),
+            true,
+        ));
+    }
+    if !blocklist.artists.is_empty() {
+        let artists = Condition::in_list(
+            "LOWER(bt.tag)",
+            blocklist.artists.iter().map(|a| folded(a)).collect(),
+            false,
+        );
+        parts.push(Condition::new(
+            format!(
+                "NOT EXISTS (SELECT 1 FROM gallery_tags bt WHERE bt.gallery_id = g.id
+                 AND bt.namespace = 'artist' AND {})",
+                artists.sql
+            ),
+            artists.params,
+        ));
+    }
+    Condition::all(parts)
+}
+
+/// Condition for the persistent default filters, or `None` when no default
+/// filter is set.
+pub fn default_filter_condition(defaults: &DefaultFilters) -> Option<Condition> {
+    let mut parts = Vec::new();
+    if !defaults.excluded_categories.is_empty() {
+        parts.push(Condition::in_list(
+            "LOWER(g.category)",
+            defaults.excluded_categories.iter().map(|c| folded(c)).collect(),
+            true,
+        ));
+    }
+    if defaults.min_rating > 0.0 {
+        parts.push(Condition::new("g.rating >= ?", vec![Value::Real(defaults.min_rating)]));
+    }
+    if !defaults.languages.is_empty() {
+        let languages = Condition::in_list(
+            "LOWER(g.language)",
+            defaults.languages.iter().map(|l| folded(l)).collect(),
+            false,
+        );
+        parts.push(Condition::new(
+            format!("g.language = '' OR {}", languages.sql),
+            languages.params,
+        ));
+    }
+    Condition::all(parts)
+}
+
+/// Condition requiring every layer of the session filter stack, or `None`
+/// when the stack has no effective filter.
+pub fn session_filter_condition(session: &[SessionFilter]) -> Option<Condition> {
+    let mut parts = Vec::new();
+    for filter in session.iter().filter(|f| !f.is_empty()) {
+        for tf in &filter.tags {
+            parts.push(Condition::new(
+                "EXISTS (SELECT 1 FROM resolved_tags st WHERE st.gallery_id = g.id
+                 AND st.namespace = ? AND LOWER(st.tag) = ?)",
+                vec![text(&tf.namespace), folded(&tf.tag)],
+            ));
+        }
+        if let Some(cat) = filter.category.as_deref().filter(|c| !c.is_empty()) {
+            parts.push(Condition::new("LOWER(g.category) = ?", vec![folded(cat)]));
+        }
+        if let Some(lang) = filter.language.as_deref().filter(|l| !l.is_empty()) {
+            parts.push(Condition::new("LOWER(g.language) = ?", vec![folded(lang)]));
+        }
+        if filter.min_rating > 0.0 {
+            parts.push(Condition::new("g.rating >= ?", vec![Value::Real(filter.min_rating)]));
+        }
+    }
+    Condition::all(parts)
+}
@@ -1,125 +1,284 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
 use walkdir::WalkDir;
 
-use crate::models::ParsedGallery;
+use crate::archive;
+use crate::models::{PageStats, ParsedGallery};
 
 /// Image extensions we recognize
 const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "bmp", "avif"];
 
 /// Parse an info.txt file into structured gallery data
 pub fn parse_info_txt(path: &Path) -> Option<ParsedGallery> {
-    let content = fs::read_to_string(path).ok()?;
-    let lines: Vec<&str> = content.lines().collect();
+    parse_info_txt_detailed(path).ok()
+}
+
+/// Compose text to Unicode NFC. macOS hands out decomposed (NFD) filenames
+/// while fetched metadata is precomposed, so paths and titles are folded to
+/// NFC before they are stored or compared.
+pub fn to_nfc(s: &str) -> String {
+    if is_nfc(s) {
+        s.to_string()
+    } else {
+        s.nfc().collect()
+    }
+}
 
-    if lines.len() < 5 {
-        return None;
+/// NFC-normalized copy of the free-text fields of parsed metadata.
+pub fn nfc_parsed(parsed: &ParsedGallery) -> ParsedGallery {
+    ParsedGallery {
+        title_en: to_nfc(&parsed.title_en),
+        title_jp: to_nfc(&parsed.title_jp),
+        uploader: to_nfc(&parsed.uploader),
+        uploader_comment: to_nfc(&parsed.uploader_comment),
+        tags: parsed
+            .tags
+            .iter()
+            .map(|(ns, tag)| (to_nfc(ns), to_nfc(tag)))
+            .collect(),
+        ..parsed.clone()
     }
+}
+
+/// Whether a root's volume is mounted and readable. A disconnected drive or
+/// network share either vanishes or fails to list, so both count as offline.
+pub fn is_root_online(root: &Path) -> bool {
+    root.is_dir() && fs::read_dir(root).is_ok()
+}
+
+/// Parse an info.txt file, returning the reason on failure
+pub fn parse_info_txt_detailed(path: &Path) -> Result<ParsedGallery, String> {
+    let content = read_text_file(path).map_err(|e| format!("Failed to read info.txt: {}", e))?;
+    parse_info_content(&content)
+}
 
-    let title_en = lines[0].trim().to_string();
-    let title_jp = if lines.len() > 1 {
-        lines[1].trim().to_string()
+/// The file whose mtime decides whether a gallery needs re-parsing: the
+/// folder's info.txt, or the archive itself.
+pub fn gallery_info_path(gallery: &Path) -> PathBuf {
+    if archive::is_archive(gallery) {
+        gallery.to_path_buf()
     } else {
-        String::new()
+        gallery.join("info.txt")
+    }
+}
+
+/// Parse a gallery folder's info.txt or an archive's embedded metadata
+pub fn parse_gallery(gallery: &Path) -> Result<ParsedGallery, String> {
+    if archive::is_archive(gallery) {
+        archive::parse_archive_info(gallery)
+    } else {
+        parse_info_txt_detailed(&gallery.join("info.txt"))
+    }
+}
+
+/// Metadata keys understood by the parser, by lowercase alias. Covers the
+/// E-Hentai Downloader layout plus HDoujin/PandaDownloader spellings.
+const KEY_ALIASES: &[(&str, &str)] = &[
+    ("title", "title_en"),
+    ("english title", "title_en"),
+    ("original title", "title_jp"),
+    ("japanese title", "title_jp"),
+    ("url", "url"),
+    ("source", "url"),
+    ("link", "url"),
+    ("category", "category"),
+    ("uploader", "uploader"),
+    ("posted", "posted"),
+    ("upload date", "posted"),
+    ("language", "language"),
+    ("file size", "file_size"),
+    ("filesize", "file_size"),
+    ("size", "file_size"),
+    ("length", "page_count"),
+    ("pages", "page_count"),
+    ("page count", "page_count"),
+    ("rating", "rating"),
+    ("average", "rating"),
+    ("favorited", "favorited"),
+    ("favorites", "favorited"),
+    ("tags", "tags"),
+    ("uploader comment", "uploader_comment"),
+    ("uploader's comment", "uploader_comment"),
+];
+
+/// Tag list separators seen across downloaders
+const TAG_SEPARATORS: &[char] = &[',', ';'];
+
+/// Split a `Key: value` line (ASCII or full-width colon) into the canonical
+/// field name and value, if the key is a known alias.
+fn split_known_key(line: &str) -> Option<(&'static str, &str)> {
+    let (pos, sep) = line.char_indices().find(|(_, c)| *c == ':' || *c == '：')?;
+    let key = line[..pos].trim().to_lowercase();
+    let field = KEY_ALIASES.iter().find(|(alias, _)| *alias == key)?.1;
+    Some((field, line[pos + sep.len_utf8()..].trim()))
+}
+
+fn is_gallery_url(line: &str) -> bool {
+    (line.starts_with("https://") || line.starts_with("http://"))
+        && (line.contains("exhentai.org/") || line.contains("e-hentai.org/"))
+}
+
+/// First integer in a value like "28 pages" or "1,234 times"
+fn leading_int(val: &str) -> i64 {
+    val.split_whitespace()
+        .next()
+        .and_then(|n| n.replace(',', "").parse().ok())
+        .unwrap_or(0)
+}
+
+/// Parse "namespace: tag1, tag2" (grouped) or "ns:tag1, ns:tag2" (inline).
+/// Tags without a namespace go to "misc".
+fn parse_tag_line(line: &str, tags: &mut Vec<(String, String)>) {
+    let line = line.trim_start_matches(['>', '-', '*', '•']).trim();
+    if line.is_empty() {
+        return;
+    }
+
+    // Grouped form: a single namespace before the first separator
+    let grouped = line
+        .char_indices()
+        .find(|(i, c)| (*c == ':' && line[i + 1..].starts_with(' ')) || *c == '：')
+        .filter(|(pos, _)| !line[..*pos].contains(TAG_SEPARATORS));
+    if let Some((pos, sep)) = grouped {
+        let namespace = line[..pos].trim().to_lowercase();
+        for tag in line[pos + sep.len_utf8()..].split(TAG_SEPARATORS) {
+            let tag = tag.trim();
+            if !tag.is_empty() {
+                tags.push((namespace.clone(), tag.to_string()));
+            }
+        }
+        return;
+    }
+
+    for item in line.split(TAG_SEPARATORS) {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+        match item.split_once(':') {
+            Some((ns, tag)) if !ns.trim().is_empty() && !tag.trim().is_empty() => {
+                tags.push((ns.trim().to_lowercase(), tag.trim().to_string()));
+            }
+            _ => tags.push(("misc".to_string(), item.to_string())),
+        }
+    }
+}
+
+fn looks_like_tag_line(line: &str) -> bool {
+    line.starts_with(['>', '-', '*', '•']) || (line.contains(':') && !is_gallery_url(line))
+}
+
+/// Parse info.txt contents. Tolerates a BOM, CRLF line endings, the
+/// E-Hentai Downloader header (title, Japanese title, URL), `Key: value`
+/// headers as written by HDoujin and PandaDownloader, full-width colons,
+/// and grouped (`> ns: a, b`) or inline (`Tags: ns:a, ns:b`) tags.
+pub fn parse_info_content(content: &str) -> Result<ParsedGallery, String> {
+    let content = content.trim_start_matches('\u{feff}');
+    let lines: Vec<&str> = content.lines().map(|l| l.trim()).collect();
+
+    let mut g = ParsedGallery {
+        title_en: String::new(),
+        title_jp: String::new(),
+        url: String::new(),
+        category: String::new(),
+        uploader: String::new(),
+        posted: String::new(),
+        language: String::new(),
+        file_size: String::new(),
+        page_count: 0,
+        rating: 0.0,
+        favorited: 0,
+        tags: Vec::new(),
+        uploader_comment: String::new(),
     };
 
-    // Find URL (line starting with https://exhentai.org or https://e-hentai.org)
-    let url = lines
+    // Positional header: title, Japanese title and URL before the first key
+    let header_len = lines
         .iter()
-        .take(5)
-        .find(|l| l.starts_with("https://exhentai.org") || l.starts_with("https://e-hentai.org"))
-        .map(|l| l.trim().to_string())
-        .unwrap_or_default();
-
-    let mut category = String::new();
-    let mut uploader = String::new();
-    let mut posted = String::new();
-    let mut language = String::new();
-    let mut file_size = String::new();
-    let mut page_count: i64 = 0;
-    let mut rating: f64 = 0.0;
-    let mut favorited: i64 = 0;
-    let mut tags: Vec<(String, String)> = Vec::new();
+        .position(|l| split_known_key(l).is_some())
+        .unwrap_or(lines.len());
+    let header = &lines[..header_len];
+    if let Some(url) = header.iter().find(|l| is_gallery_url(l)) {
+        g.url = url.to_string();
+    }
+    if let Some(first) = header.iter().position(|l| !l.is_empty() && !is_gallery_url(l)) {
+        g.title_en = header[first].to_string();
+        if let Some(next) = header.get(first + 1).filter(|l| !is_gallery_url(l)) {
+            g.title_jp = next.to_string();
+        }
+    }
 
     let mut in_tags = false;
+    let mut saw_key = false;
+    let mut comment: Option<Vec<&str>> = None;
+    for line in &lines[header_len..] {
+        // Stop processing at page listings
+        if line.starts_with("Page 1:") || line.starts_with("Downloaded at") {
+            break;
+        }
 
-    for line in &lines {
-        let line = line.trim();
-
-        // Parse key-value metadata
-        if let Some(val) = line.strip_prefix("Category: ") {
-            category = val.trim().to_string();
-        } else if let Some(val) = line.strip_prefix("Uploader: ") {
-            uploader = val.trim().to_string();
-        } else if let Some(val) = line.strip_prefix("Posted: ") {
-            posted = val.trim().to_string();
-        } else if let Some(val) = line.strip_prefix("Language: ") {
-            language = val.trim().to_string();
-        } else if let Some(val) = line.strip_prefix("File Size: ") {
-            file_size = val.trim().to_string();
-        } else if let Some(val) = line.strip_prefix("Length: ") {
-            // "28 pages" -> 28
-            page_count = val
-                .split_whitespace()
-                .next()
-                .and_then(|n| n.parse().ok())
-                .unwrap_or(0);
-        } else if let Some(val) = line.strip_prefix("Rating: ") {
-            rating = val.trim().parse().unwrap_or(0.0);
-        } else if let Some(val) = line.strip_prefix("Favorited: ") {
-            // "50 times" -> 50
-            favorited = val
-                .split_whitespace()
-                .next()
-                .and_then(|n| n.parse().ok())
-                .unwrap_or(0);
-        } else if line == "Tags:" {
-            in_tags = true;
-        } else if in_tags {
-            if line.starts_with("> ") {
-                // Parse tag line: "> namespace: tag1, tag2, tag3"
-                let tag_line = &line[2..]; // strip "> "
-                if let Some(colon_pos) = tag_line.find(": ") {
-                    let namespace = tag_line[..colon_pos].trim().to_string();
-                    let tag_str = &tag_line[colon_pos + 2..];
-                    for tag in tag_str.split(", ") {
-                        let tag = tag.trim();
-                        if !tag.is_empty() {
-                            tags.push((namespace.clone(), tag.to_string()));
-                        }
-                    }
+        // The uploader comment is free text up to the page listing
+        if let Some(comment) = comment.as_mut() {
+            comment.push(line);
+            continue;
+        }
+
+        if let Some((field, val)) = split_known_key(line) {
+            saw_key = true;
+            in_tags = field == "tags";
+            match field {
+                "title_en" => g.title_en = val.to_string(),
+                "title_jp" => g.title_jp = val.to_string(),
+                "url" if is_gallery_url(val) => g.url = val.to_string(),
+                "category" => g.category = val.to_string(),
+                "uploader" => g.uploader = val.to_string(),
+                "posted" => g.posted = val.to_string(),
+                "language" => g.language = val.to_string(),
+                "file_size" => g.file_size = val.to_string(),
+                "page_count" => g.page_count = leading_int(val),
+                "rating" => {
+                    g.rating = val
+                        .split_whitespace()
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .unwrap_or(0.0)
                 }
-            } else if !line.is_empty() && !line.starts_with("> ") {
+                "favorited" => g.favorited = leading_int(val),
+                "tags" => parse_tag_line(val, &mut g.tags),
+                "uploader_comment" => comment = Some(vec![val]),
+                _ => {}
+            }
+        } else if in_tags && !line.is_empty() {
+            if looks_like_tag_line(line) {
+                parse_tag_line(line, &mut g.tags);
+            } else {
                 // End of tags section
                 in_tags = false;
             }
         }
+    }
 
-        // Stop processing at page listings
-        if line.starts_with("Page 1:") || line.starts_with("Downloaded at") {
-            break;
-        }
+    if let Some(comment) = comment {
+        g.uploader_comment = comment.join("\n").trim().to_string();
+    }
+
+    if g.title_en.is_empty() && g.url.is_empty() && !saw_key {
+        return Err(format!(
+            "info.txt has no title, URL or metadata ({} lines)",
+            lines.len()
+        ));
     }
 
-    Some(ParsedGallery {
-        title_en,
-        title_jp,
-        url,
-        category,
-        uploader,
-        posted,
-        language,
-        file_size,
-        page_count,
-        rating,
-        favorited,
-        tags,
-    })
+    Ok(g)
 }
 
-/// Find all gallery folders (folders containing info.txt) under a root path
+/// Find all galleries under a root path: folders containing info.txt, and
+/// archives (zip, rar, 7z) that aren't inside such a folder
 pub fn find_gallery_folders(root: &Path) -> Vec<PathBuf> {
     let mut galleries = Vec::new();
+    let mut gallery_dirs: std::collections::HashSet<PathBuf> = std::collections::HashSet::new();
 
     for entry in WalkDir::new(root)
         .follow_links(true)
@@ -132,7 +291,12 @@ pub fn find_gallery_folders(root: &Path) -> Vec<PathBuf> {
                     let info_path = e.path().join("info.txt");
                     if info_path.exists() {
                         galleries.push(e.path().to_path_buf());
+                        gallery_dirs.insert(e.path().to_path_buf());
                     }
+                } else if archive::is_archive(e.path())
+                    && !e.path().parent().is_some_and(|p| gallery_dirs.contains(p))
+                {
+                    galleries.push(e.path().to_path_buf());
                 }
             }
             Err(e) => {
@@ -145,6 +309,211 @@ pub fn find_gallery_folders(root: &Path) -> Vec<PathBuf> {
     galleries
 }
 
+/// Read a text file of unknown encoding. A BOM (UTF-8/UTF-16) wins, then
+/// valid UTF-8, then whatever chardetng guesses (typically Shift-JIS or GBK
+/// for files from older downloaders).
+pub fn read_text_file(path: &Path) -> std::io::Result<String> {
+    Ok(decode_text(&fs::read(path)?))
+}
+
+/// Decode bytes of unknown encoding, as `read_text_file` does
+pub fn decode_text(bytes: &[u8]) -> String {
+    if let Some((encoding, bom_len)) = encoding_rs::Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return text.into_owned();
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, true);
+    let (text, _, _) = encoding.decode(bytes);
+    text.into_owned()
+}
+
+/// Title markers that identify a gallery's language, matched case-insensitively
+const LANGUAGE_MARKERS: &[(&str, &str)] = &[
+    ("[chinese]", "Chinese"),
+    ("汉化", "Chinese"),
+    ("漢化", "Chinese"),
+    ("中国翻訳", "Chinese"),
+    ("中國翻譯", "Chinese"),
+    ("[中文]", "Chinese"),
+    ("[english]", "English"),
+    ("[英訳]", "English"),
+    ("[korean]", "Korean"),
+    ("[韓国翻訳]", "Korean"),
+    ("[한국어]", "Korean"),
+    ("[spanish]", "Spanish"),
+    ("[español]", "Spanish"),
+    ("[french]", "French"),
+    ("[german]", "German"),
+    ("[italian]", "Italian"),
+    ("[portuguese-br]", "Portuguese"),
+    ("[portuguese]", "Portuguese"),
+    ("[russian]", "Russian"),
+    ("[русский]", "Russian"),
+    ("[thai]", "Thai"),
+    ("[ภาษาไทย]", "Thai"),
+    ("[vietnamese]", "Vietnamese"),
+    ("[tiếng việt]", "Vietnamese"),
+    ("[indonesian]", "Indonesian"),
+    ("[polish]", "Polish"),
+    ("[japanese]", "Japanese"),
+];
+
+/// Guess a gallery's language from markers like `[Chinese]`, `[英訳]` or
+/// `汉化` in its titles or folder name.
+pub fn infer_language(texts: &[&str]) -> Option<&'static str> {
+    let haystack = texts.join(" ").to_lowercase();
+    LANGUAGE_MARKERS
+        .iter()
+        .find(|(marker, _)| haystack.contains(marker))
+        .map(|(_, language)| *language)
+}
+
+/// Width EH scales images down to for its resampled (1280x) versions
+const RESAMPLE_MAX_WIDTH: u32 = 1280;
+
+/// Parse an EH file size like "45.6 MB" or "1.2 GiB" into bytes
+pub fn parse_file_size(s: &str) -> Option<u64> {
+    let mut parts = s.split_whitespace();
+    let value: f64 = parts.next()?.parse().ok()?;
+    let multiplier = match parts.next()?.to_lowercase().as_str() {
+        "b" => 1.0,
+        "kb" | "kib" => 1024.0,
+        "mb" | "mib" => 1024.0 * 1024.0,
+        "gb" | "gib" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((value * multiplier) as u64)
+}
+
+/// Whether the local copy looks like EH's resampled version rather than the
+/// original: sampled images are no wider than 1280px and the files on disk
+/// add up to clearly less than the size EH reports for the original.
+pub fn detect_resampled(dir: &Path, reported_size: &str) -> bool {
+    let reported = match parse_file_size(reported_size) {
+        Some(bytes) if bytes > 0 => bytes,
+        _ => return false,
+    };
+    if archive::is_archive(dir) {
+        return archive_resampled(dir, reported);
+    }
+    let images = get_all_images(dir);
+    if images.is_empty() {
+        return false;
+    }
+
+    // A handful of evenly spaced pages is enough to judge the dimensions
+    let step = (images.len() / 5).max(1);
+    let all_narrow = images.iter().step_by(step).take(5).all(|p| {
+        image::image_dimensions(p)
+            .map(|(w, _)| w <= RESAMPLE_MAX_WIDTH)
+            .unwrap_or(true)
+    });
+    if !all_narrow {
+        return false;
+    }
+
+    let local: u64 = images
+        .iter()
+        .filter_map(|p| fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum();
+    (local as f64) < reported as f64 * 0.9
+}
+
+/// `detect_resampled` for an archive, judged from its entries
+fn archive_resampled(path: &Path, reported: u64) -> bool {
+    let Ok(mut archive) = archive::open(path) else {
+        return false;
+    };
+    let images: Vec<archive::ArchiveEntry> = archive.image_entries().into_iter().cloned().collect();
+    if images.is_empty() {
+        return false;
+    }
+
+    let step = (images.len() / 5).max(1);
+    let all_narrow = images.iter().step_by(step).take(5).all(|entry| {
+        archive
+            .read(&entry.name)
+            .ok()
+            .and_then(|bytes| {
+                image::ImageReader::new(std::io::Cursor::new(bytes))
+                    .with_guessed_format()
+                    .ok()?
+                    .into_dimensions()
+                    .ok()
+            })
+            .map(|(w, _)| w <= RESAMPLE_MAX_WIDTH)
+            .unwrap_or(true)
+    });
+    if !all_narrow {
+        return false;
+    }
+
+    let local: u64 = images.iter().map(|e| e.size).sum();
+    (local as f64) < reported as f64 * 0.9
+}
+
+/// Page count on disk and the widest of a handful of evenly spaced pages
+/// (`None` when none could be read), for the quality report
+pub fn page_stats(dir: &Path) -> PageStats {
+    if archive::is_archive(dir) {
+        let Ok(mut archive) = archive::open(dir) else {
+            return PageStats::default();
+        };
+        let names: Vec<String> = archive.image_entries().into_iter().map(|e| e.name.clone()).collect();
+        let step = (names.len() / 5).max(1);
+        let max_width = names
+            .iter()
+            .step_by(step)
+            .take(5)
+            .filter_map(|name| {
+                let bytes = archive.read(name).ok()?;
+                image::ImageReader::new(std::io::Cursor::new(bytes))
+                    .with_guessed_format()
+                    .ok()?
+                    .into_dimensions()
+                    .ok()
+            })
+            .map(|(w, _)| w)
+            .max();
+        return PageStats {
+            pages: names.len() as i64,
+            max_width,
+        };
+    }
+    let images = get_all_images(dir);
+    let step = (images.len() / 5).max(1);
+    let max_width = images
+        .iter()
+        .step_by(step)
+        .take(5)
+        .filter_map(|p| image::image_dimensions(p).ok())
+        .map(|(w, _)| w)
+        .max();
+    PageStats {
+        pages: images.len() as i64,
+        max_width,
+    }
+}
+
+/// Whether a file name has a recognized image extension
+pub fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// Compare file names in natural order (2.jpg before 10.jpg)
+pub fn natural_cmp(a: &Path, b: &Path) -> std::cmp::Ordering {
+    natural_sort_key(a).cmp(&natural_sort_key(b))
+}
+
 /// Get the first image file in a directory (sorted naturally)
 pub fn get_first_image(dir: &Path) -> Option<PathBuf> {
     let mut images: Vec<PathBuf> = Vec::new();
@@ -164,10 +533,21 @@ pub fn get_first_image(dir: &Path) -> Option<PathBuf> {
     }
 
     // Sort naturally (handles 001.jpg, 1.jpg, etc.)
-    images.sort_by(|a, b| natural_sort_key(a).cmp(&natural_sort_key(b)));
+    images.sort_by_key(|p| natural_sort_key(p));
     images.into_iter().next()
 }
 
+/// The image a folder gallery's cover is made from: its first page not named
+/// in `skip`, or the first page if every page is skipped
+pub fn get_cover_image(dir: &Path, skip: &[String]) -> Option<PathBuf> {
+    let images = get_all_images(dir);
+    let cover = images.iter().position(|p| {
+        let name = p.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        !skip.contains(&name)
+    });
+    images.into_iter().nth(cover.unwrap_or(0))
+}
+
 /// Get all image files in a directory, sorted naturally
 pub fn get_all_images(dir: &Path) -> Vec<PathBuf> {
     let mut images: Vec<PathBuf> = Vec::new();
@@ -186,10 +566,62 @@ pub fn get_all_images(dir: &Path) -> Vec<PathBuf> {
         }
     }
 
-    images.sort_by(|a, b| natural_sort_key(a).cmp(&natural_sort_key(b)));
+    images.sort_by_key(|p| natural_sort_key(p));
     images
 }
 
+/// Reorder `pages` to follow a per-gallery override. Pages named in `order`
+/// come first, in that order; pages it doesn't mention (added since) follow
+/// in their natural order, and names that no longer exist are ignored.
+pub fn apply_page_order<T>(mut pages: Vec<T>, order: &[String], name: impl Fn(&T) -> &str) -> Vec<T> {
+    if order.is_empty() {
+        return pages;
+    }
+    let rank: HashMap<&str, usize> = order.iter().enumerate().map(|(i, n)| (n.as_str(), i)).collect();
+    pages.sort_by_key(|p| rank.get(name(p)).copied().unwrap_or(usize::MAX));
+    pages
+}
+
+/// Renames that zero-pad the last number in each file name to the widest
+/// such number in the set, so `p2.jpg` sorts before `p10.jpg` in any tool.
+/// A rename whose target name is already taken is left out.
+pub fn padding_renames(names: &[String]) -> Vec<(String, String)> {
+    // (name, byte range of the last digit run in its stem)
+    let numbered: Vec<(&String, usize, usize)> = names
+        .iter()
+        .filter_map(|name| {
+            let stem_len = name.rfind('.').unwrap_or(name.len());
+            let stem = &name[..stem_len];
+            let end = stem.rfind(|c: char| c.is_ascii_digit())? + 1;
+            let start = stem[..end]
+                .rfind(|c: char| !c.is_ascii_digit())
+                .map(|i| i + 1)
+                .unwrap_or(0);
+            Some((name, start, end))
+        })
+        .collect();
+    let width = numbered.iter().map(|(_, start, end)| end - start).max().unwrap_or(0);
+
+    let mut taken: HashSet<String> = names.iter().cloned().collect();
+    let mut renames = Vec::new();
+    for (name, start, end) in numbered {
+        if end - start == width {
+            continue;
+        }
+        let padded = format!(
+            "{}{}{}",
+            &name[..start],
+            "0".repeat(width - (end - start)),
+            &name[start..]
+        );
+        if !taken.insert(padded.clone()) {
+            continue;
+        }
+        renames.push((name.clone(), padded));
+    }
+    renames
+}
+
 /// Natural sort key: splits filename into text/number segments for proper ordering
 fn natural_sort_key(path: &Path) -> Vec<NaturalSegment> {
     let name = path
@@ -256,6 +688,21 @@ impl Ord for NaturalSegment {
     }
 }
 
+/// Bytes a gallery takes on disk: the archive file, or every file under
+/// the folder
+pub fn disk_size(path: &Path) -> u64 {
+    if path.is_file() {
+        return fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    }
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
 /// Get the modification time of a file as an ISO string
 pub fn get_file_mtime(path: &Path) -> String {
     fs::metadata(path)
@@ -269,3 +716,142 @@ pub fn get_file_mtime(path: &Path) -> String {
         })
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EH_DOWNLOADER: &str = "\
+(C99) [Circle (Artist)] English Title [English]
+(C99) [サークル (作者)] 日本語タイトル
+https://exhentai.org/g/1234567/0123456789/
+
+Category: Doujinshi
+Uploader: someone
+Posted: 2021-01-02 03:04
+Parent: None
+Visible: Yes
+Language: English  TR
+File Size: 45.6 MB
+Length: 28 pages
+Rating: 4.56
+Favorited: 1,234 times
+
+Tags:
+> parody: original
+> artist: artist name
+> female: big breasts, glasses
+
+Page 1: https://exhentai.org/s/abc/1234567-1
+Image 1: 001.jpg
+";
+
+    #[test]
+    fn parses_eh_downloader_layout() {
+        let g = parse_info_content(EH_DOWNLOADER).unwrap();
+        assert_eq!(g.title_en, "(C99) [Circle (Artist)] English Title [English]");
+        assert_eq!(g.title_jp, "(C99) [サークル (作者)] 日本語タイトル");
+        assert_eq!(g.url, "https://exhentai.org/g/1234567/0123456789/");
+        assert_eq!(g.category, "Doujinshi");
+        assert_eq!(g.language, "English  TR");
+        assert_eq!(g.page_count, 28);
+        assert_eq!(g.rating, 4.56);
+        assert_eq!(g.favorited, 1234);
+        assert_eq!(g.tags.len(), 4);
+        assert!(g.tags.contains(&("female".to_string(), "glasses".to_string())));
+    }
+
+    #[test]
+    fn tolerates_bom_and_crlf() {
+        let content = format!("\u{feff}{}", EH_DOWNLOADER.replace('\n', "\r\n"));
+        let g = parse_info_content(&content).unwrap();
+        assert_eq!(g.title_en, "(C99) [Circle (Artist)] English Title [English]");
+        assert_eq!(g.page_count, 28);
+        assert_eq!(g.tags.len(), 4);
+    }
+
+    #[test]
+    fn keeps_colons_in_titles() {
+        let content = "Re:Zero - Title: Subtitle\n\nhttps://e-hentai.org/g/1/abcdef/\n\nCategory: Manga\n";
+        let g = parse_info_content(content).unwrap();
+        assert_eq!(g.title_en, "Re:Zero - Title: Subtitle");
+        assert_eq!(g.title_jp, "");
+        assert_eq!(g.url, "https://e-hentai.org/g/1/abcdef/");
+        assert_eq!(g.category, "Manga");
+    }
+
+    #[test]
+    fn parses_hdoujin_key_value_layout() {
+        let content = "\
+Title:       English Title
+Original Title: 日本語タイトル
+Upload Date: 2020-05-06 07:08
+Uploader:    someone
+Pages:       31
+URL:         https://e-hentai.org/g/7654321/fedcba9876/
+Tags:        artist:someone, female:stockings, full color
+";
+        let g = parse_info_content(content).unwrap();
+        assert_eq!(g.title_en, "English Title");
+        assert_eq!(g.title_jp, "日本語タイトル");
+        assert_eq!(g.posted, "2020-05-06 07:08");
+        assert_eq!(g.page_count, 31);
+        assert_eq!(g.url, "https://e-hentai.org/g/7654321/fedcba9876/");
+        assert_eq!(
+            g.tags,
+            vec![
+                ("artist".to_string(), "someone".to_string()),
+                ("female".to_string(), "stockings".to_string()),
+                ("misc".to_string(), "full color".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_panda_downloader_variant() {
+        let content = "\
+English Title
+日本語タイトル
+https://exhentai.org/g/42/abcdef0123/
+Category：Non-H
+Length：12 pages
+Rating：4.20
+Tags：
+- language: chinese; translated
+- female：sole female
+";
+        let g = parse_info_content(content).unwrap();
+        assert_eq!(g.title_jp, "日本語タイトル");
+        assert_eq!(g.category, "Non-H");
+        assert_eq!(g.page_count, 12);
+        assert_eq!(g.rating, 4.2);
+        assert_eq!(g.tags.len(), 3);
+        assert!(g.tags.contains(&("language".to_string(), "translated".to_string())));
+        assert!(g.tags.contains(&("female".to_string(), "sole female".to_string())));
+    }
+
+    #[test]
+    fn parses_uploader_comment_block() {
+        let content = "\
+Title
+https://exhentai.org/g/1/abcdef/
+
+Tags:
+> female: glasses
+
+Uploader Comment:
+Thanks for reading!
+
+Source: https://example.com/
+";
+        let g = parse_info_content(content).unwrap();
+        assert_eq!(g.tags.len(), 1);
+        assert_eq!(g.uploader_comment, "Thanks for reading!\n\nSource: https://example.com/");
+    }
+
+    #[test]
+    fn rejects_empty_file() {
+        assert!(parse_info_content("").is_err());
+        assert!(parse_info_content("\n\n  \n").is_err());
+    }
+}
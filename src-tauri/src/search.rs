@@ -0,0 +1,240 @@
+use crate::models::{SearchQuery, TagFilter};
+
+/// Page size of a query parsed from a search string
+const DEFAULT_LIMIT: i64 = 200;
+
+/// Short namespace forms accepted by the site, by the full namespace
+const NAMESPACE_ALIASES: &[(&str, &str)] = &[
+    ("f", "female"),
+    ("m", "male"),
+    ("x", "mixed"),
+    ("o", "other"),
+    ("misc", "other"),
+    ("a", "artist"),
+    ("g", "group"),
+    ("circle", "group"),
+    ("p", "parody"),
+    ("series", "parody"),
+    ("c", "character"),
+    ("char", "character"),
+    ("l", "language"),
+    ("lang", "language"),
+    ("r", "reclass"),
+    ("cos", "cosplayer"),
+];
+
+/// One term of a search string
+#[derive(Debug, PartialEq)]
+struct Term {
+    negated: bool,
+    /// Prefixed with `~`
+    optional: bool,
+    namespace: Option<String>,
+    value: String,
+    /// Written in double quotes
+    quoted: bool,
+    /// Ended in `$`
+    exact: bool,
+}
+
+/// Compile an ExHentai search string into a `SearchQuery`:
+///
+/// - `namespace:tag` and `namespace:"multi word tag"` filter by tag; like on
+///   the site they match tags starting with the value unless it ends in `$`.
+///   Short namespaces (`f:`, `a:`, `l:`...) are expanded.
+/// - `uploader:name` filters by uploader.
+/// - Bare words and `"quoted phrases"` are full-text searched.
+/// - A leading `-` excludes a tag, word or phrase.
+/// - Tags with a leading `~` form one group of which any may match.
+///
+/// `~` on words or phrases isn't supported; such terms are required like any
+/// other. Excluding an uploader isn't supported either and is ignored.
+pub fn parse(input: &str) -> SearchQuery {
+    let mut query = SearchQuery {
+        text: None,
+        tags: Vec::new(),
+        category: None,
+        language: None,
+        sort_by: None,
+        sort_order: None,
+        offset: 0,
+        limit: DEFAULT_LIMIT,
+        include_blocked: false,
+        resampled: None,
+        favorite_slot: None,
+        favorite: None,
+        color_label: None,
+        ignore_defaults: false,
+        ignore_session: false,
+        excluded_tags: Vec::new(),
+        tag_groups: Vec::new(),
+        snapshot: None,
+        include_facets: false,
+        uploader: None,
+        min_rating: None,
+        min_pages: None,
+        max_pages: None,
+        posted_from: None,
+        posted_to: None,
+        excluded_words: Vec::new(),
+        read_state: None,
+    };
+    let mut text = Vec::new();
+    let mut any_of = Vec::new();
+
+    for term in tokenize(input) {
+        match term.namespace.as_deref() {
+            Some("uploader") => {
+                if !term.negated {
+                    query.uploader = Some(term.value);
+                }
+            }
+            Some(ns) => {
+                let filter = TagFilter {
+                    namespace: expand_namespace(ns).to_string(),
+                    tag: term.value,
+                    prefix: !term.exact,
+                };
+                if term.negated {
+                    query.excluded_tags.push(filter);
+                } else if term.optional {
+                    any_of.push(filter);
+                } else {
+                    query.tags.push(filter);
+                }
+            }
+            None => {
+                let word = if term.quoted {
+                    format!("\"{}\"", term.value)
+                } else {
+                    term.value
+                };
+                if term.negated {
+                    query.excluded_words.push(word);
+                } else {
+                    text.push(word);
+                }
+            }
+        }
+    }
+
+    if !text.is_empty() {
+        query.text = Some(text.join(" "));
+    }
+    if !any_of.is_empty() {
+        query.tag_groups.push(any_of);
+    }
+    query
+}
+
+fn expand_namespace(ns: &str) -> &str {
+    NAMESPACE_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == ns)
+        .map(|(_, full)| *full)
+        .unwrap_or(ns)
+}
+
+/// Split a search string into terms, keeping quoted values together
+fn tokenize(input: &str) -> Vec<Term> {
+    let mut terms = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let (mut negated, mut optional) = (false, false);
+        while let Some(c) = chars.next_if(|&c| c == '-' || c == '~') {
+            negated |= c == '-';
+            optional |= c == '~';
+        }
+
+        // A namespace, or the whole value when there's no colon
+        let mut head = String::new();
+        while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != '"' && c != ':') {
+            head.push(c);
+        }
+        let namespace = if !head.is_empty() && chars.next_if_eq(&':').is_some() {
+            Some(std::mem::take(&mut head).to_lowercase())
+        } else {
+            None
+        };
+
+        let quoted = head.is_empty() && chars.next_if_eq(&'"').is_some();
+        let mut value = head;
+        if quoted {
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                value.push(c);
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+        let exact = if quoted {
+            chars.next_if_eq(&'$').is_some()
+        } else {
+            value.ends_with('$') && {
+                value.pop();
+                true
+            }
+        };
+        // Anything glued on after a closing quote is dropped
+        while chars.next_if(|c| !c.is_whitespace()).is_some() {}
+
+        let value = value.trim().to_string();
+        if !value.is_empty() {
+            terms.push(Term {
+                negated,
+                optional,
+                namespace,
+                value,
+                quoted,
+                exact,
+            });
+        }
+    }
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_site_syntax() {
+        let q = parse(r#"l:translated$ -female:"big breasts"$ f:glasses uploader:someone "exact phrase" word -other"#);
+
+        let tags: Vec<_> = q.tags.iter().map(|t| (t.namespace.as_str(), t.tag.as_str(), t.prefix)).collect();
+        assert_eq!(tags, [("language", "translated", false), ("female", "glasses", true)]);
+        let excluded: Vec<_> = q.excluded_tags.iter().map(|t| (t.namespace.as_str(), t.tag.as_str(), t.prefix)).collect();
+        assert_eq!(excluded, [("female", "big breasts", false)]);
+        assert_eq!(q.uploader.as_deref(), Some("someone"));
+        assert_eq!(q.text.as_deref(), Some("\"exact phrase\" word"));
+        assert_eq!(q.excluded_words, ["other"]);
+    }
+
+    #[test]
+    fn groups_optional_tags() {
+        let q = parse("~artist:a$ ~a:b$ female:x");
+
+        assert_eq!(q.tags.len(), 1);
+        assert_eq!(q.tag_groups.len(), 1);
+        let group: Vec<_> = q.tag_groups[0].iter().map(|t| (t.namespace.as_str(), t.tag.as_str())).collect();
+        assert_eq!(group, [("artist", "a"), ("artist", "b")]);
+    }
+
+    #[test]
+    fn tolerates_unbalanced_quotes() {
+        let q = parse(r#"artist:"unterminated name"#);
+        assert_eq!(q.tags.len(), 1);
+        assert_eq!(q.tags[0].tag, "unterminated name");
+        assert!(q.text.is_none());
+    }
+}
@@ -0,0 +1,184 @@
+//! Sealing for secrets kept in settings.json, such as archive passwords.
+//! Values are encrypted with ChaCha20-Poly1305 under a random per-profile
+//! key stored beside the settings in `sealing.key` (readable only by the
+//! owner on Unix), so they don't sit in plain text in the settings file;
+//! anyone holding the whole profile folder can still open them.
+
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Key file in a profile directory; copied along with the profile
+pub const KEY_FILE: &str = "sealing.key";
+const KEY_LEN: usize = 32;
+
+/// Encrypt `plain` with the key of the profile in `dir`, creating the key
+/// on first use. Returns base64 text for settings.json.
+pub fn seal(dir: &Path, plain: &str) -> Result<String, String> {
+    let key = sealing_key(&load_or_create_key(dir)?)?;
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| "No secure random source available".to_string())?;
+    let mut data = plain.as_bytes().to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+        .map_err(|_| "Failed to seal value".to_string())?;
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&data);
+    Ok(base64::engine::general_purpose::STANDARD.encode(out))
+}
+
+/// Decrypt a value from `seal`
+pub fn open(dir: &Path, sealed: &str) -> Result<String, String> {
+    let key = fs::read(dir.join(KEY_FILE)).map_err(|e| format!("Can't read secret key: {}", e))?;
+    let key = sealing_key(&key)?;
+    let raw = base64::engine::general_purpose::STANDARD
+        .decode(sealed)
+        .map_err(|e| format!("Invalid sealed value: {}", e))?;
+    if raw.len() < NONCE_LEN + CHACHA20_POLY1305.tag_len() {
+        return Err("Invalid sealed value".to_string());
+    }
+    let (nonce, data) = raw.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Invalid sealed value".to_string())?;
+    let mut data = data.to_vec();
+    let plain = key
+        .open_in_place(nonce, Aad::empty(), &mut data)
+        .map_err(|_| "Sealed value doesn't match this profile's key".to_string())?;
+    String::from_utf8(plain.to_vec()).map_err(|e| e.to_string())
+}
+
+fn sealing_key(key: &[u8]) -> Result<LessSafeKey, String> {
+    UnboundKey::new(&CHACHA20_POLY1305, key)
+        .map(LessSafeKey::new)
+        .map_err(|_| "Secret key is corrupt".to_string())
+}
+
+fn load_or_create_key(dir: &Path) -> Result<Vec<u8>, String> {
+    let path = dir.join(KEY_FILE);
+    match fs::read(&path) {
+        Ok(key) => return check_key(key),
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(format!("Can't read secret key: {}", e));
+        }
+        Err(_) => {}
+    }
+    let mut key = vec![0u8; KEY_LEN];
+    let rng = SystemRandom::new();
+    rng.fill(&mut key)
+        .map_err(|_| "No secure random source available".to_string())?;
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    // Written in full under a temporary name, then linked into place, which
+    // fails rather than replacing a key another thread put there first. No
+    // reader ever sees a partly written key.
+    let mut suffix = [0u8; 8];
+    rng.fill(&mut suffix)
+        .map_err(|_| "No secure random source available".to_string())?;
+    let temp = dir.join(format!("{}.{}.tmp", KEY_FILE, hex::encode(suffix)));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let written = options
+        .open(&temp)
+        .and_then(|mut file| file.write_all(&key).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::hard_link(&temp, &path));
+    let _ = fs::remove_file(&temp);
+    match written {
+        Ok(()) => Ok(key),
+        // Created by another thread since the read; use theirs
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            check_key(fs::read(&path).map_err(|e| format!("Can't read secret key: {}", e))?)
+        }
+        Err(e) => Err(format!("Can't write secret key: {}", e)),
+    }
+}
+
+/// A key read from disk, if it's the right length. A wrong one is never
+/// replaced: values sealed under it would be lost without a word.
+fn check_key(key: Vec<u8>) -> Result<Vec<u8>, String> {
+    if key.len() == KEY_LEN {
+        Ok(key)
+    } else {
+        Err(format!("Secret key {} is corrupt", KEY_FILE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn profile_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ehmaster-secret-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn round_trips() {
+        let dir = profile_dir("round-trip");
+        let sealed = seal(&dir, "hunter2 パスワード").unwrap();
+        assert!(!sealed.contains("hunter2"));
+        assert_ne!(seal(&dir, "hunter2 パスワード").unwrap(), sealed);
+        assert_eq!(open(&dir, &sealed).unwrap(), "hunter2 パスワード");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_tampered_values() {
+        let dir = profile_dir("tamper");
+        let sealed = seal(&dir, "hunter2").unwrap();
+        let mut raw = base64::engine::general_purpose::STANDARD.decode(&sealed).unwrap();
+        for i in [0, NONCE_LEN, raw.len() - 1] {
+            raw[i] ^= 1;
+            let tampered = base64::engine::general_purpose::STANDARD.encode(&raw);
+            assert!(open(&dir, &tampered).is_err());
+            raw[i] ^= 1;
+        }
+        assert!(open(&dir, "dG9vIHNob3J0").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_other_profiles_key() {
+        let dir = profile_dir("mine");
+        let other = profile_dir("other");
+        let sealed = seal(&dir, "hunter2").unwrap();
+        seal(&other, "").unwrap();
+        assert!(open(&other, &sealed).is_err());
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_dir_all(&other);
+    }
+
+    #[test]
+    fn concurrent_first_use_shares_one_key() {
+        let dir = profile_dir("concurrent");
+        let sealed: Vec<String> = (0..8)
+            .map(|i| {
+                let dir = dir.clone();
+                std::thread::spawn(move || seal(&dir, &i.to_string()).unwrap())
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .collect();
+        for (i, value) in sealed.iter().enumerate() {
+            assert_eq!(open(&dir, value).unwrap(), i.to_string());
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn keeps_a_corrupt_key() {
+        let dir = profile_dir("corrupt");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(KEY_FILE), b"short").unwrap();
+        assert!(seal(&dir, "hunter2").is_err());
+        assert_eq!(fs::read(dir.join(KEY_FILE)).unwrap(), b"short");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
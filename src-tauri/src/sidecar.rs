@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::fetcher;
+use crate::models::{Gallery, TagEntry};
+
+/// Where a gallery's search sidecar goes: next to the folder or archive as
+/// `<name>.xmp`, not inside it, so writing one doesn't change the gallery's
+/// mtime and trigger rescans or rehashing.
+pub fn sidecar_path(gallery_path: &Path) -> PathBuf {
+    let mut name = gallery_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".xmp");
+    gallery_path.with_file_name(name)
+}
+
+/// Write `gallery`'s sidecar unless it's already up to date. Returns
+/// whether the file was written.
+pub fn write(gallery: &Gallery, tags: &[TagEntry]) -> Result<bool, String> {
+    let path = sidecar_path(Path::new(&gallery.path));
+    let xmp = render_xmp(gallery, tags);
+    if fs::read_to_string(&path).is_ok_and(|current| current == xmp) {
+        return Ok(false);
+    }
+    fetcher::write_atomic(&path, xmp.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(true)
+}
+
+/// XMP packet with the gallery's titles, artists, tags (and their
+/// translations), category, language, source URL and rating, in the
+/// Dublin Core properties desktop search and DAM tools read. Spotlight
+/// indexes its text as is; Windows Search does once `.xmp` is set to use the
+/// plain text filter.
+pub fn render_xmp(gallery: &Gallery, tags: &[TagEntry]) -> String {
+    let title = if gallery.custom_title.is_empty() {
+        &gallery.title_en
+    } else {
+        &gallery.custom_title
+    };
+    let mut titles = vec![("x-default", title.as_str())];
+    if !gallery.title_jp.is_empty() {
+        titles.push(("ja", gallery.title_jp.as_str()));
+    }
+    let creators: Vec<&str> = tags
+        .iter()
+        .filter(|t| t.namespace == "artist" || t.namespace == "group")
+        .map(|t| t.tag.as_str())
+        .collect();
+    let mut subjects: Vec<String> = Vec::new();
+    for tag in tags {
+        subjects.push(if tag.namespace.is_empty() {
+            tag.tag.clone()
+        } else {
+            format!("{}:{}", tag.namespace, tag.tag)
+        });
+        if !tag.translation.is_empty() {
+            subjects.push(tag.translation.clone());
+        }
+    }
+    let rating = gallery.my_rating.unwrap_or(gallery.rating).round().clamp(0.0, 5.0) as u8;
+
+    let mut out = String::from(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         \x20<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         \x20 <rdf:Description rdf:about=\"\"\n\
+         \x20   xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n\
+         \x20   xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">\n",
+    );
+    out.push_str("   <dc:title><rdf:Alt>");
+    for (lang, text) in titles {
+        out.push_str(&format!("<rdf:li xml:lang=\"{}\">{}</rdf:li>", lang, escape(text)));
+    }
+    out.push_str("</rdf:Alt></dc:title>\n");
+    push_list(&mut out, "dc:creator", "rdf:Seq", creators.iter().copied());
+    push_list(&mut out, "dc:subject", "rdf:Bag", subjects.iter().map(String::as_str));
+    if !gallery.language.is_empty() {
+        push_list(&mut out, "dc:language", "rdf:Bag", std::iter::once(gallery.language.as_str()));
+    }
+    if !gallery.category.is_empty() {
+        out.push_str(&format!("   <dc:type>{}</dc:type>\n", escape(&gallery.category)));
+    }
+    if !gallery.url.is_empty() {
+        out.push_str(&format!("   <dc:source>{}</dc:source>\n", escape(&gallery.url)));
+    }
+    out.push_str(&format!("   <xmp:Rating>{}</xmp:Rating>\n", rating));
+    out.push_str("   <xmp:CreatorTool>EhMaster</xmp:CreatorTool>\n");
+    out.push_str("  </rdf:Description>\n </rdf:RDF>\n</x:xmpmeta>\n<?xpacket end=\"w\"?>\n");
+    out
+}
+
+/// An RDF container property; nothing when `items` is empty
+fn push_list<'a>(out: &mut String, property: &str, container: &str, items: impl Iterator<Item = &'a str>) {
+    let items: Vec<String> = items.map(|i| format!("<rdf:li>{}</rdf:li>", escape(i))).collect();
+    if items.is_empty() {
+        return;
+    }
+    out.push_str(&format!(
+        "   <{p}><{c}>{}</{c}></{p}>\n",
+        items.join(""),
+        p = property,
+        c = container
+    ));
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
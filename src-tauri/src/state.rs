@@ -1,24 +1,86 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
 
+use crate::companion::CompanionHandle;
 use crate::db::Database;
-use crate::models::{AppSettings, ScanStatus};
+use crate::fetch_queue::FetchQueue;
+use crate::jobs::JobQueue;
+use crate::models::{AppSettings, IncomingDuplicate, PowerState, ScanStatus, SessionFilter};
+use crate::profile;
 use crate::watcher::WatcherHandle;
 
 pub struct AppState {
+    /// Database of the active profile; reopened in place on a profile switch
     pub db: Arc<Database>,
-    pub cache_dir: PathBuf,
+    /// Name of the active profile
+    pub profile: Mutex<String>,
+    /// Directory of the active profile (see `profile::profile_dir`)
+    data_dir: RwLock<PathBuf>,
     pub settings: Mutex<AppSettings>,
     pub scan_status: Arc<Mutex<ScanStatus>>,
     pub watchers: Mutex<HashMap<String, WatcherHandle>>,
+    pub gallery_locks: Arc<GalleryLocks>,
+    pub jobs: Arc<JobQueue>,
+    /// Per-gallery progress of bulk refresh and identify jobs
+    pub fetch_queue: FetchQueue,
+    /// Focus-mode filters for this run of the app, applied on top of the defaults
+    pub session_filters: Mutex<Vec<SessionFilter>>,
+    /// Browser extension endpoint, when enabled
+    pub companion: Mutex<Option<CompanionHandle>>,
+    /// New folders duplicating a library gid, waiting for the user to decide
+    pub incoming_duplicates: Mutex<Vec<IncomingDuplicate>>,
+    /// Power source and connection cost as of the last check
+    pub power: Mutex<PowerState>,
+}
+
+/// Registry of per-gallery locks so refreshes, watcher upserts, and scans
+/// touching the same folder take turns instead of interleaving writes.
+///
+/// Guards are plain `std` mutexes: hold them only around synchronous
+/// write/parse/upsert sections, never across an `.await`. Async commands
+/// with long sections (copying a gallery between volumes) run them under
+/// `spawn_blocking` so they don't tie up a runtime worker.
+#[derive(Default)]
+pub struct GalleryLocks {
+    locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl GalleryLocks {
+    /// Get the lock for a gallery path, creating it on first use.
+    /// Separators are normalized so watcher and scan paths share a lock.
+    pub fn get(&self, path: &str) -> Arc<Mutex<()>> {
+        self.get_many(&[path]).remove(0)
+    }
+
+    /// Locks for several paths (each path once), in the order they must be
+    /// taken: always the same order, so two callers locking the same paths
+    /// can't deadlock.
+    pub fn get_many(&self, paths: &[&str]) -> Vec<Arc<Mutex<()>>> {
+        let mut keys: Vec<String> = paths
+            .iter()
+            .map(|p| {
+                let key: PathBuf = Path::new(p).components().collect();
+                key.to_string_lossy().to_string()
+            })
+            .collect();
+        keys.sort();
+        keys.dedup();
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        // Drop entries nobody is holding so the map doesn't grow unbounded
+        locks.retain(|_, l| Arc::strong_count(l) > 1);
+        keys.into_iter()
+            .map(|key| Arc::clone(locks.entry(key).or_default()))
+            .collect()
+    }
 }
 
 impl AppState {
-    pub fn new(db: Database, cache_dir: PathBuf) -> Self {
+    pub fn new(db: Database, profile: String, data_dir: PathBuf) -> Self {
         Self {
             db: Arc::new(db),
-            cache_dir,
+            profile: Mutex::new(profile),
+            data_dir: RwLock::new(data_dir),
             settings: Mutex::new(AppSettings::default()),
             scan_status: Arc::new(Mutex::new(ScanStatus {
                 is_scanning: false,
@@ -27,6 +89,27 @@ impl AppState {
                 current_folder: String::new(),
             })),
             watchers: Mutex::new(HashMap::new()),
+            gallery_locks: Arc::new(GalleryLocks::default()),
+            jobs: Arc::new(JobQueue::new()),
+            fetch_queue: FetchQueue::default(),
+            session_filters: Mutex::new(Vec::new()),
+            companion: Mutex::new(None),
+            incoming_duplicates: Mutex::new(Vec::new()),
+            power: Mutex::new(PowerState::default()),
         }
     }
+
+    /// Directory of the active profile's database, settings and reports
+    pub fn data_dir(&self) -> PathBuf {
+        self.data_dir.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Thumbnail cache of the active profile
+    pub fn cache_dir(&self) -> PathBuf {
+        self.data_dir().join(profile::THUMBS_DIR)
+    }
+
+    pub fn set_data_dir(&self, dir: PathBuf) {
+        *self.data_dir.write().unwrap_or_else(|e| e.into_inner()) = dir;
+    }
 }
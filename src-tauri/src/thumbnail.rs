@@ -1,21 +1,120 @@
 use image::imageops::FilterType;
-use image::GenericImageView;
+use image::{DynamicImage, GenericImageView, ImageDecoder};
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions, Xyzd};
 use sha2::{Digest, Sha256};
 use std::fs;
+use std::io::Cursor;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+use crate::archive;
+use crate::scanner;
+
+/// Width:height ratio of smart-cropped covers (typical tankoubon cover).
+const COVER_ASPECT: f64 = 0.7;
+
+/// Source files larger than this are not decoded
+const MAX_SOURCE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Largest width or height the decoder will accept
+const MAX_DIMENSION: u32 = 16_384;
+
+/// Cap on memory the decoder may allocate for a single image
+const MAX_DECODE_ALLOC: u64 = 512 * 1024 * 1024;
+
+/// Quality of JPEG pages re-encoded after colour conversion
+const PAGE_JPEG_QUALITY: u8 = 92;
+
+/// How long a single decode + resize may take before it's abandoned
+const DECODE_TIMEOUT: Duration = Duration::from_secs(20);
 
 /// Generate a thumbnail for an image, saving it to the cache directory.
-/// Returns the path to the generated thumbnail.
+/// When `smart_crop` is set, the thumbnail is a uniformly-sized cover crop
+/// centered on the most detailed region instead of a proportional resize.
+/// Returns the path to the generated thumbnail, or why it couldn't be made.
+///
+/// Decoding runs on its own thread under size limits and a timeout, so a
+/// corrupt or enormous image fails this call instead of stalling or taking
+/// down the caller. A timed-out decode is left to finish in the background.
 pub fn generate_thumbnail(
     source_image: &Path,
     cache_dir: &Path,
     max_width: u32,
-) -> Option<PathBuf> {
+    smart_crop: bool,
+) -> Result<PathBuf, String> {
+    let source = source_image.to_path_buf();
+    cached_or_render(source_image, source_image, cache_dir, max_width, smart_crop, move || {
+        let size = fs::metadata(&source).map(|m| m.len()).unwrap_or(0);
+        if size > MAX_SOURCE_BYTES {
+            return Err(format!("Image too large ({} MB)", size / (1024 * 1024)));
+        }
+        fs::read(&source).map_err(|e| format!("Failed to open image: {}", e))
+    })
+}
+
+/// Thumbnail of an archive gallery's cover: its first page not named in
+/// `skip`. Cached under the archive's own path, so it is regenerated when
+/// the archive changes.
+pub fn generate_archive_thumbnail(
+    archive_path: &Path,
+    cache_dir: &Path,
+    max_width: u32,
+    smart_crop: bool,
+    skip: &[String],
+) -> Result<PathBuf, String> {
+    let names: Vec<String> = archive::open(archive_path)?
+        .image_entries()
+        .into_iter()
+        .map(|e| e.name.clone())
+        .collect();
+    let first = names.first().ok_or_else(|| "No images found".to_string())?;
+    let cover = names.iter().find(|n| !skip.contains(n)).unwrap_or(first);
+
+    // A cover other than the first page gets its own cache entry
+    let key = if cover == first {
+        archive_path.to_path_buf()
+    } else {
+        archive_path.join(cover)
+    };
+    let source = archive_path.to_path_buf();
+    let entry = cover.clone();
+    cached_or_render(&key, archive_path, cache_dir, max_width, smart_crop, move || {
+        archive::read_entry(&source, &entry)
+    })
+}
+
+/// Thumbnail for a gallery folder or archive, from its first page not named
+/// in `skip` (page file names, or entry names for archives)
+pub fn generate_gallery_thumbnail(
+    gallery: &Path,
+    cache_dir: &Path,
+    max_width: u32,
+    smart_crop: bool,
+    skip: &[String],
+) -> Result<PathBuf, String> {
+    if archive::is_archive(gallery) {
+        return generate_archive_thumbnail(gallery, cache_dir, max_width, smart_crop, skip);
+    }
+    let cover = scanner::get_cover_image(gallery, skip).ok_or_else(|| "No images found".to_string())?;
+    generate_thumbnail(&cover, cache_dir, max_width, smart_crop)
+}
+
+/// Return the thumbnail cached under `key` if it's newer than `source_image`,
+/// otherwise render one from the bytes `load` returns.
+fn cached_or_render(
+    key: &Path,
+    source_image: &Path,
+    cache_dir: &Path,
+    max_width: u32,
+    smart_crop: bool,
+    load: impl FnOnce() -> Result<Vec<u8>, String> + Send + 'static,
+) -> Result<PathBuf, String> {
     // Create cache directory if needed
-    fs::create_dir_all(cache_dir).ok()?;
+    fs::create_dir_all(cache_dir).map_err(|e| format!("Failed to create cache dir: {}", e))?;
 
     // Generate a deterministic filename from source path
-    let thumb_name = thumb_filename(source_image);
+    let thumb_name = thumb_filename(key, smart_crop);
     let thumb_path = cache_dir.join(&thumb_name);
 
     // Skip if thumbnail already exists and is newer than source
@@ -29,17 +128,246 @@ pub fn generate_thumbnail(
 
         if let (Some(src), Some(dst)) = (source_mtime, thumb_mtime) {
             if dst >= src {
-                return Some(thumb_path);
+                return Ok(thumb_path);
             }
         }
     }
 
-    // Load and resize the image
-    let img = image::open(source_image).ok()?;
+    let dest = thumb_path.clone();
+    with_decode_timeout(move || {
+        load().and_then(|bytes| render_thumbnail(bytes, &dest, max_width, smart_crop))
+    })
+    .map(|_| thumb_path)
+}
+
+/// Run a decode on its own thread, abandoning it after `DECODE_TIMEOUT`.
+fn with_decode_timeout<T: Send + 'static>(
+    decode: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("thumbnail".to_string())
+        .spawn(move || {
+            let _ = tx.send(decode());
+        })
+        .map_err(|e| format!("Failed to start decoder: {}", e))?;
+
+    match rx.recv_timeout(DECODE_TIMEOUT) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(format!(
+            "Decoding took longer than {}s",
+            DECODE_TIMEOUT.as_secs()
+        )),
+        // The sender only drops without sending if the decoder panicked
+        Err(RecvTimeoutError::Disconnected) => Err("Decoder crashed".to_string()),
+    }
+}
+
+/// 64-bit difference hash (dHash) of an image: each bit is whether a pixel
+/// of a 9x8 grayscale copy is brighter than its right neighbour. Re-encodes,
+/// rescans and recolourings of a picture land within a few bits of it.
+pub fn perceptual_hash(source: Vec<u8>) -> Result<u64, String> {
+    with_decode_timeout(move || Ok(dhash(&decode_image(source)?)))
+}
+
+/// What one decode of a page tells about it
+pub struct PageFeatures {
+    pub phash: u64,
+    pub width: u32,
+    pub height: u32,
+    pub grayscale: bool,
+}
+
+/// Perceptual hash, dimensions and whether the page is grayscale, from a
+/// single decode.
+pub fn page_features(source: Vec<u8>) -> Result<PageFeatures, String> {
+    with_decode_timeout(move || {
+        let img = decode_image(source)?;
+        Ok(PageFeatures {
+            phash: dhash(&img),
+            width: img.width(),
+            height: img.height(),
+            grayscale: is_grayscale(&img),
+        })
+    })
+}
+
+fn dhash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y).0[0] > small.get_pixel(x + 1, y).0[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// Spread between the strongest and weakest channel above which a pixel
+/// counts as coloured. High enough that yellowed or sepia scan paper stays
+/// gray.
+const COLOR_CHROMA: u8 = 32;
+
+/// Whether an image is black and white: a single-channel encoding, or fewer
+/// than 1% coloured pixels in a downscaled copy, so stray JPEG fringes and
+/// a coloured scanner mark don't make a scanned page count as colour.
+fn is_grayscale(img: &DynamicImage) -> bool {
+    if !img.color().has_color() {
+        return true;
+    }
+    let small = img.resize(128, 128, FilterType::Triangle).to_rgb8();
+    let colored = small
+        .pixels()
+        .filter(|p| {
+            let [r, g, b] = p.0;
+            r.max(g).max(b) - r.min(g).min(b) > COLOR_CHROMA
+        })
+        .count();
+    colored * 100 < small.pixels().len().max(1)
+}
+
+/// Decode `source` within the size limits.
+fn decode_image(source: Vec<u8>) -> Result<DynamicImage, String> {
+    decode_with_profile(source).map(|(img, _)| img)
+}
+
+/// Decode `source` within the size limits, with its embedded ICC profile if
+/// it has one.
+fn decode_with_profile(source: Vec<u8>) -> Result<(DynamicImage, Option<Vec<u8>>), String> {
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+
+    let mut reader = image::ImageReader::new(Cursor::new(source))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to open image: {}", e))?;
+    reader.limits(limits.clone());
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    let icc = decoder.icc_profile().ok().flatten();
+    limits
+        .reserve(decoder.total_bytes())
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    let img = DynamicImage::from_decoder(decoder)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+
+    if img.width() == 0 || img.height() == 0 {
+        return Err("Image has no pixels".to_string());
+    }
+    Ok((img, icc))
+}
+
+/// Convert `img` from the RGB colour space described by the ICC profile
+/// `icc` to sRGB. Thumbnails are saved without a profile, so a wide-gamut
+/// scan (Adobe RGB, Display P3) would otherwise look washed out. Images
+/// without a usable RGB profile are returned unchanged.
+fn to_srgb(img: DynamicImage, icc: Option<&[u8]>) -> DynamicImage {
+    let Some(profile) = icc.and_then(|icc| ColorProfile::new_from_slice(icc).ok()) else {
+        return img;
+    };
+    if profile.color_space != DataColorSpace::Rgb {
+        return img;
+    }
+    let (width, height) = img.dimensions();
+    let alpha = img.color().has_alpha();
+    let (layout, pixels) = if alpha {
+        (Layout::Rgba, img.to_rgba8().into_raw())
+    } else {
+        (Layout::Rgb, img.to_rgb8().into_raw())
+    };
+    let srgb = ColorProfile::new_srgb();
+    let Ok(transform) = profile.create_transform_8bit(layout, &srgb, layout, TransformOptions::default())
+    else {
+        return img;
+    };
+    let mut converted = vec![0u8; pixels.len()];
+    if let Err(e) = transform.transform(&pixels, &mut converted) {
+        log::warn!("[thumb] Colour conversion failed, keeping original colours: {}", e);
+        return img;
+    }
+    let converted = if alpha {
+        image::RgbaImage::from_raw(width, height, converted).map(DynamicImage::ImageRgba8)
+    } else {
+        image::RgbImage::from_raw(width, height, converted).map(DynamicImage::ImageRgb8)
+    };
+    converted.unwrap_or(img)
+}
+
+/// Page bytes ready for the viewer. Pages with an embedded RGB profile
+/// other than sRGB are converted to sRGB, like thumbnails, and re-encoded
+/// (JPEGs as JPEG, anything else as PNG); the rest are passed through
+/// untouched. Returns the MIME type of a re-encoded page.
+pub fn page_for_display(data: Vec<u8>) -> (Vec<u8>, Option<&'static str>) {
+    if !has_wide_gamut_profile(&data) {
+        return (data, None);
+    }
+    match convert_page(&data) {
+        Ok((converted, mime)) => (converted, Some(mime)),
+        Err(e) => {
+            log::warn!("[thumb] Serving page with its own colours: {}", e);
+            (data, None)
+        }
+    }
+}
+
+/// Whether `data` embeds an RGB profile other than sRGB. Only the image
+/// header is read.
+fn has_wide_gamut_profile(data: &[u8]) -> bool {
+    let profile = image::ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .and_then(|mut decoder| decoder.icc_profile().ok().flatten())
+        .and_then(|icc| ColorProfile::new_from_slice(&icc).ok());
+    let Some(profile) = profile else {
+        return false;
+    };
+    let srgb = ColorProfile::new_srgb();
+    let near = |a: &Xyzd, b: &Xyzd| (a.x - b.x).abs() + (a.y - b.y).abs() + (a.z - b.z).abs() < 0.01;
+    profile.color_space == DataColorSpace::Rgb
+        && !(near(&profile.red_colorant, &srgb.red_colorant)
+            && near(&profile.green_colorant, &srgb.green_colorant)
+            && near(&profile.blue_colorant, &srgb.blue_colorant))
+}
+
+fn convert_page(data: &[u8]) -> Result<(Vec<u8>, &'static str), String> {
+    let (img, icc) = decode_with_profile(data.to_vec())?;
+    let img = to_srgb(img, icc.as_deref());
+    let mut out = Vec::new();
+    if image::guess_format(data).ok() == Some(image::ImageFormat::Jpeg) {
+        let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, PAGE_JPEG_QUALITY);
+        DynamicImage::ImageRgb8(img.to_rgb8())
+            .write_with_encoder(encoder)
+            .map_err(|e| format!("Failed to encode page: {}", e))?;
+        Ok((out, "image/jpeg"))
+    } else {
+        img.write_to(&mut Cursor::new(&mut out), image::ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode page: {}", e))?;
+        Ok((out, "image/png"))
+    }
+}
+
+/// Decode `source` within the size limits and write its thumbnail to `dest`.
+fn render_thumbnail(
+    source: Vec<u8>,
+    dest: &Path,
+    max_width: u32,
+    smart_crop: bool,
+) -> Result<(), String> {
+    let (img, icc) = decode_with_profile(source)?;
     let (w, h) = img.dimensions();
 
-    if w == 0 || h == 0 {
-        return None;
+    // Converted after downscaling, which is far cheaper than the full page
+    if smart_crop {
+        let thumbnail = to_srgb(smart_crop_cover(&img, max_width), icc.as_deref());
+        return thumbnail
+            .save(dest)
+            .map_err(|e| format!("Failed to save thumbnail: {}", e));
     }
 
     // For horizontal images (w > h), use double the max_width so they stay
@@ -48,24 +376,101 @@ pub fn generate_thumbnail(
     let new_width = effective_max.min(w);
     let new_height = (h as f64 * new_width as f64 / w as f64) as u32;
 
-    let thumbnail = img.resize(new_width, new_height, FilterType::Lanczos3);
-    thumbnail.save(&thumb_path).ok()?;
+    let thumbnail = to_srgb(img.resize(new_width, new_height, FilterType::Lanczos3), icc.as_deref());
+    thumbnail
+        .save(dest)
+        .map_err(|e| format!("Failed to save thumbnail: {}", e))
+}
+
+/// Crop `img` to the `COVER_ASPECT` window with the highest luma entropy and
+/// resize it to `width` x `width / COVER_ASPECT`.
+fn smart_crop_cover(img: &DynamicImage, width: u32) -> DynamicImage {
+    let (w, h) = img.dimensions();
+    let height = (width as f64 / COVER_ASPECT).round() as u32;
+
+    // Crop window in source pixels; only one axis is ever shorter than the image
+    let crop_w = ((h as f64 * COVER_ASPECT).round() as u32).clamp(1, w);
+    let crop_h = ((w as f64 / COVER_ASPECT).round() as u32).clamp(1, h);
+    let horizontal = crop_w < w;
 
-    Some(thumb_path)
+    // Score strips on a small grayscale copy to keep this cheap on huge scans
+    let sample = img.thumbnail(128, 128).to_luma8();
+    let (sw, sh) = sample.dimensions();
+    let strips = if horizontal { sw } else { sh };
+    let scores: Vec<f64> = (0..strips)
+        .map(|i| {
+            let mut hist = [0u32; 32];
+            let len = if horizontal { sh } else { sw };
+            for j in 0..len {
+                let (x, y) = if horizontal { (i, j) } else { (j, i) };
+                hist[(sample.get_pixel(x, y).0[0] >> 3) as usize] += 1;
+            }
+            entropy(&hist, len)
+        })
+        .collect();
+
+    // Slide the window across the strips and keep the best-scoring offset
+    let (full, window) = if horizontal { (w, crop_w) } else { (h, crop_h) };
+    let win_strips = ((window as f64 / full as f64) * strips as f64).round() as usize;
+    let win_strips = win_strips.clamp(1, scores.len().max(1));
+    let mut best_start = 0usize;
+    let mut best_score = f64::MIN;
+    let mut sum: f64 = scores.iter().take(win_strips).sum();
+    for start in 0..=scores.len().saturating_sub(win_strips) {
+        if start > 0 {
+            sum += scores[start + win_strips - 1] - scores[start - 1];
+        }
+        if sum > best_score {
+            best_score = sum;
+            best_start = start;
+        }
+    }
+
+    let offset = if strips == 0 {
+        0
+    } else {
+        ((best_start as f64 / strips as f64) * full as f64) as u32
+    };
+    let offset = offset.min(full - window);
+    let cropped = if horizontal {
+        img.crop_imm(offset, 0, crop_w, h)
+    } else {
+        img.crop_imm(0, offset, w, crop_h)
+    };
+
+    cropped.resize_to_fill(width, height, FilterType::Lanczos3)
+}
+
+/// Shannon entropy of a histogram containing `total` samples
+fn entropy(hist: &[u32], total: u32) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    hist.iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
 }
 
 /// Generate a deterministic thumbnail filename from the source path
-fn thumb_filename(source: &Path) -> String {
+fn thumb_filename(source: &Path, smart_crop: bool) -> String {
     let mut hasher = Sha256::new();
     hasher.update(source.to_string_lossy().as_bytes());
     let hash = hasher.finalize();
     let hex_str = hex::encode(hash);
-    format!("{}.jpg", &hex_str[..16])
+    if smart_crop {
+        format!("{}_crop.jpg", &hex_str[..16])
+    } else {
+        format!("{}.jpg", &hex_str[..16])
+    }
 }
 
 /// Check if a thumbnail exists for a given source image
-pub fn thumbnail_exists(source_image: &Path, cache_dir: &Path) -> Option<PathBuf> {
-    let thumb_name = thumb_filename(source_image);
+pub fn thumbnail_exists(source_image: &Path, cache_dir: &Path, smart_crop: bool) -> Option<PathBuf> {
+    let thumb_name = thumb_filename(source_image, smart_crop);
     let thumb_path = cache_dir.join(&thumb_name);
     if thumb_path.exists() {
         Some(thumb_path)
@@ -73,3 +478,41 @@ pub fn thumbnail_exists(source_image: &Path, cache_dir: &Path) -> Option<PathBuf
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageEncoder;
+
+    fn png_with_profile(icc: Option<Vec<u8>>) -> Vec<u8> {
+        let pixels = [0u8, 200, 0, 120, 60, 30];
+        let mut out = Vec::new();
+        let mut encoder = image::codecs::png::PngEncoder::new(&mut out);
+        if let Some(icc) = icc {
+            encoder.set_icc_profile(icc).unwrap();
+        }
+        encoder.write_image(&pixels, 2, 1, image::ExtendedColorType::Rgb8).unwrap();
+        out
+    }
+
+    #[test]
+    fn wide_gamut_pages_are_converted_to_srgb() {
+        let p3 = png_with_profile(Some(ColorProfile::new_display_p3().encode().unwrap()));
+        let (converted, mime) = page_for_display(p3.clone());
+        assert_eq!(mime, Some("image/png"));
+        let before = image::load_from_memory(&p3).unwrap().to_rgb8();
+        let after = image::load_from_memory(&converted).unwrap().to_rgb8();
+        assert_eq!(after.dimensions(), (2, 1));
+        assert_ne!(before.as_raw(), after.as_raw());
+    }
+
+    #[test]
+    fn srgb_and_untagged_pages_pass_through() {
+        for page in [
+            png_with_profile(None),
+            png_with_profile(Some(ColorProfile::new_srgb().encode().unwrap())),
+        ] {
+            assert_eq!(page_for_display(page.clone()), (page, None));
+        }
+    }
+}
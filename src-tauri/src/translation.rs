@@ -0,0 +1,48 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Latest release of the EhTagTranslation database (Simplified Chinese)
+pub const DATABASE_URL: &str =
+    "https://github.com/EhTagTranslation/Database/releases/latest/download/db.text.json";
+
+/// Display names by `(namespace, tag)`, read from an EhTagTranslation
+/// database (`db.text.json` or `db.raw.json`):
+/// `{"data": [{"namespace": "female", "data": {"glasses": {"name": "..."}}}]}`.
+/// Namespace rows (the `rows` section) and empty names are skipped.
+pub fn load(path: &Path) -> Result<HashMap<(String, String), String>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+    parse(&content, &path.display().to_string())
+}
+
+/// `load` for database content already in memory; `origin` names it in errors
+pub fn parse(content: &str, origin: &str) -> Result<HashMap<(String, String), String>, String> {
+    let db: Value = serde_json::from_str(content)
+        .map_err(|e| format!("{} isn't a tag translation database: {}", origin, e))?;
+    let sections = db
+        .get("data")
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("{} has no translation data", origin))?;
+
+    let mut names = HashMap::new();
+    for section in sections {
+        let Some(namespace) = section.get("namespace").and_then(Value::as_str) else {
+            continue;
+        };
+        if namespace == "rows" {
+            continue;
+        }
+        let Some(tags) = section.get("data").and_then(Value::as_object) else {
+            continue;
+        };
+        for (tag, entry) in tags {
+            let name = entry.get("name").and_then(Value::as_str).unwrap_or_default().trim();
+            if !name.is_empty() {
+                names.insert((namespace.to_string(), tag.to_string()), name.to_string());
+            }
+        }
+    }
+    Ok(names)
+}
@@ -1,16 +1,57 @@
 use notify_debouncer_mini::new_debouncer;
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::Arc;
-use std::time::Duration;
-use tauri::{AppHandle, Emitter};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
 
+use crate::archive;
+use crate::commands;
 use crate::db::Database;
+use crate::models::{ChangeKind, JobPriority, JobSpec, RootPathStatus};
+use crate::power;
 use crate::scanner;
+use crate::state::{AppState, GalleryLocks};
 use crate::thumbnail;
 
+/// How often the resume/remount monitor polls
+const MONITOR_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How often the follow scheduler looks for artists due a check
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// How often the scan scheduler looks for roots due a rescan
+const SCAN_SCHEDULE_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the power monitor checks the power source and connection cost
+const POWER_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often an idle watcher checks whether it was stopped
+const STOP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Wall-clock time beyond the poll interval that counts as a sleep/resume
+const RESUME_THRESHOLD: Duration = Duration::from_secs(60);
+
 pub struct WatcherHandle {
-    _handle: Option<std::thread::JoinHandle<()>>,
+    handle: Option<std::thread::JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl WatcherHandle {
+    /// False once the watcher thread has exited, e.g. after failing to watch
+    pub fn is_running(&self) -> bool {
+        self.handle.as_ref().is_some_and(|h| !h.is_finished())
+    }
+}
+
+/// Dropping the handle stops the watcher; the thread exits within
+/// `STOP_POLL_INTERVAL`, or after the batch of events it's handling.
+impl Drop for WatcherHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
 }
 
 /// Start watching a directory for file changes
@@ -19,8 +60,12 @@ pub fn start_watcher(
     db: Arc<Database>,
     cache_dir: PathBuf,
     thumb_width: u32,
+    cover_crop: bool,
+    gallery_locks: Arc<GalleryLocks>,
     app_handle: AppHandle,
 ) -> WatcherHandle {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
     let handle = std::thread::spawn(move || {
         let (tx, rx) = std::sync::mpsc::channel();
 
@@ -43,54 +88,91 @@ pub fn start_watcher(
         log::info!("File watcher started for {:?}", root_path);
 
         loop {
-            match rx.recv() {
+            if stopped.load(Ordering::Relaxed) {
+                log::info!("File watcher stopped for {:?}", root_path);
+                break;
+            }
+            match rx.recv_timeout(STOP_POLL_INTERVAL) {
                 Ok(Ok(events)) => {
+                    // An archive is its own gallery; anything else belongs to its folder
                     let affected_folders: HashSet<PathBuf> = events
                         .iter()
-                        .filter_map(|e| e.path.parent().map(|p| p.to_path_buf()))
+                        .filter_map(|e| {
+                            if archive::has_archive_extension(&e.path) {
+                                Some(e.path.clone())
+                            } else {
+                                e.path.parent().map(|p| p.to_path_buf())
+                            }
+                        })
                         .collect();
 
                     for folder in &affected_folders {
-                        let info_path = folder.join("info.txt");
+                        let lock = gallery_locks.get(&scanner::to_nfc(&folder.to_string_lossy()));
+                        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+                        let info_path = scanner::gallery_info_path(folder);
                         if info_path.exists() {
                             // Gallery created or modified - rescan it
                             log::info!("Watcher: rescanning gallery {:?}", folder);
-                            if let Some(parsed) = scanner::parse_info_txt(&info_path) {
-                                let folder_str = folder.to_string_lossy().to_string();
+                            let parsed = match scanner::parse_gallery(folder) {
+                                Ok(parsed) => Some(parsed),
+                                Err(reason) => {
+                                    log::warn!("Watcher: failed to parse {:?}: {}", info_path, reason);
+                                    None
+                                }
+                            };
+                            let folder_str = scanner::to_nfc(&folder.to_string_lossy());
+                            let is_new = matches!(db.get_gallery_by_path(&folder_str), Ok(None));
+                            let parsed = parsed.filter(|parsed| {
+                                !(is_new && commands::hold_incoming_duplicate(&app_handle, &folder_str, parsed))
+                            });
+                            if let Some(parsed) = parsed {
                                 let info_mtime = scanner::get_file_mtime(&info_path);
 
                                 // Generate thumbnail
-                                let thumb = scanner::get_first_image(folder)
-                                    .and_then(|img| {
-                                        thumbnail::generate_thumbnail(&img, &cache_dir, thumb_width)
-                                    })
+                                let extras = db.get_page_extras_by_path(&folder_str).unwrap_or_default();
+                                let thumb = thumbnail::generate_gallery_thumbnail(folder, &cache_dir, thumb_width, cover_crop, &extras)
+                                    .map_err(|e| log::warn!("Watcher: thumbnail {:?}: {}", folder, e))
                                     .map(|p| p.to_string_lossy().to_string())
                                     .unwrap_or_default();
 
-                                if let Err(e) = db.upsert_gallery(
+                                match db.upsert_gallery(
                                     &folder_str,
                                     &parsed,
                                     &thumb,
                                     &info_mtime,
                                 ) {
-                                    log::error!("Watcher: DB upsert error: {:?}", e);
+                                    Ok(gallery_id) => {
+                                        let resampled =
+                                            scanner::detect_resampled(folder, &parsed.file_size);
+                                        let _ = db.set_resampled(gallery_id, resampled);
+                                        let _ = db.set_page_stats(gallery_id, &scanner::page_stats(folder));
+                                        let _ = db.set_disk_size(&folder_str, scanner::disk_size(folder));
+                                        commands::publish_change(
+                                            &app_handle,
+                                            ChangeKind::GalleryUpserted,
+                                            Some(gallery_id),
+                                            &folder_str,
+                                        );
+                                    }
+                                    Err(e) => log::error!("Watcher: DB upsert error: {:?}", e),
                                 }
-
-                                let _ = app_handle.emit("watcher-update", serde_json::json!({
-                                    "event_type": "upsert",
-                                    "path": folder_str,
-                                }));
                             }
                         } else {
-                            // Check if this was a gallery that got deleted
-                            let folder_str = folder.to_string_lossy().to_string();
-                            if let Ok(Some(_)) = db.get_gallery_by_path(&folder_str) {
-                                log::info!("Watcher: gallery deleted {:?}", folder);
-                                let _ = db.delete_gallery_by_path(&folder_str);
-                                let _ = app_handle.emit("watcher-update", serde_json::json!({
-                                    "event_type": "delete",
-                                    "path": folder_str,
-                                }));
+                            // Check if this was a gallery that got deleted. Keep
+                            // the row flagged missing in case it comes back, and
+                            // ignore the flood of removals from an unmounting volume.
+                            let folder_str = scanner::to_nfc(&folder.to_string_lossy());
+                            if scanner::is_root_online(&root_path)
+                                && db.mark_gallery_missing(&folder_str).unwrap_or(false)
+                            {
+                                log::info!("Watcher: gallery missing {:?}", folder);
+                                let id = db.get_gallery_by_path(&folder_str).ok().flatten().map(|g| g.id);
+                                commands::publish_change(
+                                    &app_handle,
+                                    ChangeKind::GalleryRemoved,
+                                    id,
+                                    &folder_str,
+                                );
                             }
                         }
                     }
@@ -98,6 +180,7 @@ pub fn start_watcher(
                 Ok(Err(e)) => {
                     log::error!("Watcher error: {:?}", e);
                 }
+                Err(RecvTimeoutError::Timeout) => {}
                 Err(e) => {
                     log::error!("Watcher channel error: {:?}", e);
                     break;
@@ -107,6 +190,146 @@ pub fn start_watcher(
     });
 
     WatcherHandle {
-        _handle: Some(handle),
+        handle: Some(handle),
+        stop,
     }
 }
+
+/// Watch for resume-from-sleep and root volumes reappearing. File events are
+/// lost in both cases, so affected roots get an incremental rescan (unchanged
+/// info.txt files are skipped by mtime) and remounted roots a fresh watcher.
+pub fn start_resume_monitor(app_handle: AppHandle) {
+    std::thread::spawn(move || {
+        let mut present: HashMap<String, bool> = HashMap::new();
+
+        loop {
+            // thread::sleep runs on a monotonic clock that stops while the
+            // machine is suspended, whereas SystemTime keeps advancing.
+            let before = SystemTime::now();
+            std::thread::sleep(MONITOR_INTERVAL);
+            let resumed = SystemTime::now()
+                .duration_since(before)
+                .map(|d| d > MONITOR_INTERVAL + RESUME_THRESHOLD)
+                .unwrap_or(false);
+
+            let state = app_handle.state::<AppState>();
+            let roots = state
+                .settings
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .root_paths
+                .clone();
+            present.retain(|root, _| roots.contains(root));
+
+            let mut to_scan: Vec<String> = Vec::new();
+            for root in &roots {
+                let exists = scanner::is_root_online(Path::new(root));
+                let was_present = present.insert(root.clone(), exists).unwrap_or(exists);
+                if exists != was_present {
+                    let _ = app_handle.emit(
+                        "root-status",
+                        RootPathStatus { path: root.clone(), online: exists },
+                    );
+                }
+                if !exists {
+                    if was_present {
+                        log::warn!("Root went offline: {}", root);
+                    }
+                    continue;
+                }
+                if !was_present {
+                    log::info!("Root remounted, restarting watcher: {}", root);
+                    commands::start_watcher_for_path(root, &state, &app_handle);
+                    to_scan.push(root.clone());
+                } else if resumed {
+                    to_scan.push(root.clone());
+                }
+            }
+
+            if resumed {
+                log::info!("Resume from sleep detected, rescanning {} root(s)", to_scan.len());
+            }
+            for root in to_scan {
+                commands::submit_job(&state, JobSpec::Scan { root_path: root }, JobPriority::Low);
+            }
+        }
+    });
+}
+
+/// Queue a followed-artist check whenever one is due under the
+/// `follow_check_hours` setting.
+pub fn start_follow_scheduler(app_handle: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(FOLLOW_POLL_INTERVAL);
+
+        let state = app_handle.state::<AppState>();
+        let hours = state
+            .settings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .follow_check_hours;
+        if hours == 0 || commands::follow_check_pending(&state) {
+            continue;
+        }
+        if state.db.followed_artists_due(hours).unwrap_or(false) {
+            log::info!("Followed artists due for a check");
+            commands::submit_job(&state, JobSpec::CheckFollowedArtists, JobPriority::Low);
+        }
+    });
+}
+
+/// Queue an incremental scan of each scheduled root once its interval has
+/// passed since its last finished scan. Nothing is queued while a scan or
+/// any other job is in flight, or while on battery when
+/// `scan_skip_on_battery` is set; offline roots wait until they're back.
+pub fn start_scan_scheduler(app_handle: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SCAN_SCHEDULE_POLL_INTERVAL);
+
+        let state = app_handle.state::<AppState>();
+        let (schedules, roots, skip_on_battery) = {
+            let settings = state.settings.lock().unwrap_or_else(|e| e.into_inner());
+            (
+                settings.scan_schedules.clone(),
+                settings.root_paths.clone(),
+                settings.scan_skip_on_battery,
+            )
+        };
+        if schedules.iter().all(|s| s.every_hours == 0) || commands::jobs_busy(&state) {
+            continue;
+        }
+        if skip_on_battery && power::on_battery() {
+            continue;
+        }
+        for schedule in schedules {
+            if schedule.every_hours == 0
+                || !roots.contains(&schedule.root)
+                || !scanner::is_root_online(Path::new(&schedule.root))
+                || !state
+                    .db
+                    .root_scan_due(&schedule.root, schedule.every_hours)
+                    .unwrap_or(false)
+            {
+                continue;
+            }
+            log::info!("Scheduled scan due for {}", schedule.root);
+            commands::submit_job(
+                &state,
+                JobSpec::Scan {
+                    root_path: schedule.root,
+                },
+                JobPriority::Low,
+            );
+        }
+    });
+}
+
+/// Re-check the power source and connection cost periodically so the power
+/// policy holds and releases jobs as the laptop is unplugged or moves to a
+/// metered network.
+pub fn start_power_monitor(app_handle: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(POWER_POLL_INTERVAL);
+        commands::refresh_power_state(&app_handle);
+    });
+}